@@ -0,0 +1,11 @@
+-- Create email_verification_tokens table for the email/password signup verification flow
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,        -- SHA-256 of the token sent by email (hex)
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Create index on user_id so resending can invalidate previous tokens quickly
+CREATE INDEX idx_email_verification_tokens_user_id ON email_verification_tokens(user_id);
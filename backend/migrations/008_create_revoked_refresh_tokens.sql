@@ -0,0 +1,10 @@
+-- Create revoked_refresh_tokens table to detect replay of already-rotated refresh tokens
+CREATE TABLE IF NOT EXISTS revoked_refresh_tokens (
+    token_hash TEXT PRIMARY KEY,            -- SHA-256 of the rotated refresh token (hex)
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    rotated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL         -- Original token expiry; the record is useless after this
+);
+
+-- Create index on expires_at for cleanup queries
+CREATE INDEX idx_revoked_refresh_tokens_expires_at ON revoked_refresh_tokens(expires_at);
@@ -0,0 +1,4 @@
+-- Record which Zcash network each wallet was created on
+-- Existing wallets were all created with a hardcoded mainnet network
+ALTER TABLE wallets ADD COLUMN IF NOT EXISTS network TEXT NOT NULL DEFAULT 'mainnet'
+    CHECK (network IN ('mainnet', 'testnet'));
@@ -0,0 +1,3 @@
+-- Cache the bare transparent (t-) address alongside the unified addresses
+-- Backfilled lazily by /wallet/addresses
+ALTER TABLE wallets ADD COLUMN IF NOT EXISTS transparent_receiver TEXT;
@@ -0,0 +1,7 @@
+-- Create address_counters table tracking the next diversifier index per user
+-- Index 0 means no diversified address has been handed out yet
+CREATE TABLE IF NOT EXISTS address_counters (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    next_index BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,9 @@
+-- Record which shielded pool each received note belongs to
+-- Sapling output and Orchard action indices can coincide within one transaction,
+-- so the pool becomes part of the note's identity
+ALTER TABLE received_notes ADD COLUMN IF NOT EXISTS pool TEXT NOT NULL DEFAULT 'sapling'
+    CHECK (pool IN ('sapling', 'orchard'));
+
+ALTER TABLE received_notes DROP CONSTRAINT IF EXISTS received_notes_user_id_transaction_id_note_index_key;
+ALTER TABLE received_notes ADD CONSTRAINT received_notes_user_id_transaction_id_pool_note_index_key
+    UNIQUE (user_id, transaction_id, pool, note_index);
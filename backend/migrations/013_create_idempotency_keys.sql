@@ -0,0 +1,13 @@
+-- Create idempotency_keys table so retried sends return the original result
+-- A row with a NULL response is a send still in progress
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    txid TEXT,
+    response TEXT,                          -- Serialized SendTransactionResponse
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, key)
+);
+
+-- Create index on created_at for cleanup queries
+CREATE INDEX idx_idempotency_keys_created_at ON idempotency_keys(created_at);
@@ -0,0 +1,7 @@
+-- Create login_attempts table to lock accounts after repeated wrong passwords
+CREATE TABLE IF NOT EXISTS login_attempts (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    failed_count INTEGER NOT NULL DEFAULT 0,
+    first_failed_at TIMESTAMPTZ,            -- Start of the current counting window
+    locked_until TIMESTAMPTZ                -- Logins are refused until this time
+);
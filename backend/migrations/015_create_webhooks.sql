@@ -0,0 +1,8 @@
+-- Create webhooks table so users can be notified when funds arrive
+CREATE TABLE IF NOT EXISTS webhooks (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL,                   -- HMAC-SHA256 key for the X-Shield-Signature header
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
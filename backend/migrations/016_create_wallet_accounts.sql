@@ -0,0 +1,11 @@
+-- Create wallet_accounts table for additional HD accounts derived from a user's seed
+-- Account 0 is the primary account recorded in wallets; rows here start at index 1
+CREATE TABLE IF NOT EXISTS wallet_accounts (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    account_index BIGINT NOT NULL,          -- ZIP 32 account index
+    name TEXT NOT NULL,
+    address TEXT NOT NULL,                  -- Default shielded unified address
+    birthday_height BIGINT NOT NULL,        -- Scanning for this account starts here
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, account_index)
+);
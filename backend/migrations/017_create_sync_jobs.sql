@@ -0,0 +1,19 @@
+-- Create sync_jobs table so SQLite -> PostgreSQL mirroring survives restarts
+CREATE TABLE IF NOT EXISTS sync_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    chain_tip BIGINT NOT NULL,              -- Chain tip of the scan that queued the job
+    status TEXT NOT NULL DEFAULT 'PENDING', -- PENDING, RUNNING, FAILED
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    run_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    locked_at TIMESTAMPTZ,                  -- When a worker claimed the job
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- A sync mirrors the whole wallet, so one queued job per user is enough
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sync_jobs_pending_user
+    ON sync_jobs(user_id) WHERE status = 'PENDING';
+
+CREATE INDEX IF NOT EXISTS idx_sync_jobs_status_run_after ON sync_jobs(status, run_after);
@@ -0,0 +1,10 @@
+-- Create oauth_states table holding pending Google sign-in attempts
+-- Each state is single-use and consumed by the callback, which rejects unknown values
+CREATE TABLE IF NOT EXISTS oauth_states (
+    state_hash TEXT PRIMARY KEY,            -- SHA-256 of the state sent to Google (hex)
+    nonce TEXT NOT NULL,                    -- Expected nonce claim in the returned id_token
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_oauth_states_expires_at ON oauth_states(expires_at);
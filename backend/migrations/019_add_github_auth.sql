@@ -0,0 +1,5 @@
+-- Allow GitHub as a sign-in provider
+ALTER TYPE auth_method ADD VALUE IF NOT EXISTS 'github';
+
+-- Tie each pending sign-in to the provider it was started with
+ALTER TABLE oauth_states ADD COLUMN IF NOT EXISTS provider TEXT NOT NULL DEFAULT 'google';
@@ -0,0 +1,10 @@
+-- Create transaction_labels table for private notes users attach to their transactions
+-- Labels are keyed by lowercase txid so they survive a resync that recreates transactions rows
+CREATE TABLE IF NOT EXISTS transaction_labels (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    txid TEXT NOT NULL,
+    label TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, txid)
+);
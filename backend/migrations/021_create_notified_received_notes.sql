@@ -0,0 +1,19 @@
+-- Record which received notes a received-funds webhook has already been sent for
+-- Keyed by txid rather than transactions.id, since resets and resyncs delete and recreate
+-- the transactions and received_notes rows
+CREATE TABLE IF NOT EXISTS notified_received_notes (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    txid TEXT NOT NULL,
+    pool TEXT NOT NULL,
+    note_index INTEGER NOT NULL,
+    notified_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, txid, pool, note_index)
+);
+
+-- Notes already synced have had their chance at a notification
+INSERT INTO notified_received_notes (user_id, txid, pool, note_index)
+SELECT rn.user_id, t.txid, rn.pool, rn.note_index
+FROM received_notes rn
+JOIN transactions t ON t.id = rn.transaction_id
+WHERE NOT rn.is_change
+ON CONFLICT DO NOTHING;
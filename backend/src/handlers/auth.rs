@@ -1,70 +1,189 @@
 use crate::{
-    middleware::{AppError, Result},
+    handlers::common::{get_lightwalletd_url, get_network, network_name},
+    middleware::{AppError, Json, Result, SessionId},
     models::{
-        session::{AuthResponse, Session},
-        user::{User, UserResponse, CreateUserRequest, LoginRequest, AuthMethod},
+        row::{session_from_row, user_from_row, SESSION_COLUMNS, USER_COLUMNS},
+        session::{AuthResponse, SessionResponse},
+        user::{UserResponse, CreateUserRequest, LoginRequest, AuthMethod},
     },
-    utils::JwtManager,
+    utils::{
+        email,
+        env::env_or,
+        oauth::{GitHubProvider, GoogleProvider, OAuthProvider},
+        token, JwtManager,
+    },
+};
+use axum::{
+    extract::{ConnectInfo, Extension, Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect, Response},
 };
-use axum::{extract::{Query, State}, Json, response::Redirect};
-use chrono::{DateTime, Duration, Utc};
+use axum_extra::{headers::UserAgent, TypedHeader};
+use chrono::{Duration, Utc};
 use sqlx::{PgPool, Row};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use uuid::Uuid;
 use validator::Validate;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use reqwest;
 use bip39::Mnemonic;
 use rand::RngCore;
-use zcash_protocol::consensus::Network;
-
-/// Helper to parse DateTime string from database
-fn parse_datetime(s: &str) -> std::result::Result<DateTime<Utc>, sqlx::Error> {
-    chrono::DateTime::parse_from_rfc3339(s)
-        .map(|dt| dt.with_timezone(&Utc))
-        .or_else(|_| {
-            // Try parsing with space instead of T
-            chrono::DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z")
-                .map(|dt| dt.with_timezone(&Utc))
-        })
-        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
-}
-
-/// Helper to parse User from a database row (since sqlx uuid feature is disabled)
-fn user_from_row(row: &sqlx::postgres::PgRow) -> std::result::Result<User, sqlx::Error> {
-    let id_str: String = row.try_get("id")?;
-    let auth_method_str: String = row.try_get("auth_method")?;
-    let created_at_str: String = row.try_get("created_at")?;
-    let updated_at_str: String = row.try_get("updated_at")?;
-
-    Ok(User {
-        id: Uuid::parse_str(&id_str).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
-        email: row.try_get("email")?,
-        password_hash: row.try_get("password_hash")?,
-        full_name: row.try_get("full_name")?,
-        email_verified: row.try_get("email_verified")?,
-        auth_method: AuthMethod::from_str(&auth_method_str),
-        created_at: parse_datetime(&created_at_str)?,
-        updated_at: parse_datetime(&updated_at_str)?,
-    })
+
+/// How long an email verification link stays valid
+const EMAIL_VERIFICATION_TTL_HOURS: i64 = 24;
+
+/// Defaults for account lockout, overridable with `LOGIN_MAX_FAILED_ATTEMPTS`,
+/// `LOGIN_ATTEMPT_WINDOW_SECS` and `LOGIN_LOCKOUT_SECS`
+const DEFAULT_LOGIN_MAX_FAILED_ATTEMPTS: i32 = 5;
+const DEFAULT_LOGIN_ATTEMPT_WINDOW_SECS: i64 = 900;
+const DEFAULT_LOGIN_LOCKOUT_SECS: i64 = 900;
+
+/// How often expired sessions and tokens are purged, overridable with
+/// `RETENTION_CLEANUP_INTERVAL_SECS`
+const DEFAULT_RETENTION_CLEANUP_INTERVAL_SECS: u64 = 3600;
+
+/// Wallet birthday used when the chain tip can't be fetched, overridable with
+/// `FALLBACK_BIRTHDAY_HEIGHT`
+///
+/// A mainnet height from Dec 2025. It only needs to be recent and no later than the
+/// wallet's first transaction; too old a value means a very long first scan.
+const DEFAULT_FALLBACK_BIRTHDAY_HEIGHT: i64 = 3_150_000;
+
+/// Allowed range for `BCRYPT_COST`; values outside it fall back to `bcrypt::DEFAULT_COST`
+const MIN_BCRYPT_COST: u32 = 10;
+const MAX_BCRYPT_COST: u32 = 15;
+
+/// Birthday for new wallets when the current height is unavailable, from
+/// `FALLBACK_BIRTHDAY_HEIGHT`
+fn fallback_birthday_height() -> i64 {
+    env_or("FALLBACK_BIRTHDAY_HEIGHT", DEFAULT_FALLBACK_BIRTHDAY_HEIGHT)
 }
 
-/// Helper to parse Session from a database row
-fn session_from_row(row: &sqlx::postgres::PgRow) -> std::result::Result<Session, sqlx::Error> {
-    let id_str: String = row.try_get("id")?;
-    let user_id_str: String = row.try_get("user_id")?;
-    let expires_at_str: String = row.try_get("expires_at")?;
-    let created_at_str: String = row.try_get("created_at")?;
-
-    Ok(Session {
-        id: Uuid::parse_str(&id_str).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
-        user_id: Uuid::parse_str(&user_id_str).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
-        refresh_token: row.try_get("refresh_token")?,
-        expires_at: parse_datetime(&expires_at_str)?,
-        created_at: parse_datetime(&created_at_str)?,
-        user_agent: row.try_get("user_agent")?,
-        ip_address: row.try_get("ip_address")?,
-    })
+/// Work factor for new password hashes, from `BCRYPT_COST`
+fn bcrypt_cost() -> u32 {
+    let cost = env_or("BCRYPT_COST", bcrypt::DEFAULT_COST);
+    if (MIN_BCRYPT_COST..=MAX_BCRYPT_COST).contains(&cost) {
+        cost
+    } else {
+        tracing::warn!(
+            "BCRYPT_COST {} outside {}-{}, using {}",
+            cost,
+            MIN_BCRYPT_COST,
+            MAX_BCRYPT_COST,
+            bcrypt::DEFAULT_COST
+        );
+        bcrypt::DEFAULT_COST
+    }
+}
+
+/// Re-hash a just-verified password if its stored hash is weaker than `bcrypt_cost()`
+///
+/// Keeps older accounts current as the cost is raised. Failures are logged rather than
+/// failing the login, since the existing hash is still valid.
+async fn upgrade_password_hash(db: &PgPool, user_id: Uuid, password: &str, password_hash: &str) {
+    let cost = bcrypt_cost();
+    let stored_cost = match password_hash.parse::<bcrypt::HashParts>() {
+        Ok(parts) => parts.get_cost(),
+        Err(e) => {
+            tracing::warn!("Unreadable password hash for user {}: {}", user_id, e);
+            return;
+        }
+    };
+    if stored_cost >= cost {
+        return;
+    }
+
+    let new_hash = match bcrypt::hash(password, cost) {
+        Ok(hash) => hash,
+        Err(e) => {
+            tracing::warn!("Failed to rehash password for user {}: {}", user_id, e);
+            return;
+        }
+    };
+
+    let result = sqlx::query("UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2::uuid")
+        .bind(&new_hash)
+        .bind(user_id.to_string())
+        .execute(db)
+        .await;
+    match result {
+        Ok(_) => tracing::info!(
+            "Upgraded password hash for user {} from cost {} to {}",
+            user_id,
+            stored_cost,
+            cost
+        ),
+        Err(e) => tracing::warn!("Failed to store rehashed password for user {}: {}", user_id, e),
+    }
+}
+
+/// Seconds until the user's login lockout ends, if they are locked out
+async fn login_lockout_remaining(db: &PgPool, user_id: Uuid) -> Result<Option<i64>> {
+    let remaining: Option<i64> = sqlx::query_scalar(
+        "SELECT CEIL(EXTRACT(EPOCH FROM (locked_until - NOW())))::bigint FROM login_attempts
+         WHERE user_id = $1::uuid AND locked_until > NOW()"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(db)
+    .await?;
+    Ok(remaining)
+}
+
+/// Count a wrong password, locking the account once too many land in one window
+///
+/// Returns the lockout length in seconds if this failure triggered one.
+async fn record_failed_login(db: &PgPool, user_id: Uuid) -> Result<Option<i64>> {
+    let max_attempts = env_or("LOGIN_MAX_FAILED_ATTEMPTS", DEFAULT_LOGIN_MAX_FAILED_ATTEMPTS);
+    let window_secs = env_or("LOGIN_ATTEMPT_WINDOW_SECS", DEFAULT_LOGIN_ATTEMPT_WINDOW_SECS);
+    let lockout_secs = env_or("LOGIN_LOCKOUT_SECS", DEFAULT_LOGIN_LOCKOUT_SECS);
+
+    // Failures older than the window start a fresh count
+    let failed_count: i32 = sqlx::query_scalar(
+        "INSERT INTO login_attempts (user_id, failed_count, first_failed_at)
+         VALUES ($1::uuid, 1, NOW())
+         ON CONFLICT (user_id) DO UPDATE SET
+             failed_count = CASE
+                 WHEN login_attempts.first_failed_at IS NULL
+                   OR login_attempts.first_failed_at < NOW() - ($2::bigint * INTERVAL '1 second')
+                 THEN 1 ELSE login_attempts.failed_count + 1 END,
+             first_failed_at = CASE
+                 WHEN login_attempts.first_failed_at IS NULL
+                   OR login_attempts.first_failed_at < NOW() - ($2::bigint * INTERVAL '1 second')
+                 THEN NOW() ELSE login_attempts.first_failed_at END
+         RETURNING failed_count"
+    )
+    .bind(user_id.to_string())
+    .bind(window_secs)
+    .fetch_one(db)
+    .await?;
+
+    if failed_count < max_attempts.max(1) {
+        return Ok(None);
+    }
+
+    sqlx::query(
+        "UPDATE login_attempts
+         SET locked_until = NOW() + ($2::bigint * INTERVAL '1 second'),
+             failed_count = 0, first_failed_at = NULL
+         WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .bind(lockout_secs)
+    .execute(db)
+    .await?;
+
+    tracing::warn!("Locked user {} out for {}s after {} failed logins", user_id, lockout_secs, failed_count);
+    Ok(Some(lockout_secs))
+}
+
+/// Forget failed logins, e.g. after a successful login or a password reset
+async fn clear_failed_logins(db: &PgPool, user_id: Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM login_attempts WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .execute(db)
+        .await?;
+    Ok(())
 }
 
 #[derive(Clone)]
@@ -73,10 +192,57 @@ pub struct AppState {
     pub jwt_manager: Arc<JwtManager>,
 }
 
+/// Client details recorded on a session for the devices screen
+struct ClientInfo {
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    fn new(user_agent: Option<TypedHeader<UserAgent>>, addr: SocketAddr) -> Self {
+        Self {
+            // Column is VARCHAR(500)
+            user_agent: user_agent.map(|TypedHeader(ua)| ua.as_str().chars().take(500).collect()),
+            ip_address: Some(addr.ip().to_string()),
+        }
+    }
+}
+
+/// Store a session for a newly issued refresh token - use UUID casts
+///
+/// The session expires with the refresh token, `expires_in_secs` from now.
+async fn create_session<'e>(
+    executor: impl sqlx::PgExecutor<'e>,
+    session_id: Uuid,
+    user_id: Uuid,
+    refresh_token: &str,
+    expires_in_secs: i64,
+    client: &ClientInfo,
+) -> Result<()> {
+    let expires_at = Utc::now() + Duration::seconds(expires_in_secs);
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, refresh_token, expires_at, user_agent, ip_address)
+         VALUES ($1::uuid, $2::uuid, $3, $4::timestamptz, $5, $6::inet)"
+    )
+    .bind(session_id.to_string())
+    .bind(user_id.to_string())
+    .bind(refresh_token)
+    .bind(expires_at.to_rfc3339())
+    .bind(&client.user_agent)
+    .bind(&client.ip_address)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
 pub async fn refresh(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(refresh_token): Json<String>,
 ) -> Result<Json<AuthResponse>> {
+    let client_info = ClientInfo::new(user_agent, addr);
+
     // Verify refresh token
     let claims = state.jwt_manager.verify_token(&refresh_token)?;
 
@@ -88,11 +254,34 @@ pub async fn refresh(
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))?;
 
-    // Check if session exists and is valid - use string casts for UUID
-    let session_row = sqlx::query(
-        "SELECT id::text, user_id::text, refresh_token, expires_at, created_at, user_agent, ip_address
-         FROM sessions WHERE refresh_token = $1 AND user_id = $2::uuid AND expires_at > NOW()"
+    // A token that was already rotated is being replayed: assume it was stolen and
+    // revoke every session for the user
+    let token_hash = token::hash_token(&refresh_token);
+    let reused = sqlx::query(
+        "SELECT user_id::text FROM revoked_refresh_tokens WHERE token_hash = $1"
     )
+    .bind(&token_hash)
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(row) = reused {
+        let owner: String = row.get("user_id");
+        tracing::warn!("Refresh token reuse detected for user {}, revoking all sessions", owner);
+
+        sqlx::query("DELETE FROM sessions WHERE user_id = $1::uuid")
+            .bind(&owner)
+            .execute(&state.db)
+            .await?;
+
+        return Err(AppError::Unauthorized("Token reuse detected".to_string()));
+    }
+
+    // Check if session exists and is valid - use string casts for UUID
+    let session_row = sqlx::query(&format!(
+        "SELECT {}
+         FROM sessions WHERE refresh_token = $1 AND user_id = $2::uuid AND expires_at > NOW()",
+        SESSION_COLUMNS
+    ))
     .bind(&refresh_token)
     .bind(user_id.to_string())
     .fetch_optional(&state.db)
@@ -102,10 +291,11 @@ pub async fn refresh(
     let session = session_from_row(&session_row)?;
 
     // Get user - use string casts for UUID
-    let user_row = sqlx::query(
-        "SELECT id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at, updated_at
-         FROM users WHERE id = $1::uuid"
-    )
+    let user_row = sqlx::query(&format!(
+        "SELECT {}
+         FROM users WHERE id = $1::uuid",
+        USER_COLUMNS
+    ))
     .bind(user_id.to_string())
     .fetch_one(&state.db)
     .await?;
@@ -113,25 +303,46 @@ pub async fn refresh(
     let user = user_from_row(&user_row)?;
 
     // Generate new tokens
-    let new_access_token = state.jwt_manager.generate_access_token(user.id)?;
+    let new_session_id = Uuid::new_v4();
+    let new_access_token = state.jwt_manager.generate_access_token(user.id, new_session_id)?;
     let new_refresh_token = state.jwt_manager.generate_refresh_token(user.id)?;
 
-    // Delete old refresh token and create new one - use string cast for UUID
+    // Rotate: delete old session, remember the old token, create new session - use string cast for UUID
+    let mut tx = state.db.begin().await?;
+
     sqlx::query("DELETE FROM sessions WHERE id = $1::uuid")
         .bind(session.id.to_string())
-        .execute(&state.db)
+        .execute(&mut *tx)
         .await?;
 
-    let expires_at = Utc::now() + Duration::seconds(604800);
     sqlx::query(
-        "INSERT INTO sessions (user_id, refresh_token, expires_at) VALUES ($1::uuid, $2, $3::timestamptz)"
+        "INSERT INTO revoked_refresh_tokens (token_hash, user_id, expires_at)
+         VALUES ($1, $2::uuid, $3::timestamptz)
+         ON CONFLICT (token_hash) DO NOTHING"
     )
+    .bind(&token_hash)
     .bind(user.id.to_string())
-    .bind(&new_refresh_token)
-    .bind(expires_at.to_rfc3339())
-    .execute(&state.db)
+    .bind(session.expires_at.to_rfc3339())
+    .execute(&mut *tx)
     .await?;
 
+    create_session(
+        &mut *tx,
+        new_session_id,
+        user.id,
+        &new_refresh_token,
+        state.jwt_manager.refresh_token_expiry(),
+        &client_info,
+    )
+    .await?;
+
+    // Rotated tokens can't be replayed after they expire, so their records can go
+    sqlx::query("DELETE FROM revoked_refresh_tokens WHERE expires_at < NOW()")
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
     Ok(Json(AuthResponse {
         access_token: new_access_token,
         refresh_token: new_refresh_token,
@@ -154,103 +365,271 @@ pub async fn logout(
     })))
 }
 
-// Google OAuth structures
+// Sign out everywhere: delete every session belonging to the authenticated user
+pub async fn logout_all(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<serde_json::Value>> {
+    let result = sqlx::query("DELETE FROM sessions WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .execute(&db)
+        .await?;
+
+    let sessions_revoked = result.rows_affected();
+    tracing::info!("Revoked {} sessions for user {}", sessions_revoked, user_id);
+
+    Ok(Json(serde_json::json!({
+        "message": "Logged out of all sessions",
+        "sessions_revoked": sessions_revoked
+    })))
+}
+
+// List the authenticated user's active sessions, flagging the one making the request
+pub async fn list_sessions(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    current_session: Option<Extension<SessionId>>,
+) -> Result<Json<Vec<SessionResponse>>> {
+    let rows = sqlx::query(&format!(
+        "SELECT {}
+         FROM sessions WHERE user_id = $1::uuid AND expires_at > NOW()
+         ORDER BY sessions.created_at DESC",
+        SESSION_COLUMNS
+    ))
+    .bind(user_id.to_string())
+    .fetch_all(&db)
+    .await?;
+
+    let current_id = current_session.map(|Extension(SessionId(id))| id);
+
+    let sessions = rows
+        .iter()
+        .map(|row| {
+            let session = session_from_row(row)?;
+            Ok(SessionResponse {
+                current: Some(session.id) == current_id,
+                id: session.id,
+                created_at: session.created_at,
+                expires_at: session.expires_at,
+                user_agent: session.user_agent,
+                ip_address: session.ip_address,
+            })
+        })
+        .collect::<std::result::Result<Vec<_>, sqlx::Error>>()?;
+
+    Ok(Json(sessions))
+}
+
+// Revoke one of the authenticated user's sessions
+pub async fn revoke_session(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Path(session_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>> {
+    // Scoping the delete by user_id ensures users can only revoke their own sessions
+    let result = sqlx::query("DELETE FROM sessions WHERE id = $1::uuid AND user_id = $2::uuid")
+        .bind(session_id.to_string())
+        .bind(user_id.to_string())
+        .execute(&db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Session not found".to_string()));
+    }
+
+    tracing::info!("Revoked session {} for user {}", session_id, user_id);
+
+    Ok(Json(serde_json::json!({
+        "message": "Session revoked"
+    })))
+}
+
+/// Query string Google and GitHub send to the OAuth callback
 #[derive(Debug, Deserialize)]
-pub struct GoogleAuthQuery {
+pub struct OAuthCallbackQuery {
     code: String,
     state: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
-struct GoogleTokenRequest {
-    code: String,
-    client_id: String,
-    client_secret: String,
-    redirect_uri: String,
-    grant_type: String,
+/// How long a sign-in may take between init and callback, overridable with
+/// `OAUTH_STATE_TTL_SECS`
+const DEFAULT_OAUTH_STATE_TTL_SECS: i64 = 600;
+
+/// Cookie binding a pending OAuth sign-in to the browser that started it
+const OAUTH_STATE_COOKIE: &str = "shield_oauth_state";
+
+/// Initiate the OAuth flow for `provider` (`google` or `github`)
+///
+/// Issues a single-use `state` (checked by the callback to stop CSRF and login fixation)
+/// and a `nonce` for providers that bind one into their `id_token`. Both expire after
+/// `OAUTH_STATE_TTL_SECS`. The state is also set in an HttpOnly cookie, so clients must
+/// call this with credentials; the callback only accepts it from the same browser.
+pub async fn oauth_init(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+) -> Result<Response> {
+    match provider.as_str() {
+        "google" => {
+            let google = oauth_provider(&provider, GoogleProvider::from_env())?;
+            oauth_init_with(&state, &provider, &google).await
+        }
+        "github" => {
+            let github = oauth_provider(&provider, GitHubProvider::from_env())?;
+            oauth_init_with(&state, &provider, &github).await
+        }
+        _ => Err(AppError::NotFound(format!("Unknown sign-in provider '{}'", provider))),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct GoogleTokenResponse {
-    access_token: String,
-    id_token: Option<String>,
+/// OAuth callback for `provider`: verifies the state, signs the user in and redirects
+/// to the frontend with tokens
+pub async fn oauth_callback(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user_agent: Option<TypedHeader<UserAgent>>,
+    Path(provider): Path<String>,
+    headers: HeaderMap,
+    Query(params): Query<OAuthCallbackQuery>,
+) -> Result<Response> {
+    let client_info = ClientInfo::new(user_agent, addr);
+    let redirect = match provider.as_str() {
+        "google" => {
+            let google = oauth_provider(&provider, GoogleProvider::from_env())?;
+            oauth_callback_with(&state, &provider, &google, client_info, &headers, params).await?
+        }
+        "github" => {
+            let github = oauth_provider(&provider, GitHubProvider::from_env())?;
+            oauth_callback_with(&state, &provider, &github, client_info, &headers, params).await?
+        }
+        _ => return Err(AppError::NotFound(format!("Unknown sign-in provider '{}'", provider))),
+    };
+
+    // The state is spent; drop the cookie that carried it
+    Ok(([(header::SET_COOKIE, oauth_state_cookie("", 0))], redirect).into_response())
 }
 
-#[derive(Debug, Deserialize)]
-struct GoogleUserInfo {
-    email: String,
-    name: Option<String>,
-    picture: Option<String>,
-    email_verified: Option<bool>,
+fn oauth_provider<P: OAuthProvider>(name: &str, provider: Option<P>) -> Result<P> {
+    provider.ok_or_else(|| AppError::Internal(format!("{} sign-in is not configured", name)))
 }
 
-// Initiate Google OAuth flow
-pub async fn google_auth_init() -> Result<Json<serde_json::Value>> {
-    let google_client_id = std::env::var("GOOGLE_CLIENT_ID")
-        .map_err(|_| AppError::Internal("GOOGLE_CLIENT_ID not configured".to_string()))?;
+async fn oauth_init_with<P: OAuthProvider>(
+    state: &AppState,
+    provider_name: &str,
+    provider: &P,
+) -> Result<Response> {
+    let oauth_state = token::generate_token();
+    let nonce = token::generate_token();
+    let ttl_secs = env_or("OAUTH_STATE_TTL_SECS", DEFAULT_OAUTH_STATE_TTL_SECS).max(60);
 
-    let redirect_uri = std::env::var("GOOGLE_REDIRECT_URI")
-        .unwrap_or_else(|_| "http://localhost:8000/api/auth/google/callback".to_string());
+    sqlx::query(
+        "INSERT INTO oauth_states (state_hash, provider, nonce, expires_at)
+         VALUES ($1, $2, $3, NOW() + ($4::bigint * INTERVAL '1 second'))"
+    )
+    .bind(token::hash_token(&oauth_state))
+    .bind(provider_name)
+    .bind(&nonce)
+    .bind(ttl_secs)
+    .execute(&state.db)
+    .await?;
 
-    let auth_url = format!(
-        "https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri={}&response_type=code&scope=email%20profile&access_type=offline",
-        google_client_id,
-        urlencoding::encode(&redirect_uri)
-    );
+    let body = Json(serde_json::json!({
+        "url": provider.authorize_url(&oauth_state, &nonce)
+    }));
+    Ok(([(header::SET_COOKIE, oauth_state_cookie(&oauth_state, ttl_secs))], body).into_response())
+}
 
-    Ok(Json(serde_json::json!({
-        "url": auth_url
-    })))
+/// `Set-Cookie` value carrying `value` in the OAuth state cookie for `max_age_secs`
+///
+/// SameSite=Lax still sends it on the provider's top-level redirect back to the callback.
+/// `Secure` is left off only in `DEV_MODE`, for plain-http local servers.
+fn oauth_state_cookie(value: &str, max_age_secs: i64) -> String {
+    let dev_mode = std::env::var("DEV_MODE")
+        .map(|v| v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    format!(
+        "{}={}; Path=/api/auth; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        OAUTH_STATE_COOKIE,
+        value,
+        max_age_secs,
+        if dev_mode { "" } else { "; Secure" }
+    )
 }
 
-// Google OAuth callback handler
-pub async fn google_auth_callback(
-    State(state): State<AppState>,
-    Query(params): Query<GoogleAuthQuery>,
-) -> Result<Redirect> {
-    let google_client_id = std::env::var("GOOGLE_CLIENT_ID")
-        .map_err(|_| AppError::Internal("GOOGLE_CLIENT_ID not configured".to_string()))?;
+/// Check that the browser presenting `oauth_state` is the one that started the sign-in
+///
+/// Without this, anyone could start a sign-in with their own account and send the callback
+/// URL to a victim, logging the victim in as them.
+fn check_oauth_state_cookie(headers: &HeaderMap, oauth_state: &str) -> Result<()> {
+    let cookie = headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == OAUTH_STATE_COOKIE)
+        .map(|(_, value)| value);
+
+    match cookie {
+        Some(value) if !value.is_empty() && value == oauth_state => Ok(()),
+        _ => Err(AppError::Unauthorized(
+            "OAuth sign-in was started in a different browser".to_string(),
+        )),
+    }
+}
 
-    let google_client_secret = std::env::var("GOOGLE_CLIENT_SECRET")
-        .map_err(|_| AppError::Internal("GOOGLE_CLIENT_SECRET not configured".to_string()))?;
+/// Consume a pending sign-in's state, returning its nonce if it was valid and unexpired
+async fn consume_oauth_state(db: &PgPool, provider_name: &str, oauth_state: &str) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar(
+        "DELETE FROM oauth_states
+         WHERE state_hash = $1 AND provider = $2 AND expires_at > NOW()
+         RETURNING nonce"
+    )
+    .bind(token::hash_token(oauth_state))
+    .bind(provider_name)
+    .fetch_optional(db)
+    .await?)
+}
 
-    let redirect_uri = std::env::var("GOOGLE_REDIRECT_URI")
-        .unwrap_or_else(|_| "http://localhost:8000/api/auth/google/callback".to_string());
+async fn oauth_callback_with<P: OAuthProvider>(
+    state: &AppState,
+    provider_name: &str,
+    provider: &P,
+    client_info: ClientInfo,
+    headers: &HeaderMap,
+    params: OAuthCallbackQuery,
+) -> Result<Redirect> {
+    // States are single-use: consume it before touching the authorization code, so one
+    // presented by the wrong browser is burned too
+    let oauth_state = params
+        .state
+        .as_deref()
+        .ok_or_else(|| AppError::Unauthorized("Missing OAuth state".to_string()))?;
+    let nonce = consume_oauth_state(&state.db, provider_name, oauth_state)
+        .await?
+        .ok_or_else(|| {
+            tracing::warn!("Rejected {} callback with unknown or expired state", provider_name);
+            AppError::Unauthorized("Invalid or expired OAuth state".to_string())
+        })?;
+    check_oauth_state_cookie(headers, oauth_state).inspect_err(|_| {
+        tracing::warn!("Rejected {} callback whose state cookie doesn't match", provider_name);
+    })?;
 
-    // Exchange authorization code for access token
     let client = reqwest::Client::new();
-    let token_response = client
-        .post("https://oauth2.googleapis.com/token")
-        .form(&[
-            ("code", params.code.as_str()),
-            ("client_id", &google_client_id),
-            ("client_secret", &google_client_secret),
-            ("redirect_uri", &redirect_uri),
-            ("grant_type", "authorization_code"),
-        ])
-        .send()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to exchange code: {}", e)))?
-        .json::<GoogleTokenResponse>()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to parse token response: {}", e)))?;
-
-    // Get user info from Google
-    let user_info = client
-        .get("https://www.googleapis.com/oauth2/v2/userinfo")
-        .bearer_auth(&token_response.access_token)
-        .send()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to get user info: {}", e)))?
-        .json::<GoogleUserInfo>()
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to parse user info: {}", e)))?;
+    let tokens = provider.exchange_code(&client, &params.code).await.map_err(|e| {
+        tracing::warn!("{} code exchange failed: {:#}", provider_name, e);
+        AppError::Unauthorized(format!("{} sign-in failed", provider_name))
+    })?;
+    let user_info = provider.fetch_user_info(&client, &tokens, &nonce).await.map_err(|e| {
+        tracing::warn!("{} user lookup failed: {:#}", provider_name, e);
+        AppError::Unauthorized(format!("{} sign-in failed", provider_name))
+    })?;
 
     // Check if user exists - use string cast for enum
-    let existing_user_row = sqlx::query(
-        "SELECT id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at, updated_at
-         FROM users WHERE email = $1"
-    )
+    let existing_user_row = sqlx::query(&format!(
+        "SELECT {}
+         FROM users WHERE email = $1",
+        USER_COLUMNS
+    ))
     .bind(&user_info.email)
     .fetch_optional(&state.db)
     .await?;
@@ -258,16 +637,17 @@ pub async fn google_auth_callback(
     let (user, is_new_user) = match existing_user_row {
         Some(row) => (user_from_row(&row)?, false),
         None => {
-            // Create new user with Google auth - use string cast for enum
-            let new_user_row = sqlx::query(
+            // Create new user with the provider as auth method - use string cast for enum
+            let new_user_row = sqlx::query(&format!(
                 "INSERT INTO users (email, full_name, password_hash, auth_method)
                  VALUES ($1, $2, $3, $4::auth_method)
-                 RETURNING id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at, updated_at"
-            )
+                 RETURNING {}",
+                USER_COLUMNS
+            ))
             .bind(&user_info.email)
             .bind(user_info.name.as_deref().unwrap_or(""))
             .bind(Option::<String>::None) // No password for OAuth users
-            .bind(AuthMethod::Google.as_str())
+            .bind(provider.auth_method().as_str())
             .fetch_one(&state.db)
             .await?;
             (user_from_row(&new_user_row)?, true)
@@ -299,7 +679,7 @@ pub async fn google_auth_callback(
         let mnemonic_str = mnemonic.to_string();
 
         // Create wallet from mnemonic to get address
-        let network = Network::MainNetwork;
+        let network = get_network();
         let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&mnemonic, network)
             .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
 
@@ -307,9 +687,8 @@ pub async fn google_auth_callback(
             .map_err(|e| AppError::Internal(format!("Failed to get address: {}", e)))?;
 
         // Get current block height for birthday (each wallet has its own birthday!)
-        let lightwalletd_url = std::env::var("LIGHTWALLETD_MAINNET")
-            .unwrap_or_else(|_| "https://na.zec.rocks:443".to_string());
-        let mut lightwalletd_client = crate::zcash::lightwalletd::LightwalletdClient::new(lightwalletd_url);
+        let lightwalletd_url = get_lightwalletd_url(network);
+        let mut lightwalletd_client = crate::zcash::lightwalletd::LightwalletdClient::from_endpoint_list(&lightwalletd_url);
 
         // Fetch current block height
         let birthday_height: i64 = match lightwalletd_client.connect().await {
@@ -320,14 +699,26 @@ pub async fn google_auth_callback(
                         height as i64
                     },
                     Err(e) => {
-                        tracing::warn!("Failed to get block height for OAuth user, using Sapling activation: {}", e);
-                        419200 // Sapling activation height as fallback
+                        let fallback = fallback_birthday_height();
+                        tracing::error!(
+                            "Failed to get block height for OAuth user {}, using fallback birthday {}: {}",
+                            user.id,
+                            fallback,
+                            e
+                        );
+                        fallback
                     }
                 }
             },
             Err(e) => {
-                tracing::warn!("Failed to connect to lightwalletd for OAuth user, using Sapling activation: {}", e);
-                419200 // Sapling activation height as fallback
+                let fallback = fallback_birthday_height();
+                tracing::error!(
+                    "Failed to connect to lightwalletd for OAuth user {}, using fallback birthday {}: {}",
+                    user.id,
+                    fallback,
+                    e
+                );
+                fallback
             }
         };
 
@@ -335,14 +726,15 @@ pub async fn google_auth_callback(
 
         // Store wallet with AWAIT to ensure completion - use UUID casts
         sqlx::query(
-            "INSERT INTO wallets (id, user_id, encrypted_mnemonic, address, birthday_height, created_at)
-             VALUES ($1::uuid, $2::uuid, $3, $4, $5, NOW())"
+            "INSERT INTO wallets (id, user_id, encrypted_mnemonic, address, birthday_height, network, created_at)
+             VALUES ($1::uuid, $2::uuid, $3, $4, $5, $6, NOW())"
         )
         .bind(wallet_id.to_string())
         .bind(user.id.to_string())
         .bind(&mnemonic_str) // TODO: ENCRYPT THIS IN PRODUCTION!
         .bind(&address)
         .bind(birthday_height)
+        .bind(network_name(network))
         .execute(&state.db)
         .await
         .map_err(|e| {
@@ -366,19 +758,20 @@ pub async fn google_auth_callback(
         tracing::info!("Wallet already exists for OAuth user {}, skipping creation", user.id);
     }
 
-    // Generate tokens
-    let access_token = state.jwt_manager.generate_access_token(user.id)?;
+    // Generate tokens for a new session
+    let session_id = Uuid::new_v4();
+    let access_token = state.jwt_manager.generate_access_token(user.id, session_id)?;
     let refresh_token = state.jwt_manager.generate_refresh_token(user.id)?;
 
-    // Store refresh token - use UUID cast
-    let expires_at = Utc::now() + Duration::seconds(604800);
-    sqlx::query(
-        "INSERT INTO sessions (user_id, refresh_token, expires_at) VALUES ($1::uuid, $2, $3::timestamptz)"
+    // Store refresh token
+    create_session(
+        &state.db,
+        session_id,
+        user.id,
+        &refresh_token,
+        state.jwt_manager.refresh_token_expiry(),
+        &client_info,
     )
-    .bind(user.id.to_string())
-    .bind(&refresh_token)
-    .bind(expires_at.to_rfc3339())
-    .execute(&state.db)
     .await?;
 
     // Redirect to frontend with tokens
@@ -398,8 +791,12 @@ pub async fn google_auth_callback(
 // Email/Password signup endpoint
 pub async fn signup(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(request): Json<CreateUserRequest>,
 ) -> Result<Json<AuthResponse>> {
+    let client_info = ClientInfo::new(user_agent, addr);
+
     // Validate request
     request.validate().map_err(|e| {
         AppError::Validation(format!("Validation error: {}", e))
@@ -418,15 +815,16 @@ pub async fn signup(
     }
 
     // Hash password with bcrypt
-    let password_hash = bcrypt::hash(&request.password, bcrypt::DEFAULT_COST)
+    let password_hash = bcrypt::hash(&request.password, bcrypt_cost())
         .map_err(|e| AppError::Internal(format!("Failed to hash password: {}", e)))?;
 
     // Create new user with email auth - use string casts
-    let new_user_row = sqlx::query(
+    let new_user_row = sqlx::query(&format!(
         "INSERT INTO users (email, full_name, password_hash, auth_method, email_verified)
          VALUES ($1, $2, $3, $4::auth_method, $5)
-         RETURNING id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at, updated_at"
-    )
+         RETURNING {}",
+        USER_COLUMNS
+    ))
     .bind(&request.email)
     .bind(&request.full_name)
     .bind(&password_hash)
@@ -439,6 +837,11 @@ pub async fn signup(
 
     tracing::info!("Created new email user: {}", new_user.id);
 
+    // Don't fail signup if the verification email can't be sent; the user can resend it
+    if let Err(e) = issue_verification_email(&state.db, new_user.id, &new_user.email).await {
+        tracing::error!("Failed to send verification email to user {}: {:?}", new_user.id, e);
+    }
+
     // Auto-create Zcash wallet - use UUID cast
     let existing_wallet = sqlx::query(
         "SELECT id::text FROM wallets WHERE user_id = $1::uuid"
@@ -459,7 +862,7 @@ pub async fn signup(
         let mnemonic_str = mnemonic.to_string();
 
         // Create wallet from mnemonic to get address
-        let network = Network::MainNetwork;
+        let network = get_network();
         let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&mnemonic, network)
             .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
 
@@ -467,23 +870,22 @@ pub async fn signup(
             .map_err(|e| AppError::Internal(format!("Failed to get address: {}", e)))?;
 
         // Use a recent block height as birthday (skip lightwalletd to avoid timeout)
-        // As of Dec 2025, Zcash mainnet is around block 3,154,000
-        // Setting to 3,150,000 means only ~4000 blocks to scan for new wallets
-        let birthday_height: i64 = 3150000;
+        let birthday_height = fallback_birthday_height();
         tracing::info!("Setting wallet birthday to recent height: {}", birthday_height);
 
         let wallet_id = Uuid::new_v4();
 
         // Use UUID casts for wallet insert
         sqlx::query(
-            "INSERT INTO wallets (id, user_id, encrypted_mnemonic, address, birthday_height, created_at)
-             VALUES ($1::uuid, $2::uuid, $3, $4, $5, NOW())"
+            "INSERT INTO wallets (id, user_id, encrypted_mnemonic, address, birthday_height, network, created_at)
+             VALUES ($1::uuid, $2::uuid, $3, $4, $5, $6, NOW())"
         )
         .bind(wallet_id.to_string())
         .bind(new_user.id.to_string())
         .bind(&mnemonic_str)
         .bind(&address)
         .bind(birthday_height)
+        .bind(network_name(network))
         .execute(&state.db)
         .await?;
 
@@ -500,19 +902,20 @@ pub async fn signup(
         }
     }
 
-    // Generate tokens
-    let access_token = state.jwt_manager.generate_access_token(new_user.id)?;
+    // Generate tokens for a new session
+    let session_id = Uuid::new_v4();
+    let access_token = state.jwt_manager.generate_access_token(new_user.id, session_id)?;
     let refresh_token = state.jwt_manager.generate_refresh_token(new_user.id)?;
 
-    // Store refresh token - use UUID cast
-    let expires_at = Utc::now() + Duration::seconds(604800);
-    sqlx::query(
-        "INSERT INTO sessions (user_id, refresh_token, expires_at) VALUES ($1::uuid, $2, $3::timestamptz)"
+    // Store refresh token
+    create_session(
+        &state.db,
+        session_id,
+        new_user.id,
+        &refresh_token,
+        state.jwt_manager.refresh_token_expiry(),
+        &client_info,
     )
-    .bind(new_user.id.to_string())
-    .bind(&refresh_token)
-    .bind(expires_at.to_rfc3339())
-    .execute(&state.db)
     .await?;
 
     Ok(Json(AuthResponse {
@@ -525,18 +928,23 @@ pub async fn signup(
 // Email/Password login endpoint
 pub async fn login(
     State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    user_agent: Option<TypedHeader<UserAgent>>,
     Json(request): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>> {
+    let client_info = ClientInfo::new(user_agent, addr);
+
     // Validate request
     request.validate().map_err(|e| {
         AppError::Validation(format!("Validation error: {}", e))
     })?;
 
     // Get user by email - use string casts
-    let user_row = sqlx::query(
-        "SELECT id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at::text, updated_at::text
-         FROM users WHERE email = $1"
-    )
+    let user_row = sqlx::query(&format!(
+        "SELECT {}
+         FROM users WHERE email = $1",
+        USER_COLUMNS
+    ))
     .bind(&request.email)
     .fetch_optional(&state.db)
     .await?
@@ -544,11 +952,17 @@ pub async fn login(
 
     let user = user_from_row(&user_row)?;
 
-    // Check if user registered with email/password (not Google OAuth)
+    // Check if user registered with email/password (not OAuth)
     if user.auth_method != AuthMethod::Email {
-        return Err(AppError::Validation(
-            "This email is registered with Google. Please use Google Sign In.".to_string()
-        ));
+        return Err(AppError::Validation(format!(
+            "This email is registered with {0}. Please use {0} Sign In.",
+            user.auth_method.display_name()
+        )));
+    }
+
+    // A locked account stays locked even if this password is right
+    if let Some(retry_after_secs) = login_lockout_remaining(&state.db, user.id).await? {
+        return Err(AppError::AccountLocked { retry_after_secs });
     }
 
     // Verify password
@@ -559,22 +973,29 @@ pub async fn login(
         .map_err(|e| AppError::Internal(format!("Failed to verify password: {}", e)))?;
 
     if !password_valid {
+        if let Some(retry_after_secs) = record_failed_login(&state.db, user.id).await? {
+            return Err(AppError::AccountLocked { retry_after_secs });
+        }
         return Err(AppError::Unauthorized("Invalid credentials".to_string()));
     }
 
-    // Generate tokens
-    let access_token = state.jwt_manager.generate_access_token(user.id)?;
+    clear_failed_logins(&state.db, user.id).await?;
+    upgrade_password_hash(&state.db, user.id, &request.password, password_hash).await;
+
+    // Generate tokens for a new session
+    let session_id = Uuid::new_v4();
+    let access_token = state.jwt_manager.generate_access_token(user.id, session_id)?;
     let refresh_token = state.jwt_manager.generate_refresh_token(user.id)?;
 
-    // Store refresh token - use UUID cast
-    let expires_at = Utc::now() + Duration::seconds(604800);
-    sqlx::query(
-        "INSERT INTO sessions (user_id, refresh_token, expires_at) VALUES ($1::uuid, $2, $3::timestamptz)"
+    // Store refresh token
+    create_session(
+        &state.db,
+        session_id,
+        user.id,
+        &refresh_token,
+        state.jwt_manager.refresh_token_expiry(),
+        &client_info,
     )
-    .bind(user.id.to_string())
-    .bind(&refresh_token)
-    .bind(expires_at.to_rfc3339())
-    .execute(&state.db)
     .await?;
 
     Ok(Json(AuthResponse {
@@ -583,3 +1004,222 @@ pub async fn login(
         user: UserResponse::from(user),
     }))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailQuery {
+    token: String,
+}
+
+/// Create a fresh verification token for a user and email them the link
+///
+/// Any previously issued token for the user is deleted, so only the latest link works.
+async fn issue_verification_email(db: &PgPool, user_id: Uuid, email_address: &str) -> Result<()> {
+    let verification_token = token::generate_token();
+    let expires_at = Utc::now() + Duration::hours(EMAIL_VERIFICATION_TTL_HOURS);
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (user_id, token_hash, expires_at)
+         VALUES ($1::uuid, $2, $3::timestamptz)"
+    )
+    .bind(user_id.to_string())
+    .bind(token::hash_token(&verification_token))
+    .bind(expires_at.to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let verify_url = std::env::var("EMAIL_VERIFICATION_URL")
+        .unwrap_or_else(|_| "http://localhost:8000/api/auth/verify".to_string());
+    let link = format!("{}?token={}", verify_url, verification_token);
+
+    email::send_email(
+        email_address,
+        "Verify your Shield email address",
+        &format!(
+            "Welcome to Shield!\n\n\
+            Confirm your email address by opening the link below:\n\n\
+            {}\n\n\
+            This link expires in {} hours. If you didn't sign up, you can ignore this email.",
+            link,
+            EMAIL_VERIFICATION_TTL_HOURS
+        ),
+    )
+    .await
+    .map_err(|e| AppError::Internal(format!("Failed to send verification email: {}", e)))?;
+
+    Ok(())
+}
+
+// Send (or resend) the verification email for the authenticated user
+pub async fn send_verification_email(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<serde_json::Value>> {
+    let user_row = sqlx::query(
+        "SELECT email, email_verified FROM users WHERE id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(&db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let email_address: String = user_row.get("email");
+    let email_verified: Option<bool> = user_row.get("email_verified");
+
+    if email_verified.unwrap_or(false) {
+        return Ok(Json(serde_json::json!({
+            "message": "Email already verified"
+        })));
+    }
+
+    issue_verification_email(&db, user_id, &email_address).await?;
+
+    tracing::info!("Sent verification email to user {}", user_id);
+
+    Ok(Json(serde_json::json!({
+        "message": "Verification email sent"
+    })))
+}
+
+// Email verification link handler
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Query(params): Query<VerifyEmailQuery>,
+) -> Result<Redirect> {
+    // Tokens are single-use: consume it and get the owner in one step
+    let token_row = sqlx::query(
+        "DELETE FROM email_verification_tokens
+         WHERE token_hash = $1 AND expires_at > NOW()
+         RETURNING user_id::text"
+    )
+    .bind(token::hash_token(&params.token))
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::Validation("Invalid or expired verification link".to_string()))?;
+
+    let user_id: String = token_row.get("user_id");
+
+    sqlx::query("UPDATE users SET email_verified = TRUE WHERE id = $1::uuid")
+        .bind(&user_id)
+        .execute(&state.db)
+        .await?;
+
+    tracing::info!("Verified email for user {}", user_id);
+
+    let frontend_url = std::env::var("FRONTEND_URL")
+        .unwrap_or_else(|_| "http://localhost:3000".to_string());
+
+    Ok(Redirect::to(&format!("{}/auth/verified", frontend_url)))
+}
+
+/// Spawn a task that periodically deletes expired sessions and auth tokens
+///
+/// Nothing else removes a session that simply expires, so without this the auth
+/// tables grow for as long as the deployment runs.
+pub fn spawn_retention_cleanup(db: PgPool) -> tokio::task::JoinHandle<()> {
+    let interval = std::time::Duration::from_secs(
+        env_or("RETENTION_CLEANUP_INTERVAL_SECS", DEFAULT_RETENTION_CLEANUP_INTERVAL_SECS).max(60),
+    );
+    tracing::info!("Retention cleanup enabled: every {:?}", interval);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = purge_expired_auth_data(&db).await {
+                tracing::warn!("Retention cleanup failed: {:?}", e);
+            }
+        }
+    })
+}
+
+/// Delete expired rows from each auth table, logging how many were removed
+async fn purge_expired_auth_data(db: &PgPool) -> Result<()> {
+    for table in [
+        "sessions",
+        "email_verification_tokens",
+        "revoked_refresh_tokens",
+        "oauth_states",
+    ] {
+        let removed = sqlx::query(&format!("DELETE FROM {} WHERE expires_at < NOW()", table))
+            .execute(db)
+            .await?
+            .rows_affected();
+        if removed > 0 {
+            tracing::info!("Retention cleanup: removed {} expired row(s) from {}", removed, table);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn cookie_headers(cookie: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, HeaderValue::from_str(cookie).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_oauth_state_cookie_must_match() {
+        let headers = cookie_headers("theme=dark; shield_oauth_state=abc123; other=1");
+        assert!(check_oauth_state_cookie(&headers, "abc123").is_ok());
+        assert!(matches!(
+            check_oauth_state_cookie(&headers, "attacker-state"),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn test_oauth_state_cookie_missing_or_empty() {
+        assert!(check_oauth_state_cookie(&HeaderMap::new(), "abc123").is_err());
+        assert!(check_oauth_state_cookie(&cookie_headers("shield_oauth_state="), "").is_err());
+        assert!(check_oauth_state_cookie(&cookie_headers("shield_oauth_state_x=abc123"), "abc123").is_err());
+    }
+
+    #[test]
+    fn test_oauth_state_cookie_attributes() {
+        let cookie = oauth_state_cookie("abc123", 600);
+        assert!(cookie.starts_with("shield_oauth_state=abc123;"));
+        assert!(cookie.contains("HttpOnly"));
+        assert!(cookie.contains("SameSite=Lax"));
+        assert!(cookie.contains("Max-Age=600"));
+    }
+
+    // Needs a migrated Postgres database in DATABASE_URL
+    #[tokio::test]
+    #[ignore]
+    async fn test_oauth_state_is_consumed_once() {
+        let db = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let oauth_state = token::generate_token();
+        sqlx::query(
+            "INSERT INTO oauth_states (state_hash, provider, nonce, expires_at)
+             VALUES ($1, 'google', 'nonce', NOW() + INTERVAL '1 minute')"
+        )
+        .bind(token::hash_token(&oauth_state))
+        .execute(&db)
+        .await
+        .unwrap();
+
+        // Wrong provider leaves it in place
+        assert_eq!(consume_oauth_state(&db, "github", &oauth_state).await.unwrap(), None);
+        assert_eq!(
+            consume_oauth_state(&db, "google", &oauth_state).await.unwrap().as_deref(),
+            Some("nonce")
+        );
+        assert_eq!(consume_oauth_state(&db, "google", &oauth_state).await.unwrap(), None);
+    }
+}
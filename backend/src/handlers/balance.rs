@@ -1,167 +1,751 @@
-use crate::middleware::{AppError, Result};
-use crate::zcash::{account, database, lightwalletd, scanner};
-use axum::{extract::State, Json};
-use bip39::Mnemonic;
+use crate::handlers::common::{
+    connect_lightwalletd, derive_account_spending_key, format_zec, get_lightwalletd_url, load_transparent_utxos,
+    load_wallet_config, parse_network, pooled_wallet_database, wallet_db_path, FiatQuery, WalletConfig,
+};
+use crate::middleware::{AppError, Json, Result};
+use crate::models::row::parse_optional_datetime;
+use crate::utils::{env::env_or, webhook};
+use crate::zcash::{account, database, lightwalletd, scanner, transaction};
+use axum::extract::{Extension, Query, State};
+use chrono::{DateTime, Utc};
 use once_cell::sync::Lazy;
-use rand::rngs::OsRng;
 use rusqlite::Connection as SqliteConnection;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
+use sqlx::{PgConnection, PgPool, Row};
 use std::collections::HashMap;
-use std::env;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex, Notify, OwnedMutexGuard, Semaphore};
+use tracing::Instrument;
 use uuid::Uuid;
-use zcash_client_sqlite::{util::SystemClock, WalletDb};
+use zcash_client_backend::data_api::{Account, AccountBalance, WalletRead};
+use zcash_client_sqlite::AccountUuid;
 use zcash_protocol::consensus::Network;
 
 // Global mutex map for per-user database access to prevent concurrent initialization
 static USER_DB_LOCKS: Lazy<Mutex<HashMap<Uuid, Arc<Mutex<()>>>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
 
+// Last balance computed for each user and account selection, with when it was computed
+static BALANCE_CACHE: Lazy<std::sync::Mutex<HashMap<(Uuid, Option<u32>), (Instant, BalanceResponse)>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Outcome of a scan as shared with the requests that joined it
+type SharedScanResult = std::result::Result<BalanceResponse, String>;
+
+// Scans in progress for each user and account selection; concurrent requests subscribe
+// to the running scan's result instead of queueing up to scan again
+static IN_FLIGHT_SCANS: Lazy<std::sync::Mutex<HashMap<(Uuid, Option<u32>), broadcast::Sender<SharedScanResult>>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// How long a computed balance is served without rescanning, unless overridden by
+/// `BALANCE_CACHE_TTL_SECS`
+const DEFAULT_BALANCE_CACHE_TTL_SECS: u64 = 15;
+
+/// Defaults for the background sync worker, overridable with `BACKGROUND_SYNC_INTERVAL_SECS`,
+/// `BACKGROUND_SYNC_STALE_SECS`, `BACKGROUND_SYNC_ACTIVE_HOURS` and `BACKGROUND_SYNC_CONCURRENCY`
+const DEFAULT_BACKGROUND_SYNC_INTERVAL_SECS: u64 = 300;
+const DEFAULT_BACKGROUND_SYNC_STALE_SECS: i64 = 600;
+const DEFAULT_BACKGROUND_SYNC_ACTIVE_HOURS: i32 = 72;
+const DEFAULT_BACKGROUND_SYNC_CONCURRENCY: usize = 2;
+
+/// Sync job queue tuning: idle poll interval, retry limit and backoff, and how long a
+/// claimed job may run before another worker assumes it crashed
+const SYNC_JOB_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_SYNC_JOB_ATTEMPTS: i32 = 5;
+const SYNC_JOB_RETRY_BASE_SECS: f64 = 30.0;
+const SYNC_JOB_LOCK_TIMEOUT_SECS: i64 = 600;
+
+// Wakes the sync job worker as soon as a job is queued
+static SYNC_JOB_NOTIFY: Lazy<Notify> = Lazy::new(Notify::new);
+
 #[derive(Clone)]
 pub struct BalanceState {
     pub db: PgPool,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct GetBalanceRequest {
-    pub user_id: Uuid,
+#[derive(Serialize, Deserialize, Default)]
+pub struct BalanceRequest {
+    /// Skip the short-lived balance cache and rescan
+    #[serde(default)]
+    pub force: bool,
+    /// Only report this HD account's balance (default: all accounts combined)
+    #[serde(default)]
+    pub account_index: Option<u32>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct BalanceResponse {
+    /// Confirmed total, kept for backward compatibility (same as `confirmed_zec`)
     pub balance_zec: String,
+    pub confirmed_zec: String,
+    pub pending_zec: String,
+    pub spendable_zec: String,
+    /// Unspent transparent funds; not included in the shielded figures above
+    pub transparent_zec: String,
+    /// Set when there are transparent funds, which must be shielded before they can be
+    /// spent privately
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transparent_hint: Option<String>,
     pub synced: bool,
     pub last_synced_height: Option<i64>,
     pub blocks_scanned: Option<usize>,
     pub notes_found: Option<usize>,
     pub chain_tip: Option<u64>,
+    /// Confirmed total in `fiat_currency`, only with `?fiat=<currency>` and when the price
+    /// source is reachable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance_fiat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat_currency: Option<String>,
+    /// Confirmed total behind `balance_zec`, kept for fiat conversion
+    #[serde(skip)]
+    confirmed_zatoshis: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SyncStatusResponse {
+    pub last_synced_height: Option<i64>,
+    /// `None` if lightwalletd couldn't be reached
+    pub chain_tip: Option<u64>,
+    pub blocks_behind: Option<u64>,
+    /// Within `QUICK_BALANCE_MAX_LAG_BLOCKS` of the chain tip; false if either height is unknown
+    pub is_synced: bool,
+    pub last_synced_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QuickBalanceResponse {
+    pub balance_zec: String,
+    /// True when the cached balance is too far behind the chain tip; a background sync
+    /// has been started and the UI may want to poll the full balance endpoint
+    pub stale: bool,
+    pub last_synced_height: Option<i64>,
+    pub chain_tip: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NoteInfo {
+    pub txid: String,
+    /// "sapling" or "orchard"
+    pub pool: String,
+    pub value_zatoshis: i64,
+    pub value_zec: String,
+    pub is_change: bool,
+    pub block_height: Option<i64>,
+    /// Confirmations as of the last sync (0 while unmined)
+    pub confirmations: i64,
 }
 
-/// Get wallet balance for a user
+#[derive(Serialize, Deserialize)]
+pub struct NotesResponse {
+    pub notes: Vec<NoteInfo>,
+    pub total_zec: String,
+    pub last_synced_height: Option<i64>,
+}
+
+/// Default number of blocks a wallet may lag the chain tip and still be served from cache
+const DEFAULT_QUICK_BALANCE_MAX_LAG: u64 = 10;
+
+/// Get wallet balance for the authenticated user
 /// Performs full blockchain scanning and returns actual balance
+///
+/// A balance computed within the last `BALANCE_CACHE_TTL_SECS` is returned as is,
+/// unless the request sets `force`. With `?fiat=<currency>` (e.g. `?fiat=eur`) the
+/// confirmed total is also given in that currency.
 #[axum::debug_handler]
 pub async fn get_balance(
     State(state): State<BalanceState>,
-    Json(payload): Json<GetBalanceRequest>,
+    Extension(user_id): Extension<Uuid>,
+    Query(fiat): Query<FiatQuery>,
+    payload: Option<Json<BalanceRequest>>,
 ) -> Result<Json<BalanceResponse>> {
-    tracing::info!("Balance check requested for user {}", payload.user_id);
+    tracing::info!("Balance check requested for user {}", user_id);
+    let BalanceRequest { force, account_index } = payload.map(|Json(request)| request).unwrap_or_default();
+    let fiat_price = fiat.price().await?;
+
+    let cached = if force { None } else { cached_balance(user_id, account_index) };
+    let mut balance = match cached {
+        Some(cached) => {
+            tracing::info!("Serving cached balance for user {}", user_id);
+            cached
+        }
+        None => coalesced_sync_balance(&state.db, user_id, force, account_index).await?,
+    };
 
-    // Acquire per-user lock to prevent concurrent database access
+    balance.balance_fiat = fiat_price.as_ref().map(|fiat| fiat.format(balance.confirmed_zatoshis as i64));
+    balance.fiat_currency = fiat_price.map(|fiat| fiat.currency);
+    Ok(Json(balance))
+}
+
+/// Run `sync_balance`, or wait for the identical scan already running for this user
+///
+/// The scan runs in its own task, so it completes and is shared even if the request that
+/// started it goes away. Its logs stay in the starting request's span. Requests that joined
+/// get a failed scan's error as `Internal`.
+async fn coalesced_sync_balance(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    force: bool,
+    account_index: Option<u32>,
+) -> Result<BalanceResponse> {
+    let key = (user_id, account_index);
+    let scan = {
+        let mut scans = IN_FLIGHT_SCANS.lock().unwrap_or_else(|e| e.into_inner());
+        match scans.get(&key) {
+            Some(sender) => Err(sender.subscribe()),
+            None => {
+                let (sender, _) = broadcast::channel(1);
+                scans.insert(key, sender.clone());
+
+                let pg_pool = pg_pool.clone();
+                Ok(tokio::spawn(async move {
+                    let result =
+                        sync_balance(&pg_pool, user_id, force, account_index, scanner::ScanControl::default()).await;
+
+                    // Unregister before publishing, so a request arriving after this point
+                    // starts a fresh scan rather than subscribing too late
+                    IN_FLIGHT_SCANS.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+                    let shared = match &result {
+                        Ok(balance) => Ok(balance.clone()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    let _ = sender.send(shared);
+                    result
+                }.in_current_span()))
+            }
+        }
+    };
+
+    match scan {
+        Ok(handle) => handle
+            .await
+            .map_err(|e| AppError::Internal(format!("Balance scan task failed: {}", e)))?,
+        Err(mut receiver) => {
+            tracing::info!("Joining in-flight balance scan for user {}", user_id);
+            match receiver.recv().await {
+                Ok(result) => result.map_err(AppError::Internal),
+                Err(e) => Err(AppError::Internal(format!("Balance scan ended without a result: {}", e))),
+            }
+        }
+    }
+}
+
+/// Spawn a worker that keeps recently active users' wallets close to the chain tip
+///
+/// Every `BACKGROUND_SYNC_INTERVAL_SECS` it rescans wallets last synced more than
+/// `BACKGROUND_SYNC_STALE_SECS` ago whose owner has signed in or refreshed a session within
+/// `BACKGROUND_SYNC_ACTIVE_HOURS`, at most `BACKGROUND_SYNC_CONCURRENCY` at a time. Users
+/// with a scan already running are skipped, so the next balance check stays fast without
+/// the worker ever competing with a user-initiated scan.
+pub fn spawn_background_sync(db: PgPool) -> tokio::task::JoinHandle<()> {
+    let interval = Duration::from_secs(
+        env_or("BACKGROUND_SYNC_INTERVAL_SECS", DEFAULT_BACKGROUND_SYNC_INTERVAL_SECS).max(1),
+    );
+    let concurrency =
+        env_or("BACKGROUND_SYNC_CONCURRENCY", DEFAULT_BACKGROUND_SYNC_CONCURRENCY).max(1);
+
+    tracing::info!(
+        "Background wallet sync enabled: every {:?}, {} at a time",
+        interval,
+        concurrency
+    );
+
+    tokio::spawn(async move {
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = background_sync_once(&db, &semaphore).await {
+                tracing::warn!("Background wallet sync failed: {:?}", e);
+            }
+        }
+    })
+}
+
+/// Rescan every stale, recently active wallet once, waiting for all scans to finish
+async fn background_sync_once(db: &PgPool, semaphore: &Arc<Semaphore>) -> Result<()> {
+    let user_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT w.user_id::text FROM wallets w
+         WHERE (w.last_synced_at IS NULL
+                OR w.last_synced_at < NOW() - ($1::bigint * INTERVAL '1 second'))
+           AND EXISTS (
+               SELECT 1 FROM sessions s
+               WHERE s.user_id = w.user_id
+                 AND s.created_at > NOW() - make_interval(hours => $2)
+           )
+         ORDER BY w.last_synced_at NULLS FIRST"
+    )
+    .bind(env_or("BACKGROUND_SYNC_STALE_SECS", DEFAULT_BACKGROUND_SYNC_STALE_SECS))
+    .bind(env_or("BACKGROUND_SYNC_ACTIVE_HOURS", DEFAULT_BACKGROUND_SYNC_ACTIVE_HOURS))
+    .fetch_all(db)
+    .await?;
+
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+    tracing::info!("Background sync: {} stale active wallet(s)", user_ids.len());
+
+    let mut scans = tokio::task::JoinSet::new();
+    for user_id in user_ids {
+        let Ok(user_id) = Uuid::parse_str(&user_id) else {
+            continue;
+        };
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| AppError::Internal(format!("Background sync semaphore closed: {}", e)))?;
+
+        if scan_in_progress(user_id).await {
+            tracing::debug!("Background sync: user {} is already scanning, skipping", user_id);
+            continue;
+        }
+
+        let db = db.clone();
+        scans.spawn(async move {
+            let _permit = permit;
+            // Not forced: joins or reuses a scan the user started in the meantime
+            if let Err(e) = coalesced_sync_balance(&db, user_id, false, None).await {
+                tracing::warn!("Background sync failed for user {}: {:?}", user_id, e);
+            }
+        });
+    }
+
+    while scans.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// Take the per-user lock that serializes account creation and scanning
+///
+/// Every path that may create the primary account or scan the wallet database holds
+/// this, so a balance refresh and a send can't both create the account or race each
+/// other into a checkpoint conflict.
+pub(crate) async fn lock_user_wallet(user_id: Uuid) -> OwnedMutexGuard<()> {
     let user_lock = {
         let mut locks = USER_DB_LOCKS.lock().await;
         locks
-            .entry(payload.user_id)
+            .entry(user_id)
             .or_insert_with(|| Arc::new(Mutex::new(())))
             .clone()
     };
-    let _guard = user_lock.lock().await;
-    tracing::info!("Acquired database lock for user {}", payload.user_id);
+    user_lock.lock_owned().await
+}
+
+/// Whether another task currently holds the user's scan lock
+async fn scan_in_progress(user_id: Uuid) -> bool {
+    let locks = USER_DB_LOCKS.lock().await;
+    locks
+        .get(&user_id)
+        .is_some_and(|lock| lock.try_lock().is_err())
+}
+
+/// Queue a SQLite -> PostgreSQL sync for the user, merging with one already waiting
+async fn enqueue_sync_job(pg_pool: &PgPool, user_id: Uuid, chain_tip: u64) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO sync_jobs (user_id, chain_tip)
+         VALUES ($1::uuid, $2)
+         ON CONFLICT (user_id) WHERE status = 'PENDING' DO UPDATE
+         SET chain_tip = GREATEST(sync_jobs.chain_tip, EXCLUDED.chain_tip),
+             run_after = NOW(),
+             updated_at = NOW()"
+    )
+    .bind(user_id.to_string())
+    .bind(chain_tip as i64)
+    .execute(pg_pool)
+    .await?;
+
+    SYNC_JOB_NOTIFY.notify_one();
+    Ok(())
+}
+
+/// Spawn the worker that mirrors wallet data into PostgreSQL from the `sync_jobs` queue
+///
+/// Jobs are rows rather than detached tasks, so a restart mid-sync leaves the job to be
+/// reclaimed instead of silently dropping it. Failed jobs are retried with backoff and
+/// kept as `FAILED` once they run out of attempts.
+pub fn spawn_sync_job_worker(db: PgPool) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match run_next_sync_job(&db).await {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => tracing::warn!("Sync job worker failed: {:?}", e),
+            }
+            tokio::select! {
+                _ = SYNC_JOB_NOTIFY.notified() => {}
+                _ = tokio::time::sleep(SYNC_JOB_POLL_INTERVAL) => {}
+            }
+        }
+    })
+}
+
+/// Claim and run one due job, returning whether there was one
+async fn run_next_sync_job(db: &PgPool) -> Result<bool> {
+    let Some(row) = sqlx::query(
+        "UPDATE sync_jobs
+         SET status = 'RUNNING', locked_at = NOW(), attempts = attempts + 1, updated_at = NOW()
+         WHERE id = (
+             SELECT id FROM sync_jobs
+             WHERE (status = 'PENDING' AND run_after <= NOW())
+                OR (status = 'RUNNING' AND locked_at < NOW() - ($1::bigint * INTERVAL '1 second'))
+             ORDER BY run_after
+             LIMIT 1
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING id::text AS id, user_id::text AS user_id, chain_tip, attempts"
+    )
+    .bind(SYNC_JOB_LOCK_TIMEOUT_SECS)
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    let job_id: String = row.get("id");
+    let user_id: String = row.get("user_id");
+    let chain_tip: i64 = row.get("chain_tip");
+    let attempts: i32 = row.get("attempts");
+    let user_id = Uuid::parse_str(&user_id)
+        .map_err(|e| AppError::Internal(format!("Invalid user id in sync job: {}", e)))?;
+
+    let db_path = wallet_db_path(user_id);
+    match sync_blockchain_data_to_postgres(&db_path, user_id, db, chain_tip as u64).await {
+        Ok(()) => {
+            sqlx::query("DELETE FROM sync_jobs WHERE id = $1::uuid")
+                .bind(&job_id)
+                .execute(db)
+                .await?;
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Sync job {} for user {} failed (attempt {}): {:?}",
+                job_id, user_id, attempts, e
+            );
+            fail_sync_job(db, &job_id, user_id, attempts, &e.to_string()).await?;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Schedule a retry for a failed job, or give up after `MAX_SYNC_JOB_ATTEMPTS`
+///
+/// A job queued for the same user since this one started supersedes it, since every
+/// sync mirrors the whole wallet.
+async fn fail_sync_job(db: &PgPool, job_id: &str, user_id: Uuid, attempts: i32, error: &str) -> Result<()> {
+    let superseded = sqlx::query(
+        "DELETE FROM sync_jobs
+         WHERE id = $1::uuid
+           AND EXISTS (SELECT 1 FROM sync_jobs WHERE user_id = $2::uuid AND status = 'PENDING')"
+    )
+    .bind(job_id)
+    .bind(user_id.to_string())
+    .execute(db)
+    .await?
+    .rows_affected();
+    if superseded > 0 {
+        return Ok(());
+    }
+
+    let status = if attempts >= MAX_SYNC_JOB_ATTEMPTS { "FAILED" } else { "PENDING" };
+    sqlx::query(
+        "UPDATE sync_jobs
+         SET status = $2, last_error = $3, locked_at = NULL, updated_at = NOW(),
+             run_after = NOW() + ($4::float8 * INTERVAL '1 second')
+         WHERE id = $1::uuid"
+    )
+    .bind(job_id)
+    .bind(status)
+    .bind(error)
+    .bind(sync_job_retry_delay(attempts).as_secs_f64())
+    .execute(db)
+    .await?;
+
+    if status == "FAILED" {
+        tracing::error!("Sync job {} for user {} gave up after {} attempts", job_id, user_id, attempts);
+    }
+    Ok(())
+}
+
+/// Backoff before retrying a job that has failed `attempts` times
+fn sync_job_retry_delay(attempts: i32) -> Duration {
+    Duration::from_secs_f64(SYNC_JOB_RETRY_BASE_SECS * 2f64.powi(attempts.clamp(1, 10) - 1))
+}
+
+fn balance_cache_ttl() -> Duration {
+    Duration::from_secs(env_or("BALANCE_CACHE_TTL_SECS", DEFAULT_BALANCE_CACHE_TTL_SECS))
+}
+
+/// The user's last computed balance, if it is younger than the cache TTL
+fn cached_balance(user_id: Uuid, account_index: Option<u32>) -> Option<BalanceResponse> {
+    let cache = BALANCE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .get(&(user_id, account_index))
+        .filter(|(computed_at, _)| computed_at.elapsed() < balance_cache_ttl())
+        .map(|(_, balance)| balance.clone())
+}
+
+fn cache_balance(user_id: Uuid, account_index: Option<u32>, balance: &BalanceResponse) {
+    let mut cache = BALANCE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|_, (computed_at, _)| computed_at.elapsed() < balance_cache_ttl());
+    cache.insert((user_id, account_index), (Instant::now(), balance.clone()));
+}
+
+/// Drop the user's cached balances, e.g. after a send changes them
+pub fn invalidate_cached_balance(user_id: Uuid) {
+    let mut cache = BALANCE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|(cached_user_id, _), _| *cached_user_id != user_id);
+}
+
+/// Return the cached balance from PostgreSQL without scanning
+///
+/// If `last_synced_height` is more than `QUICK_BALANCE_MAX_LAG_BLOCKS` behind the chain tip
+/// (or the tip can't be fetched) the response is flagged `stale` and a full sync is started
+/// in the background. The cache only covers notes already synced to `received_notes`.
+#[axum::debug_handler]
+pub async fn quick_balance(
+    State(state): State<BalanceState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<QuickBalanceResponse>> {
+    tracing::info!("Quick balance check requested for user {}", user_id);
 
-    // Get wallet info from PostgreSQL - use string cast for UUID
     let row = sqlx::query(
-        "SELECT encrypted_mnemonic, birthday_height FROM wallets WHERE user_id = $1::uuid"
+        "SELECT last_synced_height, network FROM wallets WHERE user_id = $1::uuid"
     )
-    .bind(payload.user_id.to_string())
+    .bind(user_id.to_string())
     .fetch_optional(&state.db)
     .await?
     .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
 
-    let encrypted_mnemonic: String = row.get("encrypted_mnemonic");
-    let birthday_height_i64: i64 = row.get("birthday_height");
+    let last_synced_height: Option<i64> = row.get("last_synced_height");
+    let network: String = row.get("network");
 
-    // Parse mnemonic (currently stored unencrypted - TODO: encrypt in production)
-    let mnemonic = Mnemonic::parse(&encrypted_mnemonic)
-        .map_err(|e| AppError::Internal(format!("Failed to parse mnemonic: {}", e)))?;
+    let cached_zatoshis: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(SUM(value_zatoshis), 0)::bigint FROM received_notes
+         WHERE user_id = $1::uuid AND spent_in_tx_id IS NULL"
+    )
+    .bind(user_id.to_string())
+    .fetch_one(&state.db)
+    .await?;
 
-    let seed = mnemonic.to_seed("");
-    let birthday_height = birthday_height_i64 as u32;  // Convert i64 to u32
+    let chain_tip = match connect_lightwalletd(parse_network(&network)).await {
+        Ok(client) => client.get_latest_block_height().await.ok(),
+        Err(_) => None,
+    };
 
-    // Get network from environment
-    let network_str = env::var("ZCASH_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
-    let network = match network_str.to_lowercase().as_str() {
-        "testnet" => Network::TestNetwork,
-        _ => Network::MainNetwork,
+    let stale = match (chain_tip, last_synced_height) {
+        (Some(tip), Some(synced)) => tip.saturating_sub(synced.max(0) as u64) > quick_balance_max_lag(),
+        _ => true,
     };
 
-    tracing::info!("Network: {:?}, Birthday height: {}", network, birthday_height);
+    if stale {
+        tracing::info!("Cached balance for user {} is stale, starting background sync", user_id);
+        let db = state.db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = coalesced_sync_balance(&db, user_id, true, None).await {
+                tracing::error!("Background balance sync failed for user {}: {:?}", user_id, e);
+            }
+        });
+    }
 
-    // Setup per-user wallet database path
-    let data_dir = PathBuf::from("./wallet_data");
-    std::fs::create_dir_all(&data_dir).ok();
-    let db_path = data_dir.join(format!("wallet_{}.db", payload.user_id));
+    Ok(Json(QuickBalanceResponse {
+        balance_zec: format_zec(cached_zatoshis.max(0) as u64),
+        stale,
+        last_synced_height,
+        chain_tip,
+    }))
+}
 
-    tracing::info!("Using wallet database: {:?}", db_path);
+fn quick_balance_max_lag() -> u64 {
+    env_or("QUICK_BALANCE_MAX_LAG_BLOCKS", DEFAULT_QUICK_BALANCE_MAX_LAG)
+}
 
-    // Step 1: Connect to lightwalletd
-    let lightwalletd_url = match network {
-        Network::MainNetwork => {
-            env::var("LIGHTWALLETD_MAINNET").unwrap_or_else(|_| "https://na.zec.rocks:443".to_string())
-        }
-        Network::TestNetwork => {
-            env::var("LIGHTWALLETD_TESTNET").unwrap_or_else(|_| "https://testnet.zec.rocks:443".to_string())
+/// Report how far the wallet's last sync is behind the chain tip, without scanning
+///
+/// Cheap enough to poll. If lightwalletd is unreachable the last synced height is still
+/// returned, with `chain_tip` unset.
+#[axum::debug_handler]
+pub async fn sync_status(
+    State(state): State<BalanceState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<SyncStatusResponse>> {
+    let row = sqlx::query(
+        "SELECT last_synced_height, last_synced_at::text, network FROM wallets WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let last_synced_height: Option<i64> = row.get("last_synced_height");
+    let last_synced_at = parse_optional_datetime(row.get("last_synced_at"));
+    let network: String = row.get("network");
+
+    let chain_tip = match connect_lightwalletd(parse_network(&network)).await {
+        Ok(client) => match client.get_latest_block_height().await {
+            Ok(tip) => Some(tip),
+            Err(e) => {
+                tracing::warn!("Failed to fetch chain tip for sync status: {}", e);
+                None
+            }
+        },
+        Err(e) => {
+            tracing::warn!("lightwalletd unavailable for sync status: {:?}", e);
+            None
         }
     };
 
+    let blocks_behind = match (chain_tip, last_synced_height) {
+        (Some(tip), Some(synced)) => Some(tip.saturating_sub(synced.max(0) as u64)),
+        _ => None,
+    };
+
+    Ok(Json(SyncStatusResponse {
+        last_synced_height,
+        chain_tip,
+        blocks_behind,
+        is_synced: blocks_behind.is_some_and(|behind| behind <= quick_balance_max_lag()),
+        last_synced_at,
+    }))
+}
+
+/// List the unspent notes that make up the wallet balance (coin-control view)
+///
+/// Reads the notes already synced to `received_notes`; confirmations are counted
+/// against `last_synced_height`, so call the balance endpoint first for fresh data.
+#[axum::debug_handler]
+pub async fn list_notes(
+    State(state): State<BalanceState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<NotesResponse>> {
+    let last_synced_height: Option<i64> = sqlx::query_scalar(
+        "SELECT last_synced_height FROM wallets WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let rows = sqlx::query(
+        "SELECT t.txid, rn.pool, rn.value_zatoshis, rn.is_change, t.block_height
+         FROM received_notes rn
+         JOIN transactions t ON t.id = rn.transaction_id AND t.user_id = rn.user_id
+         WHERE rn.user_id = $1::uuid AND rn.spent_in_tx_id IS NULL
+         ORDER BY rn.value_zatoshis DESC, t.block_height ASC NULLS LAST"
+    )
+    .bind(user_id.to_string())
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut total: u64 = 0;
+    let notes = rows
+        .into_iter()
+        .map(|row| {
+            let value_zatoshis: i64 = row.get("value_zatoshis");
+            let block_height: Option<i64> = row.get("block_height");
+            total += value_zatoshis.max(0) as u64;
+
+            let confirmations = match (block_height, last_synced_height) {
+                (Some(mined), Some(synced)) if synced >= mined => synced - mined + 1,
+                _ => 0,
+            };
+
+            NoteInfo {
+                txid: row.get("txid"),
+                pool: row.get("pool"),
+                value_zatoshis,
+                value_zec: format_zec(value_zatoshis.max(0) as u64),
+                is_change: row.get("is_change"),
+                block_height,
+                confirmations,
+            }
+        })
+        .collect();
+
+    Ok(Json(NotesResponse {
+        notes,
+        total_zec: format_zec(total),
+        last_synced_height,
+    }))
+}
+
+/// Scan the user's wallet up to the chain tip and compute its balance
+///
+/// Every account is scanned; the balance covers `account_index` only, or all accounts
+/// combined if it is `None`. Unless `force` is set, a request that waited on the per-user
+/// lock reuses the balance the scan ahead of it just cached instead of scanning again.
+pub async fn sync_balance(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    force: bool,
+    account_index: Option<u32>,
+    control: scanner::ScanControl,
+) -> Result<BalanceResponse> {
+    // Acquire per-user lock to prevent concurrent database access
+    let _guard = lock_user_wallet(user_id).await;
+    tracing::info!("Acquired database lock for user {}", user_id);
+
+    if !force {
+        if let Some(cached) = cached_balance(user_id, account_index) {
+            tracing::info!("Balance for user {} was refreshed while waiting, using it", user_id);
+            return Ok(cached);
+        }
+    }
+
+    // Load wallet configuration, including the network the wallet was created on
+    let WalletConfig {
+        seed,
+        birthday_height,
+        network,
+        db_path,
+        ..
+    } = load_wallet_config(pg_pool, user_id, false).await?;
+
+    tracing::info!("Network: {:?}, Birthday height: {}", network, birthday_height);
+    tracing::info!("Using wallet database: {:?}", db_path);
+
+    // Step 1: Connect to lightwalletd
+    let lightwalletd_url = get_lightwalletd_url(network);
+
     tracing::info!("Connecting to lightwalletd: {}", lightwalletd_url);
-    let mut client = lightwalletd::LightwalletdClient::new(lightwalletd_url);
+    let mut client = lightwalletd::LightwalletdClient::from_endpoint_list(&lightwalletd_url);
 
     client.connect().await
         .map_err(|e| AppError::Internal(format!("Failed to connect to lightwalletd: {}", e)))?;
 
     tracing::info!("Connected to lightwalletd");
 
-    // Step 2: Initialize per-user wallet database
-    // Check if database exists before deciding initialization strategy
-    let db_exists = db_path.exists();
-    tracing::info!("Database exists: {}", db_exists);
-
-    let mut db = if db_exists {
-        // Try to open existing database without running migrations
-        match database::Database::open_existing(&db_path, network) {
-            Ok(d) => d,
-            Err(e) => {
-                tracing::warn!("Failed to open existing database, will reinitialize: {}", e);
-                database::Database::new(&db_path, network)
-                    .map_err(|e| AppError::Internal(format!("Failed to initialize database: {}", e)))?
-            }
-        }
-    } else {
-        // New database - run full initialization
-        database::Database::new(&db_path, network)
-            .map_err(|e| AppError::Internal(format!("Failed to initialize database: {}", e)))?
-    };
+    // Step 2: Borrow the per-user wallet database from the shared pool
+    let pooled_db = pooled_wallet_database(user_id, &db_path, network).await?;
+    let mut db = pooled_db.lock().await;
 
     // Step 3: Check if account exists, create if needed
-    let has_accounts = match SqliteConnection::open(&db_path) {
-        Ok(conn) => {
-            match conn.query_row("SELECT COUNT(*) FROM accounts", [], |row| row.get::<_, i64>(0)) {
-                Ok(count) => {
-                    tracing::info!("Found {} existing account(s)", count);
-                    count > 0
-                }
-                Err(_) => false,
-            }
-        }
-        Err(_) => false,
-    };
+    let account_ids = db
+        .get_wallet_db()
+        .map_err(|e| AppError::Internal(format!("Failed to open wallet database: {}", e)))?
+        .get_account_ids()
+        .map_err(|e| AppError::Internal(format!("Failed to list accounts: {:?}", e)))?;
+    tracing::info!("Found {} existing account(s)", account_ids.len());
+    let has_accounts = !account_ids.is_empty();
 
     // Create account if none exists
     if !has_accounts {
-        tracing::info!("Creating new account with birthday height {}", birthday_height);
-
-        let mut account_mgr = account::AccountManager::new(db);
-        db = match account_mgr
-            .create_account("Primary", &seed, &client, Some(birthday_height))
+        // A fresh local database may just mean the volume was wiped; resume from the
+        // checkpoint in Postgres rather than rescanning from the birthday
+        let account_birthday = fresh_database_birthday(pg_pool, user_id, birthday_height).await?;
+        tracing::info!("Creating new account with birthday height {}", account_birthday);
+
+        let mut account_mgr = account::AccountManager::new(&mut db);
+        match account_mgr
+            .create_account("Primary", &seed, &client, Some(account_birthday))
             .await
         {
             Ok((account_id, _usk)) => {
                 tracing::info!("Account created: {:?}", account_id);
-                // Use open_existing since DB is now initialized
-                database::Database::open_existing(&db_path, network)
-                    .map_err(|e| AppError::Internal(format!("Failed to reopen database: {}", e)))?
             }
             Err(e) => {
                 return Err(AppError::Internal(format!("Failed to create account: {}", e)));
@@ -170,6 +754,25 @@ pub async fn get_balance(
     } else {
         tracing::info!("Using existing account(s)");
     }
+    let account_indexes =
+        import_wallet_accounts(pg_pool, user_id, &mut db, &seed, network, &client).await?;
+
+    // Transparent funds aren't found by compact block scanning; fetch them separately for
+    // every account, since the combined balance covers them all. A failure here shouldn't
+    // hide the shielded balance.
+    let mut tx_builder = transaction::TransactionBuilder::new(&mut db, network);
+    for index in account_indexes {
+        let usk = derive_account_spending_key(&seed, network, index)?;
+        if let Err(e) = load_transparent_utxos(&client, &mut tx_builder, &usk, birthday_height).await {
+            tracing::warn!(
+                "Failed to refresh transparent UTXOs for user {} account {}: {:?}",
+                user_id,
+                index,
+                e
+            );
+        }
+    }
+    drop(tx_builder);
 
     // Get chain tip
     let chain_tip = client
@@ -182,111 +785,292 @@ pub async fn get_balance(
     // Step 4: Scan blockchain
     tracing::info!("Starting blockchain scan...");
 
-    // Drop db before scanner takes ownership
-    drop(db);
-
-    // Create wallet_db for scanner
-    let wallet_db = WalletDb::<SqliteConnection, Network, SystemClock, OsRng>::for_path(
-        &db_path,
-        network,
-        SystemClock,
-        OsRng,
-    )
-    .map_err(|e| {
+    let wallet_db = db.get_wallet_db_mut().map_err(|e| {
         AppError::Internal(format!("Failed to open wallet database for scanning: {:?}", e))
     })?;
 
     // Create scanner with database path for checkpoint management
-    let mut scanner = scanner::BlockchainScanner::new_with_path(wallet_db, client, network, db_path.clone());
+    let mut scanner = scanner::BlockchainScanner::new_with_path(wallet_db, client, network, db_path.clone())
+        .with_control(control);
 
     // Run the scan
-    let scan_result = scanner.scan_from_birthday().await.map_err(|e| {
+    let scan_result = scanner.scan_from_birthday(user_id).await.map_err(|e| {
         AppError::Internal(format!("Scan failed: {}", e))
     })?;
 
+    drop(scanner);
+
     tracing::info!(
         "Scan complete! Blocks scanned: {}, Notes found: {}",
         scan_result.blocks_scanned,
         scan_result.notes_discovered
     );
 
-    // Step 5: Get balance from database
-    tracing::info!("Calculating balance from database...");
-
-    // Query balance directly from SQLite database
-    // Sum UNSPENT notes from BOTH Sapling and Orchard pools
-    let balance_zatoshis: i64 = match SqliteConnection::open(&db_path) {
-        Ok(conn) => {
-            // Query Sapling unspent notes
-            let sapling_balance: i64 = conn.query_row(
-                "SELECT COALESCE(SUM(srn.value), 0)
-                 FROM sapling_received_notes srn
-                 LEFT JOIN sapling_received_note_spends srns
-                   ON srn.id = srns.sapling_received_note_id
-                 WHERE srns.sapling_received_note_id IS NULL",
-                [],
-                |row| row.get(0),
-            ).unwrap_or(0);
-            tracing::info!("Sapling balance: {} zatoshis", sapling_balance);
-
-            // Query Orchard unspent notes (if table exists)
-            let orchard_balance: i64 = conn.query_row(
-                "SELECT COALESCE(SUM(orn.value), 0)
-                 FROM orchard_received_notes orn
-                 LEFT JOIN orchard_received_note_spends orns
-                   ON orn.id = orns.orchard_received_note_id
-                 WHERE orns.orchard_received_note_id IS NULL",
-                [],
-                |row| row.get(0),
-            ).unwrap_or_else(|e| {
-                tracing::debug!("Orchard balance query (may not exist): {:?}", e);
-                0
-            });
-            tracing::info!("Orchard balance: {} zatoshis", orchard_balance);
+    // Step 5: Get balance from the wallet summary
+    tracing::info!("Calculating balance from wallet summary...");
 
-            let total = sapling_balance + orchard_balance;
-            tracing::info!("Total balance: {} zatoshis (Sapling: {}, Orchard: {})",
-                          total, sapling_balance, orchard_balance);
-            total
-        }
-        Err(e) => {
-            tracing::warn!("Failed to open database: {:?}", e);
-            0
-        }
+    let account = match account_index {
+        Some(index) => Some(
+            account_uuid(&db, &seed, network, index)?
+                .ok_or_else(|| AppError::NotFound(format!("Account {} not found", index)))?,
+        ),
+        None => None,
     };
+    let balance = wallet_balance(&db, account)?;
 
-    let balance_f64 = balance_zatoshis as f64 / 100_000_000.0;
-    let balance_zec = format!("{:.8}", balance_f64);
+    // Release the pooled database before syncing to PostgreSQL
+    drop(db);
 
-    tracing::info!("Balance: {} ZEC", balance_f64);
+    tracing::info!(
+        "Balance: {} confirmed, {} pending, {} spendable, {} transparent (zatoshis)",
+        balance.confirmed,
+        balance.pending,
+        balance.spendable,
+        balance.transparent
+    );
 
-    // Step 6: Sync SQLite data to PostgreSQL (in background)
-    let db_path_bg = db_path.clone();
-    let user_id_bg = payload.user_id;
-    let pg_pool_bg = state.db.clone();
-    tokio::spawn(async move {
-        if let Err(e) = sync_blockchain_data_to_postgres(&db_path_bg, user_id_bg, &pg_pool_bg).await {
-            tracing::error!("Failed to sync blockchain data: {:?}", e);
-        }
-    });
+    // Step 6: Queue the SQLite -> PostgreSQL sync; the job worker runs it in the background
+    enqueue_sync_job(pg_pool, user_id, chain_tip).await?;
 
     // Update sync status in PostgreSQL
     sqlx::query(
         "UPDATE wallets SET last_synced_at = NOW(), last_synced_height = $1 WHERE user_id = $2::uuid"
     )
     .bind(chain_tip as i64)
-    .bind(payload.user_id.to_string())
-    .execute(&state.db)
+    .bind(user_id.to_string())
+    .execute(pg_pool)
     .await?;
 
-    Ok(Json(BalanceResponse {
-        balance_zec,
+    let response = BalanceResponse {
+        balance_zec: format_zec(balance.confirmed),
+        confirmed_zec: format_zec(balance.confirmed),
+        pending_zec: format_zec(balance.pending),
+        spendable_zec: format_zec(balance.spendable),
+        transparent_zec: format_zec(balance.transparent),
+        transparent_hint: (balance.transparent > 0).then(|| {
+            "Transparent funds must be shielded (POST /api/wallet/shield) before they can be spent privately".to_string()
+        }),
         synced: true,
         last_synced_height: Some(chain_tip as i64),
         blocks_scanned: Some(scan_result.blocks_scanned),
         notes_found: Some(scan_result.notes_discovered),
         chain_tip: Some(chain_tip),
-    }))
+        balance_fiat: None,
+        fiat_currency: None,
+        confirmed_zatoshis: balance.confirmed,
+    };
+    cache_balance(user_id, account_index, &response);
+
+    Ok(response)
+}
+
+/// Birthday to create the account with when the wallet's SQLite database is new
+///
+/// See `scanner::resume_birthday`. Notes whose spend hasn't been mined count as unspent,
+/// since the spend may still expire.
+pub async fn fresh_database_birthday(pg_pool: &PgPool, user_id: Uuid, birthday_height: u32) -> Result<u32> {
+    let row = sqlx::query(
+        "SELECT w.last_synced_height,
+                (SELECT MIN(t.block_height)
+                 FROM received_notes rn
+                 JOIN transactions t ON t.id = rn.transaction_id
+                 LEFT JOIN transactions st ON st.id = rn.spent_in_tx_id
+                 WHERE rn.user_id = w.user_id
+                   AND t.block_height IS NOT NULL
+                   AND (rn.spent_in_tx_id IS NULL OR st.block_height IS NULL)) AS earliest_unspent_height
+         FROM wallets w
+         WHERE w.user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_one(pg_pool)
+    .await?;
+
+    let height = |column: &str| {
+        row.get::<Option<i64>, _>(column)
+            .and_then(|height| u32::try_from(height).ok())
+    };
+    let resume = scanner::resume_birthday(
+        birthday_height,
+        height("last_synced_height"),
+        height("earliest_unspent_height"),
+    );
+
+    if resume > birthday_height {
+        tracing::warn!(
+            "Wallet database for user {} is new but Postgres has synced to {:?}; resuming from {} instead of birthday {}",
+            user_id,
+            height("last_synced_height"),
+            resume,
+            birthday_height
+        );
+    }
+    Ok(resume)
+}
+
+/// Import each additional HD account recorded in `wallet_accounts` that the wallet database
+/// doesn't hold yet
+///
+/// Called wherever the database may have just been created or recreated, so accounts added
+/// with `/wallet/accounts` stay reachable after a reset, and one whose import failed when it
+/// was created is retried. Returns the indexes of all the user's accounts, starting with 0.
+pub async fn import_wallet_accounts(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    db: &mut database::Database,
+    seed: &[u8],
+    network: Network,
+    client: &lightwalletd::LightwalletdClient,
+) -> Result<Vec<u32>> {
+    let rows = sqlx::query(
+        "SELECT account_index, name, birthday_height FROM wallet_accounts
+         WHERE user_id = $1::uuid
+         ORDER BY account_index"
+    )
+    .bind(user_id.to_string())
+    .fetch_all(pg_pool)
+    .await?;
+
+    let mut account_indexes = vec![0];
+    for row in rows {
+        let account_index = u32::try_from(row.get::<i64, _>("account_index"))
+            .map_err(|_| AppError::Internal("Invalid account index".to_string()))?;
+        account_indexes.push(account_index);
+
+        if account_uuid(db, seed, network, account_index)?.is_some() {
+            continue;
+        }
+
+        let name: String = row.get("name");
+        let birthday_height = u32::try_from(row.get::<i64, _>("birthday_height"))
+            .map_err(|_| AppError::Internal("Invalid account birthday height".to_string()))?;
+        tracing::info!(
+            "Importing account {} for user {} into the wallet database (birthday {})",
+            account_index,
+            user_id,
+            birthday_height
+        );
+
+        account::AccountManager::new(db)
+            .import_account_hd(&name, seed, client, account_index, Some(birthday_height))
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to import account {}: {}", account_index, e)))?;
+    }
+
+    Ok(account_indexes)
+}
+
+/// Wallet balance breakdown in zatoshis
+///
+/// `confirmed`, `pending` and `spendable` cover the shielded pools only.
+#[derive(Debug, Default)]
+struct WalletBalance {
+    /// Notes with enough confirmations, whether or not they are spendable yet
+    confirmed: u64,
+    /// Received notes and change that are still below the confirmation threshold
+    pending: u64,
+    /// Notes that can be spent right now
+    spendable: u64,
+    /// Unspent transparent outputs, which must be shielded before private spending
+    transparent: u64,
+}
+
+/// Compute the Sapling + Orchard and transparent balances using `WalletRead::get_wallet_summary`
+///
+/// Uses the same confirmations policy as sending so `spendable` matches what
+/// `send_transaction` can actually spend; notes shallower than `MIN_CONFIRMATIONS`
+/// are reported as pending.
+///
+/// Covers only `account` if given, otherwise every account in the wallet.
+fn wallet_balance(db: &database::Database, account: Option<AccountUuid>) -> Result<WalletBalance> {
+    // No summary means the wallet hasn't been synced yet
+    Ok(scanned_wallet_balance(db, account)?.unwrap_or_default())
+}
+
+/// Spendable Sapling + Orchard balance of HD account `account_index` as of the wallet's
+/// last scan, without scanning
+///
+/// `None` if the wallet database doesn't exist yet, has never been scanned, or doesn't
+/// hold the account.
+pub async fn last_scanned_spendable(
+    user_id: Uuid,
+    config: &WalletConfig,
+    account_index: u32,
+) -> Result<Option<u64>> {
+    if !config.db_path.exists() {
+        return Ok(None);
+    }
+
+    let pooled_db = pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let db = pooled_db.lock().await;
+    let Some(account) = account_uuid(&db, &config.seed, config.network, account_index)? else {
+        return Ok(None);
+    };
+    Ok(scanned_wallet_balance(&db, Some(account))?.map(|balance| balance.spendable))
+}
+
+/// The wallet database's id for HD account `account_index` of `seed`, if it has been added
+fn account_uuid(
+    db: &database::Database,
+    seed: &[u8],
+    network: Network,
+    account_index: u32,
+) -> Result<Option<AccountUuid>> {
+    let ufvk = derive_account_spending_key(seed, network, account_index)?.to_unified_full_viewing_key();
+    let account = db
+        .get_wallet_db()
+        .map_err(|e| AppError::Internal(format!("Failed to open wallet database: {}", e)))?
+        .get_account_for_ufvk(&ufvk)
+        .map_err(|e| AppError::Internal(format!("Failed to look up account: {:?}", e)))?;
+    Ok(account.map(|account| account.id()))
+}
+
+/// Like `wallet_balance`, but `None` if no account has been scanned yet
+fn scanned_wallet_balance(
+    db: &database::Database,
+    account: Option<AccountUuid>,
+) -> Result<Option<WalletBalance>> {
+    let wallet_db = db
+        .get_wallet_db()
+        .map_err(|e| AppError::Internal(format!("Failed to open wallet database: {}", e)))?;
+
+    let confirmations_policy = transaction::confirmations_policy(None)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let summary = wallet_db
+        .get_wallet_summary(confirmations_policy)
+        .map_err(|e| AppError::Internal(format!("Failed to get wallet summary: {:?}", e)))?;
+
+    let Some(summary) = summary.filter(|summary| !summary.account_balances().is_empty()) else {
+        return Ok(None);
+    };
+
+    Ok(Some(sum_account_balances(summary.account_balances(), account.as_ref())))
+}
+
+/// Add up the per-account balances of a wallet summary, keeping only `account` if given
+fn sum_account_balances<'a, K: PartialEq + 'a>(
+    account_balances: impl IntoIterator<Item = (&'a K, &'a AccountBalance)>,
+    account: Option<&K>,
+) -> WalletBalance {
+    let mut balance = WalletBalance::default();
+    let account_balances = account_balances
+        .into_iter()
+        .filter(|(id, _)| account.is_none_or(|account| *id == account))
+        .map(|(_, account_balance)| account_balance);
+    for account_balance in account_balances {
+        for pool in [account_balance.sapling_balance(), account_balance.orchard_balance()] {
+            let pending = u64::from(pool.change_pending_confirmation())
+                + u64::from(pool.value_pending_spendability());
+
+            balance.pending += pending;
+            balance.confirmed += u64::from(pool.total()) - pending;
+            balance.spendable += u64::from(pool.spendable_value());
+        }
+
+        balance.transparent += u64::from(account_balance.unshielded_balance().total());
+    }
+
+    balance
 }
 
 // Data structures for passing SQLite data across thread boundary
@@ -302,6 +1086,7 @@ struct TxData {
 #[derive(Debug, Clone)]
 struct NoteData {
     txid: String,
+    pool: String,
     note_index: i32,
     value: i64,
     memo: Option<Vec<u8>>,
@@ -317,6 +1102,15 @@ struct SentData {
     memo: Option<Vec<u8>>,
 }
 
+/// What the SQLite wallet currently holds, for `reconcile_wallet_data`
+#[derive(Debug, Default)]
+struct WalletSnapshot {
+    txids: Vec<String>,
+    sent_txids: Vec<String>,
+    /// (txid, pool, note index) of every received note
+    notes: Vec<(String, String, i32)>,
+}
+
 /// Sync blockchain data from SQLite to PostgreSQL
 /// This reads transactions and notes from the per-user SQLite database
 /// and stores them in the centralized PostgreSQL database
@@ -324,14 +1118,29 @@ async fn sync_blockchain_data_to_postgres(
     db_path: &PathBuf,
     user_id: Uuid,
     pg_pool: &PgPool,
+    chain_tip: u64,
 ) -> Result<()> {
     let db_path_clone = db_path.clone();
 
     // Step 1: Read ALL data from SQLite in a blocking task
-    let (tx_data, note_data, sent_data) = tokio::task::spawn_blocking(move || -> std::result::Result<(Vec<TxData>, Vec<NoteData>, Vec<SentData>), AppError> {
+    let (tx_data, note_data, sent_data, scanned_range) = tokio::task::spawn_blocking(move || -> std::result::Result<(Vec<TxData>, Vec<NoteData>, Vec<SentData>, Option<(i64, i64)>), AppError> {
         let conn = SqliteConnection::open(&db_path_clone)
             .map_err(|e| AppError::Internal(format!("Failed to open SQLite: {}", e)))?;
 
+        // Heights this database has scanned: from the earliest account birthday to the
+        // highest scanned block
+        let scanned_range = conn
+            .query_row(
+                "SELECT (SELECT MIN(birthday_height) FROM accounts), (SELECT MAX(height) FROM blocks)",
+                [],
+                |row| Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?)),
+            )
+            .map_err(|e| AppError::Internal(format!("Failed to read scanned range: {}", e)))?;
+        let scanned_range = match scanned_range {
+            (Some(from), Some(to)) if from <= to => Some((from, to)),
+            _ => None,
+        };
+
         // Read transactions
         let mut tx_vec = Vec::new();
         let mut stmt = conn.prepare(
@@ -366,35 +1175,48 @@ async fn sync_blockchain_data_to_postgres(
         // Read received notes
         let mut note_vec = Vec::new();
         let mut stmt = conn.prepare(
-            "SELECT t.hex_txid, srn.output_index, srn.value, srn.memo, srn.is_change,
+            "SELECT t.hex_txid, 'sapling', srn.output_index, srn.value, srn.memo, srn.is_change,
                     spent.spent_tx_hex
              FROM sapling_received_notes srn
              JOIN (SELECT id_tx, hex(txid) as hex_txid FROM transactions) t
-                  ON srn.tx = t.id_tx
+                  ON srn.transaction_id = t.id_tx
              LEFT JOIN (
                  SELECT sapling_received_note_id, hex(t.txid) as spent_tx_hex
                  FROM sapling_received_note_spends srns
                  JOIN transactions t ON srns.transaction_id = t.id_tx
-             ) spent ON srn.id = spent.sapling_received_note_id"
+             ) spent ON srn.id = spent.sapling_received_note_id
+             UNION ALL
+             SELECT t.hex_txid, 'orchard', orn.action_index, orn.value, orn.memo, orn.is_change,
+                    spent.spent_tx_hex
+             FROM orchard_received_notes orn
+             JOIN (SELECT id_tx, hex(txid) as hex_txid FROM transactions) t
+                  ON orn.transaction_id = t.id_tx
+             LEFT JOIN (
+                 SELECT orchard_received_note_id, hex(t.txid) as spent_tx_hex
+                 FROM orchard_received_note_spends orns
+                 JOIN transactions t ON orns.transaction_id = t.id_tx
+             ) spent ON orn.id = spent.orchard_received_note_id"
         ).map_err(|e| AppError::Internal(format!("Failed to prepare notes statement: {}", e)))?;
 
         let note_rows = stmt.query_map([], |row| {
             Ok((
                 row.get::<_, String>(0)?,
-                row.get::<_, i32>(1)?,
-                row.get::<_, i64>(2)?,
-                row.get::<_, Option<Vec<u8>>>(3)?,
-                row.get::<_, i32>(4)?,
-                row.get::<_, Option<String>>(5)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<Vec<u8>>>(4)?,
+                row.get::<_, i32>(5)?,
+                row.get::<_, Option<String>>(6)?,
             ))
         }).map_err(|e| AppError::Internal(format!("Failed to query notes: {}", e)))?;
 
         for note in note_rows {
-            let (txid, note_index, value, memo, is_change, spent_tx_hex) = note
+            let (txid, pool, note_index, value, memo, is_change, spent_tx_hex) = note
                 .map_err(|e| AppError::Internal(format!("Failed to read note: {}", e)))?;
 
             note_vec.push(NoteData {
                 txid,
+                pool,
                 note_index,
                 value,
                 memo,
@@ -409,7 +1231,7 @@ async fn sync_blockchain_data_to_postgres(
             "SELECT t.hex_txid, sn.to_address, sn.value, sn.memo
              FROM sent_notes sn
              JOIN (SELECT id_tx, hex(txid) as hex_txid FROM transactions) t
-                  ON sn.tx = t.id_tx"
+                  ON sn.transaction_id = t.id_tx"
         ).map_err(|e| AppError::Internal(format!("Failed to prepare sent notes statement: {}", e)))?;
 
         let sent_rows = stmt.query_map([], |row| {
@@ -433,12 +1255,25 @@ async fn sync_blockchain_data_to_postgres(
             });
         }
 
-        Ok((tx_vec, note_vec, sent_vec))
+        Ok((tx_vec, note_vec, sent_vec, scanned_range))
     })
     .await
     .map_err(|e| AppError::Internal(format!("Failed to read SQLite data: {}", e)))??;
 
-    // Step 2: Now insert all data into PostgreSQL (async operations are OK here)
+    // Step 2: Now insert all data into PostgreSQL (async operations are OK here). One
+    // transaction, so a failed sync never leaves the upserts without the reconcile.
+
+    let mined_heights: HashMap<String, Option<i64>> = tx_data
+        .iter()
+        .map(|tx| (tx.txid.clone(), tx.mined_height))
+        .collect();
+    let mut snapshot = WalletSnapshot {
+        txids: tx_data.iter().map(|tx| tx.txid.clone()).collect(),
+        sent_txids: sent_data.iter().map(|sent| sent.txid.clone()).collect(),
+        ..Default::default()
+    };
+
+    let mut pg_tx = pg_pool.begin().await?;
 
     // Insert transactions
     for tx in tx_data {
@@ -460,18 +1295,21 @@ async fn sync_blockchain_data_to_postgres(
         .bind(tx.tx_index)
         .bind(created_at.map(|d| d.to_rfc3339()))
         .bind(tx.fee)
-        .execute(pg_pool)
+        .execute(&mut *pg_tx)
         .await?;
     }
 
-    // Insert received notes
+    // Insert received notes, remembering the ones this sync inserted as webhook candidates
+    let mut new_notes = Vec::new();
     for note in note_data {
+        snapshot.notes.push((note.txid.clone(), note.pool.clone(), note.note_index));
+
         let tx_row = sqlx::query(
             "SELECT id FROM transactions WHERE user_id = $1::uuid AND txid = $2"
         )
         .bind(user_id.to_string())
         .bind(&note.txid)
-        .fetch_optional(pg_pool)
+        .fetch_optional(&mut *pg_tx)
         .await?;
 
         if let Some(tx) = tx_row {
@@ -482,22 +1320,24 @@ async fn sync_blockchain_data_to_postgres(
                 )
                 .bind(user_id.to_string())
                 .bind(spent_txid)
-                .fetch_optional(pg_pool)
+                .fetch_optional(&mut *pg_tx)
                 .await?;
                 result.map(|r| r.get::<i64, _>("id"))
             } else {
                 None
             };
 
-            sqlx::query(
-                "INSERT INTO received_notes (user_id, transaction_id, note_index, value_zatoshis, memo, is_change, spent_in_tx_id)
-                 VALUES ($1::uuid, $2, $3, $4, $5, $6, $7)
-                 ON CONFLICT (user_id, transaction_id, note_index)
+            // xmax is 0 only for rows created by this statement rather than updated by it
+            let inserted: bool = sqlx::query_scalar(
+                "INSERT INTO received_notes (user_id, transaction_id, note_index, value_zatoshis, memo, is_change, spent_in_tx_id, pool)
+                 VALUES ($1::uuid, $2, $3, $4, $5, $6, $7, $8)
+                 ON CONFLICT (user_id, transaction_id, pool, note_index)
                  DO UPDATE SET
                     value_zatoshis = EXCLUDED.value_zatoshis,
                     memo = EXCLUDED.memo,
                     is_change = EXCLUDED.is_change,
-                    spent_in_tx_id = EXCLUDED.spent_in_tx_id"
+                    spent_in_tx_id = EXCLUDED.spent_in_tx_id
+                 RETURNING (xmax = 0)"
             )
             .bind(user_id.to_string())
             .bind(tx_id)
@@ -506,26 +1346,39 @@ async fn sync_blockchain_data_to_postgres(
             .bind(&note.memo)
             .bind(note.is_change)
             .bind(spent_in_tx_id)
-            .execute(pg_pool)
+            .bind(&note.pool)
+            .fetch_one(&mut *pg_tx)
             .await?;
+
+            if inserted && !note.is_change {
+                new_notes.push(note);
+            }
         }
     }
 
-    // Insert sent notes
+    // Insert sent notes, replacing any recorded at broadcast time (or by a previous sync)
+    let mut replaced_txids = std::collections::HashSet::new();
     for sent in sent_data {
         let tx_row = sqlx::query(
             "SELECT id FROM transactions WHERE user_id = $1::uuid AND txid = $2"
         )
         .bind(user_id.to_string())
         .bind(&sent.txid)
-        .fetch_optional(pg_pool)
+        .fetch_optional(&mut *pg_tx)
         .await?;
 
         if let Some(tx) = tx_row {
             let tx_id: i64 = tx.get("id");
-            let memo_str = sent.memo.as_ref().and_then(|bytes| {
-                String::from_utf8(bytes.iter().filter(|&&b| b != 0).copied().collect()).ok()
-            });
+
+            if replaced_txids.insert(tx_id) {
+                sqlx::query("DELETE FROM sent_notes WHERE user_id = $1::uuid AND transaction_id = $2")
+                    .bind(user_id.to_string())
+                    .bind(tx_id)
+                    .execute(&mut *pg_tx)
+                    .await?;
+            }
+
+            let memo_str = sent.memo.as_deref().and_then(transaction::decode_memo);
 
             sqlx::query(
                 "INSERT INTO sent_notes (user_id, transaction_id, to_address, value_zatoshis, memo)
@@ -537,11 +1390,361 @@ async fn sync_blockchain_data_to_postgres(
             .bind(sent.to_address.as_deref().unwrap_or_default())
             .bind(sent.value)
             .bind(memo_str)
-            .execute(pg_pool)
+            .execute(&mut *pg_tx)
             .await?;
         }
     }
 
+    if let Some((scanned_from, scanned_to)) = scanned_range {
+        reconcile_wallet_data(&mut pg_tx, user_id, scanned_from, scanned_to, snapshot).await?;
+    }
+
+    pg_tx.commit().await?;
+
     tracing::info!("Blockchain data synced to PostgreSQL successfully");
+
+    if !new_notes.is_empty() {
+        notify_received_notes(user_id, pg_pool, chain_tip, &mined_heights, new_notes).await?;
+    }
+
     Ok(())
 }
+
+/// Delete the user's Postgres rows that no longer exist in the SQLite wallet
+///
+/// The upserts above never remove anything, so after the wallet database is rebuilt (e.g.
+/// the corrupt-database retry) transactions and notes it no longer knows about would
+/// otherwise linger and show up in balances and history. Only rows mined between
+/// `scanned_from` and `scanned_to` are compared, since the wallet can't know about anything
+/// it hasn't scanned: a database recreated from the Postgres checkpoint (see
+/// `fresh_database_birthday`) never sees older blocks, and unmined sends are recorded at
+/// broadcast time before any scan.
+async fn reconcile_wallet_data(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    scanned_from: i64,
+    scanned_to: i64,
+    snapshot: WalletSnapshot,
+) -> Result<()> {
+    let mut txids = Vec::with_capacity(snapshot.notes.len());
+    let mut pools = Vec::with_capacity(snapshot.notes.len());
+    let mut note_indexes = Vec::with_capacity(snapshot.notes.len());
+    for (txid, pool, note_index) in snapshot.notes {
+        txids.push(txid);
+        pools.push(pool);
+        note_indexes.push(note_index);
+    }
+
+    // Their notes go with them (ON DELETE CASCADE)
+    let removed_txs = sqlx::query(
+        "DELETE FROM transactions
+         WHERE user_id = $1::uuid
+           AND block_height BETWEEN $2 AND $3
+           AND txid <> ALL($4::text[])"
+    )
+    .bind(user_id.to_string())
+    .bind(scanned_from)
+    .bind(scanned_to)
+    .bind(&snapshot.txids)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    let removed_sent = sqlx::query(
+        "DELETE FROM sent_notes sn
+         USING transactions t
+         WHERE sn.user_id = $1::uuid
+           AND sn.transaction_id = t.id
+           AND t.block_height BETWEEN $2 AND $3
+           AND t.txid <> ALL($4::text[])"
+    )
+    .bind(user_id.to_string())
+    .bind(scanned_from)
+    .bind(scanned_to)
+    .bind(&snapshot.sent_txids)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    let removed_notes = sqlx::query(
+        "DELETE FROM received_notes rn
+         USING transactions t
+         WHERE rn.user_id = $1::uuid
+           AND rn.transaction_id = t.id
+           AND t.block_height BETWEEN $2 AND $3
+           AND NOT EXISTS (
+               SELECT 1 FROM unnest($4::text[], $5::text[], $6::int[]) AS s(txid, pool, note_index)
+               WHERE s.txid = t.txid AND s.pool = rn.pool AND s.note_index = rn.note_index
+           )"
+    )
+    .bind(user_id.to_string())
+    .bind(scanned_from)
+    .bind(scanned_to)
+    .bind(&txids)
+    .bind(&pools)
+    .bind(&note_indexes)
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    if removed_txs + removed_sent + removed_notes > 0 {
+        tracing::warn!(
+            "Removed {} transaction(s), {} sent note(s) and {} received note(s) for user {} that are no longer in the wallet database",
+            removed_txs,
+            removed_sent,
+            removed_notes,
+            user_id
+        );
+    }
+
+    Ok(())
+}
+
+/// POST a signed notification to the user's webhook, if any, for each newly received note
+///
+/// `notes` were inserted into `received_notes` by this sync, which also happens when a reset
+/// re-inserts old notes, so each one is first claimed in `notified_received_notes` and only
+/// notes never claimed before are delivered. Notes are claimed even without a webhook, so
+/// registering one later doesn't announce past payments. Deliveries run in the background
+/// so a slow or unreachable endpoint doesn't hold up syncing.
+async fn notify_received_notes(
+    user_id: Uuid,
+    pg_pool: &PgPool,
+    chain_tip: u64,
+    mined_heights: &HashMap<String, Option<i64>>,
+    notes: Vec<NoteData>,
+) -> Result<()> {
+    let mut txids = Vec::with_capacity(notes.len());
+    let mut pools = Vec::with_capacity(notes.len());
+    let mut note_indexes = Vec::with_capacity(notes.len());
+    for note in &notes {
+        txids.push(note.txid.clone());
+        pools.push(note.pool.clone());
+        note_indexes.push(note.note_index);
+    }
+
+    let claimed: Vec<(String, String, i32)> = sqlx::query_as(
+        "INSERT INTO notified_received_notes (user_id, txid, pool, note_index)
+         SELECT $1::uuid, s.txid, s.pool, s.note_index
+         FROM unnest($2::text[], $3::text[], $4::int[]) AS s(txid, pool, note_index)
+         ON CONFLICT DO NOTHING
+         RETURNING txid, pool, note_index"
+    )
+    .bind(user_id.to_string())
+    .bind(&txids)
+    .bind(&pools)
+    .bind(&note_indexes)
+    .fetch_all(pg_pool)
+    .await?;
+
+    let notes: Vec<NoteData> = notes
+        .into_iter()
+        .filter(|note| {
+            claimed.iter().any(|(txid, pool, note_index)| {
+                *txid == note.txid && *pool == note.pool && *note_index == note.note_index
+            })
+        })
+        .collect();
+    if notes.is_empty() {
+        return Ok(());
+    }
+
+    let Some(row) = sqlx::query("SELECT url, secret FROM webhooks WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(pg_pool)
+        .await?
+    else {
+        return Ok(());
+    };
+    let url: String = row.get("url");
+    let secret: String = row.get("secret");
+
+    for note in notes {
+        let confirmations = mined_heights
+            .get(&note.txid)
+            .copied()
+            .flatten()
+            .map(|height| chain_tip.saturating_sub(height.max(0) as u64) + 1)
+            .unwrap_or(0);
+
+        let payload = webhook::ReceivedFundsPayload {
+            user_id,
+            txid: note.txid,
+            amount_zec: format_zec(note.value.max(0) as u64),
+            confirmations,
+        };
+        let url = url.clone();
+        let secret = secret.clone();
+        tokio::spawn(async move {
+            if let Err(e) = webhook::deliver(&url, &secret, &payload).await {
+                tracing::error!("Failed to deliver received-funds webhook for {}: {:?}", payload.txid, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zcash_protocol::value::{BalanceError, Zatoshis};
+
+    fn account_balance(spendable: u64, pending_change: u64, transparent: u64) -> AccountBalance {
+        let mut balance = AccountBalance::ZERO;
+        balance
+            .with_orchard_balance_mut(|pool| -> std::result::Result<(), BalanceError> {
+                pool.add_spendable_value(Zatoshis::const_from_u64(spendable))?;
+                pool.add_pending_change_value(Zatoshis::const_from_u64(pending_change))
+            })
+            .unwrap();
+        balance
+            .with_unshielded_balance_mut(|pool| -> std::result::Result<(), BalanceError> {
+                pool.add_spendable_value(Zatoshis::const_from_u64(transparent))
+            })
+            .unwrap();
+        balance
+    }
+
+    #[test]
+    fn test_sum_account_balances_filters_by_account() {
+        let balances = HashMap::from([
+            (0u32, account_balance(100_000, 5_000, 0)),
+            (1u32, account_balance(20_000, 0, 7_000)),
+        ]);
+
+        let primary = sum_account_balances(&balances, Some(&0));
+        assert_eq!(primary.spendable, 100_000);
+        assert_eq!(primary.confirmed, 100_000);
+        assert_eq!(primary.pending, 5_000);
+        assert_eq!(primary.transparent, 0);
+
+        let second = sum_account_balances(&balances, Some(&1));
+        assert_eq!(second.spendable, 20_000);
+        assert_eq!(second.pending, 0);
+        assert_eq!(second.transparent, 7_000);
+
+        let combined = sum_account_balances(&balances, None);
+        assert_eq!(combined.spendable, 120_000);
+        assert_eq!(combined.pending, 5_000);
+        assert_eq!(combined.transparent, 7_000);
+
+        let missing = sum_account_balances(&balances, Some(&2));
+        assert_eq!(missing.spendable, 0);
+    }
+
+    async fn insert_transaction(db: &PgPool, user_id: Uuid, txid: &str, height: Option<i64>) -> i64 {
+        sqlx::query_scalar(
+            "INSERT INTO transactions (user_id, txid, block_height) VALUES ($1::uuid, $2, $3) RETURNING id"
+        )
+        .bind(user_id.to_string())
+        .bind(txid)
+        .bind(height)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+
+    async fn insert_received_note(db: &PgPool, user_id: Uuid, tx_id: i64, note_index: i32) {
+        sqlx::query(
+            "INSERT INTO received_notes (user_id, transaction_id, note_index, value_zatoshis, pool)
+             VALUES ($1::uuid, $2, $3, 10000, 'sapling')"
+        )
+        .bind(user_id.to_string())
+        .bind(tx_id)
+        .bind(note_index)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_sent_note(db: &PgPool, user_id: Uuid, tx_id: i64) {
+        sqlx::query(
+            "INSERT INTO sent_notes (user_id, transaction_id, to_address, value_zatoshis)
+             VALUES ($1::uuid, $2, 'to', 10000)"
+        )
+        .bind(user_id.to_string())
+        .bind(tx_id)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    // Needs a migrated Postgres database in DATABASE_URL
+    #[tokio::test]
+    #[ignore]
+    async fn test_reconcile_only_touches_scanned_heights() {
+        let db = PgPool::connect(&std::env::var("DATABASE_URL").unwrap()).await.unwrap();
+        let user_id: Uuid = sqlx::query_scalar::<_, String>(
+            "INSERT INTO users (email, password_hash) VALUES ($1, 'x') RETURNING id::text"
+        )
+        .bind(format!("reconcile-{}@example.com", Uuid::new_v4()))
+        .fetch_one(&db)
+        .await
+        .unwrap()
+        .parse()
+        .unwrap();
+
+        // Kept, but one of its notes is gone from the wallet
+        let kept = insert_transaction(&db, user_id, "aa", Some(150)).await;
+        insert_received_note(&db, user_id, kept, 0).await;
+        insert_received_note(&db, user_id, kept, 1).await;
+        insert_sent_note(&db, user_id, kept).await;
+        // Gone from the wallet entirely
+        let gone = insert_transaction(&db, user_id, "bb", Some(160)).await;
+        insert_received_note(&db, user_id, gone, 0).await;
+        // Still in the wallet, but it no longer records any outputs sent in it
+        let no_sent = insert_transaction(&db, user_id, "ee", Some(170)).await;
+        insert_sent_note(&db, user_id, no_sent).await;
+        // Below the wallet birthday, and not yet mined
+        let old = insert_transaction(&db, user_id, "cc", Some(50)).await;
+        insert_received_note(&db, user_id, old, 0).await;
+        let pending = insert_transaction(&db, user_id, "dd", None).await;
+        insert_sent_note(&db, user_id, pending).await;
+
+        let snapshot = WalletSnapshot {
+            txids: vec!["aa".to_string(), "ee".to_string()],
+            sent_txids: vec!["aa".to_string()],
+            notes: vec![("aa".to_string(), "sapling".to_string(), 0)],
+        };
+        let mut conn = db.acquire().await.unwrap();
+        reconcile_wallet_data(&mut conn, user_id, 100, 200, snapshot).await.unwrap();
+
+        let txids: Vec<String> = sqlx::query_scalar(
+            "SELECT txid FROM transactions WHERE user_id = $1::uuid ORDER BY txid"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(txids, vec!["aa", "cc", "dd", "ee"]);
+
+        let notes: Vec<(String, i32)> = sqlx::query_as(
+            "SELECT t.txid, rn.note_index FROM received_notes rn
+             JOIN transactions t ON rn.transaction_id = t.id
+             WHERE rn.user_id = $1::uuid ORDER BY t.txid, rn.note_index"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(notes, vec![("aa".to_string(), 0), ("cc".to_string(), 0)]);
+
+        let sent: Vec<String> = sqlx::query_scalar(
+            "SELECT t.txid FROM sent_notes sn
+             JOIN transactions t ON sn.transaction_id = t.id
+             WHERE sn.user_id = $1::uuid ORDER BY t.txid"
+        )
+        .bind(user_id.to_string())
+        .fetch_all(&db)
+        .await
+        .unwrap();
+        assert_eq!(sent, vec!["aa", "dd"]);
+
+        sqlx::query("DELETE FROM users WHERE id = $1::uuid")
+            .bind(user_id.to_string())
+            .execute(&db)
+            .await
+            .unwrap();
+    }
+}
@@ -1,12 +1,19 @@
 use crate::middleware::{AppError, Result};
-use crate::zcash::{database, lightwalletd};
+use crate::models::user::AuthMethod;
+use crate::zcash::{lightwalletd, pool, transaction};
 use bip39::Mnemonic;
+use serde::{Deserialize, Deserializer};
 use sqlx::{PgPool, Row};
 use std::env;
+use std::future::Future;
 use std::path::PathBuf;
+use tracing::Instrument;
 use uuid::Uuid;
+use zcash_address::ZcashAddress;
+use zcash_keys::address::Address;
 use zcash_keys::keys::UnifiedSpendingKey;
-use zcash_protocol::consensus::Network;
+use zcash_protocol::consensus::{Network, NetworkType};
+use zcash_protocol::value::Zatoshis;
 use zip32::AccountId;
 
 /// Conversion constant: 1 ZEC = 100,000,000 zatoshis
@@ -22,6 +29,74 @@ pub struct WalletConfig {
     pub db_path: PathBuf,
 }
 
+/// Currencies `?fiat=` accepts when `FIAT_CURRENCIES` is not set
+const DEFAULT_FIAT_CURRENCIES: &str = "usd,eur,gbp,jpy,cad,aud,chf";
+
+/// `?fiat=<currency>` query asking for fiat values alongside ZEC amounts, e.g. `?fiat=eur`
+#[derive(Deserialize, Default)]
+pub struct FiatQuery {
+    pub fiat: Option<String>,
+}
+
+/// ZEC price in the currency a request asked for
+pub struct FiatPrice {
+    /// Lowercase currency code, as given back in `fiat_currency`
+    pub currency: String,
+    pub price: f64,
+}
+
+impl FiatPrice {
+    /// Value of `zatoshis` in this currency, rounded to cents
+    pub fn format(&self, zatoshis: i64) -> String {
+        crate::utils::price::format_fiat(zatoshis, self.price)
+    }
+}
+
+/// Lowercase currency codes listed in `FIAT_CURRENCIES`
+///
+/// The list bounds which codes clients can make the server look up at the price source.
+fn fiat_currencies() -> Vec<String> {
+    env::var("FIAT_CURRENCIES")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_FIAT_CURRENCIES.to_string())
+        .split(',')
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .collect()
+}
+
+impl FiatQuery {
+    /// The requested currency, lowercased, or `None` if none was requested
+    pub fn currency(&self) -> Result<Option<String>> {
+        let currency = match self.fiat.as_deref().map(str::trim) {
+            None | Some("") => return Ok(None),
+            Some(currency) => currency.to_lowercase(),
+        };
+
+        let supported = fiat_currencies();
+        if !supported.contains(&currency) {
+            return Err(AppError::Validation(format!(
+                "Unsupported fiat currency '{}'; supported: {}",
+                currency,
+                supported.join(", ")
+            )));
+        }
+        Ok(Some(currency))
+    }
+
+    /// Price to convert with, or `None` if not requested or the price source is
+    /// unavailable (fiat fields are then left out of the response)
+    pub async fn price(&self) -> Result<Option<FiatPrice>> {
+        let Some(currency) = self.currency()? else {
+            return Ok(None);
+        };
+        Ok(crate::utils::price::zec_price(&currency)
+            .await
+            .map(|price| FiatPrice { currency, price }))
+    }
+}
+
 /// Load wallet configuration from PostgreSQL
 pub async fn load_wallet_config(
     db: &PgPool,
@@ -29,9 +104,9 @@ pub async fn load_wallet_config(
     include_address: bool,
 ) -> Result<WalletConfig> {
     // Get wallet info from PostgreSQL - use string cast for UUID since sqlx uuid feature disabled
-    let (encrypted_mnemonic, birthday_height, address) = if include_address {
+    let (encrypted_mnemonic, birthday_height, address, network) = if include_address {
         let row = sqlx::query(
-            "SELECT encrypted_mnemonic, birthday_height, address FROM wallets WHERE user_id = $1::uuid"
+            "SELECT encrypted_mnemonic, birthday_height, address, network FROM wallets WHERE user_id = $1::uuid"
         )
         .bind(user_id.to_string())
         .fetch_optional(db)
@@ -41,10 +116,11 @@ pub async fn load_wallet_config(
         let encrypted_mnemonic: String = row.get("encrypted_mnemonic");
         let birthday_height: i64 = row.get("birthday_height");
         let address: String = row.get("address");
-        (encrypted_mnemonic, birthday_height, Some(address))
+        let network: String = row.get("network");
+        (encrypted_mnemonic, birthday_height, Some(address), network)
     } else {
         let row = sqlx::query(
-            "SELECT encrypted_mnemonic, birthday_height FROM wallets WHERE user_id = $1::uuid"
+            "SELECT encrypted_mnemonic, birthday_height, network FROM wallets WHERE user_id = $1::uuid"
         )
         .bind(user_id.to_string())
         .fetch_optional(db)
@@ -53,7 +129,8 @@ pub async fn load_wallet_config(
 
         let encrypted_mnemonic: String = row.get("encrypted_mnemonic");
         let birthday_height: i64 = row.get("birthday_height");
-        (encrypted_mnemonic, birthday_height, None)
+        let network: String = row.get("network");
+        (encrypted_mnemonic, birthday_height, None, network)
     };
 
     // Parse mnemonic
@@ -63,13 +140,14 @@ pub async fn load_wallet_config(
     let seed = mnemonic.to_seed("");
     let birthday_height_u32 = birthday_height as u32;
 
-    // Get network from environment
-    let network = get_network();
+    // Use the network the wallet was created on, not the current ZCASH_NETWORK
+    let network = parse_network(&network);
 
     // Setup per-user wallet database path
-    let data_dir = PathBuf::from("./wallet_data");
-    std::fs::create_dir_all(&data_dir).ok();
-    let db_path = data_dir.join(format!("wallet_{}.db", user_id));
+    let db_path = wallet_db_path(user_id);
+    if let Some(data_dir) = db_path.parent() {
+        std::fs::create_dir_all(data_dir).ok();
+    }
 
     Ok(WalletConfig {
         mnemonic,
@@ -81,16 +159,131 @@ pub async fn load_wallet_config(
     })
 }
 
+/// Directory for per-user wallet databases when `WALLET_DATA_DIR` is unset
+const DEFAULT_WALLET_DATA_DIR: &str = "./wallet_data";
+
+/// Directory holding the per-user wallet databases, from `WALLET_DATA_DIR`
+pub fn wallet_data_dir() -> PathBuf {
+    env::var("WALLET_DATA_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_WALLET_DATA_DIR))
+}
+
+/// Create the wallet data directory and check that it is writable
+///
+/// Called at startup so a bad `WALLET_DATA_DIR` fails immediately instead of on the
+/// first wallet request.
+pub fn ensure_wallet_data_dir() -> std::io::Result<PathBuf> {
+    let dir = wallet_data_dir();
+    std::fs::create_dir_all(&dir)?;
+
+    let probe = dir.join(format!(".write_test_{}", Uuid::new_v4()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)?;
+
+    Ok(dir)
+}
+
+/// Path of the user's on-disk wallet database
+pub fn wallet_db_path(user_id: Uuid) -> PathBuf {
+    wallet_data_dir().join(format!("wallet_{}.db", user_id))
+}
+
+/// Close the user's pooled wallet database and delete it along with its WAL files
+///
+/// Files that are already gone are fine; other failures are logged, not returned, since
+/// callers run this after their Postgres changes are committed.
+pub async fn remove_wallet_db(user_id: Uuid) {
+    // Release the pooled handle first so nothing writes the files back
+    pool::WALLET_DB_POOL.evict(user_id).await;
+    let db_path = wallet_db_path(user_id);
+    for suffix in ["", "-wal", "-shm"] {
+        let mut path = db_path.clone().into_os_string();
+        path.push(suffix);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::error!("Failed to delete wallet file {:?}: {}", path, e),
+        }
+    }
+}
+
+/// Reject the request if REQUIRE_EMAIL_VERIFICATION is enabled and the user hasn't verified
+///
+/// OAuth accounts are treated as verified since the provider already confirmed the address.
+pub async fn ensure_email_verified(db: &PgPool, user_id: Uuid) -> Result<()> {
+    let required = env::var("REQUIRE_EMAIL_VERIFICATION")
+        .map(|v| v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    if !required {
+        return Ok(());
+    }
+
+    let verified: Option<bool> = sqlx::query_scalar(
+        "SELECT COALESCE(email_verified, FALSE) OR auth_method <> 'email' FROM users WHERE id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(db)
+    .await?;
+
+    if verified != Some(true) {
+        return Err(AppError::Unauthorized(
+            "Please verify your email address before sending funds".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 /// Get network configuration from environment
+///
+/// Only used when creating wallets; existing wallets use their stored network.
 pub fn get_network() -> Network {
     let network_str = env::var("ZCASH_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
-    match network_str.to_lowercase().as_str() {
+    parse_network(&network_str)
+}
+
+/// Parse a network name as stored in `wallets.network`, defaulting to mainnet
+pub fn parse_network(name: &str) -> Network {
+    match name.to_lowercase().as_str() {
         "testnet" => Network::TestNetwork,
         _ => Network::MainNetwork,
     }
 }
 
+/// Network name to store in `wallets.network`
+pub fn network_name(network: Network) -> &'static str {
+    match network {
+        Network::MainNetwork => "mainnet",
+        Network::TestNetwork => "testnet",
+    }
+}
+
+/// Check that a Zcash address parses and belongs to the given network
+pub fn validate_zcash_address(address: &str, network: Network) -> Result<()> {
+    let parsed = ZcashAddress::try_from_encoded(address)
+        .map_err(|_| AppError::InvalidAddress("Invalid Zcash address".to_string()))?;
+
+    let network_type = match network {
+        Network::MainNetwork => NetworkType::Main,
+        Network::TestNetwork => NetworkType::Test,
+    };
+
+    parsed.convert_if_network::<Address>(network_type).map_err(|_| {
+        AppError::InvalidAddress(format!(
+            "Zcash address is not a {} address",
+            network_name(network)
+        ))
+    })?;
+
+    Ok(())
+}
+
 /// Get lightwalletd URL for the given network
+///
+/// May be a comma-separated list; entries after the first are fallbacks.
 pub fn get_lightwalletd_url(network: Network) -> String {
     match network {
         Network::MainNetwork => {
@@ -109,34 +302,214 @@ pub async fn connect_lightwalletd(network: Network) -> Result<lightwalletd::Ligh
     let url = get_lightwalletd_url(network);
     tracing::info!("Connecting to lightwalletd: {}", url);
 
-    let mut client = lightwalletd::LightwalletdClient::new(url);
+    let mut client = lightwalletd::LightwalletdClient::from_endpoint_list(&url);
     client
         .connect()
         .await
         .map_err(|e| AppError::Internal(format!("Failed to connect to lightwalletd: {}", e)))?;
 
-    tracing::info!("Connected to lightwalletd");
+    tracing::info!(
+        "Connected to lightwalletd: {}",
+        client.connected_endpoint().unwrap_or_default()
+    );
     Ok(client)
 }
 
-/// Open or create wallet database
-pub fn open_wallet_database(
+/// Borrow the user's wallet database from the shared pool, opening it if needed
+pub async fn pooled_wallet_database(
+    user_id: Uuid,
     db_path: &std::path::Path,
     network: Network,
-) -> Result<database::Database> {
-    database::Database::new(db_path, network)
+) -> Result<pool::PooledDatabase> {
+    pool::WALLET_DB_POOL
+        .get(user_id, db_path, network)
+        .await
         .map_err(|e| AppError::Internal(format!("Failed to open database: {}", e)))
 }
 
+/// Fetch the account's transparent UTXOs from lightwalletd and store them in the wallet
+///
+/// Returns the total transparent value in zatoshis.
+pub async fn load_transparent_utxos(
+    client: &lightwalletd::LightwalletdClient,
+    tx_builder: &mut transaction::TransactionBuilder<'_>,
+    usk: &UnifiedSpendingKey,
+    birthday_height: u32,
+) -> Result<u64> {
+    let addresses = tx_builder
+        .transparent_receivers(usk)
+        .map_err(|e| AppError::Internal(format!("Failed to list transparent receivers: {}", e)))?;
+
+    if addresses.is_empty() {
+        return Ok(0);
+    }
+
+    let utxos = client
+        .get_address_utxos(addresses, birthday_height as u64)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to fetch transparent UTXOs: {}", e)))?;
+
+    let transparent_zatoshis: i64 = utxos.iter().map(|utxo| utxo.value_zat).sum();
+    if transparent_zatoshis <= 0 {
+        return Ok(0);
+    }
+
+    tracing::info!(
+        "Found {} transparent UTXO(s) totalling {} zatoshis",
+        utxos.len(),
+        transparent_zatoshis
+    );
+
+    tx_builder
+        .store_transparent_utxos(&utxos)
+        .map_err(|e| AppError::Internal(format!("Failed to store transparent UTXOs: {}", e)))?;
+
+    Ok(transparent_zatoshis as u64)
+}
+
 /// Derive unified spending key from seed
 pub fn derive_spending_key(seed: &[u8], network: Network) -> Result<UnifiedSpendingKey> {
-    UnifiedSpendingKey::from_seed(&network, seed, AccountId::try_from(0).unwrap())
+    derive_account_spending_key(seed, network, 0)
+}
+
+/// Derive the spending key of HD account `account_index` (0 is the primary account)
+pub fn derive_account_spending_key(
+    seed: &[u8],
+    network: Network,
+    account_index: u32,
+) -> Result<UnifiedSpendingKey> {
+    let account_id = AccountId::try_from(account_index)
+        .map_err(|_| AppError::Validation(format!("Invalid account index {}", account_index)))?;
+    UnifiedSpendingKey::from_seed(&network, seed, account_id)
         .map_err(|e| AppError::Internal(format!("Failed to derive key: {:?}", e)))
 }
 
-/// Convert ZEC to zatoshis
-pub fn zec_to_zatoshis(zec: f64) -> u64 {
-    (zec * ZATOSHIS_PER_ZEC) as u64
+/// Shielded address of one of the user's HD accounts
+///
+/// Account 0 is the wallet's primary account; others are added with `POST /wallet/accounts`.
+/// Returns 404 if the user has no such account.
+pub async fn account_address(db: &PgPool, user_id: Uuid, account_index: u32) -> Result<String> {
+    let address: Option<String> = if account_index == 0 {
+        sqlx::query_scalar("SELECT address FROM wallets WHERE user_id = $1::uuid")
+            .bind(user_id.to_string())
+            .fetch_optional(db)
+            .await?
+    } else {
+        sqlx::query_scalar(
+            "SELECT address FROM wallet_accounts WHERE user_id = $1::uuid AND account_index = $2"
+        )
+        .bind(user_id.to_string())
+        .bind(account_index as i64)
+        .fetch_optional(db)
+        .await?
+    };
+
+    address.ok_or_else(|| AppError::NotFound(format!("Account {} not found", account_index)))
+}
+
+/// Re-authenticate a sensitive action with the user's current password
+///
+/// Only email users have a password; OAuth users have nothing to confirm and pass.
+pub async fn confirm_password(db: &PgPool, user_id: Uuid, password: Option<&str>) -> Result<()> {
+    let row = sqlx::query("SELECT password_hash, auth_method::text FROM users WHERE id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let auth_method: String = row.get("auth_method");
+    if AuthMethod::from_str(&auth_method) != AuthMethod::Email {
+        return Ok(());
+    }
+
+    let password = password
+        .ok_or_else(|| AppError::Validation("Password is required to confirm this action".to_string()))?;
+    let password_hash: String = row
+        .get::<Option<String>, _>("password_hash")
+        .ok_or_else(|| AppError::Internal("Password hash not found".to_string()))?;
+
+    let password_valid = bcrypt::verify(password, &password_hash)
+        .map_err(|e| AppError::Internal(format!("Failed to verify password: {}", e)))?;
+    if !password_valid {
+        return Err(AppError::Unauthorized("Invalid password".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Parse a decimal ZEC amount such as `"0.1"` into exact zatoshis
+///
+/// Uses integer arithmetic on the whole and fractional digits so no precision is lost.
+/// Rejects signs, exponents and more than 8 fractional digits.
+pub fn parse_zec_amount(amount: &str) -> Result<Zatoshis> {
+    let amount = amount.trim();
+    let invalid = || AppError::Validation(format!("Invalid ZEC amount: '{}'", amount));
+
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return Err(invalid());
+    }
+    if !whole.bytes().all(|b| b.is_ascii_digit()) || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    if fraction.len() > 8 {
+        return Err(AppError::Validation(format!(
+            "Invalid ZEC amount: '{}' has more than 8 decimal places",
+            amount
+        )));
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| invalid())?
+    };
+    let fraction: u64 = format!("{:0<8}", fraction).parse().map_err(|_| invalid())?;
+
+    let zatoshis = whole
+        .checked_mul(100_000_000)
+        .and_then(|z| z.checked_add(fraction))
+        .ok_or_else(invalid)?;
+
+    Zatoshis::from_u64(zatoshis).map_err(|_| {
+        AppError::Validation(format!("Invalid ZEC amount: '{}' exceeds the maximum supply", amount))
+    })
+}
+
+/// Deserialize a ZEC amount sent as either a JSON string or a JSON number
+///
+/// Numbers are formatted with their shortest round-trip representation, so `0.1`
+/// becomes `"0.1"` and is parsed exactly by `parse_zec_amount`.
+pub fn deserialize_zec_amount<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Text(String),
+        Number(f64),
+    }
+
+    Ok(match Amount::deserialize(deserializer)? {
+        Amount::Text(text) => text,
+        Amount::Number(number) => number.to_string(),
+    })
+}
+
+/// Like `deserialize_zec_amount`, for fields that may be omitted or null
+///
+/// Use together with `#[serde(default)]`.
+pub fn deserialize_optional_zec_amount<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "deserialize_zec_amount")] String);
+
+    Ok(Option::<Wrapper>::deserialize(deserializer)?.map(|Wrapper(amount)| amount))
 }
 
 /// Convert zatoshis to ZEC
@@ -144,6 +517,14 @@ pub fn zatoshis_to_zec(zatoshis: u64) -> f64 {
     zatoshis as f64 / ZATOSHIS_PER_ZEC
 }
 
+/// Format zatoshis as a ZEC string with 8 decimal places, without going through `f64`
+pub fn format_zec(zatoshis: impl Into<i128>) -> String {
+    let zatoshis: i128 = zatoshis.into();
+    let sign = if zatoshis < 0 { "-" } else { "" };
+    let zatoshis = zatoshis.unsigned_abs();
+    format!("{}{}.{:08}", sign, zatoshis / 100_000_000, zatoshis % 100_000_000)
+}
+
 /// Get block explorer URL for a transaction
 pub fn get_explorer_url(network: Network, txid: &str) -> String {
     match network {
@@ -155,3 +536,33 @@ pub fn get_explorer_url(network: Network, txid: &str) -> String {
         }
     }
 }
+
+/// Run a handler's work in its own task so it finishes even if the request is dropped
+///
+/// Routes sit behind a `TimeoutLayer`, which drops the handler future on timeout. For a send
+/// that could stop mid-build or mid-broadcast, leaving a transaction on chain that was never
+/// recorded, so here a timed-out request only stops waiting for the result.
+pub async fn run_to_completion<T, F>(work: F) -> Result<T>
+where
+    F: Future<Output = Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::spawn(work.in_current_span())
+        .await
+        .map_err(|e| AppError::Internal(format!("Request task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_zec() {
+        assert_eq!(format_zec(0u64), "0.00000000");
+        assert_eq!(format_zec(1u64), "0.00000001");
+        assert_eq!(format_zec(150_000_000u64), "1.50000000");
+        assert_eq!(format_zec(-25_000i64), "-0.00025000");
+        // Exact beyond the 53 bits an f64 holds
+        assert_eq!(format_zec(u64::MAX), "184467440737.09551615");
+    }
+}
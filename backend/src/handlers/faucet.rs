@@ -0,0 +1,137 @@
+use crate::handlers::common::{get_network, parse_network, validate_zcash_address};
+use crate::middleware::{AppError, Json, Result};
+use axum::{extract::Extension, routing::post, Router};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use std::time::Duration;
+use uuid::Uuid;
+use zcash_protocol::consensus::Network;
+
+// Testnet faucet for local development. Only compiled with the `testnet-faucet` feature,
+// and only mounted when the server creates testnet wallets and `TESTNET_FAUCET_URL` is set;
+// mainnet wallets and addresses are refused even then.
+
+/// Amount requested when the body doesn't name one (0.1 TAZ)
+const DEFAULT_FAUCET_ZATOSHIS: u64 = 10_000_000;
+
+/// Largest amount one request may ask for (1 TAZ)
+const MAX_FAUCET_ZATOSHIS: u64 = 100_000_000;
+
+const FAUCET_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct FaucetRequest {
+    /// Testnet address to fund (default: the wallet's address)
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub amount_zatoshis: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FaucetResponse {
+    pub address: String,
+    pub amount_zatoshis: u64,
+    /// Funding transaction, if the faucet reports one
+    pub txid: Option<String>,
+}
+
+/// Faucet endpoint, or `None` if `TESTNET_FAUCET_URL` is unset
+fn faucet_url() -> Option<String> {
+    std::env::var("TESTNET_FAUCET_URL")
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// Add `POST /wallet/faucet` to `router` if this server runs on testnet with a faucet configured
+pub fn mount(router: Router) -> Router {
+    if get_network() != Network::TestNetwork {
+        tracing::warn!("testnet-faucet feature is enabled but ZCASH_NETWORK is not testnet; faucet not mounted");
+        return router;
+    }
+    if faucet_url().is_none() {
+        tracing::warn!("testnet-faucet feature is enabled but TESTNET_FAUCET_URL is not set; faucet not mounted");
+        return router;
+    }
+
+    tracing::warn!("Testnet faucet mounted at /api/wallet/faucet; do not use this build in production");
+    router.route("/wallet/faucet", post(request_funds))
+}
+
+/// Ask the configured testnet faucet to fund the authenticated user's wallet
+///
+/// POSTs `{"address", "amount_zatoshis"}` to `TESTNET_FAUCET_URL`, with
+/// `TESTNET_FAUCET_API_KEY` as a bearer token when set, and passes on the `txid` from the
+/// faucet's JSON reply if there is one.
+pub async fn request_funds(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    payload: Option<Json<FaucetRequest>>,
+) -> Result<Json<FaucetResponse>> {
+    let payload = payload.map(|Json(request)| request).unwrap_or_default();
+    let url = faucet_url().ok_or_else(|| AppError::NotFound("Faucet is not configured".to_string()))?;
+
+    let row = sqlx::query("SELECT address, network FROM wallets WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(&db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+    let network: String = row.get("network");
+
+    // Checked per wallet as well: wallets keep the network they were created on
+    if parse_network(&network) != Network::TestNetwork {
+        return Err(AppError::Validation("The faucet only funds testnet wallets".to_string()));
+    }
+
+    let address = match payload.address {
+        Some(address) => address.trim().to_string(),
+        None => row.get("address"),
+    };
+    validate_zcash_address(&address, Network::TestNetwork)?;
+
+    let amount_zatoshis = payload.amount_zatoshis.unwrap_or(DEFAULT_FAUCET_ZATOSHIS);
+    if amount_zatoshis == 0 || amount_zatoshis > MAX_FAUCET_ZATOSHIS {
+        return Err(AppError::Validation(format!(
+            "amount_zatoshis must be between 1 and {}",
+            MAX_FAUCET_ZATOSHIS
+        )));
+    }
+
+    tracing::info!(
+        "Requesting {} zatoshis from the testnet faucet for user {}",
+        amount_zatoshis,
+        user_id
+    );
+
+    let client = reqwest::Client::builder()
+        .timeout(FAUCET_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to build HTTP client: {}", e)))?;
+    let mut request = client.post(&url).json(&serde_json::json!({
+        "address": address,
+        "amount_zatoshis": amount_zatoshis,
+    }));
+    if let Some(api_key) = std::env::var("TESTNET_FAUCET_API_KEY").ok().filter(|key| !key.is_empty()) {
+        request = request.bearer_auth(api_key);
+    }
+
+    let response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| AppError::Internal(format!("Testnet faucet request failed: {}", e)))?;
+
+    // Faucets differ in what they return; only a top-level `txid` is picked up
+    let txid = response
+        .json::<serde_json::Value>()
+        .await
+        .ok()
+        .and_then(|body| body.get("txid").and_then(|txid| txid.as_str()).map(str::to_string));
+
+    Ok(Json(FaucetResponse {
+        address,
+        amount_zatoshis,
+        txid,
+    }))
+}
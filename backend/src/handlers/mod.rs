@@ -1,6 +1,9 @@
 pub mod auth;
 pub mod balance;
 pub mod common;
+#[cfg(feature = "testnet-faucet")]
+pub mod faucet;
+pub mod scan;
 pub mod send;
 pub mod solana_wallet;
 pub mod transactions;
@@ -0,0 +1,159 @@
+use crate::handlers::balance::{self, BalanceState};
+use crate::middleware::{AppError, Json, Result};
+use crate::zcash::scanner::{ScanControl, ScanProgress};
+use axum::{extract::{Extension, Path, State}, http::StatusCode};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::Instrument;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+/// How long a finished job's final status stays available
+const FINISHED_JOB_RETENTION: Duration = Duration::from_secs(3600);
+
+// Background scan jobs by job id
+static SCAN_JOBS: Lazy<Mutex<HashMap<Uuid, ScanJob>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanJobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+struct ScanJob {
+    user_id: Uuid,
+    status: ScanJobStatus,
+    error: Option<String>,
+    progress: watch::Receiver<ScanProgress>,
+    cancel: CancellationToken,
+    finished_at: Option<Instant>,
+}
+
+#[derive(Serialize)]
+pub struct ScanJobResponse {
+    pub job_id: Uuid,
+    pub status: ScanJobStatus,
+    pub progress: ScanProgress,
+    pub error: Option<String>,
+}
+
+impl ScanJobResponse {
+    fn new(job_id: Uuid, job: &ScanJob) -> Self {
+        Self {
+            job_id,
+            status: job.status,
+            progress: *job.progress.borrow(),
+            error: job.error.clone(),
+        }
+    }
+}
+
+/// Start scanning the authenticated user's wallet in the background
+///
+/// Returns the job id immediately; poll `GET /wallet/scan/:job_id` for progress. If the user
+/// already has a scan running, that job is returned instead of starting another. Progress is
+/// saved per batch, so a cancelled or failed scan resumes where it stopped next time.
+pub async fn start_scan(
+    State(state): State<BalanceState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<(StatusCode, Json<ScanJobResponse>)> {
+    let mut jobs = SCAN_JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    jobs.retain(|_, job| {
+        job.finished_at
+            .is_none_or(|finished_at| finished_at.elapsed() < FINISHED_JOB_RETENTION)
+    });
+
+    if let Some((job_id, job)) = jobs
+        .iter()
+        .find(|(_, job)| job.user_id == user_id && job.status == ScanJobStatus::Running)
+    {
+        return Ok((StatusCode::OK, Json(ScanJobResponse::new(*job_id, job))));
+    }
+
+    let job_id = Uuid::new_v4();
+    let cancel = CancellationToken::new();
+    let (progress_tx, progress_rx) = watch::channel(ScanProgress::default());
+
+    let job = ScanJob {
+        user_id,
+        status: ScanJobStatus::Running,
+        error: None,
+        progress: progress_rx,
+        cancel: cancel.clone(),
+        finished_at: None,
+    };
+    let response = ScanJobResponse::new(job_id, &job);
+    jobs.insert(job_id, job);
+    drop(jobs);
+
+    tracing::info!("Starting scan job {} for user {}", job_id, user_id);
+
+    let control = ScanControl {
+        cancel: Some(cancel.clone()),
+        progress: Some(progress_tx),
+    };
+    tokio::spawn(async move {
+        let result = balance::sync_balance(&state.db, user_id, true, None, control).await;
+
+        let (status, error) = match result {
+            Ok(_) => (ScanJobStatus::Completed, None),
+            Err(_) if cancel.is_cancelled() => (ScanJobStatus::Cancelled, None),
+            Err(e) => {
+                tracing::error!("Scan job {} failed: {:?}", job_id, e);
+                (ScanJobStatus::Failed, Some(e.to_string()))
+            }
+        };
+        tracing::info!("Scan job {} finished: {:?}", job_id, status);
+
+        let mut jobs = SCAN_JOBS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.status = status;
+            job.error = error;
+            job.finished_at = Some(Instant::now());
+        }
+    }.in_current_span());
+
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+/// Status and progress of one of the authenticated user's scan jobs
+pub async fn get_scan_job(
+    Extension(user_id): Extension<Uuid>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<ScanJobResponse>> {
+    let jobs = SCAN_JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    let job = user_job(&jobs, user_id, job_id)?;
+    Ok(Json(ScanJobResponse::new(job_id, job)))
+}
+
+/// Cancel one of the authenticated user's scan jobs
+///
+/// The scan stops after its current batch; the job reports `cancelled` once it has.
+pub async fn cancel_scan_job(
+    Extension(user_id): Extension<Uuid>,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<ScanJobResponse>> {
+    let jobs = SCAN_JOBS.lock().unwrap_or_else(|e| e.into_inner());
+    let job = user_job(&jobs, user_id, job_id)?;
+
+    if job.status == ScanJobStatus::Running {
+        tracing::info!("Cancelling scan job {} for user {}", job_id, user_id);
+        job.cancel.cancel();
+    }
+
+    Ok(Json(ScanJobResponse::new(job_id, job)))
+}
+
+/// Look up a job, hiding other users' jobs behind the same 404 as unknown ones
+fn user_job(jobs: &HashMap<Uuid, ScanJob>, user_id: Uuid, job_id: Uuid) -> Result<&ScanJob> {
+    jobs.get(&job_id)
+        .filter(|job| job.user_id == user_id)
+        .ok_or_else(|| AppError::NotFound("Scan job not found".to_string()))
+}
@@ -1,16 +1,24 @@
 use crate::handlers::common::{
-    connect_lightwalletd, derive_spending_key, get_explorer_url, get_lightwalletd_url,
-    load_wallet_config, open_wallet_database, zatoshis_to_zec, zec_to_zatoshis,
+    account_address, connect_lightwalletd, derive_account_spending_key, derive_spending_key,
+    deserialize_optional_zec_amount, deserialize_zec_amount, ensure_email_verified,
+    get_explorer_url, get_lightwalletd_url, load_transparent_utxos, load_wallet_config,
+    parse_network, parse_zec_amount, pooled_wallet_database, run_to_completion, zatoshis_to_zec,
+    WalletConfig,
 };
-use crate::middleware::{AppError, Result};
-use crate::zcash::{account, lightwalletd, scanner, transaction};
-use axum::{extract::State, Json};
-use rand::rngs::OsRng;
-use rusqlite::Connection as SqliteConnection;
+use crate::handlers::balance;
+use crate::middleware::{AppError, Json, Result};
+use crate::utils::env::env_or;
+use crate::zcash::{account, lightwalletd, pool, scanner, transaction};
+use axum::{extract::{Extension, State}, http::HeaderMap};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
-use zcash_client_sqlite::{util::SystemClock, WalletDb};
+use zcash_client_backend::data_api::WalletRead;
+use zcash_primitives::transaction::fees::zip317;
 use zcash_protocol::consensus::Network;
 
 #[derive(Clone)]
@@ -20,29 +28,58 @@ pub struct SendState {
 
 #[derive(Serialize, Deserialize)]
 pub struct SendTransactionRequest {
-    pub user_id: Uuid,
     pub to_address: String,
-    pub amount_zec: f64,
+    /// Decimal ZEC amount, as a string (preferred) or a number; omit when `send_max` is set
+    #[serde(default, deserialize_with = "deserialize_optional_zec_amount")]
+    pub amount_zec: Option<String>,
+    /// Send every spendable note, with the fee deducted from the amount
+    #[serde(default)]
+    pub send_max: bool,
     pub memo: Option<String>,
+    /// Memo for the wallet's own change output (default none); unused with `send_max`
+    #[serde(default)]
+    pub change_memo: Option<String>,
+    /// Confirmations required on spent notes (default `MIN_CONFIRMATIONS`, must be nonzero)
+    pub min_confirmations: Option<u32>,
+    /// Fee rule name (default "zip317")
+    pub fee_rule: Option<String>,
+    /// Pay the recipient and take change only through this pool ("orchard" or "sapling");
+    /// the recipient's address must have a receiver in it. Not supported with `send_max`
+    #[serde(default)]
+    pub prefer_pool: Option<String>,
+    /// HD account to spend from (default 0, the primary account)
+    #[serde(default)]
+    pub account_index: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SendTransactionResponse {
+    /// ID of the final transaction, the one paying the recipient
     pub txid: String,
+    /// Every transaction broadcast for this send, in broadcast order
+    #[serde(default)]
+    pub txids: Vec<String>,
     pub from_address: String,
     pub to_address: String,
     pub amount_zec: f64,
     pub fee_zec: f64,
     pub explorer_url: String,
+    #[serde(default)]
+    pub explorer_urls: Vec<String>,
     pub message: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct EstimateFeeRequest {
-    pub user_id: Uuid,
     pub to_address: String,
-    pub amount_zec: f64,
+    /// Decimal ZEC amount, as a string (preferred) or a number
+    #[serde(deserialize_with = "deserialize_zec_amount")]
+    pub amount_zec: String,
     pub memo: Option<String>,
+    /// Confirmations required on spent notes (default `MIN_CONFIRMATIONS`, must be nonzero)
+    pub min_confirmations: Option<u32>,
+    /// Fee rule name (default "zip317")
+    pub fee_rule: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -51,22 +88,348 @@ pub struct EstimateFeeResponse {
     pub total_zec: f64,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct BatchOutput {
+    pub to_address: String,
+    /// Decimal ZEC amount, as a string (preferred) or a number
+    #[serde(deserialize_with = "deserialize_zec_amount")]
+    pub amount_zec: String,
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SendBatchRequest {
+    pub outputs: Vec<BatchOutput>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SendBatchResponse {
+    /// ID of the final transaction, the one paying the recipients
+    pub txid: String,
+    /// Every transaction broadcast for this batch, in broadcast order
+    pub txids: Vec<String>,
+    pub num_outputs: usize,
+    pub total_amount_zec: f64,
+    pub fee_zec: f64,
+    pub explorer_url: String,
+    pub explorer_urls: Vec<String>,
+    pub message: String,
+}
+
+/// Operation to dry-run in `estimate`
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum EstimateOperation {
+    Send {
+        to_address: String,
+        #[serde(deserialize_with = "deserialize_zec_amount")]
+        amount_zec: String,
+        memo: Option<String>,
+    },
+    Batch {
+        outputs: Vec<BatchOutput>,
+    },
+    Shield,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EstimateResponse {
+    pub estimated_fee_zec: f64,
+    pub total_zec: f64,
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShieldFundsResponse {
+    pub txid: Option<String>,
+    /// Every transaction broadcast for this shielding, in broadcast order
+    pub txids: Vec<String>,
+    pub shielded_zec: f64,
+    pub fee_zec: f64,
+    pub explorer_url: Option<String>,
+    pub explorer_urls: Vec<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ParsePaymentUriRequest {
+    pub uri: String,
+}
+
+/// A ZIP-321 payment in the shape `send_transaction` accepts, plus the URI's display fields
+#[derive(Serialize, Deserialize)]
+pub struct ParsePaymentUriResponse {
+    pub to_address: String,
+    pub amount_zec: String,
+    pub memo: Option<String>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PrepareSendResponse {
+    /// Pass to `/wallet/send/confirm` to broadcast, or `/wallet/send/cancel` to discard
+    pub prepare_id: Uuid,
+    /// ID the final transaction will have once broadcast
+    pub txid: Option<String>,
+    pub from_address: String,
+    pub to_address: String,
+    pub amount_zec: f64,
+    pub fee_zec: f64,
+    pub total_zec: f64,
+    /// Seconds left to confirm before the prepared send is discarded
+    pub expires_in_secs: u64,
+    /// Block height after which the signed transaction can no longer be mined
+    pub expiry_height: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PreparedSendRequest {
+    pub prepare_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CancelSendResponse {
+    pub cancelled: bool,
+    /// The inputs stay reserved by the wallet until this height, when the transaction expires
+    pub funds_reserved_until_height: Option<u32>,
+}
+
+/// A signed send waiting for `/wallet/send/confirm`
+struct PreparedSend {
+    user_id: Uuid,
+    network: Network,
+    raw_txs: Vec<Vec<u8>>,
+    txid: Option<String>,
+    pending_txid: Option<String>,
+    expiry_height: Option<u32>,
+    from_address: String,
+    to_address: String,
+    memo: Option<String>,
+    amount_zatoshis: u64,
+    fee_zatoshis: u64,
+    prepared_at: Instant,
+}
+
+// Prepared sends by prepare id; only kept in memory, so a restart discards them
+static PREPARED_SENDS: Lazy<Mutex<HashMap<Uuid, PreparedSend>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Default time a prepared send can be confirmed for, unless overridden by `PREPARED_SEND_TTL_SECS`
+const DEFAULT_PREPARED_SEND_TTL_SECS: u64 = 300;
+
+fn prepared_send_ttl() -> Duration {
+    Duration::from_secs(env_or("PREPARED_SEND_TTL_SECS", DEFAULT_PREPARED_SEND_TTL_SECS))
+}
+
+/// Header clients set so a retried send returns the original result
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Longest accepted `Idempotency-Key` value
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Default time a stored idempotent response is replayed for
+const DEFAULT_IDEMPOTENCY_KEY_TTL_SECS: i64 = 86_400;
+
 /// Send ZEC transaction
 /// Scans blockchain, builds and signs transaction, then broadcasts it
+///
+/// With an `Idempotency-Key` header, a repeated key (per user, within
+/// `IDEMPOTENCY_KEY_TTL_SECS`) returns the original response instead of sending again.
+/// The key is released for reuse only when nothing reached the network; a send that
+/// failed partway keeps it, and retries get the txids that did go out.
 #[axum::debug_handler]
 pub async fn send_transaction(
     State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<SendTransactionRequest>,
+) -> Result<Json<SendTransactionResponse>> {
+    let key = idempotency_key(&headers)?;
+    run_to_completion(idempotent_send(state, user_id, key, payload)).await
+}
+
+/// Send, storing the result under `key` if the client gave one
+async fn idempotent_send(
+    state: SendState,
+    user_id: Uuid,
+    key: Option<String>,
+    payload: SendTransactionRequest,
+) -> Result<Json<SendTransactionResponse>> {
+    let Some(key) = key else {
+        return Ok(Json(build_and_send(&state, user_id, payload).await?));
+    };
+
+    if let Some(previous) = claim_idempotency_key(&state.db, user_id, &key).await? {
+        tracing::info!("Replaying send for user {} with idempotency key {}", user_id, key);
+        return Ok(Json(previous));
+    }
+
+    match build_and_send(&state, user_id, payload).await {
+        Ok(response) => {
+            // The transaction is already broadcast, so a failure here must not fail the request
+            if let Err(e) = store_idempotent_response(&state.db, user_id, &key, &response).await {
+                tracing::error!("Failed to store idempotent response for user {}: {:?}", user_id, e);
+            }
+            Ok(Json(response))
+        }
+        Err(SendFailure { error, partial: Some(partial) }) => {
+            // Part of the send may be on the network; a retry must replay this, not send again
+            if let Err(e) = store_idempotent_response(&state.db, user_id, &key, &partial).await {
+                tracing::error!("Failed to store partial idempotent response for user {}: {:?}", user_id, e);
+            }
+            Err(error)
+        }
+        Err(SendFailure { error, partial: None }) => {
+            // Nothing was broadcast; let the client retry with the same key
+            if let Err(release_err) = release_idempotency_key(&state.db, user_id, &key).await {
+                tracing::error!("Failed to release idempotency key for user {}: {:?}", user_id, release_err);
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Build and sign a send without broadcasting it
+///
+/// The signed transaction is held server-side for `PREPARED_SEND_TTL_SECS` so the client
+/// can show the exact fee and txid before the user commits to `/wallet/send/confirm`.
+#[axum::debug_handler]
+pub async fn prepare_send(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
     Json(payload): Json<SendTransactionRequest>,
+) -> Result<Json<PrepareSendResponse>> {
+    // Signing reserves the inputs, so don't let a timeout lose the signed transaction
+    let prepared = run_to_completion(async move { build_send(&state, user_id, payload).await }).await?;
+    let ttl = prepared_send_ttl();
+    let prepare_id = Uuid::new_v4();
+
+    let response = PrepareSendResponse {
+        prepare_id,
+        txid: prepared.txid.clone(),
+        from_address: prepared.from_address.clone(),
+        to_address: prepared.to_address.clone(),
+        amount_zec: zatoshis_to_zec(prepared.amount_zatoshis),
+        fee_zec: zatoshis_to_zec(prepared.fee_zatoshis),
+        total_zec: zatoshis_to_zec(prepared.amount_zatoshis + prepared.fee_zatoshis),
+        expires_in_secs: ttl.as_secs(),
+        expiry_height: prepared.expiry_height,
+    };
+
+    let mut prepared_sends = PREPARED_SENDS.lock().unwrap_or_else(|e| e.into_inner());
+    prepared_sends.retain(|_, send| send.prepared_at.elapsed() < ttl);
+    prepared_sends.insert(prepare_id, prepared);
+    drop(prepared_sends);
+
+    tracing::info!("Prepared send {} for user {}", prepare_id, user_id);
+    Ok(Json(response))
+}
+
+/// Broadcast a send made by `/wallet/send/prepare`
+///
+/// Each prepared send can be confirmed once; the id is consumed before broadcasting, so
+/// the broadcast runs to completion even if the request times out.
+#[axum::debug_handler]
+pub async fn confirm_send(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(payload): Json<PreparedSendRequest>,
 ) -> Result<Json<SendTransactionResponse>> {
+    let prepared = take_prepared_send(user_id, payload.prepare_id)?;
+    tracing::info!("Confirming prepared send {} for user {}", payload.prepare_id, user_id);
+    run_to_completion(async move { Ok(Json(broadcast_send(&state, prepared).await?)) }).await
+}
+
+/// Discard a send made by `/wallet/send/prepare` without broadcasting it
+///
+/// The wallet keeps the transaction's inputs reserved until it expires, so the response
+/// reports the height at which they become spendable again.
+pub async fn cancel_send(
+    Extension(user_id): Extension<Uuid>,
+    Json(payload): Json<PreparedSendRequest>,
+) -> Result<Json<CancelSendResponse>> {
+    let prepared = take_prepared_send(user_id, payload.prepare_id)?;
+    tracing::info!("Cancelled prepared send {} for user {}", payload.prepare_id, user_id);
+
+    Ok(Json(CancelSendResponse {
+        cancelled: true,
+        funds_reserved_until_height: prepared.expiry_height,
+    }))
+}
+
+/// Remove and return the user's unexpired prepared send with this id
+fn take_prepared_send(user_id: Uuid, prepare_id: Uuid) -> Result<PreparedSend> {
+    let mut prepared_sends = PREPARED_SENDS.lock().unwrap_or_else(|e| e.into_inner());
+
+    // Someone else's id is treated like an unknown one and left in place
+    if prepared_sends.get(&prepare_id).map(|send| send.user_id) != Some(user_id) {
+        return Err(AppError::NotFound("Prepared send not found or expired".to_string()));
+    }
+
+    match prepared_sends.remove(&prepare_id) {
+        Some(prepared) if prepared.prepared_at.elapsed() < prepared_send_ttl() => Ok(prepared),
+        _ => Err(AppError::NotFound("Prepared send not found or expired".to_string())),
+    }
+}
+
+/// A send that failed, with what reached the network before it did
+struct SendFailure {
+    error: AppError,
+    /// Response describing the transactions that may already be on the network, stored
+    /// for the send's idempotency key; `None` if nothing left the server
+    partial: Option<SendTransactionResponse>,
+}
+
+impl From<AppError> for SendFailure {
+    fn from(error: AppError) -> Self {
+        Self { error, partial: None }
+    }
+}
+
+impl From<SendFailure> for AppError {
+    fn from(failure: SendFailure) -> Self {
+        failure.error
+    }
+}
+
+/// Scan, build, sign and broadcast a single-recipient send
+async fn build_and_send(
+    state: &SendState,
+    user_id: Uuid,
+    payload: SendTransactionRequest,
+) -> std::result::Result<SendTransactionResponse, SendFailure> {
+    let prepared = build_send(state, user_id, payload).await?;
+    broadcast_send(state, prepared).await
+}
+
+/// Scan, build and sign a single-recipient send without broadcasting it
+///
+/// The wallet database records the transaction as soon as it's signed, so its inputs stay
+/// reserved until it's mined or expires whether or not it's ever broadcast.
+async fn build_send(
+    state: &SendState,
+    user_id: Uuid,
+    payload: SendTransactionRequest,
+) -> Result<PreparedSend> {
     tracing::info!(
         "Send transaction requested for user {} to {} amount {}",
-        payload.user_id,
+        user_id,
         payload.to_address,
-        payload.amount_zec
+        payload.amount_zec.as_deref().unwrap_or("max")
     );
 
+    let requested_zatoshis = requested_zatoshis(&payload)?;
+    validate_memo(payload.memo.as_deref())?;
+    validate_memo(payload.change_memo.as_deref())?;
+    validate_min_confirmations(payload.min_confirmations)?;
+    transaction::parse_fee_rule(payload.fee_rule.as_deref())
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    ensure_email_verified(&state.db, user_id).await?;
+
     // Load wallet configuration
-    let config = load_wallet_config(&state.db, payload.user_id, true).await?;
+    let config = load_wallet_config(&state.db, user_id, false).await?;
 
     tracing::info!(
         "Network: {:?}, Birthday height: {}",
@@ -75,121 +438,191 @@ pub async fn send_transaction(
     );
     tracing::info!("Using wallet database: {:?}", config.db_path);
 
-    // Connect to lightwalletd
-    let client = connect_lightwalletd(config.network).await?;
+    transaction::validate_recipient(
+        config.network,
+        &payload.to_address,
+        payload.memo.as_deref(),
+        payload.prefer_pool.as_deref(),
+    )
+    .map_err(|e| AppError::Validation(e.to_string()))?;
 
-    // Initialize per-user wallet database
-    let mut db = open_wallet_database(&config.db_path, config.network)?;
+    let account_index = payload.account_index.unwrap_or(0);
+    let from_address = account_address(&state.db, user_id, account_index).await?;
 
-    // Check if account exists, create if needed
-    let has_accounts = check_account_exists(&config.db_path)?;
+    if let Some(amount_zatoshis) = requested_zatoshis {
+        check_last_scanned_balance(user_id, &config, account_index, amount_zatoshis).await?;
+    }
 
-    if !has_accounts {
-        tracing::info!(
-            "Creating new account with birthday height {}",
-            config.birthday_height
-        );
+    // Connect to lightwalletd
+    let client = connect_lightwalletd(config.network).await?;
 
-        let mut account_mgr = account::AccountManager::new(db);
-        db = match account_mgr
-            .create_account("Primary", &config.seed, &client, Some(config.birthday_height))
-            .await
-        {
-            Ok((account_id, _usk)) => {
-                tracing::info!("Account created: {:?}", account_id);
-                open_wallet_database(&config.db_path, config.network)?
-            }
-            Err(e) => {
-                return Err(AppError::Internal(format!("Failed to create account: {}", e)));
-            }
-        };
-    } else {
-        tracing::info!("Using existing account(s)");
-    }
+    // Hold the per-user wallet lock while creating the account and scanning, so a
+    // concurrent balance refresh can't create it twice or cause a checkpoint conflict
+    let wallet_guard = balance::lock_user_wallet(user_id).await;
+
+    // Make sure the wallet database has an account for this seed
+    ensure_account(&state.db, user_id, &config, &client).await?;
 
     // Scan blockchain to find spendable funds
     tracing::info!("Scanning blockchain for spendable funds...");
 
-    // Drop db before scanner takes ownership
-    drop(db);
-
     // Scan blockchain with checkpoint conflict handling
     scan_blockchain_with_retry(
         &config.db_path,
         config.network,
         &config.seed,
         config.birthday_height,
-        payload.user_id,
+        user_id,
         &state.db,
     )
     .await?;
+    drop(wallet_guard);
 
     tracing::info!("Blockchain scanned successfully");
 
     // Derive USK for signing
     tracing::info!("Preparing signing key...");
-    let usk = derive_spending_key(&config.seed, config.network)?;
+    let usk = derive_account_spending_key(&config.seed, config.network, account_index)?;
 
     // Build and sign transaction
     tracing::info!("Building and signing transaction...");
 
-    let db = open_wallet_database(&config.db_path, config.network)?;
-    let mut tx_builder = transaction::TransactionBuilder::new(db, config.network);
+    // Re-fetch from the pool: a checkpoint-conflict retry may have replaced the database
+    let pooled_db =
+        pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let mut db = pooled_db.lock().await;
+    let mut tx_builder = transaction::TransactionBuilder::new(&mut db, config.network)
+        .with_min_confirmations(payload.min_confirmations)
+        .and_then(|builder| builder.with_fee_rule(payload.fee_rule.as_deref()))
+        .and_then(|builder| builder.with_change_memo(payload.change_memo.as_deref()))
+        .and_then(|builder| builder.with_preferred_pool(payload.prefer_pool.as_deref()))
+        .map_err(|e| AppError::Validation(e.to_string()))?;
 
-    let amount_zatoshis = zec_to_zatoshis(payload.amount_zec);
+    let (raw_txs, amount_zatoshis, fee_zatoshis) = match requested_zatoshis {
+        Some(amount_zatoshis) => tx_builder
+            .build_and_sign_transaction(
+                &usk,
+                &payload.to_address,
+                amount_zatoshis,
+                payload.memo.as_deref(),
+            )
+            .await
+            .map(|(raw_txs, fee_zatoshis)| (raw_txs, amount_zatoshis, fee_zatoshis)),
+        None => {
+            tx_builder
+                .build_and_sign_send_max(&usk, &payload.to_address, payload.memo.as_deref())
+                .await
+        }
+    }
+    .map_err(|e| build_error(&tx_builder, e, "Failed to build transaction"))?;
 
-    let (raw_tx, fee_zatoshis) = tx_builder
-        .build_and_sign_transaction(
-            &usk,
-            &payload.to_address,
-            amount_zatoshis,
-            payload.memo.as_deref(),
-        )
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to build transaction: {}", e)))?;
+    let pending_txid = tx_builder.last_txid_hex();
+    let txid = tx_builder.last_txid_display();
+    let expiry_height = tx_builder.last_expiry_height();
+    drop(tx_builder);
+    drop(db);
 
-    let fee_zec = zatoshis_to_zec(fee_zatoshis);
     tracing::info!(
-        "Transaction built ({} bytes, fee: {} ZEC)",
-        raw_tx.len(),
-        fee_zec
+        "Transaction built ({} transaction(s), fee: {} ZEC)",
+        raw_txs.len(),
+        zatoshis_to_zec(fee_zatoshis)
     );
 
+    Ok(PreparedSend {
+        user_id,
+        network: config.network,
+        raw_txs,
+        txid,
+        pending_txid,
+        expiry_height,
+        from_address,
+        to_address: payload.to_address,
+        memo: payload.memo,
+        amount_zatoshis,
+        fee_zatoshis,
+        prepared_at: Instant::now(),
+    })
+}
+
+/// Broadcast a send built by `build_send` and record it as pending
+async fn broadcast_send(
+    state: &SendState,
+    prepared: PreparedSend,
+) -> std::result::Result<SendTransactionResponse, SendFailure> {
+    let PreparedSend {
+        user_id,
+        network,
+        raw_txs,
+        pending_txid,
+        from_address,
+        to_address,
+        memo,
+        amount_zatoshis,
+        fee_zatoshis,
+        ..
+    } = prepared;
+    let fee_zec = zatoshis_to_zec(fee_zatoshis);
+
     // Broadcast transaction
     tracing::info!("Broadcasting transaction...");
 
     // Reconnect to lightwalletd for broadcasting
-    let lightwalletd_url = get_lightwalletd_url(config.network);
-    let mut client = lightwalletd::LightwalletdClient::new(lightwalletd_url);
+    let lightwalletd_url = get_lightwalletd_url(network);
+    let mut client = lightwalletd::LightwalletdClient::from_endpoint_list(&lightwalletd_url);
     client
         .connect()
         .await
         .map_err(|e| AppError::Internal(format!("Failed to connect to lightwalletd: {}", e)))?;
 
-    let response = client
-        .send_transaction(raw_tx)
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to broadcast transaction: {}", e)))?;
-
-    // The txid is in error_message field (confusing API)
-    let txid = hex::encode(&response.error_message);
+    let txids = match broadcast_all(&client, raw_txs).await {
+        Ok(txids) => txids,
+        Err(e) if e.reached_network() => {
+            balance::invalidate_cached_balance(user_id);
+            let partial = partial_send_response(
+                network,
+                &e,
+                &from_address,
+                &to_address,
+                amount_zatoshis,
+                fee_zatoshis,
+            );
+            return Err(SendFailure { error: e.error, partial: Some(partial) });
+        }
+        Err(e) => return Err(e.error.into()),
+    };
+    let txid = final_txid(&txids)?;
 
     tracing::info!("Transaction broadcast! TxID: {}", txid);
+    balance::invalidate_cached_balance(user_id);
 
-    // Create block explorer URL
-    let explorer_url = get_explorer_url(config.network, &txid);
+    // Show the send in history right away; the next sync fills in the mined height
+    if let Some(pending_txid) = pending_txid {
+        let output = transaction::PaymentOutput {
+            to_address: &to_address,
+            amount_zat: amount_zatoshis,
+            memo: memo.as_deref(),
+        };
+        record_pending_send(&state.db, user_id, &pending_txid, fee_zatoshis, &[output]).await;
+    }
 
-    let from_address = config
-        .address
-        .ok_or_else(|| AppError::Internal("Missing wallet address".to_string()))?;
+    // Create block explorer URLs
+    let explorer_urls: Vec<String> = txids
+        .iter()
+        .map(|txid| get_explorer_url(network, txid))
+        .collect();
+    let explorer_url = get_explorer_url(network, &txid);
 
-    Ok(Json(SendTransactionResponse {
+    let amount_zec = zatoshis_to_zec(amount_zatoshis);
+
+    Ok(SendTransactionResponse {
         txid: txid.clone(),
+        txids,
         from_address: from_address.clone(),
-        to_address: payload.to_address.clone(),
-        amount_zec: payload.amount_zec,
+        to_address: to_address.clone(),
+        amount_zec,
         fee_zec,
         explorer_url: explorer_url.clone(),
+        explorer_urls,
         message: format!(
             "Transaction sent successfully!\n\n\
             Transaction Details:\n\
@@ -203,176 +636,1006 @@ pub async fn send_transaction(
             {}",
             txid,
             from_address,
-            payload.to_address,
-            payload.amount_zec,
+            to_address,
+            amount_zec,
             fee_zec,
-            payload.memo.as_deref().unwrap_or("(none)"),
+            memo.as_deref().unwrap_or("(none)"),
             explorer_url
         ),
-    }))
+    })
 }
 
-/// Estimate transaction fee before sending
-/// This is much faster than building the full transaction as it skips zk-SNARK generation
-#[axum::debug_handler]
-pub async fn estimate_fee(
-    State(state): State<SendState>,
-    Json(payload): Json<EstimateFeeRequest>,
-) -> Result<Json<EstimateFeeResponse>> {
-    tracing::info!(
-        "Fee estimation requested for user {} to {} amount {}",
-        payload.user_id,
-        payload.to_address,
-        payload.amount_zec
-    );
-
-    // Load wallet configuration
-    let config = load_wallet_config(&state.db, payload.user_id, false).await?;
+/// Response recorded for a send whose broadcast failed after part of it may have gone out
+fn partial_send_response(
+    network: Network,
+    failure: &BroadcastError,
+    from_address: &str,
+    to_address: &str,
+    amount_zatoshis: u64,
+    fee_zatoshis: u64,
+) -> SendTransactionResponse {
+    let txids = failure.broadcast.clone();
+    let txid = txids.last().cloned().unwrap_or_default();
+    let explorer_urls: Vec<String> = txids.iter().map(|txid| get_explorer_url(network, txid)).collect();
+    let message = if txids.is_empty() {
+        "The broadcast failed in transit and may have reached the network. \
+         Check your transaction history before sending again."
+            .to_string()
+    } else {
+        format!(
+            "Only {} transaction(s) of this send were broadcast ({}). \
+             Check your transaction history before sending again.",
+            txids.len(),
+            txids.join(", ")
+        )
+    };
 
-    // Derive USK
-    let usk = derive_spending_key(&config.seed, config.network)?;
+    SendTransactionResponse {
+        txid,
+        explorer_url: explorer_urls.last().cloned().unwrap_or_default(),
+        txids,
+        from_address: from_address.to_string(),
+        to_address: to_address.to_string(),
+        amount_zec: zatoshis_to_zec(amount_zatoshis),
+        fee_zec: zatoshis_to_zec(fee_zatoshis),
+        explorer_urls,
+        message,
+    }
+}
 
-    // Open database
-    let db = open_wallet_database(&config.db_path, config.network)?;
+/// A broadcast that stopped partway through a proposal
+struct BroadcastError {
+    /// Txids lightwalletd accepted before the failing step
+    broadcast: Vec<String>,
+    /// The failing step errored in transit rather than being rejected, so it may have
+    /// reached the network anyway
+    uncertain: bool,
+    error: AppError,
+}
 
-    // Estimate fee
-    let mut tx_builder = transaction::TransactionBuilder::new(db, config.network);
-    let amount_zatoshis = zec_to_zatoshis(payload.amount_zec);
+impl BroadcastError {
+    fn new(broadcast: Vec<String>, uncertain: bool, step: usize, total: usize, reason: String) -> Self {
+        let error = if broadcast.is_empty() {
+            AppError::Internal(format!("Failed to broadcast transaction: {}", reason))
+        } else {
+            AppError::Internal(format!(
+                "Failed to broadcast transaction {} of {} (already broadcast: {}): {}",
+                step + 1,
+                total,
+                broadcast.join(", "),
+                reason
+            ))
+        };
+        Self { broadcast, uncertain, error }
+    }
 
-    let fee_zatoshis = tx_builder
-        .estimate_fee(
-            &usk,
-            &payload.to_address,
-            amount_zatoshis,
-            payload.memo.as_deref(),
-        )
-        .await
-        .map_err(|e| AppError::Internal(format!("Failed to estimate fee: {}", e)))?;
+    /// Whether any part of the send may be on the network, so it must not be retried blindly
+    fn reached_network(&self) -> bool {
+        !self.broadcast.is_empty() || self.uncertain
+    }
+}
 
-    let fee_zec = zatoshis_to_zec(fee_zatoshis);
-    let total_zec = payload.amount_zec + fee_zec;
+impl From<BroadcastError> for AppError {
+    fn from(e: BroadcastError) -> Self {
+        e.error
+    }
+}
 
-    tracing::info!("Estimated fee: {} ZEC (total: {} ZEC)", fee_zec, total_zec);
+/// Broadcast each transaction of a proposal in order, returning their txids
+///
+/// Stops at the first failure: later steps spend outputs of earlier ones, so they
+/// cannot be mined without them. A step counts as failed if the request fails or
+/// lightwalletd answers with a nonzero `error_code`; the error keeps the txids already
+/// broadcast so callers can tell a partial send from one that never left the server.
+async fn broadcast_all(
+    client: &lightwalletd::LightwalletdClient,
+    raw_txs: Vec<Vec<u8>>,
+) -> std::result::Result<Vec<String>, BroadcastError> {
+    let total = raw_txs.len();
+    let mut txids = Vec::with_capacity(total);
 
-    Ok(Json(EstimateFeeResponse {
-        estimated_fee_zec: fee_zec,
-        total_zec,
-    }))
-}
+    for (step, raw_tx) in raw_txs.into_iter().enumerate() {
+        let response = match client.send_transaction(raw_tx).await {
+            Ok(response) => response,
+            Err(e) => return Err(BroadcastError::new(txids, true, step, total, e.to_string())),
+        };
+        if response.error_code != 0 {
+            let reason = format!(
+                "rejected by lightwalletd (code {}): {}",
+                response.error_code, response.error_message
+            );
+            return Err(BroadcastError::new(txids, false, step, total, reason));
+        }
 
-/// Check if account exists in wallet database
-fn check_account_exists(db_path: &std::path::Path) -> Result<bool> {
-    match SqliteConnection::open(db_path) {
-        Ok(conn) => {
-            match conn.query_row("SELECT COUNT(*) FROM accounts", [], |row| {
-                row.get::<_, i64>(0)
-            }) {
-                Ok(count) => {
-                    tracing::info!("Found {} existing account(s)", count);
-                    Ok(count > 0)
-                }
-                Err(_) => Ok(false),
-            }
+        // The txid is in error_message field (confusing API)
+        let txid = hex::encode(&response.error_message);
+        if total > 1 {
+            tracing::info!("Broadcast transaction {} of {}: {}", step + 1, total, txid);
         }
-        Err(_) => Ok(false),
+        txids.push(txid);
     }
+
+    Ok(txids)
 }
 
-/// Scan blockchain with automatic retry on checkpoint conflict
-async fn scan_blockchain_with_retry(
-    db_path: &std::path::Path,
-    network: Network,
-    seed: &[u8],
-    birthday_height: u32,
+/// The txid of the last broadcast step, which pays the recipients
+fn final_txid(txids: &[String]) -> Result<String> {
+    txids
+        .last()
+        .cloned()
+        .ok_or_else(|| AppError::Internal("Proposal produced no transactions".to_string()))
+}
+
+/// Record a just-broadcast transaction in PostgreSQL with no block height (pending)
+///
+/// The wallet sync upserts on `(user_id, txid)` and replaces the transaction's sent
+/// notes, so this row is completed in place once the transaction is scanned. Failures
+/// are logged rather than returned because the transaction is already broadcast.
+async fn record_pending_send(
+    db: &PgPool,
     user_id: Uuid,
-    pg_pool: &PgPool,
-) -> Result<()> {
-    // Create wallet_db for scanner
-    let wallet_db = WalletDb::<SqliteConnection, Network, SystemClock, OsRng>::for_path(
-        db_path, network, SystemClock, OsRng,
-    )
-    .map_err(|e| {
-        AppError::Internal(format!(
-            "Failed to open wallet database for scanning: {:?}",
-            e
-        ))
-    })?;
+    txid: &str,
+    fee_zatoshis: u64,
+    outputs: &[transaction::PaymentOutput<'_>],
+) {
+    let result: std::result::Result<(), sqlx::Error> = async {
+        let mut tx = db.begin().await?;
 
-    // Create scanner WITH db_path so checkpoint clearing works
-    let client = connect_lightwalletd(network).await?;
-    let mut scanner = scanner::BlockchainScanner::new_with_path(
-        wallet_db, client, network, db_path.to_path_buf()
-    );
+        let transaction_id: Option<i64> = sqlx::query_scalar(
+            "INSERT INTO transactions (user_id, txid, block_height, created_at, fee_zatoshis)
+             VALUES ($1::uuid, $2, NULL, NOW(), $3)
+             ON CONFLICT (user_id, txid) DO NOTHING
+             RETURNING id"
+        )
+        .bind(user_id.to_string())
+        .bind(txid)
+        .bind(fee_zatoshis as i64)
+        .fetch_optional(&mut *tx)
+        .await?;
 
-    // Try to scan, if checkpoint conflict occurs, delete DB and retry
-    let scan_result = scanner.scan_from_birthday().await;
-    if let Err(ref e) = scan_result {
-        let error_msg = format!("{}", e);
-        if error_msg.contains("CheckpointConflict") {
-            tracing::warn!(
-                "Checkpoint conflict detected, deleting corrupted database and retrying..."
-            );
+        // Already known (e.g. a sync raced us), nothing to add
+        let Some(transaction_id) = transaction_id else {
+            return Ok(());
+        };
 
-            // Drop scanner to release the database
-            drop(scanner);
+        for output in outputs {
+            sqlx::query(
+                "INSERT INTO sent_notes (user_id, transaction_id, to_address, value_zatoshis, memo)
+                 VALUES ($1::uuid, $2, $3, $4, $5)"
+            )
+            .bind(user_id.to_string())
+            .bind(transaction_id)
+            .bind(output.to_address)
+            .bind(output.amount_zat as i64)
+            .bind(output.memo)
+            .execute(&mut *tx)
+            .await?;
+        }
 
-            // Delete the corrupted database file
-            if db_path.exists() {
-                std::fs::remove_file(db_path).map_err(|e| {
-                    AppError::Internal(format!("Failed to delete corrupted database: {}", e))
-                })?;
-                tracing::info!("Deleted corrupted database file");
-            }
+        tx.commit().await
+    }
+    .await;
 
-            // Clear PostgreSQL transaction data to avoid conflicts
-            clear_transaction_data(pg_pool, user_id).await?;
+    if let Err(e) = result {
+        tracing::error!("Failed to record pending transaction {} for user {}: {:?}", txid, user_id, e);
+    }
+}
 
-            // Recreate database and account
-            let db = open_wallet_database(db_path, network)?;
-            let mut account_mgr = account::AccountManager::new(db);
-            let client_retry = connect_lightwalletd(network).await?;
+/// Read and validate the optional `Idempotency-Key` header
+fn idempotency_key(headers: &HeaderMap) -> Result<Option<String>> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
 
-            account_mgr
-                .create_account("Primary", seed, &client_retry, Some(birthday_height))
-                .await
-                .map_err(|e| {
-                    AppError::Internal(format!("Failed to recreate account: {}", e))
-                })?;
-
-            drop(account_mgr);
-
-            // Retry scan with fresh database - use new_with_path for checkpoint management
-            let wallet_db_retry =
-                WalletDb::<SqliteConnection, Network, SystemClock, OsRng>::for_path(
-                    db_path, network, SystemClock, OsRng,
-                )
-                .map_err(|e| {
-                    AppError::Internal(format!("Failed to reopen wallet database: {:?}", e))
-                })?;
-
-            let client_retry2 = connect_lightwalletd(network).await?;
-            let mut scanner_retry = scanner::BlockchainScanner::new_with_path(
-                wallet_db_retry, client_retry2, network, db_path.to_path_buf()
-            );
-            scanner_retry.scan_from_birthday().await.map_err(|e| {
-                AppError::Internal(format!("Failed to scan blockchain after retry: {}", e))
-            })?;
+    let key = value
+        .to_str()
+        .map_err(|_| AppError::Validation("Idempotency-Key must be visible ASCII".to_string()))?
+        .trim();
 
-            tracing::info!("Successfully scanned after clearing corrupted state");
-        } else {
-            return Err(AppError::Internal(format!(
-                "Failed to scan blockchain: {}",
-                e
-            )));
-        }
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(AppError::Validation(format!(
+            "Idempotency-Key must be 1-{} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        )));
     }
 
-    Ok(())
+    Ok(Some(key.to_string()))
 }
 
-/// Clear transaction data from PostgreSQL
-async fn clear_transaction_data(pg_pool: &PgPool, user_id: Uuid) -> Result<()> {
-    tracing::info!("Clearing stale transaction data from PostgreSQL...");
+/// Reserve an idempotency key for this user
+///
+/// Returns the stored response if the key was already used successfully, `None` if
+/// the key is now reserved for this request, or a conflict if the earlier request
+/// with this key is still running.
+async fn claim_idempotency_key(
+    db: &PgPool,
+    user_id: Uuid,
+    key: &str,
+) -> Result<Option<SendTransactionResponse>> {
+    let ttl_secs: i64 = env_or("IDEMPOTENCY_KEY_TTL_SECS", DEFAULT_IDEMPOTENCY_KEY_TTL_SECS);
+
+    // Expired keys may be reused
+    sqlx::query(
+        "DELETE FROM idempotency_keys
+         WHERE user_id = $1::uuid AND key = $2
+           AND created_at < NOW() - ($3::bigint * INTERVAL '1 second')"
+    )
+    .bind(user_id.to_string())
+    .bind(key)
+    .bind(ttl_secs)
+    .execute(db)
+    .await?;
+
+    let claimed = sqlx::query(
+        "INSERT INTO idempotency_keys (user_id, key) VALUES ($1::uuid, $2)
+         ON CONFLICT (user_id, key) DO NOTHING"
+    )
+    .bind(user_id.to_string())
+    .bind(key)
+    .execute(db)
+    .await?
+    .rows_affected()
+        == 1;
+
+    if claimed {
+        return Ok(None);
+    }
+
+    let row = sqlx::query("SELECT response FROM idempotency_keys WHERE user_id = $1::uuid AND key = $2")
+        .bind(user_id.to_string())
+        .bind(key)
+        .fetch_optional(db)
+        .await?;
+
+    match row.and_then(|row| row.get::<Option<String>, _>("response")) {
+        Some(response) => serde_json::from_str(&response)
+            .map(Some)
+            .map_err(|e| AppError::Internal(format!("Invalid stored idempotent response: {}", e))),
+        None => Err(AppError::Conflict(
+            "A send with this Idempotency-Key is still in progress".to_string(),
+        )),
+    }
+}
+
+/// Record the response for a reserved idempotency key
+async fn store_idempotent_response(
+    db: &PgPool,
+    user_id: Uuid,
+    key: &str,
+    response: &SendTransactionResponse,
+) -> Result<()> {
+    let body = serde_json::to_string(response)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize response: {}", e)))?;
+
+    sqlx::query(
+        "UPDATE idempotency_keys SET txid = $3, response = $4
+         WHERE user_id = $1::uuid AND key = $2"
+    )
+    .bind(user_id.to_string())
+    .bind(key)
+    .bind(&response.txid)
+    .bind(body)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Drop a reservation whose send failed before broadcasting
+async fn release_idempotency_key(db: &PgPool, user_id: Uuid, key: &str) -> Result<()> {
+    sqlx::query(
+        "DELETE FROM idempotency_keys
+         WHERE user_id = $1::uuid AND key = $2 AND response IS NULL"
+    )
+    .bind(user_id.to_string())
+    .bind(key)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Send ZEC to several recipients in a single transaction
+///
+/// Every output is validated before scanning; one invalid output fails the whole batch.
+#[axum::debug_handler]
+pub async fn send_batch(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(payload): Json<SendBatchRequest>,
+) -> Result<Json<SendBatchResponse>> {
+    run_to_completion(build_and_send_batch(state, user_id, payload)).await
+}
+
+async fn build_and_send_batch(
+    state: SendState,
+    user_id: Uuid,
+    payload: SendBatchRequest,
+) -> Result<Json<SendBatchResponse>> {
+    tracing::info!(
+        "Batch send requested for user {} with {} outputs",
+        user_id,
+        payload.outputs.len()
+    );
+
+    ensure_email_verified(&state.db, user_id).await?;
+
+    // Load wallet configuration
+    let config = load_wallet_config(&state.db, user_id, false).await?;
+
+    let outputs = payment_outputs(&payload.outputs)?;
+
+    transaction::payment_request(config.network, &outputs)
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    // Connect to lightwalletd
+    let client = connect_lightwalletd(config.network).await?;
+
+    // Same per-user wallet lock as balance scans; see send_transaction
+    let wallet_guard = balance::lock_user_wallet(user_id).await;
+
+    // Make sure the wallet database has an account for this seed
+    ensure_account(&state.db, user_id, &config, &client).await?;
+
+    // Scan blockchain to find spendable funds
+    scan_blockchain_with_retry(
+        &config.db_path,
+        config.network,
+        &config.seed,
+        config.birthday_height,
+        user_id,
+        &state.db,
+    )
+    .await?;
+    drop(wallet_guard);
+
+    let usk = derive_spending_key(&config.seed, config.network)?;
+
+    let pooled_db =
+        pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let mut db = pooled_db.lock().await;
+    let mut tx_builder = transaction::TransactionBuilder::new(&mut db, config.network);
+
+    let (raw_txs, fee_zatoshis) = tx_builder
+        .build_and_sign_batch(&usk, &outputs)
+        .await
+        .map_err(|e| build_error(&tx_builder, e, "Failed to build batch transaction"))?;
+
+    let pending_txid = tx_builder.last_txid_hex();
+    drop(tx_builder);
+    drop(db);
+
+    // Broadcast transactions
+    let txids = broadcast_all(&client, raw_txs).await?;
+    let txid = final_txid(&txids)?;
+    let explorer_urls = txids
+        .iter()
+        .map(|txid| get_explorer_url(config.network, txid))
+        .collect();
+    let explorer_url = get_explorer_url(config.network, &txid);
+    let total_amount_zec =
+        zatoshis_to_zec(outputs.iter().map(|output| output.amount_zat).sum());
+    let fee_zec = zatoshis_to_zec(fee_zatoshis);
+
+    tracing::info!("Batch transaction broadcast! TxID: {}", txid);
+    balance::invalidate_cached_balance(user_id);
+
+    if let Some(pending_txid) = pending_txid {
+        record_pending_send(&state.db, user_id, &pending_txid, fee_zatoshis, &outputs).await;
+    }
+
+    Ok(Json(SendBatchResponse {
+        txid,
+        txids,
+        num_outputs: outputs.len(),
+        total_amount_zec,
+        fee_zec,
+        explorer_url,
+        explorer_urls,
+        message: format!(
+            "Sent {} ZEC to {} recipients in one transaction (fee: {} ZEC)",
+            total_amount_zec,
+            outputs.len(),
+            fee_zec
+        ),
+    }))
+}
+
+/// Decode a pasted or scanned `zcash:` payment URI into send fields
+///
+/// The recipient must be on the wallet's network. Nothing is sent; the client reviews
+/// the fields and submits them to `/wallet/send`.
+pub async fn parse_payment_uri(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(payload): Json<ParsePaymentUriRequest>,
+) -> Result<Json<ParsePaymentUriResponse>> {
+    let network: String = sqlx::query_scalar("SELECT network FROM wallets WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let payment = transaction::parse_payment_uri(parse_network(&network), &payload.uri)
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    Ok(Json(ParsePaymentUriResponse {
+        to_address: payment.to_address,
+        amount_zec: format!("{}.{:08}", payment.amount_zat / 100_000_000, payment.amount_zat % 100_000_000),
+        memo: payment.memo,
+        label: payment.label,
+        message: payment.message,
+    }))
+}
+
+/// Estimate transaction fee before sending
+/// This is much faster than building the full transaction as it skips zk-SNARK generation
+///
+/// Amounts above the spendable balance fail fast with `InsufficientFunds`.
+#[axum::debug_handler]
+pub async fn estimate_fee(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(payload): Json<EstimateFeeRequest>,
+) -> Result<Json<EstimateFeeResponse>> {
+    tracing::info!(
+        "Fee estimation requested for user {} to {} amount {}",
+        user_id,
+        payload.to_address,
+        payload.amount_zec
+    );
+
+    let amount_zatoshis = u64::from(parse_zec_amount(&payload.amount_zec)?);
+    validate_memo(payload.memo.as_deref())?;
+    validate_min_confirmations(payload.min_confirmations)?;
+    transaction::parse_fee_rule(payload.fee_rule.as_deref())
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    // Load wallet configuration
+    let config = load_wallet_config(&state.db, user_id, false).await?;
+
+    // Derive USK
+    let usk = derive_spending_key(&config.seed, config.network)?;
+
+    // Borrow database from the pool
+    let pooled_db =
+        pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let mut db = pooled_db.lock().await;
+
+    // Estimate fee
+    let mut tx_builder = transaction::TransactionBuilder::new(&mut db, config.network)
+        .with_min_confirmations(payload.min_confirmations)
+        .and_then(|builder| builder.with_fee_rule(payload.fee_rule.as_deref()))
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    // A fee for an unaffordable amount is meaningless; fail before building a proposal.
+    // The fee isn't known yet, so required is the amount alone.
+    let spendable_zatoshis = tx_builder
+        .spendable_zat()
+        .map_err(|e| AppError::Internal(format!("Failed to get spendable balance: {}", e)))?;
+    if amount_zatoshis > spendable_zatoshis {
+        return Err(AppError::InsufficientFunds {
+            available_zat: spendable_zatoshis,
+            required_zat: amount_zatoshis,
+        });
+    }
+
+    let fee_zatoshis = tx_builder
+        .estimate_fee(
+            &usk,
+            &payload.to_address,
+            amount_zatoshis,
+            payload.memo.as_deref(),
+        )
+        .await
+        .map_err(|e| build_error(&tx_builder, e, "Failed to estimate fee"))?;
+
+    let fee_zec = zatoshis_to_zec(fee_zatoshis);
+    let total_zec = zatoshis_to_zec(amount_zatoshis + fee_zatoshis);
+
+    tracing::info!("Estimated fee: {} ZEC (total: {} ZEC)", fee_zec, total_zec);
+
+    Ok(Json(EstimateFeeResponse {
+        estimated_fee_zec: fee_zec,
+        total_zec,
+    }))
+}
+
+/// Dry-run a send, batch send, or shielding operation
+///
+/// Builds the proposal for the requested operation but skips proof generation, returning
+/// the fee plus input/output counts so the UI can warn about fees from many inputs.
+/// Nothing is written to the wallet database; shielding is estimated from the transparent
+/// UTXOs stored by the last balance sync.
+#[axum::debug_handler]
+pub async fn estimate(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(operation): Json<EstimateOperation>,
+) -> Result<Json<EstimateResponse>> {
+    tracing::info!("Fee dry-run requested for user {}", user_id);
+
+    // Load wallet configuration
+    let config = load_wallet_config(&state.db, user_id, false).await?;
+
+    // Derive USK
+    let usk = derive_spending_key(&config.seed, config.network)?;
+
+    let pooled_db =
+        pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let mut db = pooled_db.lock().await;
+    let mut tx_builder = transaction::TransactionBuilder::new(&mut db, config.network);
+
+    let estimate = match &operation {
+        EstimateOperation::Send {
+            to_address,
+            amount_zec,
+            memo,
+        } => {
+            let amount_zatoshis = u64::from(parse_zec_amount(amount_zec)?);
+            validate_memo(memo.as_deref())?;
+            tx_builder
+                .estimate_transfer(&usk, to_address, amount_zatoshis, memo.as_deref())
+                .await
+        }
+        EstimateOperation::Batch { outputs } => {
+            let outputs = payment_outputs(outputs)?;
+            transaction::payment_request(config.network, &outputs)
+                .map_err(|e| AppError::Validation(e.to_string()))?;
+            tx_builder.estimate_batch(&usk, &outputs).await
+        }
+        EstimateOperation::Shield => tx_builder.estimate_shielding(&usk).await,
+    }
+    .map_err(|e| build_error(&tx_builder, e, "Failed to estimate fee"))?;
+
+    tracing::info!(
+        "Estimated fee: {} zatoshis ({} inputs, {} outputs)",
+        estimate.fee_zat,
+        estimate.num_inputs,
+        estimate.num_outputs
+    );
+
+    Ok(Json(EstimateResponse {
+        estimated_fee_zec: zatoshis_to_zec(estimate.fee_zat),
+        total_zec: zatoshis_to_zec(estimate.total_zat()),
+        num_inputs: estimate.num_inputs,
+        num_outputs: estimate.num_outputs,
+    }))
+}
+
+/// Shield transparent funds into the Orchard pool
+/// Fetches transparent UTXOs for the account from lightwalletd, then builds,
+/// signs and broadcasts a shielding transaction
+#[axum::debug_handler]
+pub async fn shield_funds(
+    State(state): State<SendState>,
+    Extension(user_id): Extension<Uuid>,
+) -> Result<Json<ShieldFundsResponse>> {
+    run_to_completion(build_and_send_shielding(state, user_id)).await
+}
+
+async fn build_and_send_shielding(state: SendState, user_id: Uuid) -> Result<Json<ShieldFundsResponse>> {
+    tracing::info!("Shielding requested for user {}", user_id);
+
+    // Load wallet configuration
+    let config = load_wallet_config(&state.db, user_id, false).await?;
+
+    // Connect to lightwalletd
+    let client = connect_lightwalletd(config.network).await?;
+
+    // Same per-user wallet lock as balance scans; see send_transaction
+    let wallet_guard = balance::lock_user_wallet(user_id).await;
+
+    // Make sure the wallet database has an account for this seed
+    ensure_account(&state.db, user_id, &config, &client).await?;
+
+    // Scan so the wallet knows the current chain tip
+    scan_blockchain_with_retry(
+        &config.db_path,
+        config.network,
+        &config.seed,
+        config.birthday_height,
+        user_id,
+        &state.db,
+    )
+    .await?;
+    drop(wallet_guard);
+
+    let usk = derive_spending_key(&config.seed, config.network)?;
+
+    let pooled_db =
+        pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let mut db = pooled_db.lock().await;
+    let mut tx_builder = transaction::TransactionBuilder::new(&mut db, config.network);
+
+    let transparent_zatoshis =
+        load_transparent_utxos(&client, &mut tx_builder, &usk, config.birthday_height).await?;
+
+    if let Some(message) = nothing_to_shield(transparent_zatoshis) {
+        tracing::info!("Not shielding for user {}: {}", user_id, message);
+        return Ok(Json(ShieldFundsResponse {
+            txid: None,
+            txids: Vec::new(),
+            shielded_zec: 0.0,
+            fee_zec: 0.0,
+            explorer_url: None,
+            explorer_urls: Vec::new(),
+            message,
+        }));
+    }
+
+    let (raw_txs, shielded_zatoshis, fee_zatoshis) = tx_builder
+        .build_shielding_transaction(&usk)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to build shielding transaction: {}", e)))?;
+
+    drop(tx_builder);
+    drop(db);
+
+    // Broadcast transactions
+    let txids = broadcast_all(&client, raw_txs).await?;
+    let txid = final_txid(&txids)?;
+    let explorer_urls = txids
+        .iter()
+        .map(|txid| get_explorer_url(config.network, txid))
+        .collect();
+    let explorer_url = get_explorer_url(config.network, &txid);
+    let shielded_zec = zatoshis_to_zec(shielded_zatoshis);
+    let fee_zec = zatoshis_to_zec(fee_zatoshis);
+
+    tracing::info!("Shielding transaction broadcast! TxID: {}", txid);
+    balance::invalidate_cached_balance(user_id);
+
+    Ok(Json(ShieldFundsResponse {
+        txid: Some(txid),
+        txids,
+        shielded_zec,
+        fee_zec,
+        explorer_url: Some(explorer_url),
+        explorer_urls,
+        message: format!("Shielded {} ZEC into the Orchard pool (fee: {} ZEC)", shielded_zec, fee_zec),
+    }))
+}
+
+/// Map a proposal or build failure to an `AppError`
+///
+/// Lack of funds becomes `InsufficientFunds`, reporting the wallet summary's spendable balance
+/// and the amount the proposal needed including fee. A wrong-network recipient is a 400.
+fn build_error(
+    tx_builder: &transaction::TransactionBuilder,
+    e: anyhow::Error,
+    context: &str,
+) -> AppError {
+    if let Some(wrong_network) = e.downcast_ref::<transaction::WrongNetwork>() {
+        return AppError::Validation(wrong_network.to_string());
+    }
+
+    match e.downcast_ref::<transaction::InsufficientFunds>() {
+        Some(shortfall) => AppError::InsufficientFunds {
+            available_zat: tx_builder
+                .spendable_zat()
+                .unwrap_or(shortfall.available_zat),
+            required_zat: shortfall.required_zat,
+        },
+        None => AppError::Internal(format!("{}: {}", context, e)),
+    }
+}
+
+/// Parse batch outputs into exact zatoshi amounts
+fn payment_outputs(outputs: &[BatchOutput]) -> Result<Vec<transaction::PaymentOutput<'_>>> {
+    outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| {
+            let amount = parse_zec_amount(&output.amount_zec).map_err(|e| match e {
+                AppError::Validation(msg) => AppError::Validation(format!("Output {}: {}", index, msg)),
+                other => other,
+            })?;
+            Ok(transaction::PaymentOutput {
+                to_address: &output.to_address,
+                amount_zat: amount.into(),
+                memo: output.memo.as_deref(),
+            })
+        })
+        .collect()
+}
+
+/// Reject oversized memos up front so the caller gets a 400 with the byte count
+fn validate_memo(memo: Option<&str>) -> Result<()> {
+    if let Some(text) = memo {
+        transaction::encode_text_memo(text).map_err(|e| AppError::Validation(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Why shielding `transparent_zatoshis` would be pointless, if it would be
+///
+/// A balance at or below the ZIP-317 marginal fee is all dust: the wallet skips such
+/// inputs, so building the transaction would only fail.
+fn nothing_to_shield(transparent_zatoshis: u64) -> Option<String> {
+    if transparent_zatoshis == 0 {
+        return Some("No transparent funds to shield".to_string());
+    }
+
+    let dust_threshold = u64::from(zip317::MARGINAL_FEE);
+    if transparent_zatoshis <= dust_threshold {
+        return Some(format!(
+            "Transparent balance of {} ZEC is too small to shield (must be more than {} ZEC)",
+            zatoshis_to_zec(transparent_zatoshis),
+            zatoshis_to_zec(dust_threshold)
+        ));
+    }
+
+    None
+}
+
+/// Amount to send in zatoshis, or `None` to send the maximum spendable amount
+///
+/// Exactly one of `amount_zec` and `send_max` must be given, and `send_max` can't be
+/// combined with `prefer_pool`.
+fn requested_zatoshis(payload: &SendTransactionRequest) -> Result<Option<u64>> {
+    match (&payload.amount_zec, payload.send_max) {
+        (Some(_), true) => Err(AppError::Validation(
+            "amount_zec cannot be combined with send_max".to_string(),
+        )),
+        (Some(amount), false) => {
+            let amount_zatoshis = u64::from(parse_zec_amount(amount)?);
+            validate_send_amount(amount_zatoshis)?;
+            Ok(Some(amount_zatoshis))
+        }
+        (None, true) if payload.prefer_pool.is_some() => Err(AppError::Validation(
+            "prefer_pool is not supported with send_max".to_string(),
+        )),
+        (None, true) => Ok(None),
+        (None, false) => Err(AppError::Validation(
+            "amount_zec is required unless send_max is set".to_string(),
+        )),
+    }
+}
+
+/// Reject zero and dust amounts before doing any work
+///
+/// An output below the ZIP-317 marginal fee costs more to include than it carries, and the
+/// wallet's change strategy rejects it as dust.
+fn validate_send_amount(amount_zatoshis: u64) -> Result<()> {
+    if amount_zatoshis == 0 {
+        return Err(AppError::Validation("Amount must be greater than zero".to_string()));
+    }
+
+    let dust_threshold = u64::from(zip317::MARGINAL_FEE);
+    if amount_zatoshis < dust_threshold {
+        return Err(AppError::Validation(format!(
+            "Amount {} ZEC is below the dust threshold of {} ZEC",
+            zatoshis_to_zec(amount_zatoshis),
+            zatoshis_to_zec(dust_threshold)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Fail fast when the balance from the last scan can't cover the amount plus the minimum fee
+///
+/// Skipped for wallets that have never been scanned. Funds received since the last scan
+/// aren't counted, so the error asks the user to refresh their balance.
+async fn check_last_scanned_balance(
+    user_id: Uuid,
+    config: &WalletConfig,
+    account_index: u32,
+    amount_zatoshis: u64,
+) -> Result<()> {
+    match balance::last_scanned_spendable(user_id, config, account_index).await? {
+        Some(spendable) => check_spendable(amount_zatoshis, spendable),
+        None => Ok(()),
+    }
+}
+
+/// Reject an amount that, with the minimum fee, is more than `spendable`
+fn check_spendable(amount_zatoshis: u64, spendable: u64) -> Result<()> {
+    let minimum_fee = u64::from(zip317::MINIMUM_FEE);
+    let required = amount_zatoshis.saturating_add(minimum_fee);
+    if required > spendable {
+        return Err(AppError::Validation(format!(
+            "Insufficient spendable balance: sending {} ZEC with an estimated fee of {} ZEC needs {} ZEC, \
+             but {} ZEC was spendable at the last balance sync. Refresh your balance and try again",
+            zatoshis_to_zec(amount_zatoshis),
+            zatoshis_to_zec(minimum_fee),
+            zatoshis_to_zec(required),
+            zatoshis_to_zec(spendable)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reject a zero confirmations requirement before doing any work
+fn validate_min_confirmations(min_confirmations: Option<u32>) -> Result<()> {
+    transaction::confirmations_policy(min_confirmations)
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+    Ok(())
+}
+
+/// Create the wallet accounts in the pooled database if they don't exist yet
+///
+/// A new primary account resumes from the Postgres sync checkpoint, as in
+/// `balance::sync_balance`; accounts recorded in `wallet_accounts` are imported after it.
+pub async fn ensure_account(
+    pg_pool: &PgPool,
+    user_id: Uuid,
+    config: &WalletConfig,
+    client: &lightwalletd::LightwalletdClient,
+) -> Result<()> {
+    let pooled_db = pooled_wallet_database(user_id, &config.db_path, config.network).await?;
+    let mut db = pooled_db.lock().await;
+
+    // Check if account exists, create if needed
+    let account_ids = db
+        .get_wallet_db()
+        .map_err(|e| AppError::Internal(format!("Failed to open wallet database: {}", e)))?
+        .get_account_ids()
+        .map_err(|e| AppError::Internal(format!("Failed to list accounts: {:?}", e)))?;
+    tracing::info!("Found {} existing account(s)", account_ids.len());
+
+    if account_ids.is_empty() {
+        let account_birthday =
+            balance::fresh_database_birthday(pg_pool, user_id, config.birthday_height).await?;
+        tracing::info!("Creating new account with birthday height {}", account_birthday);
+
+        let mut account_mgr = account::AccountManager::new(&mut db);
+        match account_mgr
+            .create_account("Primary", &config.seed, client, Some(account_birthday))
+            .await
+        {
+            Ok((account_id, _usk)) => {
+                tracing::info!("Account created: {:?}", account_id);
+            }
+            Err(e) => {
+                return Err(AppError::Internal(format!("Failed to create account: {}", e)));
+            }
+        };
+    } else {
+        tracing::info!("Using existing account(s)");
+    }
+
+    balance::import_wallet_accounts(pg_pool, user_id, &mut db, &config.seed, config.network, client)
+        .await?;
+
+    Ok(())
+}
+
+/// Scan blockchain with automatic retry on checkpoint conflict
+///
+/// A conflict is first recovered by rewinding the wallet a few blocks and rescanning
+/// the tail. Only if that also fails is the wallet database deleted and rescanned from
+/// the birthday.
+async fn scan_blockchain_with_retry(
+    db_path: &std::path::Path,
+    network: Network,
+    seed: &[u8],
+    birthday_height: u32,
+    user_id: Uuid,
+    pg_pool: &PgPool,
+) -> Result<()> {
+    let scan_result = scan_pooled_database(user_id, db_path, network).await?;
+    let Err(e) = scan_result else {
+        return Ok(());
+    };
+    if !format!("{}", e).contains("CheckpointConflict") {
+        return Err(AppError::Internal(format!("Failed to scan blockchain: {}", e)));
+    }
+
+    tracing::warn!("Checkpoint conflict detected, rewinding wallet and rescanning...");
+    match rewind_and_rescan(user_id, db_path, network).await? {
+        Ok(()) => {
+            tracing::info!("Successfully rescanned after rewinding past the checkpoint conflict");
+            return Ok(());
+        }
+        Err(e) => {
+            tracing::warn!("Targeted checkpoint recovery failed, resetting wallet database: {:#}", e);
+        }
+    }
+
+    // Evict the pooled handle to release the database
+    pool::WALLET_DB_POOL.evict(user_id).await;
+
+    // Delete the corrupted database file
+    if db_path.exists() {
+        std::fs::remove_file(db_path).map_err(|e| {
+            AppError::Internal(format!("Failed to delete corrupted database: {}", e))
+        })?;
+        tracing::info!("Deleted corrupted database file");
+    }
+
+    // Clear PostgreSQL transaction data to avoid conflicts
+    clear_transaction_data(pg_pool, user_id).await?;
+
+    // Recreate database and account
+    {
+        let pooled_db = pooled_wallet_database(user_id, db_path, network).await?;
+        let mut db = pooled_db.lock().await;
+        let mut account_mgr = account::AccountManager::new(&mut db);
+        let client_retry = connect_lightwalletd(network).await?;
+
+        account_mgr
+            .create_account("Primary", seed, &client_retry, Some(birthday_height))
+            .await
+            .map_err(|e| {
+                AppError::Internal(format!("Failed to recreate account: {}", e))
+            })?;
+        balance::import_wallet_accounts(pg_pool, user_id, &mut db, seed, network, &client_retry)
+            .await?;
+    }
+
+    // Retry scan with fresh database
+    scan_pooled_database(user_id, db_path, network)
+        .await?
+        .map_err(|e| {
+            AppError::Internal(format!("Failed to scan blockchain after retry: {}", e))
+        })?;
+
+    tracing::info!("Successfully scanned after clearing corrupted state");
+    Ok(())
+}
+
+/// Rewind the user's wallet below the conflicting checkpoint and scan it again
+///
+/// Like `scan_pooled_database`, the outer `Result` covers setup failures and the inner
+/// one whether the targeted recovery worked.
+async fn rewind_and_rescan(
+    user_id: Uuid,
+    db_path: &std::path::Path,
+    network: Network,
+) -> Result<anyhow::Result<()>> {
+    let pooled_db = pooled_wallet_database(user_id, db_path, network).await?;
+    let mut db = pooled_db.lock().await;
+    let wallet_db = db.get_wallet_db_mut().map_err(|e| {
+        AppError::Internal(format!(
+            "Failed to open wallet database for scanning: {:?}",
+            e
+        ))
+    })?;
+
+    let client = connect_lightwalletd(network).await?;
+    let mut scanner = scanner::BlockchainScanner::new_with_path(
+        wallet_db, client, network, db_path.to_path_buf()
+    );
+
+    if let Err(e) = scanner.rewind_for_checkpoint_conflict() {
+        return Ok(Err(e));
+    }
+    Ok(scanner.scan_from_birthday(user_id).await.map(|_| ()))
+}
+
+/// Run a single scan against the user's pooled wallet database
+///
+/// The outer `Result` covers setup failures; the inner one is the scan outcome,
+/// which the caller inspects for checkpoint conflicts.
+async fn scan_pooled_database(
+    user_id: Uuid,
+    db_path: &std::path::Path,
+    network: Network,
+) -> Result<anyhow::Result<scanner::ScanSummary>> {
+    let pooled_db = pooled_wallet_database(user_id, db_path, network).await?;
+    let mut db = pooled_db.lock().await;
+    let wallet_db = db.get_wallet_db_mut().map_err(|e| {
+        AppError::Internal(format!(
+            "Failed to open wallet database for scanning: {:?}",
+            e
+        ))
+    })?;
+
+    // Create scanner WITH db_path so checkpoint clearing works
+    let client = connect_lightwalletd(network).await?;
+    let mut scanner = scanner::BlockchainScanner::new_with_path(
+        wallet_db, client, network, db_path.to_path_buf()
+    );
+
+    Ok(scanner.scan_from_birthday(user_id).await)
+}
+
+/// Clear transaction data from PostgreSQL
+async fn clear_transaction_data(pg_pool: &PgPool, user_id: Uuid) -> Result<()> {
+    tracing::info!("Clearing stale transaction data from PostgreSQL...");
 
     sqlx::query("DELETE FROM sent_notes WHERE user_id = $1::uuid")
         .bind(user_id.to_string())
@@ -389,6 +1652,154 @@ async fn clear_transaction_data(pg_pool: &PgPool, user_id: Uuid) -> Result<()> {
         .execute(pg_pool)
         .await?;
 
+    // The history is gone, so the recreated wallet must rescan from its birthday
+    // rather than resume from the old checkpoint
+    sqlx::query("UPDATE wallets SET last_synced_height = NULL WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .execute(pg_pool)
+        .await?;
+
     tracing::info!("Cleared stale PostgreSQL data");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared_send(user_id: Uuid, prepared_at: Instant) -> PreparedSend {
+        PreparedSend {
+            user_id,
+            network: Network::TestNetwork,
+            raw_txs: vec![vec![0u8; 4]],
+            txid: None,
+            pending_txid: None,
+            expiry_height: Some(1_000),
+            from_address: "from".to_string(),
+            to_address: "to".to_string(),
+            memo: None,
+            amount_zatoshis: 50_000,
+            fee_zatoshis: 10_000,
+            prepared_at,
+        }
+    }
+
+    fn insert_prepared_send(prepared: PreparedSend) -> Uuid {
+        let prepare_id = Uuid::new_v4();
+        PREPARED_SENDS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(prepare_id, prepared);
+        prepare_id
+    }
+
+    #[test]
+    fn test_take_prepared_send_is_single_use() {
+        let user_id = Uuid::new_v4();
+        let prepare_id = insert_prepared_send(prepared_send(user_id, Instant::now()));
+
+        let prepared = take_prepared_send(user_id, prepare_id).unwrap();
+        assert_eq!(prepared.amount_zatoshis, 50_000);
+        assert!(matches!(take_prepared_send(user_id, prepare_id), Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_take_prepared_send_rejects_other_user() {
+        let owner = Uuid::new_v4();
+        let prepare_id = insert_prepared_send(prepared_send(owner, Instant::now()));
+
+        assert!(matches!(
+            take_prepared_send(Uuid::new_v4(), prepare_id),
+            Err(AppError::NotFound(_))
+        ));
+        // Someone else's attempt leaves the send in place for its owner
+        assert!(take_prepared_send(owner, prepare_id).is_ok());
+    }
+
+    #[test]
+    fn test_take_prepared_send_rejects_expired() {
+        let user_id = Uuid::new_v4();
+        let prepared_at = Instant::now()
+            .checked_sub(prepared_send_ttl() + Duration::from_secs(1))
+            .expect("monotonic clock is past the TTL");
+        let prepare_id = insert_prepared_send(prepared_send(user_id, prepared_at));
+
+        assert!(matches!(take_prepared_send(user_id, prepare_id), Err(AppError::NotFound(_))));
+        // Expired sends are dropped rather than left for a later attempt
+        assert!(!PREPARED_SENDS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(&prepare_id));
+    }
+
+    fn send_request(amount_zec: Option<&str>, send_max: bool, prefer_pool: Option<&str>) -> SendTransactionRequest {
+        serde_json::from_value(serde_json::json!({
+            "to_address": "to",
+            "amount_zec": amount_zec,
+            "send_max": send_max,
+            "prefer_pool": prefer_pool,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_requested_zatoshis_send_max_combinations() {
+        assert_eq!(requested_zatoshis(&send_request(Some("0.5"), false, None)).unwrap(), Some(50_000_000));
+        assert_eq!(requested_zatoshis(&send_request(None, true, None)).unwrap(), None);
+        assert_eq!(
+            requested_zatoshis(&send_request(Some("0.5"), false, Some("orchard"))).unwrap(),
+            Some(50_000_000)
+        );
+
+        let rejected = [
+            (send_request(Some("0.5"), true, None), "cannot be combined with send_max"),
+            (send_request(None, false, None), "required unless send_max is set"),
+            (send_request(None, true, Some("orchard")), "prefer_pool is not supported"),
+        ];
+        for (request, expected) in rejected {
+            match requested_zatoshis(&request) {
+                Err(AppError::Validation(message)) => assert!(message.contains(expected), "{}", message),
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_send_amount() {
+        let dust_threshold = u64::from(zip317::MARGINAL_FEE);
+        assert!(matches!(validate_send_amount(0), Err(AppError::Validation(_))));
+        assert!(matches!(validate_send_amount(dust_threshold - 1), Err(AppError::Validation(_))));
+        assert!(validate_send_amount(dust_threshold).is_ok());
+    }
+
+    #[test]
+    fn test_check_spendable_exact_balance() {
+        let minimum_fee = u64::from(zip317::MINIMUM_FEE);
+        assert!(check_spendable(50_000, 50_000 + minimum_fee).is_ok());
+        assert!(matches!(
+            check_spendable(50_000, 50_000 + minimum_fee - 1),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_spendable_stale_balance() {
+        // Funds received after the last scan aren't counted, so the user is told to refresh
+        match check_spendable(1_000_000, 0) {
+            Err(AppError::Validation(message)) => {
+                assert!(message.contains("at the last balance sync"), "{}", message);
+                assert!(message.contains("Refresh your balance"), "{}", message);
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_nothing_to_shield() {
+        let marginal_fee = u64::from(zip317::MARGINAL_FEE);
+        assert_eq!(nothing_to_shield(0).as_deref(), Some("No transparent funds to shield"));
+        assert!(nothing_to_shield(1).unwrap().contains("too small to shield"));
+        assert!(nothing_to_shield(marginal_fee).unwrap().contains("too small to shield"));
+        assert_eq!(nothing_to_shield(marginal_fee + 1), None);
+    }
+}
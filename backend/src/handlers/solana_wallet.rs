@@ -1,8 +1,11 @@
 use crate::{
-    middleware::{AppError, Result},
+    handlers::common::{format_zec, get_network, run_to_completion, validate_zcash_address},
+    middleware::{AppError, Json, Result},
+    models::row::parse_optional_datetime,
     solana::{bridge, rpc, wallet},
 };
-use axum::{extract::Extension, Json};
+use axum::extract::Extension;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
@@ -23,6 +26,15 @@ pub struct GetBalanceResponse {
 pub struct BridgeQuoteRequest {
     pub amount_lamports: u64,
     pub recipient_zcash_address: String,
+    /// Slippage tolerance in basis points (defaults to 100, clamped to 10–1000)
+    #[serde(default)]
+    pub slippage_bps: Option<i32>,
+    /// Hours until the quote expires (defaults to `BRIDGE_QUOTE_DEADLINE_HOURS`, clamped to 1–24)
+    #[serde(default)]
+    pub deadline_hours: Option<i64>,
+    /// Price the swap without reserving a deposit address (for live estimates)
+    #[serde(default)]
+    pub dry: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -33,12 +45,39 @@ pub struct BridgeQuoteResponse {
     pub amount_out_formatted: String,
     pub deposit_address: String,
     pub time_estimate: i64,
+    pub slippage_bps: i32,
+    /// When the quote expires; deposits after this are refunded
+    pub deadline: DateTime<Utc>,
+    /// False for dry-run quotes, whose deposit address is only a placeholder
+    pub deposit_address_reserved: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BridgeEstimateRequest {
+    pub amount_lamports: u64,
+}
+
+/// Expected output only; no deposit address is reserved
+#[derive(Debug, Serialize)]
+pub struct BridgeEstimateResponse {
+    pub amount_out_formatted: String,
+    pub time_estimate: i64,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ExecuteBridgeRequest {
     pub amount_lamports: u64,
     pub recipient_zcash_address: String,
+    /// Slippage tolerance in basis points (defaults to 100, clamped to 10–1000)
+    #[serde(default)]
+    pub slippage_bps: Option<i32>,
+    /// Hours until the quote expires (defaults to `BRIDGE_QUOTE_DEADLINE_HOURS`, clamped to 1–24)
+    #[serde(default)]
+    pub deadline_hours: Option<i64>,
+    /// Least ZEC to accept, in zatoshis like the quote's `amount_out`; the bridge is refused
+    /// if the fresh quote is below it
+    #[serde(default)]
+    pub min_amount_out: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -47,6 +86,7 @@ pub struct ExecuteBridgeResponse {
     pub solana_signature: String,
     pub deposit_address: String,
     pub expected_zec: String,
+    pub deadline: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -54,6 +94,38 @@ pub struct BridgeStatusRequest {
     pub deposit_address: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BridgeHistoryRequest {
+    pub page: Option<i64>,      // Page number (0-indexed)
+    pub page_size: Option<i64>, // Number of items per page (default: 20, max: 100)
+}
+
+#[derive(Debug, Serialize)]
+pub struct BridgeRecord {
+    pub id: Uuid,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub amount_sol: String,
+    pub expected_zec: Option<String>,
+    pub actual_zec: Option<String>,
+    pub deposit_address: String,
+    pub recipient_address: String,
+    pub solana_signature: Option<String>,
+    pub zec_tx_hash: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BridgeHistoryResponse {
+    pub bridges: Vec<BridgeRecord>,
+    pub total_count: i64,
+    pub page: i64,
+    pub page_size: i64,
+    pub has_more: bool,
+}
+
 /// Get Solana wallet balance
 pub async fn get_balance(
     Extension(user_id): Extension<Uuid>,
@@ -91,6 +163,9 @@ pub async fn get_bridge_quote(
 ) -> Result<Json<BridgeQuoteResponse>> {
     tracing::info!("Bridge quote requested - amount: {} lamports, user: {}", request.amount_lamports, user_id);
 
+    // Reject undeliverable destinations before calling NEAR Intents
+    validate_zcash_address(&request.recipient_zcash_address, get_network())?;
+
     // Get user's Solana wallet (for refund address)
     let (public_key, _) = wallet::get_solana_wallet(&db, user_id)
         .await?
@@ -98,11 +173,16 @@ pub async fn get_bridge_quote(
 
     tracing::info!("Calling NEAR Intents API for quote - refund: {}, recipient: {}", public_key, request.recipient_zcash_address);
 
+    let slippage_bps = bridge::effective_slippage_bps(request.slippage_bps);
+
     // Get quote from NEAR Intents
     let quote = bridge::get_bridge_quote(
         request.amount_lamports,
         &public_key,
         &request.recipient_zcash_address,
+        slippage_bps,
+        bridge::effective_deadline_hours(request.deadline_hours),
+        request.dry,
     )
     .await
     .map_err(|e| {
@@ -117,15 +197,78 @@ pub async fn get_bridge_quote(
         amount_out_formatted: quote.amount_out_formatted,
         deposit_address: quote.deposit_address,
         time_estimate: quote.time_estimate,
+        slippage_bps,
+        deadline: quote.deadline,
+        deposit_address_reserved: !request.dry,
+    }))
+}
+
+/// Preview the ZEC received for a SOL amount, for live estimates while the user types
+///
+/// Backed by a briefly cached dry-run quote, so it is cheap to call repeatedly and never
+/// reserves a deposit address. Quote with `/solana/bridge/quote` before executing.
+pub async fn estimate_bridge(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<BridgeEstimateRequest>,
+) -> Result<Json<BridgeEstimateResponse>> {
+    if request.amount_lamports == 0 {
+        return Err(AppError::Validation("amount_lamports must be greater than zero".to_string()));
+    }
+
+    // NEAR Intents wants real refund and recipient addresses even for dry quotes
+    let (public_key, _) = wallet::get_solana_wallet(&db, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Solana wallet not found".to_string()))?;
+    let recipient: String = sqlx::query_scalar("SELECT address FROM wallets WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(&db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let estimate = bridge::estimate_bridge_output(request.amount_lamports, &public_key, &recipient)
+        .await
+        .map_err(|e| {
+            tracing::error!("Bridge estimate failed: {:?}", e);
+            AppError::Internal(format!("Failed to estimate bridge output: {}", e))
+        })?;
+
+    Ok(Json(BridgeEstimateResponse {
+        amount_out_formatted: estimate.amount_out_formatted,
+        time_estimate: estimate.time_estimate,
     }))
 }
 
 /// Execute bridge transaction (send SOL to NEAR Intents)
+///
+/// Runs to completion even if the request times out, so a sent SOL transfer is always
+/// recorded against its bridge transaction.
 pub async fn execute_bridge(
     Extension(user_id): Extension<Uuid>,
     Extension(db): Extension<PgPool>,
     Json(request): Json<ExecuteBridgeRequest>,
 ) -> Result<Json<ExecuteBridgeResponse>> {
+    run_to_completion(send_bridge_deposit(user_id, db, request)).await
+}
+
+async fn send_bridge_deposit(
+    user_id: Uuid,
+    db: PgPool,
+    request: ExecuteBridgeRequest,
+) -> Result<Json<ExecuteBridgeResponse>> {
+    // Reject undeliverable destinations before any funds move
+    validate_zcash_address(&request.recipient_zcash_address, get_network())?;
+
+    let min_amount_out = request
+        .min_amount_out
+        .as_deref()
+        .map(|min| {
+            min.trim().parse::<u64>().map_err(|_| {
+                AppError::Validation("min_amount_out must be a whole number of zatoshis".to_string())
+            })
+        })
+        .transpose()?;
+
     // Get user's Solana wallet
     let (public_key, keypair_bytes) = wallet::get_solana_wallet(&db, user_id)
         .await?
@@ -139,9 +282,23 @@ pub async fn execute_bridge(
         request.amount_lamports,
         &public_key,
         &request.recipient_zcash_address,
+        bridge::effective_slippage_bps(request.slippage_bps),
+        bridge::effective_deadline_hours(request.deadline_hours),
+        false,
     )
     .await?;
 
+    // The rate may have moved since the user saw it; don't send SOL below their floor
+    if let Some(min_amount_out) = min_amount_out {
+        let quoted = quote.amount_out.parse::<u64>().unwrap_or(0);
+        if quoted < min_amount_out {
+            return Err(AppError::Conflict(format!(
+                "Quoted amount {} zatoshis is below min_amount_out {}",
+                quoted, min_amount_out
+            )));
+        }
+    }
+
     // Create bridge transaction record in database
     let expected_zec_zatoshis = quote
         .amount_out
@@ -160,14 +317,28 @@ pub async fn execute_bridge(
     .await?;
 
     // Execute the SOL transfer
-    let solana_signature = bridge::execute_bridge(
+    let transaction = bridge::sign_bridge_transfer(
         &keypair,
         &quote.deposit_address,
         request.amount_lamports,
     )
     .await?;
 
-    // Update bridge transaction with signature
+    // Record the signature before sending; if confirmation fails or the request is
+    // dropped, the poller settles the BROADCAST row from the chain
+    let solana_signature = bridge::transaction_signature(&transaction)?;
+    bridge::record_bridge_broadcast(&db, bridge_tx_id, &solana_signature).await?;
+
+    if let Err(e) = bridge::send_bridge_transfer(transaction).await {
+        tracing::warn!(
+            "Bridge transfer {} for transaction {} not confirmed: {:#}",
+            solana_signature,
+            bridge_tx_id,
+            e
+        );
+        return Err(e.into());
+    }
+
     bridge::update_bridge_tx_signature(&db, bridge_tx_id, &solana_signature).await?;
 
     Ok(Json(ExecuteBridgeResponse {
@@ -175,6 +346,7 @@ pub async fn execute_bridge(
         solana_signature,
         deposit_address: quote.deposit_address,
         expected_zec: quote.amount_out_formatted,
+        deadline: quote.deadline,
     }))
 }
 
@@ -202,20 +374,7 @@ pub async fn get_bridge_status(
     let status = bridge::get_bridge_status(&request.deposit_address).await?;
 
     // Update database if status changed
-    if let Some(status_str) = status.get("status").and_then(|s| s.as_str()) {
-        let zec_tx_hash = status
-            .get("swapDetails")
-            .and_then(|sd| sd.get("destinationChainTxHashes"))
-            .and_then(|hashes| hashes.get(0))
-            .and_then(|h| h.get("hash"))
-            .and_then(|h| h.as_str());
-
-        let actual_zec = status
-            .get("swapDetails")
-            .and_then(|sd| sd.get("amountOut"))
-            .and_then(|a| a.as_str())
-            .and_then(|s| s.parse::<i64>().ok());
-
+    if let Some(update) = bridge::parse_status_update(&status) {
         // Get UUID as String and parse it
         let tx_id_str: String = tx.get("id");
         let tx_id = Uuid::parse_str(&tx_id_str)
@@ -223,9 +382,9 @@ pub async fn get_bridge_status(
         bridge::update_bridge_status(
             &db,
             tx_id,
-            status_str,
-            zec_tx_hash,
-            actual_zec,
+            &update.status,
+            update.zec_tx_hash.as_deref(),
+            update.actual_zec_zatoshis,
             None,
         )
         .await?;
@@ -233,3 +392,78 @@ pub async fn get_bridge_status(
 
     Ok(Json(status))
 }
+
+/// List the user's SOL → ZEC bridges, newest first
+///
+/// Reads only what's recorded in the database; use `get_bridge_status` to refresh a
+/// bridge that's still in flight.
+pub async fn list_bridges(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<BridgeHistoryRequest>,
+) -> Result<Json<BridgeHistoryResponse>> {
+    let page = request.page.unwrap_or(0).max(0);
+    let page_size = request.page_size.unwrap_or(20).min(100).max(1);
+    let offset = page * page_size;
+
+    let total_count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM bridge_transactions WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_one(&db)
+    .await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT id::text, status, error_message, amount_sol_lamports, expected_zec_zatoshis,
+               actual_zec_zatoshis, deposit_address, recipient_address, solana_tx_signature,
+               zec_tx_hash, created_at::text, updated_at::text, completed_at::text
+        FROM bridge_transactions
+        WHERE user_id = $1::uuid
+        ORDER BY created_at DESC, id
+        LIMIT $2 OFFSET $3
+        "#
+    )
+    .bind(user_id.to_string())
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(&db)
+    .await?;
+
+    let bridges = rows
+        .iter()
+        .map(|row| {
+            let id: String = row.get("id");
+            let amount_sol_lamports: i64 = row.get("amount_sol_lamports");
+            let expected_zec_zatoshis: Option<i64> = row.get("expected_zec_zatoshis");
+            let actual_zec_zatoshis: Option<i64> = row.get("actual_zec_zatoshis");
+
+            Ok(BridgeRecord {
+                id: Uuid::parse_str(&id)
+                    .map_err(|e| AppError::Internal(format!("Invalid UUID: {}", e)))?,
+                status: row.get("status"),
+                error_message: row.get("error_message"),
+                amount_sol: format!("{:.9}", amount_sol_lamports as f64 / 1_000_000_000.0),
+                expected_zec: expected_zec_zatoshis.map(format_zec),
+                actual_zec: actual_zec_zatoshis.map(format_zec),
+                deposit_address: row.get("deposit_address"),
+                recipient_address: row.get("recipient_address"),
+                solana_signature: row.get("solana_tx_signature"),
+                zec_tx_hash: row.get("zec_tx_hash"),
+                created_at: parse_optional_datetime(row.get("created_at")),
+                updated_at: parse_optional_datetime(row.get("updated_at")),
+                completed_at: parse_optional_datetime(row.get("completed_at")),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let has_more = offset + (bridges.len() as i64) < total_count;
+
+    Ok(Json(BridgeHistoryResponse {
+        bridges,
+        total_count,
+        page,
+        page_size,
+        has_more,
+    }))
+}
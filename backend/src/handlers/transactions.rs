@@ -1,5 +1,8 @@
-use crate::middleware::Result;
-use axum::{extract::State, Json};
+use crate::handlers::common::{format_zec, FiatQuery};
+use crate::middleware::{AppError, Json, Result};
+use crate::models::row::parse_optional_datetime;
+use crate::zcash::transaction::decode_memo;
+use axum::extract::{Extension, Query, State};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
@@ -12,9 +15,42 @@ pub struct TransactionsState {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetTransactionsRequest {
-    pub user_id: Uuid,
     pub page: Option<i64>,      // Page number (0-indexed)
     pub page_size: Option<i64>, // Number of items per page (default: 20, max: 100)
+    /// Return transactions after this cursor (a previous response's `next_cursor`)
+    /// instead of using `page`. Stable while new transactions arrive; use it for infinite scroll.
+    pub before: Option<String>,
+}
+
+/// Sort key standing in for the block height of unmined transactions, so they list first
+const PENDING_SORT_HEIGHT: i64 = i64::MAX;
+
+/// Position in the history, encoded as `<block height>:<txid>`
+struct Cursor {
+    sort_height: i64,
+    txid: String,
+}
+
+impl Cursor {
+    fn new(block_height: Option<i64>, txid: &str) -> Self {
+        Self {
+            sort_height: block_height.unwrap_or(PENDING_SORT_HEIGHT),
+            txid: txid.to_string(),
+        }
+    }
+
+    fn parse(cursor: &str) -> Result<Self> {
+        let invalid = || AppError::Validation("Invalid pagination cursor".to_string());
+        let (height, txid) = cursor.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            sort_height: height.parse().map_err(|_| invalid())?,
+            txid: txid.to_string(),
+        })
+    }
+
+    fn encode(&self) -> String {
+        format!("{}:{}", self.sort_height, self.txid)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -23,9 +59,33 @@ pub struct Transaction {
     pub timestamp: Option<DateTime<Utc>>,
     pub block_height: Option<i64>,
     pub amount_zec: String,
+    /// In the response's `fiat_currency`; only with `?fiat=<currency>` and when the price
+    /// source is reachable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_fiat: Option<String>,
     pub direction: TransactionDirection,
     pub memo: Option<String>,
     pub fee_zec: Option<String>,
+    pub status: TransactionStatus,
+    /// Private note set with `POST /wallet/transactions/label`; never on-chain
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    /// Broadcast but not yet seen in a block
+    Pending,
+    Mined,
+}
+
+impl TransactionStatus {
+    fn from_block_height(block_height: Option<i64>) -> Self {
+        match block_height {
+            Some(_) => Self::Mined,
+            None => Self::Pending,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -35,6 +95,65 @@ pub enum TransactionDirection {
     Sent,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GetTransactionRequest {
+    pub txid: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetLabelRequest {
+    pub txid: String,
+    /// New label; empty or omitted removes it
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetLabelResponse {
+    pub txid: String,
+    pub label: Option<String>,
+}
+
+/// Longest accepted transaction label, in characters
+const MAX_LABEL_LEN: usize = 200;
+
+/// One note of a transaction: a note the wallet received, or an output it sent
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransactionOutput {
+    pub direction: TransactionDirection,
+    /// Shielded pool of a received note ("sapling" or "orchard")
+    pub pool: Option<String>,
+    /// Output (Sapling) or action (Orchard) index of a received note
+    pub note_index: Option<i32>,
+    /// Recipient of a sent output
+    pub address: Option<String>,
+    pub amount_zec: String,
+    /// In the response's `fiat_currency`; only with `?fiat=<currency>` and when the price
+    /// source is reachable
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_fiat: Option<String>,
+    pub memo: Option<String>,
+    pub is_change: bool,
+    /// Whether a received note has since been spent
+    pub spent: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransactionDetail {
+    pub txid: String,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub block_height: Option<i64>,
+    pub fee_zec: Option<String>,
+    pub status: TransactionStatus,
+    /// Blocks since the transaction was mined, as of the wallet's last sync (0 while pending)
+    pub confirmations: i64,
+    pub label: Option<String>,
+    pub outputs: Vec<TransactionOutput>,
+    /// Currency of the outputs' `amount_fiat`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat_currency: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct TransactionsResponse {
     pub transactions: Vec<Transaction>,
@@ -42,29 +161,42 @@ pub struct TransactionsResponse {
     pub page: i64,
     pub page_size: i64,
     pub has_more: bool,
+    /// Pass as `before` to fetch the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+    /// Currency of the transactions' `amount_fiat`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fiat_currency: Option<String>,
 }
 
-/// Get transaction history for a user
+/// Get transaction history for the authenticated user
 /// Returns list of all transactions (sent and received) with details
+///
+/// Newest first, ordered by `(block_height DESC, txid)` with pending transactions on top.
+/// Pages are selected by `before` cursor when given, otherwise by `page` offset. With
+/// `?fiat=<currency>` amounts are also given in that currency at the current price.
 #[axum::debug_handler]
 pub async fn get_transactions(
     State(state): State<TransactionsState>,
+    Extension(user_id): Extension<Uuid>,
+    Query(fiat): Query<FiatQuery>,
     Json(payload): Json<GetTransactionsRequest>,
 ) -> Result<Json<TransactionsResponse>> {
-    let page = payload.page.unwrap_or(0).max(0);
+    let cursor = payload.before.as_deref().map(Cursor::parse).transpose()?;
+    let page = if cursor.is_some() { 0 } else { payload.page.unwrap_or(0).max(0) };
     let page_size = payload.page_size.unwrap_or(20).min(100).max(1);
     let offset = page * page_size;
+    let fiat_price = fiat.price().await?;
 
     tracing::info!(
         "Transaction history requested for user {} (page: {}, size: {})",
-        payload.user_id,
+        user_id,
         page,
         page_size
     );
 
     // First, get total count
     let total_count_result = sqlx::query("SELECT COUNT(DISTINCT txid) as count FROM transactions WHERE user_id = $1::uuid")
-        .bind(payload.user_id.to_string())
+        .bind(user_id.to_string())
         .fetch_one(&state.db)
         .await?;
 
@@ -72,6 +204,7 @@ pub async fn get_transactions(
 
     // Query to get paginated transactions with their notes
     // We need to determine direction based on whether the transaction has sent_notes
+    // One extra row is fetched to tell whether another page follows
     let tx_records = sqlx::query(
         r#"
         WITH tx_summary AS (
@@ -82,12 +215,16 @@ pub async fn get_transactions(
                 t.created_at,
                 t.block_height,
                 t.fee_zatoshis,
+                COALESCE(t.block_height, $4) as sort_height,
                 CAST(COALESCE(SUM(CASE WHEN rn.is_change = false AND rn.spent_in_tx_id IS NULL
                                   THEN rn.value_zatoshis ELSE 0 END), 0) AS BIGINT) as received_value,
                 CAST(COALESCE(SUM(CASE WHEN sn.id IS NOT NULL
                                   THEN sn.value_zatoshis ELSE 0 END), 0) AS BIGINT) as sent_value,
                 COUNT(DISTINCT sn.id) as sent_count,
-                COUNT(DISTINCT CASE WHEN rn.is_change = false THEN rn.id END) as received_count
+                COUNT(DISTINCT CASE WHEN rn.is_change = false THEN rn.id END) as received_count,
+                (ARRAY_AGG(rn.memo ORDER BY rn.note_index)
+                    FILTER (WHERE rn.is_change = false AND rn.memo IS NOT NULL))[1] as received_memo,
+                (ARRAY_AGG(sn.memo ORDER BY sn.id) FILTER (WHERE sn.memo IS NOT NULL))[1] as sent_memo
             FROM transactions t
             LEFT JOIN received_notes rn ON rn.transaction_id = t.id AND rn.user_id = t.user_id
             LEFT JOIN sent_notes sn ON sn.transaction_id = t.id AND sn.user_id = t.user_id
@@ -96,38 +233,41 @@ pub async fn get_transactions(
         )
         SELECT
             ts.txid,
-            ts.created_at,
+            ts.created_at::text AS created_at,
             ts.block_height,
             ts.fee_zatoshis,
             ts.received_value,
             ts.sent_value,
             ts.sent_count,
             ts.received_count,
-            sn.memo as sent_memo
+            ts.received_memo,
+            ts.sent_memo,
+            tl.label
         FROM tx_summary ts
-        LEFT JOIN sent_notes sn ON sn.transaction_id = ts.id AND sn.user_id = ts.user_id
-        ORDER BY ts.block_height DESC NULLS LAST, ts.created_at DESC NULLS LAST
+        LEFT JOIN transaction_labels tl ON tl.user_id = ts.user_id AND tl.txid = lower(ts.txid)
+        WHERE $5::bigint IS NULL
+           OR ts.sort_height < $5
+           OR (ts.sort_height = $5 AND ts.txid > $6)
+        ORDER BY ts.sort_height DESC, ts.txid
         LIMIT $2 OFFSET $3
         "#
     )
-    .bind(payload.user_id.to_string())
-    .bind(page_size)
+    .bind(user_id.to_string())
+    .bind(page_size + 1)
     .bind(offset)
+    .bind(PENDING_SORT_HEIGHT)
+    .bind(cursor.as_ref().map(|c| c.sort_height))
+    .bind(cursor.as_ref().map(|c| c.txid.as_str()))
     .fetch_all(&state.db)
     .await?;
 
+    let has_more = tx_records.len() as i64 > page_size;
+
     let mut transactions: Vec<Transaction> = Vec::new();
-    let mut seen_txids: std::collections::HashSet<String> = std::collections::HashSet::new();
 
-    for record in tx_records {
+    for record in tx_records.into_iter().take(page_size as usize) {
         let txid: String = record.get("txid");
 
-        // Skip if we've already processed this txid (due to multiple sent_notes)
-        if seen_txids.contains(&txid) {
-            continue;
-        }
-        seen_txids.insert(txid.clone());
-
         let sent_count: i64 = record.get::<Option<i64>, _>("sent_count").unwrap_or(0);
         let _received_count: i64 = record.get::<Option<i64>, _>("received_count").unwrap_or(0);
         let sent_value: i64 = record.get::<Option<i64>, _>("sent_value").unwrap_or(0);
@@ -146,41 +286,46 @@ pub async fn get_transactions(
             (TransactionDirection::Received, received_value)
         };
 
-        let amount_zec = format!("{:.8}", amount_zatoshis as f64 / 100_000_000.0);
+        let amount_zec = format_zec(amount_zatoshis);
 
         let fee_zatoshis: Option<i64> = record.get("fee_zatoshis");
-        let fee_zec = fee_zatoshis.map(|fee| {
-            format!("{:.8}", fee as f64 / 100_000_000.0)
-        });
+        let fee_zec = fee_zatoshis.map(format_zec);
 
-        // Parse memo if present
-        let memo: Option<String> = record.get("sent_memo");
+        // Sent memos are stored decoded; received memos are raw bytes
+        let memo: Option<String> = match direction {
+            TransactionDirection::Sent => record.get("sent_memo"),
+            TransactionDirection::Received => record
+                .get::<Option<Vec<u8>>, _>("received_memo")
+                .and_then(|bytes| decode_memo(&bytes)),
+        };
 
-        // Get created_at as String and parse it
-        let created_at_str: Option<String> = record.get("created_at");
-        let timestamp = created_at_str.and_then(|s| {
-            chrono::DateTime::parse_from_rfc3339(&s)
-                .ok()
-                .map(|dt| dt.with_timezone(&Utc))
-        });
+        let timestamp = parse_optional_datetime(record.get("created_at"));
+
+        let block_height: Option<i64> = record.get("block_height");
 
         transactions.push(Transaction {
             txid,
             timestamp,
-            block_height: record.get("block_height"),
+            block_height,
             amount_zec,
+            amount_fiat: fiat_price.as_ref().map(|fiat| fiat.format(amount_zatoshis)),
             direction,
             memo,
             fee_zec,
+            status: TransactionStatus::from_block_height(block_height),
+            label: record.get("label"),
         });
     }
 
-    let has_more = (offset + transactions.len() as i64) < total_count;
+    let next_cursor = transactions
+        .last()
+        .filter(|_| has_more)
+        .map(|tx| Cursor::new(tx.block_height, &tx.txid).encode());
 
     tracing::info!(
         "Found {} transactions for user {} (total: {}, has_more: {})",
         transactions.len(),
-        payload.user_id,
+        user_id,
         total_count,
         has_more
     );
@@ -191,5 +336,179 @@ pub async fn get_transactions(
         page,
         page_size,
         has_more,
+        next_cursor,
+        fiat_currency: fiat_price.map(|fiat| fiat.currency),
     }))
 }
+
+/// Get the full detail of one of the authenticated user's transactions
+///
+/// Lists every received note and sent output with its memo and change flag. Returns 404 if
+/// the txid isn't one of the user's transactions. With `?fiat=<currency>` output amounts are
+/// also given in that currency at the current price.
+#[axum::debug_handler]
+pub async fn get_transaction(
+    State(state): State<TransactionsState>,
+    Extension(user_id): Extension<Uuid>,
+    Query(fiat): Query<FiatQuery>,
+    Json(payload): Json<GetTransactionRequest>,
+) -> Result<Json<TransactionDetail>> {
+    let tx_row = sqlx::query(
+        "SELECT t.id, t.txid, t.created_at::text, t.block_height, t.fee_zatoshis, tl.label
+         FROM transactions t
+         LEFT JOIN transaction_labels tl ON tl.user_id = t.user_id AND tl.txid = lower(t.txid)
+         WHERE t.user_id = $1::uuid AND lower(t.txid) = lower($2)"
+    )
+    .bind(user_id.to_string())
+    .bind(payload.txid.trim())
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Transaction not found".to_string()))?;
+
+    let tx_id: i64 = tx_row.get("id");
+    let block_height: Option<i64> = tx_row.get("block_height");
+
+    let last_synced_height: Option<i64> = sqlx::query_scalar(
+        "SELECT last_synced_height FROM wallets WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(&state.db)
+    .await?
+    .flatten();
+
+    let confirmations = match (block_height, last_synced_height) {
+        (Some(mined), Some(synced)) if synced >= mined => synced - mined + 1,
+        _ => 0,
+    };
+
+    let received_rows = sqlx::query(
+        "SELECT pool, note_index, value_zatoshis, memo, is_change, spent_in_tx_id IS NOT NULL AS spent
+         FROM received_notes
+         WHERE user_id = $1::uuid AND transaction_id = $2
+         ORDER BY pool, note_index"
+    )
+    .bind(user_id.to_string())
+    .bind(tx_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let sent_rows = sqlx::query(
+        "SELECT to_address, value_zatoshis, memo
+         FROM sent_notes
+         WHERE user_id = $1::uuid AND transaction_id = $2
+         ORDER BY id"
+    )
+    .bind(user_id.to_string())
+    .bind(tx_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    let fiat_price = fiat.price().await?;
+    let amount_fiat = |value_zatoshis: i64| fiat_price.as_ref().map(|fiat| fiat.format(value_zatoshis));
+
+    let mut outputs: Vec<TransactionOutput> = received_rows
+        .iter()
+        .map(|row| {
+            let memo: Option<Vec<u8>> = row.get("memo");
+            TransactionOutput {
+                direction: TransactionDirection::Received,
+                pool: row.get("pool"),
+                note_index: Some(row.get("note_index")),
+                address: None,
+                amount_zec: format_zec(row.get::<i64, _>("value_zatoshis")),
+                amount_fiat: amount_fiat(row.get("value_zatoshis")),
+                memo: memo.and_then(|bytes| decode_memo(&bytes)),
+                is_change: row.get("is_change"),
+                spent: row.get("spent"),
+            }
+        })
+        .collect();
+
+    outputs.extend(sent_rows.iter().map(|row| TransactionOutput {
+        direction: TransactionDirection::Sent,
+        pool: None,
+        note_index: None,
+        address: Some(row.get("to_address")),
+        amount_zec: format_zec(row.get::<i64, _>("value_zatoshis")),
+        amount_fiat: amount_fiat(row.get("value_zatoshis")),
+        memo: row.get("memo"),
+        is_change: false,
+        spent: false,
+    }));
+
+    let timestamp = parse_optional_datetime(tx_row.get("created_at"));
+
+    let fee_zatoshis: Option<i64> = tx_row.get("fee_zatoshis");
+
+    Ok(Json(TransactionDetail {
+        txid: tx_row.get("txid"),
+        timestamp,
+        block_height,
+        fee_zec: fee_zatoshis.map(format_zec),
+        status: TransactionStatus::from_block_height(block_height),
+        confirmations,
+        label: tx_row.get("label"),
+        outputs,
+        fiat_currency: fiat_price.map(|fiat| fiat.currency),
+    }))
+}
+
+/// Set or clear the private label of one of the authenticated user's transactions
+///
+/// Labels are stored only in our database and never go on-chain. Returns 404 if the txid
+/// isn't one of the user's transactions.
+#[axum::debug_handler]
+pub async fn set_label(
+    State(state): State<TransactionsState>,
+    Extension(user_id): Extension<Uuid>,
+    Json(payload): Json<SetLabelRequest>,
+) -> Result<Json<SetLabelResponse>> {
+    let txid = payload.txid.trim().to_lowercase();
+    let label = payload
+        .label
+        .as_deref()
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string);
+    if label.as_ref().is_some_and(|label| label.chars().count() > MAX_LABEL_LEN) {
+        return Err(AppError::Validation(format!(
+            "Label must be at most {} characters",
+            MAX_LABEL_LEN
+        )));
+    }
+
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM transactions WHERE user_id = $1::uuid AND lower(txid) = $2)"
+    )
+    .bind(user_id.to_string())
+    .bind(&txid)
+    .fetch_one(&state.db)
+    .await?;
+    if !exists {
+        return Err(AppError::NotFound("Transaction not found".to_string()));
+    }
+
+    match &label {
+        Some(label) => {
+            sqlx::query(
+                "INSERT INTO transaction_labels (user_id, txid, label)
+                 VALUES ($1::uuid, $2, $3)
+                 ON CONFLICT (user_id, txid) DO UPDATE SET label = EXCLUDED.label, updated_at = NOW()"
+            )
+            .bind(user_id.to_string())
+            .bind(&txid)
+            .bind(label)
+            .execute(&state.db)
+            .await?;
+        }
+        None => {
+            sqlx::query("DELETE FROM transaction_labels WHERE user_id = $1::uuid AND txid = $2")
+                .bind(user_id.to_string())
+                .bind(&txid)
+                .execute(&state.db)
+                .await?;
+        }
+    }
+
+    Ok(Json(SetLabelResponse { txid, label }))
+}
@@ -1,52 +1,71 @@
 use crate::{
-    middleware::{AppError, Result},
-    models::user::{User, UserResponse},
+    handlers::common::{confirm_password, remove_wallet_db},
+    middleware::{AppError, Json, Result},
+    models::{
+        row::{user_from_row, USER_COLUMNS},
+        user::{
+            DeleteAccountRequest, RegisterWebhookRequest, UpdateUserRequest, UserResponse,
+            WebhookResponse,
+        },
+    },
+    utils::{token::generate_token, webhook},
 };
-use axum::{extract::Extension, Json};
-use chrono::{DateTime, Utc};
+use axum::{extract::Extension, http::StatusCode};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
+use validator::Validate;
 
-/// Helper to parse DateTime string from database
-fn parse_datetime(s: &str) -> std::result::Result<DateTime<Utc>, sqlx::Error> {
-    chrono::DateTime::parse_from_rfc3339(s)
-        .map(|dt| dt.with_timezone(&Utc))
-        .or_else(|_| {
-            chrono::DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z")
-                .map(|dt| dt.with_timezone(&Utc))
-        })
-        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
-}
-
-/// Helper to parse User from a database row (since sqlx uuid/chrono features are disabled)
-fn user_from_row(row: &sqlx::postgres::PgRow) -> std::result::Result<User, sqlx::Error> {
-    let id_str: String = row.try_get("id")?;
-    let auth_method_str: String = row.try_get("auth_method")?;
-    let created_at_str: String = row.try_get("created_at")?;
-    let updated_at_str: String = row.try_get("updated_at")?;
-
-    Ok(User {
-        id: Uuid::parse_str(&id_str).map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
-        email: row.try_get("email")?,
-        password_hash: row.try_get("password_hash")?,
-        full_name: row.try_get("full_name")?,
-        email_verified: row.try_get("email_verified")?,
-        auth_method: crate::models::user::AuthMethod::from_str(&auth_method_str),
-        created_at: parse_datetime(&created_at_str)?,
-        updated_at: parse_datetime(&updated_at_str)?,
-    })
+pub async fn get_me(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<UserResponse>> {
+    user_response(&db, user_id).await.map(Json)
 }
 
-pub async fn get_me(
+/// Update the authenticated user's display name
+///
+/// Email changes are rejected here; they need re-verification and are handled separately.
+pub async fn update_me(
     Extension(user_id): Extension<Uuid>,
     Extension(db): Extension<PgPool>,
+    Json(mut request): Json<UpdateUserRequest>,
 ) -> Result<Json<UserResponse>> {
-    let user_row = sqlx::query(
-        "SELECT id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at::text, updated_at::text
-         FROM users WHERE id = $1::uuid"
-    )
+    if request.email.is_some() {
+        return Err(AppError::Validation(
+            "Email cannot be changed with this endpoint".to_string(),
+        ));
+    }
+
+    request.full_name = request.full_name.trim().to_string();
+    request.validate().map_err(|e| {
+        AppError::Validation(format!("Validation error: {}", e))
+    })?;
+
+    let updated = sqlx::query("UPDATE users SET full_name = $1, updated_at = NOW() WHERE id = $2::uuid")
+        .bind(&request.full_name)
         .bind(user_id.to_string())
-        .fetch_optional(&db)
+        .execute(&db)
+        .await?
+        .rows_affected();
+
+    if updated == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    tracing::info!("Updated full name for user {}", user_id);
+
+    user_response(&db, user_id).await.map(Json)
+}
+
+/// The user's profile with their Zcash and Solana addresses
+async fn user_response(db: &PgPool, user_id: Uuid) -> Result<UserResponse> {
+    let user_row = sqlx::query(&format!(
+        "SELECT {}
+         FROM users WHERE id = $1::uuid",
+        USER_COLUMNS
+    ))
+        .bind(user_id.to_string())
+        .fetch_optional(db)
         .await?
         .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
 
@@ -55,7 +74,7 @@ pub async fn get_me(
     // Fetch wallet address for this user
     let wallet_data = sqlx::query("SELECT address FROM wallets WHERE user_id = $1::uuid")
         .bind(user_id.to_string())
-        .fetch_optional(&db)
+        .fetch_optional(db)
         .await?;
 
     let wallet_address = wallet_data.map(|row| row.get("address"));
@@ -63,12 +82,12 @@ pub async fn get_me(
     // Fetch Solana wallet address
     let solana_data = sqlx::query("SELECT public_key FROM solana_wallets WHERE user_id = $1::uuid")
         .bind(user_id.to_string())
-        .fetch_optional(&db)
+        .fetch_optional(db)
         .await?;
 
     let solana_address = solana_data.map(|row| row.get("public_key"));
 
-    Ok(Json(UserResponse {
+    Ok(UserResponse {
         id: user.id,
         email: user.email,
         full_name: user.full_name,
@@ -76,5 +95,87 @@ pub async fn get_me(
         created_at: user.created_at,
         wallet_address,
         solana_address,
+    })
+}
+
+/// Permanently delete the authenticated user's account and all associated data
+///
+/// Email users must confirm with their current password. Database rows are removed in
+/// one transaction; the on-disk wallet database is deleted after the commit.
+pub async fn delete_account(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<DeleteAccountRequest>,
+) -> Result<StatusCode> {
+    confirm_password(&db, user_id, request.password.as_deref()).await?;
+
+    let mut tx = db.begin().await?;
+
+    // Children before parents; tables not listed here cascade from users
+    for table in [
+        "sessions",
+        "sent_notes",
+        "received_notes",
+        "transactions",
+        "bridge_transactions",
+        "solana_wallets",
+        "wallets",
+    ] {
+        sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1::uuid", table))
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    sqlx::query("DELETE FROM users WHERE id = $1::uuid")
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    remove_wallet_db(user_id).await;
+
+    tracing::info!("Deleted account for user {}", user_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Register (or replace) the URL notified when the wallet receives funds
+///
+/// A fresh signing secret is generated on every registration and returned once; receivers
+/// verify deliveries by recomputing the HMAC-SHA256 of the body with it.
+pub async fn register_webhook(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<RegisterWebhookRequest>,
+) -> Result<Json<WebhookResponse>> {
+    request.validate().map_err(|e| {
+        AppError::Validation(format!("Validation error: {}", e))
+    })?;
+
+    webhook::check_url(&request.url)
+        .await
+        .map_err(|e| AppError::Validation(e.to_string()))?;
+
+    let secret = generate_token();
+
+    sqlx::query(
+        "INSERT INTO webhooks (user_id, url, secret)
+         VALUES ($1::uuid, $2, $3)
+         ON CONFLICT (user_id)
+         DO UPDATE SET url = EXCLUDED.url, secret = EXCLUDED.secret, updated_at = NOW()"
+    )
+    .bind(user_id.to_string())
+    .bind(&request.url)
+    .bind(&secret)
+    .execute(&db)
+    .await?;
+
+    tracing::info!("Registered webhook for user {}", user_id);
+
+    Ok(Json(WebhookResponse {
+        url: request.url,
+        secret,
     }))
 }
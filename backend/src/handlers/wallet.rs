@@ -1,6 +1,21 @@
-use crate::middleware::{AppError, Result};
+use crate::middleware::{AppError, Json, Result};
 use crate::handlers::AppState;
-use axum::{extract::State, Json};
+use crate::handlers::common::{
+    confirm_password, connect_lightwalletd, derive_spending_key,
+    get_network, load_wallet_config, network_name, parse_network, parse_zec_amount,
+    remove_wallet_db,
+};
+use crate::handlers::balance;
+use crate::handlers::send::ensure_account;
+use crate::zcash::backup::{self, BackupContents, EncryptedBackup};
+use crate::zcash::transaction;
+use axum::{
+    extract::{Extension, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use qrcode::QrCode;
+use sqlx::PgPool;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use bip39::Mnemonic;
@@ -30,6 +45,108 @@ pub struct AddressResponse {
     pub address: String,
 }
 
+/// Optional payment details for `GET /wallet/address/qr`
+#[derive(Debug, Deserialize)]
+pub struct AddressQrQuery {
+    /// Decimal ZEC amount; when set the QR code encodes a ZIP-321 payment URI
+    pub amount: Option<String>,
+    /// Text memo for the payment URI (requires `amount`)
+    pub memo: Option<String>,
+    /// `png` (default) or `svg`
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DiversifiedAddressResponse {
+    pub address: String,
+    pub diversifier_index: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaplingAddressResponse {
+    /// Legacy Sapling address (`zs1...`)
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AddressesResponse {
+    /// Unified address with shielded and transparent receivers
+    pub unified: String,
+    /// Unified address with shielded receivers only
+    pub shielded: String,
+    /// Bare transparent address for senders that only support t-addresses
+    pub transparent: String,
+}
+
+/// Addresses as stored in the wallets table; the cached ones may not be set yet
+#[derive(Serialize, Deserialize)]
+pub struct StoredAddresses {
+    pub shielded: String,
+    pub unified: Option<String>,
+    pub transparent: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RederiveAddressResponse {
+    /// True if any stored address differed and was replaced
+    pub changed: bool,
+    pub old: StoredAddresses,
+    pub new: AddressesResponse,
+}
+
+/// Password confirmation for exporting the viewing key
+#[derive(Deserialize)]
+pub struct ExportViewingKeyRequest {
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportViewingKeyResponse {
+    /// Bech32m-encoded unified full viewing key (`uview...`)
+    pub ufvk: String,
+}
+
+/// Password confirmation and backup passphrase for `POST /wallet/backup`
+#[derive(Deserialize)]
+pub struct ExportBackupRequest {
+    pub password: Option<String>,
+    /// Passphrase the backup is encrypted with; never stored
+    pub passphrase: String,
+}
+
+#[derive(Deserialize)]
+pub struct ImportBackupRequest {
+    pub password: Option<String>,
+    pub passphrase: String,
+    pub backup: EncryptedBackup,
+    /// Replace the current wallet when the backup holds a different seed
+    #[serde(default)]
+    pub replace: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportBackupResponse {
+    pub address: String,
+    pub birthday_height: i64,
+    pub network: String,
+    /// False when the backup matched the current wallet and nothing changed
+    pub restored: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAccountRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccountResponse {
+    /// ZIP 32 account index; 0 is the primary account
+    pub account_index: u32,
+    pub name: String,
+    pub address: String,
+    pub birthday_height: i64,
+}
+
 /// Create a new Zcash wallet for a user
 #[axum::debug_handler]
 pub async fn create_wallet(
@@ -56,7 +173,7 @@ pub async fn create_wallet(
     let mnemonic_str = mnemonic.to_string();
 
     // Create wallet from mnemonic to get address
-    let network = Network::MainNetwork; // TODO: Make this configurable
+    let network = get_network();
     let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&mnemonic, network)
         .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
 
@@ -72,14 +189,15 @@ pub async fn create_wallet(
 
     // SECURITY WARNING: In production, ENCRYPT the mnemonic before storing!
     sqlx::query(
-        "INSERT INTO wallets (id, user_id, encrypted_mnemonic, address, birthday_height, created_at)
-         VALUES ($1::uuid, $2::uuid, $3, $4, $5, NOW())"
+        "INSERT INTO wallets (id, user_id, encrypted_mnemonic, address, birthday_height, network, created_at)
+         VALUES ($1::uuid, $2::uuid, $3, $4, $5, $6, NOW())"
     )
     .bind(wallet_id.to_string())
     .bind(payload.user_id.to_string())
     .bind(&mnemonic_str) // TODO: ENCRYPT THIS IN PRODUCTION!
     .bind(&address)
     .bind(birthday_height)
+    .bind(network_name(network))
     .execute(&state.db)
     .await?;
 
@@ -107,6 +225,271 @@ pub async fn get_address(
     }))
 }
 
+/// Get every deposit address for the authenticated user
+///
+/// Addresses are cached in the wallets table; deriving them needs the seed, so it is only
+/// done the first time for wallets created before the columns existed.
+pub async fn get_addresses(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<AddressesResponse>> {
+    let row = sqlx::query(
+        "SELECT address, transparent_address, transparent_receiver FROM wallets WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(&db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let shielded: String = row.get("address");
+    let unified: Option<String> = row.get("transparent_address");
+    let transparent: Option<String> = row.get("transparent_receiver");
+
+    if let (Some(unified), Some(transparent)) = (unified, transparent) {
+        return Ok(Json(AddressesResponse {
+            unified,
+            shielded,
+            transparent,
+        }));
+    }
+
+    tracing::info!("Deriving and caching addresses for user {}", user_id);
+
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&config.mnemonic, config.network)
+        .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
+    let addresses = wallet.get_addresses()
+        .map_err(|e| AppError::Internal(format!("Failed to derive addresses: {}", e)))?;
+
+    sqlx::query(
+        "UPDATE wallets SET transparent_address = $1, transparent_receiver = $2 WHERE user_id = $3::uuid"
+    )
+    .bind(&addresses.unified)
+    .bind(&addresses.transparent)
+    .bind(user_id.to_string())
+    .execute(&db)
+    .await?;
+
+    Ok(Json(AddressesResponse {
+        unified: addresses.unified,
+        shielded,
+        transparent: addresses.transparent,
+    }))
+}
+
+/// Re-derive the authenticated user's addresses from the seed and fix stale stored ones
+///
+/// Stored addresses can drift from the current derivation when it changes (as it did for
+/// transparent receivers). Keys and funds are unaffected; only the addresses shown to
+/// users and counterparties are rewritten, and the old values are returned.
+pub async fn rederive_addresses(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<RederiveAddressResponse>> {
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&config.mnemonic, config.network)
+        .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
+    let addresses = wallet.get_addresses()
+        .map_err(|e| AppError::Internal(format!("Failed to derive addresses: {}", e)))?;
+
+    // Lock the row so a concurrent get_addresses can't cache over the fix
+    let mut tx = db.begin().await?;
+
+    let row = sqlx::query(
+        "SELECT address, transparent_address, transparent_receiver FROM wallets
+         WHERE user_id = $1::uuid FOR UPDATE"
+    )
+    .bind(user_id.to_string())
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let old = StoredAddresses {
+        shielded: row.get("address"),
+        unified: row.get("transparent_address"),
+        transparent: row.get("transparent_receiver"),
+    };
+    let changed = old.shielded != addresses.shielded
+        || old.unified.as_deref() != Some(addresses.unified.as_str())
+        || old.transparent.as_deref() != Some(addresses.transparent.as_str());
+
+    if changed {
+        sqlx::query(
+            "UPDATE wallets SET address = $1, transparent_address = $2, transparent_receiver = $3
+             WHERE user_id = $4::uuid"
+        )
+        .bind(&addresses.shielded)
+        .bind(&addresses.unified)
+        .bind(&addresses.transparent)
+        .bind(user_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        tracing::warn!(
+            "Replaced stale stored address for user {}: {} -> {}",
+            user_id,
+            old.shielded,
+            addresses.shielded
+        );
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(RederiveAddressResponse {
+        changed,
+        old,
+        new: AddressesResponse {
+            unified: addresses.unified,
+            shielded: addresses.shielded,
+            transparent: addresses.transparent,
+        },
+    }))
+}
+
+/// Render the authenticated user's receive address as a QR code
+///
+/// With `amount` (and optionally `memo`) the code encodes a ZIP-321 payment URI rather
+/// than the bare address, so wallets that scan it prefill the payment.
+pub async fn address_qr(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Query(query): Query<AddressQrQuery>,
+) -> Result<Response> {
+    let format = query.format.as_deref().unwrap_or("png").to_ascii_lowercase();
+    if format != "png" && format != "svg" {
+        return Err(AppError::Validation("format must be 'png' or 'svg'".to_string()));
+    }
+
+    let row = sqlx::query("SELECT address, network FROM wallets WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(&db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+    let address: String = row.get("address");
+    let network: String = row.get("network");
+
+    let contents = match query.amount.as_deref() {
+        Some(amount) => {
+            let amount = parse_zec_amount(amount)?;
+            let output = transaction::PaymentOutput {
+                to_address: &address,
+                amount_zat: amount.into_u64(),
+                memo: query.memo.as_deref(),
+            };
+            transaction::payment_request(parse_network(&network), &[output])
+                .map_err(|e| AppError::Validation(e.to_string()))?
+                .to_uri()
+        }
+        None if query.memo.is_some() => {
+            return Err(AppError::Validation("memo requires an amount".to_string()));
+        }
+        None => address,
+    };
+
+    let code = QrCode::new(contents.as_bytes())
+        .map_err(|e| AppError::Internal(format!("Failed to encode QR code: {}", e)))?;
+
+    let (content_type, body) = if format == "svg" {
+        let svg = code
+            .render::<qrcode::render::svg::Color>()
+            .min_dimensions(256, 256)
+            .build();
+        ("image/svg+xml", svg.into_bytes())
+    } else {
+        let image = code.render::<image::Luma<u8>>().min_dimensions(256, 256).build();
+        let mut png = Vec::new();
+        image
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .map_err(|e| AppError::Internal(format!("Failed to render QR code: {}", e)))?;
+        ("image/png", png)
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CACHE_CONTROL, "private, no-store"),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Get the authenticated user's legacy Sapling address
+///
+/// The Sapling receiver of the default unified address, for counterparties that don't
+/// accept unified addresses. Funds sent to it arrive in the same account.
+pub async fn get_sapling_address(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<SaplingAddressResponse>> {
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&config.mnemonic, config.network)
+        .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
+    let address = wallet.get_sapling_address()
+        .map_err(|e| AppError::Internal(format!("Failed to derive Sapling address: {}", e)))?;
+
+    Ok(Json(SaplingAddressResponse { address }))
+}
+
+/// Hand out a fresh shielded address for the authenticated user
+///
+/// Each call uses the next unused diversifier index, so payers can be told apart on-chain
+/// while all payments still land in the same account.
+pub async fn new_diversified_address(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<DiversifiedAddressResponse>> {
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&config.mnemonic, config.network)
+        .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
+
+    // Lock the counter row so concurrent requests never hand out the same index
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO address_counters (user_id) VALUES ($1::uuid) ON CONFLICT (user_id) DO NOTHING"
+    )
+    .bind(user_id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    let next_index: i64 = sqlx::query_scalar(
+        "SELECT next_index FROM address_counters WHERE user_id = $1::uuid FOR UPDATE"
+    )
+    .bind(user_id.to_string())
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // Start past the default address on first use
+    let start = if next_index == 0 {
+        let (_, default_index) = wallet.get_diversified_address(0)
+            .map_err(|e| AppError::Internal(format!("Failed to derive address: {}", e)))?;
+        default_index + 1
+    } else {
+        next_index as u64
+    };
+
+    let (address, diversifier_index) = wallet.get_diversified_address(start)
+        .map_err(|e| AppError::Internal(format!("Failed to derive address: {}", e)))?;
+
+    sqlx::query(
+        "UPDATE address_counters SET next_index = $1, updated_at = NOW() WHERE user_id = $2::uuid"
+    )
+    .bind((diversifier_index + 1) as i64)
+    .bind(user_id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    tracing::info!("Issued diversified address {} for user {}", diversifier_index, user_id);
+
+    Ok(Json(DiversifiedAddressResponse {
+        address,
+        diversifier_index,
+    }))
+}
+
 /// Check if user has a wallet
 pub async fn has_wallet(
     State(state): State<AppState>,
@@ -121,3 +504,330 @@ pub async fn has_wallet(
         "has_wallet": wallet_exists.get::<Option<bool>, _>("exists").unwrap_or(false)
     })))
 }
+
+/// Export the authenticated user's unified full viewing key
+///
+/// The UFVK lets third-party tools see incoming and outgoing transactions but can't spend.
+/// Email users must confirm their password; the seed and spending key never leave the server.
+pub async fn export_viewing_key(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<ExportViewingKeyRequest>,
+) -> Result<Json<ExportViewingKeyResponse>> {
+    confirm_password(&db, user_id, request.password.as_deref()).await?;
+
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let usk = derive_spending_key(&config.seed, config.network)?;
+    let ufvk = usk.to_unified_full_viewing_key().encode(&config.network);
+
+    tracing::info!("Exported viewing key for user {}", user_id);
+
+    Ok(Json(ExportViewingKeyResponse { ufvk }))
+}
+
+/// Export the authenticated user's seed as a passphrase-encrypted backup
+///
+/// The blob holds the mnemonic and birthday height, sealed with a key derived from the
+/// user's passphrase, so it can be stored anywhere. The raw mnemonic is never returned.
+pub async fn export_backup(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<ExportBackupRequest>,
+) -> Result<Json<EncryptedBackup>> {
+    confirm_password(&db, user_id, request.password.as_deref()).await?;
+
+    if request.passphrase.chars().count() < backup::MIN_PASSPHRASE_LEN {
+        return Err(AppError::Validation(format!(
+            "Backup passphrase must be at least {} characters",
+            backup::MIN_PASSPHRASE_LEN
+        )));
+    }
+
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let contents = BackupContents {
+        mnemonic: config.mnemonic.to_string(),
+        birthday_height: config.birthday_height,
+        network: network_name(config.network).to_string(),
+    };
+
+    // Argon2 takes a noticeable amount of CPU and memory; keep it off the async workers
+    let encrypted = tokio::task::spawn_blocking(move || backup::encrypt_backup(&contents, &request.passphrase))
+        .await
+        .map_err(|e| AppError::Internal(format!("Backup task failed: {}", e)))?
+        .map_err(|e| AppError::Internal(format!("Failed to encrypt backup: {}", e)))?;
+
+    tracing::info!("Exported encrypted wallet backup for user {}", user_id);
+
+    Ok(Json(encrypted))
+}
+
+/// Network of a backup, which must be the network this server runs on
+///
+/// A mainnet seed restored on a testnet server (or the reverse) would derive addresses
+/// and scan a chain the funds were never on.
+fn backup_network(name: &str, expected: Network) -> Result<Network> {
+    let network = match name.to_lowercase().as_str() {
+        "mainnet" | "testnet" => parse_network(name),
+        _ => {
+            return Err(AppError::Validation(format!(
+                "Backup has an unknown network '{}'",
+                name
+            )))
+        }
+    };
+
+    if network != expected {
+        return Err(AppError::Validation(format!(
+            "Backup is for {} but this server runs on {}",
+            network_name(network),
+            network_name(expected)
+        )));
+    }
+
+    Ok(network)
+}
+
+/// Restore the authenticated user's wallet from an encrypted backup
+///
+/// A backup of the current seed is a no-op. A different seed is only accepted with
+/// `replace: true`, since it discards the existing wallet: cached addresses, extra
+/// accounts, synced notes and the on-disk wallet database are cleared and the restored
+/// wallet rescans from the backup's birthday height.
+pub async fn import_backup(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<ImportBackupRequest>,
+) -> Result<Json<ImportBackupResponse>> {
+    confirm_password(&db, user_id, request.password.as_deref()).await?;
+
+    let ImportBackupRequest { passphrase, backup: encrypted, replace, .. } = request;
+    let contents = tokio::task::spawn_blocking(move || backup::decrypt_backup(&encrypted, &passphrase))
+        .await
+        .map_err(|e| AppError::Internal(format!("Backup task failed: {}", e)))?
+        .map_err(|e| AppError::Validation(format!("Failed to decrypt backup: {}", e)))?;
+
+    let mnemonic = Mnemonic::parse(&contents.mnemonic)
+        .map_err(|e| AppError::Validation(format!("Backup contains an invalid mnemonic: {}", e)))?;
+    let network = backup_network(&contents.network, get_network())?;
+    let wallet = crate::zcash::wallet::Wallet::from_mnemonic(&mnemonic, network)
+        .map_err(|e| AppError::Internal(format!("Failed to create wallet: {}", e)))?;
+    let address = wallet.get_address()
+        .map_err(|e| AppError::Internal(format!("Failed to get address: {}", e)))?;
+
+    let current = load_wallet_config(&db, user_id, false).await?;
+    if current.mnemonic.to_string() == mnemonic.to_string() && current.network == network {
+        return Ok(Json(ImportBackupResponse {
+            address,
+            birthday_height: current.birthday_height as i64,
+            network: network_name(network).to_string(),
+            restored: false,
+        }));
+    }
+
+    if !replace {
+        return Err(AppError::Conflict(
+            "Backup belongs to a different wallet; set replace to overwrite the current wallet".to_string(),
+        ));
+    }
+
+    // Keep syncs and sends out while the wallet is swapped
+    let _wallet_guard = balance::lock_user_wallet(user_id).await;
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        "UPDATE wallets
+         SET encrypted_mnemonic = $1, address = $2, birthday_height = $3, network = $4,
+             transparent_address = NULL, transparent_receiver = NULL,
+             last_synced_at = NULL, last_synced_height = NULL
+         WHERE user_id = $5::uuid"
+    )
+    .bind(mnemonic.to_string())
+    .bind(&address)
+    .bind(contents.birthday_height as i64)
+    .bind(network_name(network))
+    .bind(user_id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    // Everything derived from the old seed
+    for table in [
+        "wallet_accounts",
+        "address_counters",
+        "sent_notes",
+        "received_notes",
+        "transactions",
+        "transaction_labels",
+        "sync_jobs",
+    ] {
+        sqlx::query(&format!("DELETE FROM {} WHERE user_id = $1::uuid", table))
+            .bind(user_id.to_string())
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    remove_wallet_db(user_id).await;
+    balance::invalidate_cached_balance(user_id);
+
+    tracing::info!("Restored wallet from backup for user {}", user_id);
+
+    Ok(Json(ImportBackupResponse {
+        address,
+        birthday_height: contents.birthday_height as i64,
+        network: network_name(network).to_string(),
+        restored: true,
+    }))
+}
+
+/// Add another HD account under the authenticated user's seed
+///
+/// Accounts get the next unused ZIP 32 index (1, 2, ...) and their own keys and address.
+/// A new account can't have received anything yet, so its birthday is the current chain
+/// tip and it only needs scanning from there; the wallet scans all accounts together.
+pub async fn create_account(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+    Json(request): Json<CreateAccountRequest>,
+) -> Result<Json<AccountResponse>> {
+    let name = request.name.trim().to_string();
+    if name.is_empty() || name.len() > 64 {
+        return Err(AppError::Validation("Account name must be 1-64 characters".to_string()));
+    }
+
+    let config = load_wallet_config(&db, user_id, false).await?;
+    let client = connect_lightwalletd(config.network).await?;
+
+    let chain_tip = client
+        .get_latest_block_height()
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to get block height: {}", e)))?;
+    let birthday_height = chain_tip as u32;
+
+    // Keep scans out while the account is recorded and imported
+    let _wallet_guard = balance::lock_user_wallet(user_id).await;
+
+    // Lock the wallet row so concurrent requests never pick the same index. Only the index
+    // is reserved here; lightwalletd is never called with the row locked.
+    let mut tx = db.begin().await?;
+
+    sqlx::query("SELECT id FROM wallets WHERE user_id = $1::uuid FOR UPDATE")
+        .bind(user_id.to_string())
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let max_index: Option<i64> = sqlx::query_scalar(
+        "SELECT MAX(account_index) FROM wallet_accounts WHERE user_id = $1::uuid"
+    )
+    .bind(user_id.to_string())
+    .fetch_one(&mut *tx)
+    .await?;
+    let account_index = max_index.map_or(1, |index| index as u32 + 1);
+
+    let wallet = crate::zcash::wallet::Wallet::from_mnemonic_account(&config.mnemonic, config.network, account_index)
+        .map_err(|e| AppError::Internal(format!("Failed to derive account: {}", e)))?;
+    let address = wallet.get_address()
+        .map_err(|e| AppError::Internal(format!("Failed to get address: {}", e)))?;
+
+    sqlx::query(
+        "INSERT INTO wallet_accounts (user_id, account_index, name, address, birthday_height)
+         VALUES ($1::uuid, $2, $3, $4, $5)"
+    )
+    .bind(user_id.to_string())
+    .bind(account_index as i64)
+    .bind(&name)
+    .bind(&address)
+    .bind(birthday_height as i64)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    // Creates the primary account first if needed, then imports every recorded account,
+    // including this one. The account is already recorded, so if this fails the next sync
+    // imports it instead.
+    if let Err(e) = ensure_account(&db, user_id, &config, &client).await {
+        tracing::warn!(
+            "Account {} for user {} is recorded but not yet in the wallet database: {:?}",
+            account_index,
+            user_id,
+            e
+        );
+    }
+
+    tracing::info!("Created account {} for user {}", account_index, user_id);
+
+    Ok(Json(AccountResponse {
+        account_index,
+        name,
+        address,
+        birthday_height: birthday_height as i64,
+    }))
+}
+
+/// List the authenticated user's HD accounts, starting with the primary account
+pub async fn list_accounts(
+    Extension(user_id): Extension<Uuid>,
+    Extension(db): Extension<PgPool>,
+) -> Result<Json<Vec<AccountResponse>>> {
+    let primary = sqlx::query("SELECT address, birthday_height FROM wallets WHERE user_id = $1::uuid")
+        .bind(user_id.to_string())
+        .fetch_optional(&db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Wallet not found".to_string()))?;
+
+    let mut accounts = vec![AccountResponse {
+        account_index: 0,
+        name: "Primary".to_string(),
+        address: primary.get("address"),
+        birthday_height: primary.get("birthday_height"),
+    }];
+
+    let rows = sqlx::query(
+        "SELECT account_index, name, address, birthday_height
+         FROM wallet_accounts
+         WHERE user_id = $1::uuid
+         ORDER BY account_index"
+    )
+    .bind(user_id.to_string())
+    .fetch_all(&db)
+    .await?;
+
+    accounts.extend(rows.iter().map(|row| AccountResponse {
+        account_index: row.get::<i64, _>("account_index") as u32,
+        name: row.get("name"),
+        address: row.get("address"),
+        birthday_height: row.get("birthday_height"),
+    }));
+
+    Ok(Json(accounts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_network_matches_server() {
+        assert_eq!(backup_network("mainnet", Network::MainNetwork).unwrap(), Network::MainNetwork);
+        assert_eq!(backup_network("Testnet", Network::TestNetwork).unwrap(), Network::TestNetwork);
+    }
+
+    #[test]
+    fn test_backup_network_rejects_other_network() {
+        assert!(matches!(
+            backup_network("testnet", Network::MainNetwork),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            backup_network("mainnet", Network::TestNetwork),
+            Err(AppError::Validation(_))
+        ));
+        // Unknown names aren't silently read as mainnet
+        assert!(matches!(
+            backup_network("regtest", Network::MainNetwork),
+            Err(AppError::Validation(_))
+        ));
+    }
+}
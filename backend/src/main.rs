@@ -6,17 +6,24 @@ mod zcash;
 mod solana;
 
 use axum::{
+    error_handling::HandleErrorLayer,
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
     middleware as axum_middleware,
-    routing::{get, post},
-    Extension, Json, Router,
+    routing::{delete, get, post},
+    BoxError, Extension, Json, Router,
 };
-use handlers::{auth, balance, send, solana_wallet, transactions, user, wallet, AppState};
-use middleware::{auth::AuthState, auth_middleware};
+use handlers::{auth, balance, scan, send, solana_wallet, transactions, user, wallet, AppState};
+use middleware::{auth::AuthState, auth_middleware, request_id::request_id_header, request_id_middleware};
 use serde::{Deserialize, Serialize};
-use sqlx::postgres::PgPoolOptions;
-use std::{env, net::SocketAddr, sync::Arc};
-use tower_http::cors::{CorsLayer, Any};
-use utils::JwtManager;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::{env, net::SocketAddr, sync::Arc, time::Duration};
+use tower::{timeout::TimeoutLayer, ServiceBuilder};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use utils::{
+    env::{env_or, env_or_positive},
+    JwtManager,
+};
 
 #[derive(Serialize, Deserialize)]
 struct HealthResponse {
@@ -29,17 +36,190 @@ async fn health_check() -> Json<HealthResponse> {
     })
 }
 
+/// Defaults for request limits, overridable with `MAX_REQUEST_BODY_BYTES`,
+/// `REQUEST_TIMEOUT_SECS` and `SCAN_REQUEST_TIMEOUT_SECS`
+const DEFAULT_MAX_REQUEST_BODY_BYTES: usize = 256 * 1024;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_SCAN_REQUEST_TIMEOUT_SECS: u64 = 600;
+
+/// Defaults for the Postgres pool, overridable with `DB_MAX_CONNECTIONS`,
+/// `DB_MIN_CONNECTIONS` and `DB_ACQUIRE_TIMEOUT_SECS`
+const DEFAULT_DB_MAX_CONNECTIONS: u32 = 20;
+const DEFAULT_DB_MIN_CONNECTIONS: u32 = 2;
+const DEFAULT_DB_ACQUIRE_TIMEOUT_SECS: u64 = 30;
+
+/// How long a pooled wallet database may sit unused before it's closed, unless overridden
+/// by `WALLET_DB_IDLE_SECS`
+const DEFAULT_WALLET_DB_IDLE_SECS: u64 = 600;
+
+/// Turn a request that hit its `TimeoutLayer` deadline into a 408
+async fn handle_timeout_error(err: BoxError) -> (StatusCode, Json<serde_json::Value>) {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        (
+            StatusCode::REQUEST_TIMEOUT,
+            Json(serde_json::json!({ "error": "Request timed out" })),
+        )
+    } else {
+        tracing::error!("Unhandled middleware error: {}", err);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "An error occurred" })),
+        )
+    }
+}
+
+/// How long each readiness dependency check may take before it counts as failed
+const READINESS_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Serialize, Deserialize)]
+struct DependencyStatus {
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DependencyStatus {
+    fn from_result<T>(result: Result<Result<T, String>, tokio::time::error::Elapsed>) -> Self {
+        let error = match result {
+            Ok(Ok(_)) => None,
+            Ok(Err(e)) => Some(e),
+            Err(_) => Some("timed out".to_string()),
+        };
+        Self {
+            status: if error.is_none() { "ok" } else { "unavailable" }.to_string(),
+            error,
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReadinessResponse {
+    status: String,
+    database: DependencyStatus,
+    lightwalletd: DependencyStatus,
+}
+
+/// Readiness probe: checks Postgres and lightwalletd, returning 503 if either is down
+///
+/// `/health` stays a cheap liveness probe that never touches dependencies.
+async fn readiness_check(Extension(db): Extension<PgPool>) -> (StatusCode, Json<ReadinessResponse>) {
+    let (database, lightwalletd) = tokio::join!(
+        tokio::time::timeout(READINESS_TIMEOUT, async {
+            sqlx::query("SELECT 1").execute(&db).await.map_err(|e| e.to_string())
+        }),
+        tokio::time::timeout(READINESS_TIMEOUT, async {
+            let client = handlers::common::connect_lightwalletd(handlers::common::get_network())
+                .await
+                .map_err(|e| e.to_string())?;
+            client.get_latest_block_height().await.map_err(|e| e.to_string())
+        }),
+    );
+
+    let database = DependencyStatus::from_result(database);
+    let lightwalletd = DependencyStatus::from_result(lightwalletd);
+    let ready = database.is_ok() && lightwalletd.is_ok();
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (
+        status,
+        Json(ReadinessResponse {
+            status: if ready { "ok" } else { "unavailable" }.to_string(),
+            database,
+            lightwalletd,
+        }),
+    )
+}
+
+/// Build the CORS layer from `CORS_ALLOWED_ORIGINS` (comma-separated)
+///
+/// With no allowlist, any origin is accepted only when `DEV_MODE=true`; otherwise
+/// cross-origin requests are refused so other sites cannot drive authenticated calls.
+fn cors_layer() -> CorsLayer {
+    let origins: Vec<HeaderValue> = env::var("CORS_ALLOWED_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            origin
+                .parse()
+                .unwrap_or_else(|_| panic!("Invalid origin in CORS_ALLOWED_ORIGINS: {}", origin))
+        })
+        .collect();
+
+    if origins.is_empty() {
+        let dev_mode = env::var("DEV_MODE")
+            .map(|v| v.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        if dev_mode {
+            tracing::warn!("CORS_ALLOWED_ORIGINS not set; allowing any origin because DEV_MODE is on");
+            return CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any)
+                .expose_headers([request_id_header()]);
+        }
+        tracing::warn!("CORS_ALLOWED_ORIGINS not set; cross-origin requests will be refused");
+        return CorsLayer::new();
+    }
+
+    tracing::info!("CORS allowed origins: {:?}", origins);
+    allowlist_cors_layer(origins)
+}
+
+/// CORS layer for an explicit origin allowlist, allowing every method the API routes use
+fn allowlist_cors_layer(origins: Vec<HeaderValue>) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            HeaderName::from_static("idempotency-key"),
+            request_id_header(),
+        ])
+        .expose_headers([request_id_header()])
+        .allow_credentials(true)
+}
+
 #[tokio::main]
 async fn main() {
     // Load environment variables
     dotenv::dotenv().ok();
 
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    // Initialize tracing: human-readable by default, one JSON object per line with
+    // LOG_FORMAT=json. JSON logs also record span close events, whose busy/idle timings
+    // give per-user scan durations.
+    let json_logs = env::var("LOG_FORMAT")
+        .map(|v| v.eq_ignore_ascii_case("json"))
+        .unwrap_or(false);
+    if json_logs {
+        tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .with_target(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
 
     // Get configuration from environment
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let jwt_secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
     let jwt_access_token_expiry: i64 = env::var("JWT_ACCESS_TOKEN_EXPIRY")
         .unwrap_or_else(|_| "900".to_string())
         .parse()
@@ -55,20 +235,84 @@ async fn main() {
         .expect("PORT must be a valid number");
 
     // Create database connection pool
+    let max_connections = env_or("DB_MAX_CONNECTIONS", DEFAULT_DB_MAX_CONNECTIONS).max(1);
+    let min_connections = env_or("DB_MIN_CONNECTIONS", DEFAULT_DB_MIN_CONNECTIONS).min(max_connections);
+    let acquire_timeout = Duration::from_secs(env_or("DB_ACQUIRE_TIMEOUT_SECS", DEFAULT_DB_ACQUIRE_TIMEOUT_SECS));
+    tracing::info!(
+        "Database pool: {}-{} connections, {:?} acquire timeout",
+        min_connections,
+        max_connections,
+        acquire_timeout
+    );
+
     let db = PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
+        .min_connections(min_connections)
+        .acquire_timeout(acquire_timeout)
         .connect(&database_url)
         .await
         .expect("Failed to connect to database");
 
     tracing::info!("Connected to database");
 
-    // Create JWT manager
-    let jwt_manager = Arc::new(JwtManager::new(
-        jwt_secret,
-        jwt_access_token_expiry,
-        jwt_refresh_token_expiry,
-    ));
+    // Per-user wallet databases live here; fail fast if it isn't usable
+    let wallet_data_dir = handlers::common::ensure_wallet_data_dir()
+        .expect("WALLET_DATA_DIR must be a writable directory");
+    tracing::info!("Wallet data directory: {:?}", wallet_data_dir);
+
+    // Close pooled wallet databases that have gone idle
+    zcash::pool::spawn_reaper(
+        Duration::from_secs(60),
+        Duration::from_secs(env_or_positive("WALLET_DB_IDLE_SECS", DEFAULT_WALLET_DB_IDLE_SECS)),
+    );
+
+    // Download and verify the Sapling parameters before serving any sends
+    tokio::task::spawn_blocking(zcash::params::ensure_params)
+        .await
+        .expect("Proving parameter check panicked")
+        .expect("Failed to download or verify Zcash proving parameters");
+
+    // Load the Sapling proving parameters now so the first send isn't slow
+    tokio::task::spawn_blocking(|| {
+        if let Err(e) = zcash::prover::get_prover() {
+            tracing::error!("Failed to warm up prover: {:?}", e);
+        }
+    });
+
+    // Record NEAR Intents settlements even if the client stops polling
+    solana::poller::spawn_bridge_poller(db.clone());
+
+    // Purge expired sessions and auth tokens so the tables don't grow forever
+    auth::spawn_retention_cleanup(db.clone());
+
+    // Mirror scanned wallet data into PostgreSQL from the durable sync_jobs queue
+    balance::spawn_sync_job_worker(db.clone());
+
+    // Opt-in: keep recently active wallets near the chain tip between visits
+    let background_sync = env::var("ENABLE_BACKGROUND_SYNC")
+        .map(|v| v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    if background_sync {
+        balance::spawn_background_sync(db.clone());
+    }
+
+    // Create JWT manager: JWT_SECRETS (kid:secret,...) with JWT_ACTIVE_KID allows key
+    // rotation; otherwise a single JWT_SECRET is used
+    let jwt_manager = match env::var("JWT_SECRETS").ok().filter(|v| !v.trim().is_empty()) {
+        Some(secrets) => {
+            let keys = utils::jwt::parse_jwt_secrets(&secrets).expect("Invalid JWT_SECRETS");
+            let active_kid = env::var("JWT_ACTIVE_KID")
+                .expect("JWT_ACTIVE_KID must be set when JWT_SECRETS is set");
+            JwtManager::with_keys(keys, &active_kid, jwt_access_token_expiry, jwt_refresh_token_expiry)
+                .expect("Invalid JWT key configuration")
+        }
+        None => JwtManager::new(
+            env::var("JWT_SECRET").expect("JWT_SECRET or JWT_SECRETS must be set"),
+            jwt_access_token_expiry,
+            jwt_refresh_token_expiry,
+        ),
+    };
+    let jwt_manager = Arc::new(jwt_manager);
 
     // Create application state
     let app_state = AppState {
@@ -95,40 +339,93 @@ async fn main() {
         db: db.clone(),
     };
 
+    // Scanning and proving can legitimately take minutes, so those routes get a longer
+    // timeout than everything else
+    let request_timeout = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(handle_timeout_error))
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            env_or("REQUEST_TIMEOUT_SECS", DEFAULT_REQUEST_TIMEOUT_SECS),
+        )));
+    let scan_request_timeout = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(handle_timeout_error))
+        .layer(TimeoutLayer::new(Duration::from_secs(
+            env_or("SCAN_REQUEST_TIMEOUT_SECS", DEFAULT_SCAN_REQUEST_TIMEOUT_SECS),
+        )));
+
     // Build public routes (no auth required)
     let public_routes = Router::new()
         .route("/auth/signup", post(auth::signup))
         .route("/auth/login", post(auth::login))
         .route("/auth/refresh", post(auth::refresh))
         .route("/auth/logout", post(auth::logout))
-        .route("/auth/google", get(auth::google_auth_init))
-        .route("/auth/google/callback", get(auth::google_auth_callback))
+        .route("/auth/:provider", get(auth::oauth_init))
+        .route("/auth/:provider/callback", get(auth::oauth_callback))
+        .route("/auth/verify", get(auth::verify_email))
         .route("/wallet/create", post(wallet::create_wallet))
         .route("/wallet/address", post(wallet::get_address))
+        .layer(request_timeout.clone())
         .with_state(app_state.clone());
 
-    // Build balance routes (separate state)
+    // Build balance routes (separate state, user taken from the access token)
+    // (`Router::layer` only wraps routes added before it, so the short timeout stays off the
+    // scanning routes added after it)
     let balance_routes = Router::new()
-        .route("/wallet/balance", post(balance::get_balance))
-        .with_state(balance_state);
+        .route("/wallet/balance/quick", post(balance::quick_balance))
+        .route("/wallet/sync-status", post(balance::sync_status))
+        .route("/wallet/notes", post(balance::list_notes))
+        .route("/wallet/scan", post(scan::start_scan))
+        .route("/wallet/scan/:job_id", get(scan::get_scan_job).delete(scan::cancel_scan_job))
+        .layer(request_timeout.clone())
+        .route("/wallet/balance", get(balance::get_balance).post(balance::get_balance))
+        .layer(scan_request_timeout.clone())
+        .with_state(balance_state)
+        .layer(axum_middleware::from_fn_with_state(
+            auth_state.clone(),
+            auth_middleware,
+        ));
 
-    // Build send routes (separate state)
+    // Build send routes (separate state, user taken from the access token)
+    // (handlers that move funds run in their own task, so a timeout only stops the client
+    // waiting; the send itself still completes and is recorded)
     let send_routes = Router::new()
-        .route("/wallet/send", post(send::send_transaction))
         .route("/wallet/estimate-fee", post(send::estimate_fee))
-        .with_state(send_state);
+        .route("/wallet/estimate", post(send::estimate))
+        .route("/wallet/parse-uri", post(send::parse_payment_uri))
+        .route("/wallet/send/cancel", post(send::cancel_send))
+        .layer(request_timeout.clone())
+        .route("/wallet/send", post(send::send_transaction))
+        .route("/wallet/send/prepare", post(send::prepare_send))
+        .route("/wallet/send/confirm", post(send::confirm_send))
+        .route("/wallet/send-batch", post(send::send_batch))
+        .route("/wallet/shield", post(send::shield_funds))
+        .layer(scan_request_timeout.clone())
+        .with_state(send_state)
+        .layer(axum_middleware::from_fn_with_state(
+            auth_state.clone(),
+            auth_middleware,
+        ));
 
-    // Build transactions routes (separate state)
+    // Build transactions routes (separate state, user taken from the access token)
     let transactions_routes = Router::new()
         .route("/wallet/transactions", post(transactions::get_transactions))
-        .with_state(transactions_state);
+        .route("/wallet/transactions/detail", post(transactions::get_transaction))
+        .route("/wallet/transactions/label", post(transactions::set_label))
+        .layer(request_timeout.clone())
+        .with_state(transactions_state)
+        .layer(axum_middleware::from_fn_with_state(
+            auth_state.clone(),
+            auth_middleware,
+        ));
 
     // Build Solana routes (protected, require auth)
     let solana_routes = Router::new()
         .route("/solana/balance", post(solana_wallet::get_balance))
         .route("/solana/bridge/quote", post(solana_wallet::get_bridge_quote))
+        .route("/solana/bridge/estimate", post(solana_wallet::estimate_bridge))
         .route("/solana/bridge/execute", post(solana_wallet::execute_bridge))
         .route("/solana/bridge/status", post(solana_wallet::get_bridge_status))
+        .route("/solana/bridge/history", post(solana_wallet::list_bridges))
+        .layer(request_timeout.clone())
         .layer(axum_middleware::from_fn_with_state(
             auth_state.clone(),
             auth_middleware,
@@ -137,7 +434,28 @@ async fn main() {
 
     // Build protected routes (auth required)
     let protected_routes = Router::new()
-        .route("/users/me", get(user::get_me))
+        .route(
+            "/users/me",
+            get(user::get_me).patch(user::update_me).delete(user::delete_account),
+        )
+        .route("/auth/send-verification", post(auth::send_verification_email))
+        .route("/auth/logout-all", post(auth::logout_all))
+        .route("/users/me/sessions", get(auth::list_sessions))
+        .route("/users/me/sessions/:id", delete(auth::revoke_session))
+        .route("/users/me/webhook", post(user::register_webhook))
+        .route("/wallet/addresses", post(wallet::get_addresses))
+        .route("/wallet/address/qr", get(wallet::address_qr))
+        .route("/wallet/address/new", post(wallet::new_diversified_address))
+        .route("/wallet/address/sapling", post(wallet::get_sapling_address))
+        .route("/wallet/address/rederive", post(wallet::rederive_addresses))
+        .route("/wallet/export-ufvk", post(wallet::export_viewing_key))
+        .route("/wallet/backup", post(wallet::export_backup))
+        .route("/wallet/backup/import", post(wallet::import_backup))
+        .route("/wallet/accounts", get(wallet::list_accounts).post(wallet::create_account));
+    #[cfg(feature = "testnet-faucet")]
+    let protected_routes = handlers::faucet::mount(protected_routes);
+    let protected_routes = protected_routes
+        .layer(request_timeout)
         .layer(axum_middleware::from_fn_with_state(
             auth_state.clone(),
             auth_middleware,
@@ -156,18 +474,53 @@ async fn main() {
     // Build main app
     let app = Router::new()
         .route("/health", get(health_check))
+        .route("/health/ready", get(readiness_check).layer(Extension(db.clone())))
         .nest("/api", api_routes)
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any),
-        );
+        .layer(RequestBodyLimitLayer::new(env_or(
+            "MAX_REQUEST_BODY_BYTES",
+            DEFAULT_MAX_REQUEST_BODY_BYTES,
+        )))
+        .layer(axum_middleware::from_fn(request_id_middleware))
+        .layer(cors_layer());
 
     // Start server
     let addr = SocketAddr::from(([127, 0, 0, 1], port));
     tracing::info!("Backend server running on http://{}:{}", host, port);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::patch};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_cors_preflight_allows_patch() {
+        let origin = "https://wallet.example.com";
+        let app = Router::new()
+            .route("/api/users/me", patch(|| async { "ok" }))
+            .layer(allowlist_cors_layer(vec![HeaderValue::from_static(origin)]));
+
+        let preflight = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/users/me")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PATCH")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(preflight).await.unwrap();
+
+        assert!(response.status().is_success());
+        assert_eq!(response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN], origin);
+        let allowed_methods = response.headers()[header::ACCESS_CONTROL_ALLOW_METHODS]
+            .to_str()
+            .unwrap();
+        assert!(allowed_methods.contains("PATCH"), "{}", allowed_methods);
+    }
 }
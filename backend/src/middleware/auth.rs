@@ -8,6 +8,10 @@ use axum::{
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// Session id from the access token, added to request extensions alongside the user id
+#[derive(Clone, Copy, Debug)]
+pub struct SessionId(pub Uuid);
+
 #[derive(Clone)]
 pub struct AuthState {
     pub jwt_manager: Arc<JwtManager>,
@@ -37,6 +41,9 @@ pub async fn auth_middleware(
 
     // Add user_id to request extensions
     request.extensions_mut().insert(user_id);
+    if let Some(session_id) = claims.sid.as_deref().and_then(|sid| Uuid::parse_str(sid).ok()) {
+        request.extensions_mut().insert(SessionId(session_id));
+    }
 
     Ok(next.run(request).await)
 }
@@ -32,12 +32,54 @@ pub enum AppError {
     #[error("Internal server error: {0}")]
     Internal(String),
 
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+
+    #[error("Account temporarily locked, retry in {retry_after_secs} seconds")]
+    AccountLocked { retry_after_secs: i64 },
+
+    #[error("Insufficient funds: {required_zat} zatoshis required (including fee), {available_zat} available")]
+    InsufficientFunds { available_zat: u64, required_zat: u64 },
+
+    /// A request body the extractor refused for a reason other than its contents
+    /// (wrong content type, too large), keeping the extractor's status
+    #[error("Request rejected: {message}")]
+    RequestRejected { status: StatusCode, message: String },
+
     #[error("Solana error: {0}")]
     Anyhow(#[from] anyhow::Error),
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        // Include the amounts so the UI can show how much is missing
+        if let AppError::InsufficientFunds { available_zat, required_zat } = self {
+            let body = Json(json!({
+                "error": self.to_string(),
+                "available_zat": available_zat,
+                "required_zat": required_zat,
+            }));
+            return (StatusCode::BAD_REQUEST, body).into_response();
+        }
+
+        if let AppError::InvalidAddress(ref msg) = self {
+            let body = Json(json!({
+                "error": msg,
+                "code": "INVALID_ADDRESS",
+            }));
+            return (StatusCode::BAD_REQUEST, body).into_response();
+        }
+
+        // Tell the client how long to wait before trying again
+        if let AppError::AccountLocked { retry_after_secs } = self {
+            let body = Json(json!({
+                "error": "Account temporarily locked after too many failed logins",
+                "code": "ACCOUNT_LOCKED",
+                "retry_after_secs": retry_after_secs,
+            }));
+            return (StatusCode::LOCKED, body).into_response();
+        }
+
         let (status, message) = match self {
             AppError::Database(ref e) => {
                 tracing::error!("Database error: {:?}", e);
@@ -50,6 +92,10 @@ impl IntoResponse for AppError {
             AppError::NotFound(ref msg) => (StatusCode::NOT_FOUND, msg.as_str()),
             AppError::Conflict(ref msg) => (StatusCode::CONFLICT, msg.as_str()),
             AppError::Internal(ref msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.as_str()),
+            AppError::InvalidAddress(ref msg) => (StatusCode::BAD_REQUEST, msg.as_str()),
+            AppError::InsufficientFunds { .. } => (StatusCode::BAD_REQUEST, "Insufficient funds"),
+            AppError::AccountLocked { .. } => (StatusCode::LOCKED, "Account temporarily locked"),
+            AppError::RequestRejected { status, ref message } => (status, message.as_str()),
             AppError::Anyhow(ref e) => {
                 tracing::error!("Anyhow error: {:?}", e);
                 (StatusCode::INTERNAL_SERVER_ERROR, "An error occurred")
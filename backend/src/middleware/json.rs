@@ -0,0 +1,81 @@
+use crate::middleware::AppError;
+use axum::{
+    extract::{rejection::JsonRejection, FromRequest},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+/// `axum::Json` whose rejections use our `{"error": ...}` envelope
+///
+/// Invalid JSON and bodies of the wrong shape become `AppError::Validation`, where serde's
+/// message names the offending field. Other rejections (a missing content type, an
+/// oversized body) keep their own status.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::JsonSyntaxError(_) | JsonRejection::JsonDataError(_) => {
+                AppError::Validation(rejection.body_text())
+            }
+            _ => AppError::RequestRejected {
+                status: rejection.status(),
+                message: rejection.body_text(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::{header, Request, StatusCode}, routing::post, Router};
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize)]
+    struct Payload {
+        amount: u64,
+    }
+
+    async fn post_body(content_type: Option<&str>, body: &'static str) -> (StatusCode, serde_json::Value) {
+        let app = Router::new().route("/", post(|Json(payload): Json<Payload>| async move { payload.amount.to_string() }));
+        let mut request = Request::post("/");
+        if let Some(content_type) = content_type {
+            request = request.header(header::CONTENT_TYPE, content_type);
+        }
+        let response = app.oneshot(request.body(Body::from(body)).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_wrong_field_type_names_the_field() {
+        let (status, body) = post_body(Some("application/json"), r#"{"amount": "ten"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("amount"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_is_a_validation_error() {
+        let (status, body) = post_body(Some("application/json"), r#"{"amount": "#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_missing_content_type_keeps_its_status() {
+        let (status, body) = post_body(None, r#"{"amount": 10}"#).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(body["error"].is_string());
+    }
+}
@@ -1,5 +1,9 @@
 pub mod auth;
 pub mod error;
+pub mod json;
+pub mod request_id;
 
 pub use auth::*;
 pub use error::*;
+pub use json::Json;
+pub use request_id::request_id_middleware;
@@ -0,0 +1,114 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use once_cell::sync::Lazy;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header carrying the request id, unless overridden by `REQUEST_ID_HEADER`
+const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied id accepted; anything longer is replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+static REQUEST_ID_HEADER: Lazy<HeaderName> = Lazy::new(|| {
+    std::env::var("REQUEST_ID_HEADER")
+        .ok()
+        .and_then(|name| HeaderName::try_from(name.trim()).ok())
+        .unwrap_or_else(|| HeaderName::from_static(DEFAULT_REQUEST_ID_HEADER))
+});
+
+/// Name of the request id header, for CORS to expose
+pub fn request_id_header() -> HeaderName {
+    REQUEST_ID_HEADER.clone()
+}
+
+/// Tag each request with a correlation id
+///
+/// Uses the client's id when it sent a reasonable one, otherwise generates a UUID. Every
+/// log line written while handling the request, including in scans it starts, carries it
+/// in the `request` span, and the response echoes it back.
+pub async fn request_id_middleware(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&*REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+
+    let mut response = next.run(request).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn response_request_id(client_id: Option<&str>) -> String {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn(request_id_middleware));
+        let mut request = Request::get("/");
+        if let Some(client_id) = client_id {
+            request = request.header(DEFAULT_REQUEST_ID_HEADER, client_id);
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        response
+            .headers()
+            .get(DEFAULT_REQUEST_ID_HEADER)
+            .expect("response carries the request id")
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn is_generated(request_id: &str) -> bool {
+        Uuid::parse_str(request_id).is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_echoes_client_request_id() {
+        assert_eq!(response_request_id(Some("client-abc.123")).await, "client-abc.123");
+        assert_eq!(response_request_id(Some("  padded  ")).await, "padded");
+    }
+
+    #[tokio::test]
+    async fn test_generates_request_id_when_missing() {
+        assert!(is_generated(&response_request_id(None).await));
+        assert!(is_generated(&response_request_id(Some("")).await));
+    }
+
+    #[tokio::test]
+    async fn test_replaces_unreasonable_request_ids() {
+        let too_long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        assert!(is_generated(&response_request_id(Some(&too_long)).await));
+        assert!(is_generated(&response_request_id(Some("has spaces")).await));
+
+        let longest = "a".repeat(MAX_REQUEST_ID_LEN);
+        assert_eq!(response_request_id(Some(&longest)).await, longest);
+    }
+
+    #[tokio::test]
+    async fn test_generated_request_ids_are_unique() {
+        assert_ne!(response_request_id(None).await, response_request_id(None).await);
+    }
+}
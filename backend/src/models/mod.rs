@@ -1,3 +1,4 @@
+pub mod row;
 pub mod session;
 pub mod user;
 pub mod wallet;
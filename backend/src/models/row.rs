@@ -0,0 +1,102 @@
+use crate::models::{session::Session, user::{AuthMethod, User}};
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use uuid::Uuid;
+
+// The sqlx uuid and chrono features are disabled, so UUID and timestamp columns are
+// selected as text and parsed here. Select with these column lists so every query
+// returns what `user_from_row` and `session_from_row` expect.
+
+/// Columns for `user_from_row`, for `SELECT` and `RETURNING`
+pub const USER_COLUMNS: &str =
+    "id::text, email, password_hash, full_name, email_verified, auth_method::text, created_at::text, updated_at::text";
+
+/// Columns for `session_from_row`, for `SELECT` and `RETURNING`
+///
+/// `inet::text` appends the netmask (`203.0.113.7/32`), so the address goes through `host()`.
+pub const SESSION_COLUMNS: &str =
+    "id::text, user_id::text, refresh_token, expires_at::text, created_at::text, user_agent, host(ip_address) AS ip_address";
+
+/// Parse a `timestamptz::text` value (or RFC 3339)
+pub fn parse_datetime(s: &str) -> Result<DateTime<Utc>, sqlx::Error> {
+    DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z")
+        .or_else(|_| DateTime::parse_from_rfc3339(s))
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Like `parse_datetime` for nullable columns; unparseable values become `None`
+pub fn parse_optional_datetime(value: Option<String>) -> Option<DateTime<Utc>> {
+    value.and_then(|s| parse_datetime(&s).ok())
+}
+
+/// Parse a `uuid::text` value
+pub fn parse_uuid(s: &str) -> Result<Uuid, sqlx::Error> {
+    Uuid::parse_str(s).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// Parse a User from a row selected with `USER_COLUMNS`
+pub fn user_from_row(row: &sqlx::postgres::PgRow) -> Result<User, sqlx::Error> {
+    let auth_method: String = row.try_get("auth_method")?;
+
+    Ok(User {
+        id: parse_uuid(row.try_get("id")?)?,
+        email: row.try_get("email")?,
+        password_hash: row.try_get("password_hash")?,
+        full_name: row.try_get("full_name")?,
+        email_verified: row.try_get("email_verified")?,
+        auth_method: AuthMethod::from_str(&auth_method),
+        created_at: parse_datetime(row.try_get("created_at")?)?,
+        updated_at: parse_datetime(row.try_get("updated_at")?)?,
+    })
+}
+
+/// Parse a Session from a row selected with `SESSION_COLUMNS`
+pub fn session_from_row(row: &sqlx::postgres::PgRow) -> Result<Session, sqlx::Error> {
+    Ok(Session {
+        id: parse_uuid(row.try_get("id")?)?,
+        user_id: parse_uuid(row.try_get("user_id")?)?,
+        refresh_token: row.try_get("refresh_token")?,
+        expires_at: parse_datetime(row.try_get("expires_at")?)?,
+        created_at: parse_datetime(row.try_get("created_at")?)?,
+        user_agent: row.try_get("user_agent")?,
+        ip_address: row.try_get("ip_address")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_datetime_postgres_text() {
+        let expected = Utc.with_ymd_and_hms(2025, 12, 1, 8, 30, 0).unwrap();
+
+        assert_eq!(parse_datetime("2025-12-01 08:30:00+00").unwrap(), expected);
+        assert_eq!(parse_datetime("2025-12-01 10:30:00+02").unwrap(), expected);
+        assert_eq!(
+            parse_datetime("2025-12-01 08:30:00.25+00").unwrap(),
+            expected + chrono::Duration::milliseconds(250)
+        );
+        assert_eq!(parse_datetime("2025-12-01T08:30:00Z").unwrap(), expected);
+        assert!(parse_datetime("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_optional_datetime_and_uuid() {
+        assert!(parse_optional_datetime(None).is_none());
+        assert!(parse_optional_datetime(Some("not a date".to_string())).is_none());
+        assert!(parse_optional_datetime(Some("2025-12-01 08:30:00+00".to_string())).is_some());
+
+        let id = Uuid::new_v4();
+        assert_eq!(parse_uuid(&id.to_string()).unwrap(), id);
+        assert!(parse_uuid("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn test_session_columns_select_bare_ip_address() {
+        assert!(SESSION_COLUMNS.contains("host(ip_address) AS ip_address"));
+        assert!(!SESSION_COLUMNS.contains("ip_address::text"));
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 // NOTE: FromRow removed because sqlx uuid feature is disabled
-// Sessions are manually deserialized in auth.rs
+// Sessions are manually deserialized by models::row::session_from_row
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
     pub id: Uuid,
@@ -23,6 +23,18 @@ pub struct AuthResponse {
     pub user: UserResponse,
 }
 
+/// A session as shown on the security/devices screen
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// True for the session the request was made with
+    pub current: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RefreshTokenRequest {
     pub refresh_token: String,
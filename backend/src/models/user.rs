@@ -8,6 +8,8 @@ use validator::Validate;
 #[serde(rename_all = "lowercase")]
 pub enum AuthMethod {
     Google,
+    #[serde(rename = "github")]
+    GitHub,
     Email,
 }
 
@@ -15,6 +17,7 @@ impl AuthMethod {
     pub fn from_str(s: &str) -> Self {
         match s.to_lowercase().as_str() {
             "google" => AuthMethod::Google,
+            "github" => AuthMethod::GitHub,
             _ => AuthMethod::Email,
         }
     }
@@ -22,13 +25,23 @@ impl AuthMethod {
     pub fn as_str(&self) -> &'static str {
         match self {
             AuthMethod::Google => "google",
+            AuthMethod::GitHub => "github",
+            AuthMethod::Email => "email",
+        }
+    }
+
+    /// Human-readable provider name for user-facing messages
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            AuthMethod::Google => "Google",
+            AuthMethod::GitHub => "GitHub",
             AuthMethod::Email => "email",
         }
     }
 }
 
 // NOTE: FromRow removed because sqlx uuid feature is disabled
-// Users are manually deserialized in auth.rs
+// Users are manually deserialized by models::row::user_from_row
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
@@ -58,6 +71,36 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+/// Profile changes for the authenticated user
+///
+/// `email` is only accepted so it can be rejected explicitly; changing it needs re-verification.
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateUserRequest {
+    #[validate(length(min = 1, max = 100, message = "Full name must be 1-100 characters"))]
+    pub full_name: String,
+    pub email: Option<String>,
+}
+
+/// Confirmation for account deletion; email users must re-enter their password
+#[derive(Debug, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: Option<String>,
+}
+
+/// URL that is POSTed a signed notification whenever the wallet receives funds
+#[derive(Debug, Deserialize, Validate)]
+pub struct RegisterWebhookRequest {
+    #[validate(url(message = "Invalid webhook URL"))]
+    pub url: String,
+}
+
+/// The registered webhook; `secret` keys the HMAC-SHA256 in the X-Shield-Signature header
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub url: String,
+    pub secret: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
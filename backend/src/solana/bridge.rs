@@ -4,16 +4,65 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
+    commitment_config::CommitmentConfig,
     pubkey::Pubkey,
-    signature::{Keypair, Signer},
+    signature::{Keypair, Signature, Signer},
     system_instruction,
     transaction::Transaction,
 };
+use once_cell::sync::Lazy;
 use sqlx::{PgPool, Row};
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
-const NEAR_INTENTS_API_URL: &str = "https://1click.chaindefuser.com";
+use crate::utils::env::env_or;
+use super::rpc::{get_rpc_url, retry_blocking};
+
+/// Defaults for the NEAR Intents endpoint and asset ids, overridable with `NEAR_INTENTS_URL`,
+/// `NEAR_ORIGIN_ASSET` and `NEAR_DESTINATION_ASSET`
+const DEFAULT_NEAR_INTENTS_URL: &str = "https://1click.chaindefuser.com";
+const DEFAULT_NEAR_ORIGIN_ASSET: &str = "nep141:sol.omft.near";
+const DEFAULT_NEAR_DESTINATION_ASSET: &str = "nep141:zec.omft.near";
+
+/// Bridge statuses after which NEAR Intents will not update the swap again
+pub const TERMINAL_STATUSES: [&str; 3] = ["SUCCESS", "FAILED", "REFUNDED"];
+
+/// Slippage tolerance used when the client doesn't ask for one (1%)
+pub const DEFAULT_SLIPPAGE_BPS: i32 = 100;
+
+/// Bounds applied to client-supplied slippage (0.1% – 10%)
+pub const MIN_SLIPPAGE_BPS: i32 = 10;
+pub const MAX_SLIPPAGE_BPS: i32 = 1000;
+
+/// Resolve the slippage tolerance to request, clamping client input to a sane range
+pub fn effective_slippage_bps(requested: Option<i32>) -> i32 {
+    requested
+        .unwrap_or(DEFAULT_SLIPPAGE_BPS)
+        .clamp(MIN_SLIPPAGE_BPS, MAX_SLIPPAGE_BPS)
+}
+
+/// Quote deadline used when the client doesn't ask for one, overridable with
+/// `BRIDGE_QUOTE_DEADLINE_HOURS`
+const DEFAULT_QUOTE_DEADLINE_HOURS: i64 = 24;
+
+/// Bounds applied to quote deadlines; the bridge poller stops tracking swaps after 24 hours
+pub const MIN_QUOTE_DEADLINE_HOURS: i64 = 1;
+pub const MAX_QUOTE_DEADLINE_HOURS: i64 = 24;
+
+/// Resolve the quote deadline to request, clamping client input and the env default
+pub fn effective_deadline_hours(requested: Option<i64>) -> i64 {
+    requested
+        .or_else(|| {
+            std::env::var("BRIDGE_QUOTE_DEADLINE_HOURS")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+        })
+        .unwrap_or(DEFAULT_QUOTE_DEADLINE_HOURS)
+        .clamp(MIN_QUOTE_DEADLINE_HOURS, MAX_QUOTE_DEADLINE_HOURS)
+}
 
 #[derive(Debug, Serialize)]
 struct QuoteRequest {
@@ -47,6 +96,8 @@ pub struct BridgeQuote {
     pub amount_out_formatted: String,
     pub deposit_address: String,
     pub time_estimate: i64,
+    /// When NEAR Intents stops honouring the quote and refunds late deposits
+    pub deadline: chrono::DateTime<chrono::Utc>,
 }
 
 /// Get JWT token from environment
@@ -54,24 +105,37 @@ fn get_jwt_token() -> Option<String> {
     std::env::var("NEAR_INTENTS_JWT").ok()
 }
 
+/// NEAR Intents 1Click API base URL, without a trailing slash
+fn near_intents_url() -> String {
+    env_or("NEAR_INTENTS_URL", DEFAULT_NEAR_INTENTS_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
 /// Get bridge quote from NEAR Intents for SOL → ZEC swap
+///
+/// With `dry` set, NEAR Intents only prices the swap and doesn't reserve a
+/// deposit address, so `deposit_address` will be empty.
 pub async fn get_bridge_quote(
     amount_lamports: u64,
     refund_address: &str,
     recipient_address: &str,
+    slippage_bps: i32,
+    deadline_hours: i64,
+    dry: bool,
 ) -> Result<BridgeQuote> {
     let client = Client::new();
-    let url = format!("{}/v0/quote", NEAR_INTENTS_API_URL);
+    let url = format!("{}/v0/quote", near_intents_url());
 
-    let deadline = chrono::Utc::now() + chrono::Duration::hours(24);
+    let deadline = chrono::Utc::now() + chrono::Duration::hours(deadline_hours);
 
     let quote_request = QuoteRequest {
-        dry: false, // Real swap
+        dry,
         swap_type: "EXACT_INPUT".to_string(),
-        slippage_tolerance: 100, // 1%
-        origin_asset: "nep141:sol.omft.near".to_string(),
+        slippage_tolerance: slippage_bps,
+        origin_asset: env_or("NEAR_ORIGIN_ASSET", DEFAULT_NEAR_ORIGIN_ASSET.to_string()),
         deposit_type: "ORIGIN_CHAIN".to_string(),
-        destination_asset: "nep141:zec.omft.near".to_string(),
+        destination_asset: env_or("NEAR_DESTINATION_ASSET", DEFAULT_NEAR_DESTINATION_ASSET.to_string()),
         amount: amount_lamports.to_string(),
         refund_to: refund_address.to_string(),
         refund_type: "ORIGIN_CHAIN".to_string(),
@@ -130,63 +194,217 @@ pub async fn get_bridge_quote(
             .as_str()
             .unwrap_or("0")
             .to_string(),
-        deposit_address: quote["depositAddress"]
-            .as_str()
-            .context("No deposit address in quote")?
-            .to_string(),
+        deposit_address: match quote["depositAddress"].as_str() {
+            Some(address) => address.to_string(),
+            None if dry => String::new(),
+            None => anyhow::bail!("No deposit address in quote"),
+        },
         time_estimate: quote["timeEstimate"]
             .as_i64()
             .unwrap_or(180),
+        deadline,
     };
 
     Ok(bridge_quote)
 }
 
-/// Execute bridge transaction by sending SOL to NEAR Intents deposit address
-pub async fn execute_bridge(
+/// How long a bridge output estimate is reused for the same amount, overridable with
+/// `BRIDGE_ESTIMATE_CACHE_SECS`
+const DEFAULT_ESTIMATE_CACHE_SECS: u64 = 15;
+
+/// Most distinct amounts kept in the estimate cache
+const MAX_CACHED_ESTIMATES: usize = 512;
+
+/// Expected output of a swap, for live previews
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeEstimate {
+    pub amount_out_formatted: String,
+    pub time_estimate: i64,
+}
+
+/// Recent estimates keyed by input amount in lamports
+///
+/// Prices don't depend on who asks, so the cache is shared by all users.
+struct EstimateCache {
+    entries: HashMap<u64, (Instant, BridgeEstimate)>,
+}
+
+impl EstimateCache {
+    fn get(&self, amount_lamports: u64, ttl: Duration, now: Instant) -> Option<BridgeEstimate> {
+        self.entries
+            .get(&amount_lamports)
+            .filter(|(stored_at, _)| now.duration_since(*stored_at) < ttl)
+            .map(|(_, estimate)| estimate.clone())
+    }
+
+    fn insert(&mut self, amount_lamports: u64, estimate: BridgeEstimate, ttl: Duration, now: Instant) {
+        self.entries
+            .retain(|_, (stored_at, _)| now.duration_since(*stored_at) < ttl);
+        if self.entries.len() >= MAX_CACHED_ESTIMATES {
+            if let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (stored_at, _))| *stored_at)
+                .map(|(amount, _)| *amount)
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(amount_lamports, (now, estimate));
+    }
+}
+
+static ESTIMATE_CACHE: Lazy<Mutex<EstimateCache>> = Lazy::new(|| {
+    Mutex::new(EstimateCache {
+        entries: HashMap::new(),
+    })
+});
+
+fn estimate_cache_ttl() -> Duration {
+    Duration::from_secs(env_or("BRIDGE_ESTIMATE_CACHE_SECS", DEFAULT_ESTIMATE_CACHE_SECS))
+}
+
+/// Estimate the ZEC received for `amount_lamports` without reserving a deposit address
+///
+/// Uses a dry-run quote at the default slippage. Results are cached briefly per amount so
+/// a UI slider doesn't send NEAR Intents a request for every movement.
+pub async fn estimate_bridge_output(
+    amount_lamports: u64,
+    refund_address: &str,
+    recipient_address: &str,
+) -> Result<BridgeEstimate> {
+    let ttl = estimate_cache_ttl();
+    let cached = ESTIMATE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(amount_lamports, ttl, Instant::now());
+    if let Some(estimate) = cached {
+        return Ok(estimate);
+    }
+
+    let quote = get_bridge_quote(
+        amount_lamports,
+        refund_address,
+        recipient_address,
+        DEFAULT_SLIPPAGE_BPS,
+        effective_deadline_hours(None),
+        true,
+    )
+    .await?;
+    let estimate = BridgeEstimate {
+        amount_out_formatted: quote.amount_out_formatted,
+        time_estimate: quote.time_estimate,
+    };
+
+    ESTIMATE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(amount_lamports, estimate.clone(), ttl, Instant::now());
+
+    Ok(estimate)
+}
+
+/// Build and sign the SOL transfer to the NEAR Intents deposit address
+///
+/// Signing happens before anything is sent so the caller can persist the signature
+/// first; a transfer that lands while the request dies is then still tracked.
+pub async fn sign_bridge_transfer(
     keypair: &Keypair,
     deposit_address: &str,
     amount_lamports: u64,
-) -> Result<String> {
-    let rpc_url = std::env::var("SOLANA_RPC_URL")
-        .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
-    let rpc_client = RpcClient::new(rpc_url);
+) -> Result<Transaction> {
+    let rpc_url = get_rpc_url();
+    let keypair = keypair.insecure_clone();
 
     let to_pubkey = Pubkey::from_str(deposit_address)
         .context("Invalid deposit address")?;
 
-    // Create transfer instruction
-    let instruction = system_instruction::transfer(
-        &keypair.pubkey(),
-        &to_pubkey,
-        amount_lamports,
-    );
-
-    // Get recent blockhash
-    let recent_blockhash = rpc_client
-        .get_latest_blockhash()
+    // Run the blocking RPC calls in a separate thread
+    tokio::task::spawn_blocking(move || {
+        let rpc_client = RpcClient::new(rpc_url);
+
+        // Create transfer instruction
+        let instruction = system_instruction::transfer(
+            &keypair.pubkey(),
+            &to_pubkey,
+            amount_lamports,
+        );
+
+        // Get recent blockhash
+        let recent_blockhash = retry_blocking("getLatestBlockhash", || {
+            rpc_client.get_latest_blockhash()
+        })
         .context("Failed to get latest blockhash")?;
 
-    // Create transaction
-    let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
-        Some(&keypair.pubkey()),
-        &[keypair],
-        recent_blockhash,
-    );
-
-    // Send transaction
-    let signature = rpc_client
-        .send_and_confirm_transaction(&transaction)
+        Ok(Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&keypair.pubkey()),
+            &[&keypair],
+            recent_blockhash,
+        ))
+    })
+    .await
+    .context("Failed to spawn blocking task")?
+}
+
+/// Signature of a signed transaction, as stored in `solana_tx_signature`
+pub fn transaction_signature(transaction: &Transaction) -> Result<String> {
+    transaction
+        .signatures
+        .first()
+        .map(|signature| signature.to_string())
+        .context("Transaction is not signed")
+}
+
+/// Send a signed bridge transfer and wait for confirmation
+pub async fn send_bridge_transfer(transaction: Transaction) -> Result<String> {
+    let rpc_url = get_rpc_url();
+
+    tokio::task::spawn_blocking(move || {
+        let rpc_client = RpcClient::new(rpc_url);
+
+        // Send transaction; resending the same signed transaction is safe because the
+        // cluster deduplicates by signature
+        let signature = retry_blocking("sendTransaction", || {
+            rpc_client.send_and_confirm_transaction(&transaction)
+        })
         .context("Failed to send transaction")?;
 
-    Ok(signature.to_string())
+        Ok(signature.to_string())
+    })
+    .await
+    .context("Failed to spawn blocking task")?
+}
+
+/// Look up the confirmed on-chain status of a Solana transaction
+///
+/// Returns `None` if the cluster has not seen the signature, otherwise whether the
+/// transaction succeeded, with the error message if it failed.
+pub async fn solana_signature_status(signature: &str) -> Result<Option<Result<(), String>>> {
+    let rpc_url = get_rpc_url();
+    let signature = Signature::from_str(signature).context("Invalid Solana signature")?;
+
+    tokio::task::spawn_blocking(move || {
+        let rpc_client = RpcClient::new(rpc_url);
+
+        let status = retry_blocking("getSignatureStatuses", || {
+            rpc_client.get_signature_status_with_commitment(
+                &signature,
+                CommitmentConfig::confirmed(),
+            )
+        })
+        .context("Failed to get signature status")?;
+
+        Ok(status.map(|result| result.map_err(|e| e.to_string())))
+    })
+    .await
+    .context("Failed to spawn blocking task")?
 }
 
 /// Get bridge transaction status from NEAR Intents
 pub async fn get_bridge_status(deposit_address: &str) -> Result<Value> {
     let client = Client::new();
-    let url = format!("{}/v0/status", NEAR_INTENTS_API_URL);
+    let url = format!("{}/v0/status", near_intents_url());
 
     let mut request = client
         .get(&url)
@@ -207,6 +425,48 @@ pub async fn get_bridge_status(deposit_address: &str) -> Result<Value> {
     Ok(status)
 }
 
+/// Fields extracted from a NEAR Intents status response
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeStatusUpdate {
+    pub status: String,
+    pub zec_tx_hash: Option<String>,
+    pub actual_zec_zatoshis: Option<i64>,
+}
+
+impl BridgeStatusUpdate {
+    /// Whether the swap has settled and no further polling is needed
+    pub fn is_terminal(&self) -> bool {
+        TERMINAL_STATUSES.contains(&self.status.as_str())
+    }
+}
+
+/// Extract the status, ZEC tx hash and delivered amount from a status response
+///
+/// Returns None if the response has no `status` field.
+pub fn parse_status_update(status: &Value) -> Option<BridgeStatusUpdate> {
+    let status_str = status.get("status").and_then(|s| s.as_str())?;
+
+    let zec_tx_hash = status
+        .get("swapDetails")
+        .and_then(|sd| sd.get("destinationChainTxHashes"))
+        .and_then(|hashes| hashes.get(0))
+        .and_then(|h| h.get("hash"))
+        .and_then(|h| h.as_str())
+        .map(|h| h.to_string());
+
+    let actual_zec_zatoshis = status
+        .get("swapDetails")
+        .and_then(|sd| sd.get("amountOut"))
+        .and_then(|a| a.as_str())
+        .and_then(|s| s.parse::<i64>().ok());
+
+    Some(BridgeStatusUpdate {
+        status: status_str.to_string(),
+        zec_tx_hash,
+        actual_zec_zatoshis,
+    })
+}
+
 /// Create a bridge transaction record in the database
 pub async fn create_bridge_transaction(
     db: &PgPool,
@@ -246,6 +506,33 @@ pub async fn create_bridge_transaction(
     Uuid::parse_str(&id_str).context("Failed to parse bridge transaction id")
 }
 
+/// Record the signature of a transfer that is about to be sent
+///
+/// `BROADCAST` rows are confirmed or failed by the bridge poller, so the funds stay
+/// tracked even if confirmation never completes in the request.
+pub async fn record_bridge_broadcast(
+    db: &PgPool,
+    bridge_tx_id: Uuid,
+    solana_signature: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE bridge_transactions
+        SET solana_tx_signature = $1,
+            status = 'BROADCAST',
+            updated_at = NOW()
+        WHERE id = $2::uuid
+        "#
+    )
+    .bind(solana_signature)
+    .bind(bridge_tx_id.to_string())
+    .execute(db)
+    .await
+    .context("Failed to record bridge transaction signature")?;
+
+    Ok(())
+}
+
 /// Update bridge transaction with Solana transaction signature
 pub async fn update_bridge_tx_signature(
     db: &PgPool,
@@ -314,4 +601,72 @@ mod tests {
         let sol = lamports as f64 / 1_000_000_000.0;
         assert_eq!(sol, 0.05);
     }
+
+    #[test]
+    fn test_estimate_cache_expiry_and_capacity() {
+        let ttl = Duration::from_secs(15);
+        let start = Instant::now();
+        let estimate = |out: &str| BridgeEstimate {
+            amount_out_formatted: out.to_string(),
+            time_estimate: 60,
+        };
+
+        let mut cache = EstimateCache { entries: HashMap::new() };
+        cache.insert(1_000, estimate("0.5"), ttl, start);
+        assert_eq!(cache.get(1_000, ttl, start + Duration::from_secs(5)), Some(estimate("0.5")));
+        assert_eq!(cache.get(2_000, ttl, start), None);
+        assert_eq!(cache.get(1_000, ttl, start + ttl), None);
+
+        // Full cache evicts the oldest amount
+        for amount in 0..MAX_CACHED_ESTIMATES as u64 {
+            cache.insert(10_000 + amount, estimate("1"), ttl, start + Duration::from_millis(amount + 1));
+        }
+        assert_eq!(cache.entries.len(), MAX_CACHED_ESTIMATES);
+        assert_eq!(cache.get(1_000, ttl, start), None);
+        assert!(cache.get(10_000 + MAX_CACHED_ESTIMATES as u64 - 1, ttl, start + Duration::from_secs(1)).is_some());
+    }
+
+    #[test]
+    fn test_effective_slippage_bps() {
+        assert_eq!(effective_slippage_bps(None), DEFAULT_SLIPPAGE_BPS);
+        assert_eq!(effective_slippage_bps(Some(250)), 250);
+        assert_eq!(effective_slippage_bps(Some(0)), MIN_SLIPPAGE_BPS);
+        assert_eq!(effective_slippage_bps(Some(-5)), MIN_SLIPPAGE_BPS);
+        assert_eq!(effective_slippage_bps(Some(5000)), MAX_SLIPPAGE_BPS);
+    }
+
+    #[test]
+    fn test_effective_deadline_hours() {
+        assert_eq!(effective_deadline_hours(Some(6)), 6);
+        assert_eq!(effective_deadline_hours(Some(0)), MIN_QUOTE_DEADLINE_HOURS);
+        assert_eq!(effective_deadline_hours(Some(-3)), MIN_QUOTE_DEADLINE_HOURS);
+        assert_eq!(effective_deadline_hours(Some(72)), MAX_QUOTE_DEADLINE_HOURS);
+    }
+
+    #[test]
+    fn test_parse_status_update() {
+        let status = serde_json::json!({
+            "status": "SUCCESS",
+            "swapDetails": {
+                "amountOut": "1234567",
+                "destinationChainTxHashes": [{ "hash": "abcd" }]
+            }
+        });
+
+        let update = parse_status_update(&status).unwrap();
+        assert_eq!(update.status, "SUCCESS");
+        assert_eq!(update.zec_tx_hash.as_deref(), Some("abcd"));
+        assert_eq!(update.actual_zec_zatoshis, Some(1_234_567));
+        assert!(update.is_terminal());
+    }
+
+    #[test]
+    fn test_parse_status_update_pending() {
+        let status = serde_json::json!({ "status": "PENDING_DEPOSIT" });
+
+        let update = parse_status_update(&status).unwrap();
+        assert_eq!(update.zec_tx_hash, None);
+        assert!(!update.is_terminal());
+        assert!(parse_status_update(&serde_json::json!({})).is_none());
+    }
 }
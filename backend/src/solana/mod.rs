@@ -1,7 +1,8 @@
 pub mod wallet;
 pub mod rpc;
 pub mod bridge;
+pub mod poller;
 
 pub use wallet::{create_solana_wallet, get_solana_wallet};
 pub use rpc::get_sol_balance;
-pub use bridge::{get_bridge_quote, execute_bridge, get_bridge_status};
+pub use bridge::{get_bridge_quote, sign_bridge_transfer, send_bridge_transfer, get_bridge_status};
@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use sqlx::{PgPool, Row};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::bridge;
+
+/// How often the poller wakes up to look for unsettled bridge transactions
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Upper bound on the per-transaction backoff
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+/// Stop polling once a transaction is older than the quote deadline
+const POLL_WINDOW_HOURS: i32 = 24;
+
+/// How long a `BROADCAST` transfer may stay unseen on chain before it is failed;
+/// well past the ~150 slot lifetime of the blockhash it was signed with
+const BROADCAST_EXPIRY: Duration = Duration::from_secs(5 * 60);
+
+/// Per-transaction retry state kept between ticks
+struct Backoff {
+    attempts: u32,
+    next_poll: Instant,
+    last_status: String,
+}
+
+/// Delay before the next poll after `attempts` polls without progress
+fn backoff_delay(attempts: u32) -> Duration {
+    POLL_INTERVAL
+        .checked_mul(1u32 << attempts.min(16))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+/// Spawn a background task that settles pending NEAR Intents bridge transactions
+///
+/// `get_bridge_status` only updates the database when the client polls it, so a
+/// user closing the tab after `execute_bridge` would never get the ZEC tx hash
+/// recorded. Transfers left in `BROADCAST` are first checked on chain and moved
+/// to `PROCESSING` or `FAILED`. This worker polls every unsettled transaction created within the
+/// last 24 hours until NEAR Intents reports a terminal status.
+pub fn spawn_bridge_poller(db: PgPool) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut backoffs: HashMap<Uuid, Backoff> = HashMap::new();
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = reconcile_broadcasts(&db).await {
+                tracing::warn!("Bridge broadcast reconciliation failed: {:#}", e);
+            }
+            if let Err(e) = poll_once(&db, &mut backoffs).await {
+                tracing::warn!("Bridge status poll failed: {:#}", e);
+            }
+        }
+    })
+}
+
+/// What the chain says about a `BROADCAST` transfer
+#[derive(Debug, PartialEq)]
+enum BroadcastOutcome {
+    Confirmed,
+    Failed(String),
+    Pending,
+}
+
+/// Decide a `BROADCAST` transfer's fate from its signature status and how long ago it was sent
+fn broadcast_outcome(status: Option<Result<(), String>>, age: Duration) -> BroadcastOutcome {
+    match status {
+        Some(Ok(())) => BroadcastOutcome::Confirmed,
+        Some(Err(e)) => BroadcastOutcome::Failed(format!("Solana transfer failed: {}", e)),
+        None if age > BROADCAST_EXPIRY => {
+            BroadcastOutcome::Failed("Solana transfer never landed on chain".to_string())
+        }
+        None => BroadcastOutcome::Pending,
+    }
+}
+
+/// Settle transfers whose signature was recorded but whose confirmation was never seen
+async fn reconcile_broadcasts(db: &PgPool) -> Result<()> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id::text AS id, solana_tx_signature,
+               EXTRACT(EPOCH FROM NOW() - updated_at)::float8 AS age_secs
+        FROM bridge_transactions
+        WHERE status = 'BROADCAST' AND solana_tx_signature IS NOT NULL
+        "#
+    )
+    .fetch_all(db)
+    .await
+    .context("Failed to load broadcast bridge transactions")?;
+
+    for row in rows {
+        let id_str: String = row.get("id");
+        let id = Uuid::parse_str(&id_str).context("Failed to parse bridge transaction id")?;
+        let signature: String = row.get("solana_tx_signature");
+        let age_secs: f64 = row.get("age_secs");
+
+        let status = match bridge::solana_signature_status(&signature).await {
+            Ok(status) => status,
+            Err(e) => {
+                tracing::warn!("Failed to check Solana transfer {}: {:#}", signature, e);
+                continue;
+            }
+        };
+
+        match broadcast_outcome(status, Duration::from_secs_f64(age_secs.max(0.0))) {
+            BroadcastOutcome::Confirmed => {
+                tracing::info!("Bridge transfer {} for transaction {} confirmed", signature, id);
+                bridge::update_bridge_tx_signature(db, id, &signature).await?;
+            }
+            BroadcastOutcome::Failed(error) => {
+                tracing::warn!("Bridge transaction {} failed: {}", id, error);
+                bridge::update_bridge_status(db, id, "FAILED", None, None, Some(&error)).await?;
+            }
+            BroadcastOutcome::Pending => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Poll every due transaction once
+async fn poll_once(db: &PgPool, backoffs: &mut HashMap<Uuid, Backoff>) -> Result<()> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id::text AS id, deposit_address, status
+        FROM bridge_transactions
+        WHERE status IN ('PENDING', 'PROCESSING')
+          AND created_at > NOW() - make_interval(hours => $1)
+        "#
+    )
+    .bind(POLL_WINDOW_HOURS)
+    .fetch_all(db)
+    .await
+    .context("Failed to load pending bridge transactions")?;
+
+    let mut pending = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id_str: String = row.get("id");
+        let id = Uuid::parse_str(&id_str).context("Failed to parse bridge transaction id")?;
+        let deposit_address: String = row.get("deposit_address");
+        let status: String = row.get("status");
+        pending.push((id, deposit_address, status));
+    }
+
+    // Forget transactions that settled or aged out since the last tick
+    backoffs.retain(|id, _| pending.iter().any(|(pending_id, _, _)| pending_id == id));
+
+    let now = Instant::now();
+    for (id, deposit_address, db_status) in pending {
+        if backoffs.get(&id).is_some_and(|b| b.next_poll > now) {
+            continue;
+        }
+
+        let observed = match poll_transaction(db, id, &deposit_address).await {
+            Ok(Some(update)) if update.is_terminal() => {
+                tracing::info!("Bridge transaction {} settled with status {}", id, update.status);
+                backoffs.remove(&id);
+                continue;
+            }
+            Ok(Some(update)) => Some(update.status),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::warn!("Failed to poll bridge transaction {}: {:#}", id, e);
+                None
+            }
+        };
+
+        let backoff = backoffs.entry(id).or_insert_with(|| Backoff {
+            attempts: 0,
+            next_poll: now,
+            last_status: db_status,
+        });
+
+        // Reset the backoff whenever NEAR Intents reports a new intermediate status
+        match observed {
+            Some(status) if status != backoff.last_status => {
+                backoff.attempts = 0;
+                backoff.last_status = status;
+            }
+            _ => backoff.attempts += 1,
+        }
+        backoff.next_poll = now + backoff_delay(backoff.attempts);
+    }
+
+    Ok(())
+}
+
+/// Fetch the NEAR Intents status for one transaction
+///
+/// Only terminal statuses are written back; intermediate NEAR Intents statuses
+/// such as `PENDING_DEPOSIT` would otherwise drop the row out of the poll query.
+async fn poll_transaction(
+    db: &PgPool,
+    id: Uuid,
+    deposit_address: &str,
+) -> Result<Option<bridge::BridgeStatusUpdate>> {
+    let status = bridge::get_bridge_status(deposit_address).await?;
+
+    let Some(update) = bridge::parse_status_update(&status) else {
+        return Ok(None);
+    };
+
+    if update.is_terminal() {
+        bridge::update_bridge_status(
+            db,
+            id,
+            &update.status,
+            update.zec_tx_hash.as_deref(),
+            update.actual_zec_zatoshis,
+            None,
+        )
+        .await?;
+    }
+
+    Ok(Some(update))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_until_cap() {
+        assert_eq!(backoff_delay(0), POLL_INTERVAL);
+        assert_eq!(backoff_delay(1), POLL_INTERVAL * 2);
+        assert_eq!(backoff_delay(3), POLL_INTERVAL * 8);
+        assert_eq!(backoff_delay(10), MAX_BACKOFF);
+        assert_eq!(backoff_delay(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_broadcast_outcome() {
+        let fresh = Duration::from_secs(10);
+        let stale = BROADCAST_EXPIRY + Duration::from_secs(1);
+
+        assert_eq!(broadcast_outcome(Some(Ok(())), stale), BroadcastOutcome::Confirmed);
+        assert!(matches!(
+            broadcast_outcome(Some(Err("InsufficientFundsForFee".into())), fresh),
+            BroadcastOutcome::Failed(_)
+        ));
+        assert_eq!(broadcast_outcome(None, fresh), BroadcastOutcome::Pending);
+        assert!(matches!(broadcast_outcome(None, stale), BroadcastOutcome::Failed(_)));
+    }
+}
@@ -1,7 +1,17 @@
 use anyhow::{Context, Result};
+use solana_client::client_error::{ClientError, ClientErrorKind};
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 use std::str::FromStr;
+use std::time::Duration;
+
+use crate::utils::env::env_or;
+
+/// RPC attempts made when `SOLANA_RPC_MAX_ATTEMPTS` is unset
+const DEFAULT_RPC_MAX_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled after each failed attempt
+const RPC_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
 
 /// Get Solana RPC URL from environment or use default
 pub fn get_rpc_url() -> String {
@@ -9,6 +19,71 @@ pub fn get_rpc_url() -> String {
         .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string())
 }
 
+/// Attempts per RPC call, from `SOLANA_RPC_MAX_ATTEMPTS` (at least 1)
+pub fn rpc_max_attempts() -> u32 {
+    env_or("SOLANA_RPC_MAX_ATTEMPTS", DEFAULT_RPC_MAX_ATTEMPTS).max(1)
+}
+
+/// Whether an RPC failure is transient (timeout, connection, rate limit, 5xx)
+///
+/// Anything else, such as a rejected transaction or malformed response, fails the same
+/// way on every attempt.
+pub fn is_retryable(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.status().map_or(false, |status| {
+                    status.as_u16() == 429 || status.is_server_error()
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Run a blocking RPC call, retrying transient failures with exponential backoff
+///
+/// Must be called from a blocking context (e.g. inside `spawn_blocking`).
+pub fn retry_blocking<T>(
+    operation: &str,
+    mut call: impl FnMut() -> std::result::Result<T, ClientError>,
+) -> Result<T> {
+    retry_with(operation, rpc_max_attempts(), RPC_RETRY_BASE_DELAY, &mut call)
+}
+
+fn retry_with<T>(
+    operation: &str,
+    max_attempts: u32,
+    base_delay: Duration,
+    call: &mut impl FnMut() -> std::result::Result<T, ClientError>,
+) -> Result<T> {
+    let mut delay = base_delay;
+    let mut attempt = 1;
+    loop {
+        match call() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < max_attempts && is_retryable(&e) => {
+                tracing::warn!(
+                    "Solana RPC {} failed (attempt {}/{}), retrying in {:?}: {}",
+                    operation,
+                    attempt,
+                    max_attempts,
+                    delay,
+                    e
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("Solana RPC {} failed after {} attempt(s)", operation, attempt)))
+            }
+        }
+    }
+}
+
 /// Get SOL balance for a given address
 pub async fn get_sol_balance(address: &str) -> Result<u64> {
     let rpc_url = get_rpc_url();
@@ -23,8 +98,7 @@ pub async fn get_sol_balance(address: &str) -> Result<u64> {
             .context("Invalid Solana address")?;
 
         // Get balance (in lamports)
-        let balance = rpc_client
-            .get_balance(&pubkey)
+        let balance = retry_blocking("getBalance", || rpc_client.get_balance(&pubkey))
             .context("Failed to get balance from Solana RPC")?;
 
         Ok(balance)
@@ -58,6 +132,37 @@ mod tests {
         assert!(!is_valid_address(""));
     }
 
+    #[test]
+    fn test_is_retryable() {
+        let transport = ClientError::from(std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out"));
+        assert!(is_retryable(&transport));
+
+        let permanent = ClientError::from(ClientErrorKind::Custom("Invalid param".to_string()));
+        assert!(!is_retryable(&permanent));
+    }
+
+    #[test]
+    fn test_retry_stops_on_success_and_permanent_errors() {
+        let mut calls = 0;
+        let result = retry_with("test", 3, Duration::ZERO, &mut || {
+            calls += 1;
+            if calls < 3 {
+                Err(ClientError::from(std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset")))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let result: Result<()> = retry_with("test", 3, Duration::ZERO, &mut || {
+            calls += 1;
+            Err(ClientError::from(ClientErrorKind::Custom("Invalid param".to_string())))
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
     #[test]
     fn test_balance_conversion() {
         let lamports: u64 = 1_000_000_000;
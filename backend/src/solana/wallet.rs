@@ -3,7 +3,10 @@ use solana_sdk::signature::{Keypair, Signer};
 use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
-/// Create a new Solana wallet for a user
+/// Create a Solana wallet for a user, or return the existing one
+///
+/// An existing keypair is never replaced: it may hold funds, and the auth flow
+/// calls this again on re-login.
 pub async fn create_solana_wallet(db: &PgPool, user_id: Uuid) -> Result<(String, Vec<u8>)> {
     // Generate new Solana keypair
     let keypair = Keypair::new();
@@ -15,14 +18,11 @@ pub async fn create_solana_wallet(db: &PgPool, user_id: Uuid) -> Result<(String,
     let keypair_bytes = keypair.to_bytes().to_vec();
 
     // Store in database (unencrypted for now - encryption will be added later)
-    sqlx::query(
+    let inserted = sqlx::query(
         r#"
         INSERT INTO solana_wallets (user_id, encrypted_keypair, public_key)
         VALUES ($1::uuid, $2, $3)
-        ON CONFLICT (user_id) DO UPDATE
-        SET encrypted_keypair = EXCLUDED.encrypted_keypair,
-            public_key = EXCLUDED.public_key,
-            updated_at = NOW()
+        ON CONFLICT (user_id) DO NOTHING
         "#
     )
     .bind(user_id.to_string())
@@ -30,7 +30,15 @@ pub async fn create_solana_wallet(db: &PgPool, user_id: Uuid) -> Result<(String,
     .bind(public_key.clone())
     .execute(db)
     .await
-    .context("Failed to insert Solana wallet into database")?;
+    .context("Failed to insert Solana wallet into database")?
+    .rows_affected();
+
+    if inserted == 0 {
+        tracing::info!("Solana wallet already exists for user {}, keeping it", user_id);
+        return get_solana_wallet(db, user_id)
+            .await?
+            .context("Solana wallet disappeared after insert conflict");
+    }
 
     tracing::info!("Created Solana wallet for user {}: {}", user_id, public_key);
 
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+/// Send a plain-text email through the SMTP server configured in the environment
+///
+/// If SMTP_HOST is not set (local development) the email is logged instead of sent.
+pub async fn send_email(to: &str, subject: &str, body: &str) -> Result<()> {
+    let Some(smtp_host) = std::env::var("SMTP_HOST").ok().filter(|h| !h.is_empty()) else {
+        tracing::warn!("SMTP_HOST not set, not sending email to {}: {}\n{}", to, subject, body);
+        return Ok(());
+    };
+
+    let from = std::env::var("SMTP_FROM").unwrap_or_else(|_| "Shield <no-reply@localhost>".to_string());
+
+    let message = Message::builder()
+        .from(from.parse().context("Invalid SMTP_FROM address")?)
+        .to(to.parse().context("Invalid recipient email address")?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string())
+        .context("Failed to build email")?;
+
+    let mut transport = AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp_host)
+        .context("Failed to configure SMTP transport")?;
+
+    if let (Ok(username), Ok(password)) = (std::env::var("SMTP_USERNAME"), std::env::var("SMTP_PASSWORD")) {
+        transport = transport.credentials(Credentials::new(username, password));
+    }
+
+    transport
+        .build()
+        .send(message)
+        .await
+        .context("Failed to send email")?;
+
+    Ok(())
+}
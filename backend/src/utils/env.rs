@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+/// Read a setting from the environment, falling back to `default`
+///
+/// The value is trimmed first; unset, blank and unparseable values all give `default`.
+pub fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Like `env_or`, for counts, sizes and durations that must be positive: zero gives `default`
+pub fn env_or_positive<T: FromStr + PartialOrd + Default>(name: &str, default: T) -> T {
+    let value = env_or(name, T::default());
+    if value > T::default() {
+        value
+    } else {
+        default
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_or() {
+        let name = "SHIELD_TEST_ENV_OR";
+        std::env::remove_var(name);
+        assert_eq!(env_or(name, 30u64), 30);
+
+        std::env::set_var(name, " 45 ");
+        assert_eq!(env_or(name, 30u64), 45);
+
+        std::env::set_var(name, "soon");
+        assert_eq!(env_or(name, 30u64), 30);
+        std::env::remove_var(name);
+    }
+
+    #[test]
+    fn test_env_or_positive() {
+        let name = "SHIELD_TEST_ENV_OR_POSITIVE";
+        std::env::set_var(name, "0");
+        assert_eq!(env_or_positive(name, 8usize), 8);
+
+        std::env::set_var(name, "-3");
+        assert_eq!(env_or_positive(name, 8i64), 8);
+
+        std::env::set_var(name, "2");
+        assert_eq!(env_or_positive(name, 8u32), 2);
+        std::env::remove_var(name);
+    }
+
+    #[test]
+    fn test_env_or_string() {
+        let name = "SHIELD_TEST_ENV_OR_STRING";
+        std::env::remove_var(name);
+        assert_eq!(env_or(name, "fallback".to_string()), "fallback");
+
+        std::env::set_var(name, "   ");
+        assert_eq!(env_or(name, "fallback".to_string()), "fallback");
+
+        std::env::set_var(name, " nep141:custom.near ");
+        assert_eq!(env_or(name, "fallback".to_string()), "nep141:custom.near");
+        std::env::remove_var(name);
+    }
+}
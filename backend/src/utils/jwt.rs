@@ -1,14 +1,22 @@
+use anyhow::Result;
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Key ID used when only a single `JWT_SECRET` is configured
+pub const DEFAULT_KID: &str = "default";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // Subject (user ID)
     pub exp: i64,    // Expiry time
     pub iat: i64,    // Issued at
     pub token_type: TokenType,
+    /// Session the access token was issued for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -17,22 +25,99 @@ pub enum TokenType {
     Refresh,
 }
 
-pub struct JwtManager {
+/// A signing secret and the key ID (`kid`) that names it in token headers
+struct JwtKey {
+    kid: String,
     secret: String,
+}
+
+/// Parse `JWT_SECRETS`: comma-separated `kid:secret` pairs
+pub fn parse_jwt_secrets(list: &str) -> Result<Vec<(String, String)>> {
+    let mut keys: Vec<(String, String)> = Vec::new();
+    for entry in list.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (kid, secret) = entry
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("JWT_SECRETS entries must be kid:secret"))?;
+        let (kid, secret) = (kid.trim(), secret.trim());
+        if kid.is_empty() || secret.is_empty() {
+            anyhow::bail!("JWT_SECRETS entries need a non-empty kid and secret");
+        }
+        if keys.iter().any(|(existing, _)| existing == kid) {
+            anyhow::bail!("Duplicate kid in JWT_SECRETS: {}", kid);
+        }
+        keys.push((kid.to_string(), secret.to_string()));
+    }
+    Ok(keys)
+}
+
+/// Signs tokens with the active key and verifies them with any configured key
+///
+/// Keeping the previous key configured (but not active) for as long as the longest
+/// token lifetime lets the secret be rotated without logging everyone out.
+pub struct JwtManager {
+    keys: Vec<JwtKey>,
+    active: usize,
     access_token_expiry: i64,
     refresh_token_expiry: i64,
 }
 
 impl JwtManager {
+    /// Manager with a single secret, used under `DEFAULT_KID`
     pub fn new(secret: String, access_token_expiry: i64, refresh_token_expiry: i64) -> Self {
         Self {
-            secret,
+            keys: vec![JwtKey {
+                kid: DEFAULT_KID.to_string(),
+                secret,
+            }],
+            active: 0,
             access_token_expiry,
             refresh_token_expiry,
         }
     }
 
-    pub fn generate_access_token(&self, user_id: Uuid) -> Result<String, jsonwebtoken::errors::Error> {
+    /// Manager with several `(kid, secret)` keys, signing with `active_kid`
+    pub fn with_keys(
+        keys: Vec<(String, String)>,
+        active_kid: &str,
+        access_token_expiry: i64,
+        refresh_token_expiry: i64,
+    ) -> Result<Self> {
+        let active = keys
+            .iter()
+            .position(|(kid, _)| kid == active_kid)
+            .ok_or_else(|| anyhow::anyhow!("Active JWT kid {} is not configured", active_kid))?;
+
+        Ok(Self {
+            keys: keys
+                .into_iter()
+                .map(|(kid, secret)| JwtKey { kid, secret })
+                .collect(),
+            active,
+            access_token_expiry,
+            refresh_token_expiry,
+        })
+    }
+
+    /// Lifetime of refresh tokens (and the sessions that hold them), in seconds
+    pub fn refresh_token_expiry(&self) -> i64 {
+        self.refresh_token_expiry
+    }
+
+    /// Encode claims with the active key, naming it in the header
+    fn sign(&self, claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
+        let key = &self.keys[self.active];
+        let header = Header {
+            kid: Some(key.kid.clone()),
+            ..Header::default()
+        };
+        encode(&header, claims, &EncodingKey::from_secret(key.secret.as_bytes()))
+    }
+
+    pub fn generate_access_token(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
         let now = Utc::now();
         let expiry = now + Duration::seconds(self.access_token_expiry);
 
@@ -41,13 +126,10 @@ impl JwtManager {
             exp: expiry.timestamp(),
             iat: now.timestamp(),
             token_type: TokenType::Access,
+            sid: Some(session_id.to_string()),
         };
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.secret.as_bytes()),
-        )
+        self.sign(&claims)
     }
 
     pub fn generate_refresh_token(&self, user_id: Uuid) -> Result<String, jsonwebtoken::errors::Error> {
@@ -59,22 +141,113 @@ impl JwtManager {
             exp: expiry.timestamp(),
             iat: now.timestamp(),
             token_type: TokenType::Refresh,
+            sid: None,
         };
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.secret.as_bytes()),
-        )
+        self.sign(&claims)
     }
 
+    /// Verify a token with the key named by its `kid`
+    ///
+    /// Tokens issued before key IDs were added carry no `kid`; those are tried
+    /// against every configured key.
     pub fn verify_token(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(self.secret.as_bytes()),
-            &Validation::default(),
-        )?;
+        let header = decode_header(token)?;
+
+        let candidates: Vec<&JwtKey> = match header.kid {
+            Some(kid) => self.keys.iter().filter(|key| key.kid == kid).collect(),
+            None => self.keys.iter().collect(),
+        };
+
+        let mut last_error = jsonwebtoken::errors::Error::from(ErrorKind::InvalidToken);
+        for key in candidates {
+            match decode::<Claims>(
+                token,
+                &DecodingKey::from_secret(key.secret.as_bytes()),
+                &Validation::default(),
+            ) {
+                Ok(token_data) => return Ok(token_data.claims),
+                Err(e) => last_error = e,
+            }
+        }
+
+        Err(last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> Vec<(String, String)> {
+        vec![
+            ("old".to_string(), "old-secret".to_string()),
+            ("new".to_string(), "new-secret".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_token_signed_with_old_kid_still_verifies() {
+        let before_rotation = JwtManager::with_keys(keys(), "old", 900, 3600).unwrap();
+        let after_rotation = JwtManager::with_keys(keys(), "new", 900, 3600).unwrap();
+        let user_id = Uuid::new_v4();
+
+        let token = before_rotation.generate_refresh_token(user_id).unwrap();
+        let claims = after_rotation.verify_token(&token).unwrap();
+        assert_eq!(claims.sub, user_id.to_string());
+        assert_eq!(claims.token_type, TokenType::Refresh);
+    }
+
+    #[test]
+    fn test_new_tokens_use_active_kid() {
+        let manager = JwtManager::with_keys(keys(), "new", 900, 3600).unwrap();
+        let token = manager
+            .generate_access_token(Uuid::new_v4(), Uuid::new_v4())
+            .unwrap();
+        assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("new"));
+
+        // Only the active secret can have produced it
+        let old_only = JwtManager::with_keys(
+            vec![("new".to_string(), "old-secret".to_string())],
+            "new",
+            900,
+            3600,
+        )
+        .unwrap();
+        assert!(old_only.verify_token(&token).is_err());
+    }
+
+    #[test]
+    fn test_unknown_kid_is_rejected() {
+        let retired = JwtManager::with_keys(
+            vec![("retired".to_string(), "new-secret".to_string())],
+            "retired",
+            900,
+            3600,
+        )
+        .unwrap();
+        let token = retired.generate_refresh_token(Uuid::new_v4()).unwrap();
+
+        // Same secret, but the kid is no longer configured
+        let manager = JwtManager::with_keys(keys(), "new", 900, 3600).unwrap();
+        let err = manager.verify_token(&token).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::InvalidToken), "{:?}", err);
+    }
+
+    #[test]
+    fn test_active_kid_must_be_configured() {
+        assert!(JwtManager::with_keys(keys(), "missing", 900, 3600).is_err());
+    }
 
-        Ok(token_data.claims)
+    #[test]
+    fn test_parse_jwt_secrets() {
+        let keys = parse_jwt_secrets(" old:one , new:two,").unwrap();
+        assert_eq!(
+            keys,
+            vec![("old".to_string(), "one".to_string()), ("new".to_string(), "two".to_string())]
+        );
+        assert!(parse_jwt_secrets("old:one,old:two").is_err());
+        assert!(parse_jwt_secrets("no-separator").is_err());
+        assert!(parse_jwt_secrets("kid:").is_err());
     }
 }
@@ -1,3 +1,9 @@
+pub mod email;
+pub mod env;
 pub mod jwt;
+pub mod oauth;
+pub mod price;
+pub mod token;
+pub mod webhook;
 
 pub use jwt::*;
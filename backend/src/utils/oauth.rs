@@ -0,0 +1,399 @@
+use crate::models::user::AuthMethod;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use std::future::Future;
+
+/// Google's signing keys for `id_token`s
+const GOOGLE_CERTS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+
+/// Issuers Google puts in `id_token`s
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+
+/// GitHub's REST API rejects requests without a User-Agent
+const GITHUB_USER_AGENT: &str = "shield-backend";
+
+/// Tokens returned by a provider's code exchange; which ones are set depends on the provider
+#[derive(Debug, Deserialize)]
+pub struct OAuthTokens {
+    pub access_token: Option<String>,
+    pub id_token: Option<String>,
+}
+
+/// Verified identity of the user signing in
+#[derive(Debug)]
+pub struct OAuthUserInfo {
+    pub email: String,
+    pub name: Option<String>,
+}
+
+/// An external identity provider for the authorization code flow
+///
+/// Implementations must only return emails the provider has verified, since accounts
+/// are matched by email.
+pub trait OAuthProvider: Send + Sync {
+    /// Stored in `users.auth_method` for accounts created through this provider
+    fn auth_method(&self) -> AuthMethod;
+
+    /// URL the user is sent to, carrying the single-use `state` and `nonce`
+    fn authorize_url(&self, state: &str, nonce: &str) -> String;
+
+    /// Exchange the authorization code from the callback for tokens
+    fn exchange_code(
+        &self,
+        client: &reqwest::Client,
+        code: &str,
+    ) -> impl Future<Output = Result<OAuthTokens>> + Send;
+
+    /// Identify the user from the exchanged tokens
+    fn fetch_user_info(
+        &self,
+        client: &reqwest::Client,
+        tokens: &OAuthTokens,
+        nonce: &str,
+    ) -> impl Future<Output = Result<OAuthUserInfo>> + Send;
+}
+
+/// Client credentials for one provider, read from `<PREFIX>_CLIENT_ID`,
+/// `<PREFIX>_CLIENT_SECRET` and `<PREFIX>_REDIRECT_URI`
+struct ClientConfig {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+}
+
+impl ClientConfig {
+    fn from_env(prefix: &str, default_redirect_uri: &str) -> Option<Self> {
+        let var = |name: &str| {
+            std::env::var(format!("{}_{}", prefix, name))
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+        };
+        Some(Self {
+            client_id: var("CLIENT_ID")?,
+            client_secret: var("CLIENT_SECRET")?,
+            redirect_uri: var("REDIRECT_URI").unwrap_or_else(|| default_redirect_uri.to_string()),
+        })
+    }
+}
+
+/// Sign in with Google (OpenID Connect)
+pub struct GoogleProvider {
+    config: ClientConfig,
+}
+
+impl GoogleProvider {
+    /// `None` if `GOOGLE_CLIENT_ID` or `GOOGLE_CLIENT_SECRET` is unset
+    pub fn from_env() -> Option<Self> {
+        ClientConfig::from_env("GOOGLE", "http://localhost:8000/api/auth/google/callback")
+            .map(|config| Self { config })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleIdClaims {
+    email: String,
+    name: Option<String>,
+    email_verified: Option<bool>,
+    nonce: Option<String>,
+}
+
+impl OAuthProvider for GoogleProvider {
+    fn auth_method(&self) -> AuthMethod {
+        AuthMethod::Google
+    }
+
+    fn authorize_url(&self, state: &str, nonce: &str) -> String {
+        format!(
+            "https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri={}&response_type=code&scope=openid%20email%20profile&access_type=offline&state={}&nonce={}",
+            urlencoding::encode(&self.config.client_id),
+            urlencoding::encode(&self.config.redirect_uri),
+            state,
+            nonce
+        )
+    }
+
+    async fn exchange_code(&self, client: &reqwest::Client, code: &str) -> Result<OAuthTokens> {
+        client
+            .post("https://oauth2.googleapis.com/token")
+            .form(&[
+                ("code", code),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("redirect_uri", &self.config.redirect_uri),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await
+            .context("Failed to exchange code")?
+            .error_for_status()
+            .context("Google rejected the authorization code")?
+            .json::<OAuthTokens>()
+            .await
+            .context("Failed to parse token response")
+    }
+
+    /// Verify the `id_token` rather than trusting an unauthenticated userinfo lookup
+    ///
+    /// Checks the signature against Google's published keys, the audience, issuer and
+    /// expiry, and that the nonce matches the one issued with the sign-in's state.
+    async fn fetch_user_info(
+        &self,
+        client: &reqwest::Client,
+        tokens: &OAuthTokens,
+        nonce: &str,
+    ) -> Result<OAuthUserInfo> {
+        let id_token = tokens
+            .id_token
+            .as_deref()
+            .ok_or_else(|| anyhow!("Google did not return an id_token"))?;
+
+        let kid = jsonwebtoken::decode_header(id_token)?
+            .kid
+            .ok_or_else(|| anyhow!("Google id_token has no key id"))?;
+
+        let keys = client
+            .get(GOOGLE_CERTS_URL)
+            .send()
+            .await
+            .context("Failed to fetch Google signing keys")?
+            .json::<jsonwebtoken::jwk::JwkSet>()
+            .await
+            .context("Failed to parse Google signing keys")?;
+        let jwk = keys
+            .find(&kid)
+            .ok_or_else(|| anyhow!("Google id_token signed with an unknown key"))?;
+
+        let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_issuer(&GOOGLE_ISSUERS);
+
+        let claims = jsonwebtoken::decode::<GoogleIdClaims>(
+            id_token,
+            &jsonwebtoken::DecodingKey::from_jwk(jwk)?,
+            &validation,
+        )
+        .context("Invalid Google id_token")?
+        .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            bail!("Google id_token nonce mismatch");
+        }
+        if claims.email_verified != Some(true) {
+            bail!("Google account email is not verified");
+        }
+
+        Ok(OAuthUserInfo {
+            email: claims.email,
+            name: claims.name,
+        })
+    }
+}
+
+/// Sign in with GitHub (OAuth apps)
+pub struct GitHubProvider {
+    config: ClientConfig,
+}
+
+impl GitHubProvider {
+    /// `None` if `GITHUB_CLIENT_ID` or `GITHUB_CLIENT_SECRET` is unset
+    pub fn from_env() -> Option<Self> {
+        ClientConfig::from_env("GITHUB", "http://localhost:8000/api/auth/github/callback")
+            .map(|config| Self { config })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    login: String,
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// The account's primary email, if GitHub has verified it
+fn primary_verified_email(emails: Vec<GitHubEmail>) -> Result<String> {
+    emails
+        .into_iter()
+        .find(|email| email.primary && email.verified)
+        .map(|email| email.email)
+        .ok_or_else(|| anyhow!("GitHub account has no verified primary email"))
+}
+
+impl OAuthProvider for GitHubProvider {
+    fn auth_method(&self) -> AuthMethod {
+        AuthMethod::GitHub
+    }
+
+    /// GitHub has no OpenID nonce; the state alone protects the flow
+    fn authorize_url(&self, state: &str, _nonce: &str) -> String {
+        format!(
+            "https://github.com/login/oauth/authorize?client_id={}&redirect_uri={}&scope=read%3Auser%20user%3Aemail&state={}",
+            urlencoding::encode(&self.config.client_id),
+            urlencoding::encode(&self.config.redirect_uri),
+            state
+        )
+    }
+
+    async fn exchange_code(&self, client: &reqwest::Client, code: &str) -> Result<OAuthTokens> {
+        let tokens = client
+            .post("https://github.com/login/oauth/access_token")
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&[
+                ("code", code),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("redirect_uri", &self.config.redirect_uri),
+            ])
+            .send()
+            .await
+            .context("Failed to exchange code")?
+            .error_for_status()
+            .context("GitHub rejected the authorization code")?
+            .json::<OAuthTokens>()
+            .await
+            .context("Failed to parse token response")?;
+
+        // GitHub reports a bad code with 200 and an error body, so check for the token
+        if tokens.access_token.is_none() {
+            bail!("GitHub did not return an access token");
+        }
+        Ok(tokens)
+    }
+
+    /// Uses the primary email, and only if GitHub has verified it
+    async fn fetch_user_info(
+        &self,
+        client: &reqwest::Client,
+        tokens: &OAuthTokens,
+        _nonce: &str,
+    ) -> Result<OAuthUserInfo> {
+        let access_token = tokens
+            .access_token
+            .as_deref()
+            .ok_or_else(|| anyhow!("GitHub did not return an access token"))?;
+
+        let user = client
+            .get("https://api.github.com/user")
+            .bearer_auth(access_token)
+            .header(reqwest::header::USER_AGENT, GITHUB_USER_AGENT)
+            .send()
+            .await
+            .context("Failed to get GitHub user")?
+            .error_for_status()
+            .context("GitHub rejected the access token")?
+            .json::<GitHubUser>()
+            .await
+            .context("Failed to parse GitHub user")?;
+
+        let emails = client
+            .get("https://api.github.com/user/emails")
+            .bearer_auth(access_token)
+            .header(reqwest::header::USER_AGENT, GITHUB_USER_AGENT)
+            .send()
+            .await
+            .context("Failed to get GitHub emails")?
+            .error_for_status()
+            .context("GitHub rejected the access token")?
+            .json::<Vec<GitHubEmail>>()
+            .await
+            .context("Failed to parse GitHub emails")?;
+
+        Ok(OAuthUserInfo {
+            email: primary_verified_email(emails)?,
+            name: user.name.filter(|name| !name.is_empty()).or(Some(user.login)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ClientConfig {
+        ClientConfig {
+            client_id: "id with space&more".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: "https://shield.example/api/auth/callback?x=1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_google_authorize_url_encodes_parameters() {
+        let url = GoogleProvider { config: config() }.authorize_url("state123", "nonce456");
+        assert!(url.starts_with("https://accounts.google.com/o/oauth2/v2/auth?"));
+        assert!(url.contains("client_id=id%20with%20space%26more&"));
+        assert!(url.contains("redirect_uri=https%3A%2F%2Fshield.example%2Fapi%2Fauth%2Fcallback%3Fx%3D1&"));
+        assert!(url.contains("scope=openid%20email%20profile"));
+        assert!(url.contains("&state=state123&nonce=nonce456"));
+    }
+
+    #[test]
+    fn test_github_authorize_url_encodes_parameters_without_nonce() {
+        let url = GitHubProvider { config: config() }.authorize_url("state123", "nonce456");
+        assert!(url.starts_with("https://github.com/login/oauth/authorize?"));
+        assert!(url.contains("client_id=id%20with%20space%26more&"));
+        assert!(url.contains("redirect_uri=https%3A%2F%2Fshield.example%2Fapi%2Fauth%2Fcallback%3Fx%3D1&"));
+        assert!(url.contains("scope=read%3Auser%20user%3Aemail"));
+        assert!(url.ends_with("&state=state123"));
+        assert!(!url.contains("nonce456"));
+    }
+
+    // Each test uses its own variable prefix, so they don't race with each other
+    #[test]
+    fn test_client_config_requires_non_blank_credentials() {
+        std::env::set_var("SHIELD_TEST_BLANK_CLIENT_ID", "id");
+        std::env::set_var("SHIELD_TEST_BLANK_CLIENT_SECRET", "   ");
+        assert!(ClientConfig::from_env("SHIELD_TEST_BLANK", "https://default").is_none());
+
+        assert!(ClientConfig::from_env("SHIELD_TEST_UNSET", "https://default").is_none());
+    }
+
+    #[test]
+    fn test_client_config_defaults_redirect_uri() {
+        std::env::set_var("SHIELD_TEST_SET_CLIENT_ID", "id");
+        std::env::set_var("SHIELD_TEST_SET_CLIENT_SECRET", "secret");
+        std::env::set_var("SHIELD_TEST_SET_REDIRECT_URI", "");
+
+        let config = ClientConfig::from_env("SHIELD_TEST_SET", "https://default").unwrap();
+        assert_eq!(config.client_id, "id");
+        assert_eq!(config.client_secret, "secret");
+        assert_eq!(config.redirect_uri, "https://default");
+    }
+
+    #[test]
+    fn test_auth_method_round_trip() {
+        for method in [AuthMethod::Google, AuthMethod::GitHub, AuthMethod::Email] {
+            assert_eq!(AuthMethod::from_str(method.as_str()), method);
+        }
+        assert_eq!(AuthMethod::GitHub.as_str(), "github");
+        assert_eq!(AuthMethod::from_str("GitHub"), AuthMethod::GitHub);
+        assert_eq!(serde_json::to_string(&AuthMethod::GitHub).unwrap(), "\"github\"");
+    }
+
+    #[test]
+    fn test_github_uses_primary_verified_email() {
+        let email = |email: &str, primary, verified| GitHubEmail {
+            email: email.to_string(),
+            primary,
+            verified,
+        };
+
+        let emails = vec![
+            email("other@example.com", false, true),
+            email("primary@example.com", true, true),
+        ];
+        assert_eq!(primary_verified_email(emails).unwrap(), "primary@example.com");
+
+        // An unverified primary isn't replaced by another verified address
+        let emails = vec![
+            email("primary@example.com", true, false),
+            email("other@example.com", false, true),
+        ];
+        assert!(primary_verified_email(emails).is_err());
+    }
+}
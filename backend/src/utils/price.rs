@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::env::env_or;
+
+/// How long a fetched price is reused, unless overridden by `PRICE_CACHE_SECS`
+const DEFAULT_PRICE_CACHE_SECS: u64 = 60;
+
+/// How long to wait before asking the source again after a failed fetch, so an outage
+/// doesn't add a timeout to every request
+const FAILED_FETCH_RETRY: Duration = Duration::from_secs(15);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where ZEC prices are fetched from, chosen with `PRICE_SOURCE` (`coingecko` or `coinbase`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PriceSource {
+    CoinGecko,
+    Coinbase,
+}
+
+impl PriceSource {
+    fn from_env() -> Self {
+        match std::env::var("PRICE_SOURCE").map(|v| v.trim().to_lowercase()).as_deref() {
+            Ok("coinbase") => PriceSource::Coinbase,
+            _ => PriceSource::CoinGecko,
+        }
+    }
+
+    async fn fetch(self, client: &reqwest::Client, currency: &str) -> Result<f64> {
+        match self {
+            PriceSource::CoinGecko => {
+                let prices = client
+                    .get("https://api.coingecko.com/api/v3/simple/price")
+                    .query(&[("ids", "zcash"), ("vs_currencies", currency)])
+                    .send()
+                    .await
+                    .context("Failed to reach CoinGecko")?
+                    .error_for_status()
+                    .context("CoinGecko rejected the price request")?
+                    .json::<HashMap<String, HashMap<String, f64>>>()
+                    .await
+                    .context("Failed to parse CoinGecko prices")?;
+
+                prices
+                    .get("zcash")
+                    .and_then(|by_currency| by_currency.get(currency))
+                    .copied()
+                    .ok_or_else(|| anyhow!("CoinGecko has no ZEC price in {}", currency))
+            }
+            PriceSource::Coinbase => {
+                #[derive(Deserialize)]
+                struct SpotPrice {
+                    amount: String,
+                }
+                #[derive(Deserialize)]
+                struct SpotResponse {
+                    data: SpotPrice,
+                }
+
+                let spot = client
+                    .get(format!(
+                        "https://api.coinbase.com/v2/prices/ZEC-{}/spot",
+                        currency.to_uppercase()
+                    ))
+                    .send()
+                    .await
+                    .context("Failed to reach Coinbase")?
+                    .error_for_status()
+                    .context("Coinbase rejected the price request")?
+                    .json::<SpotResponse>()
+                    .await
+                    .context("Failed to parse Coinbase price")?;
+
+                spot.data
+                    .amount
+                    .parse()
+                    .context("Coinbase returned a non-numeric price")
+            }
+        }
+    }
+}
+
+// Last fetch per currency: when it happened and the price, or `None` if it failed
+static PRICE_CACHE: Lazy<Mutex<HashMap<String, (Instant, Option<f64>)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn price_cache_ttl() -> Duration {
+    Duration::from_secs(env_or("PRICE_CACHE_SECS", DEFAULT_PRICE_CACHE_SECS))
+}
+
+/// What a cache entry (when it was fetched, and the price or `None` on failure) answers
+/// at `now`: `Some` if it can be served as is, `None` if the price should be fetched again
+fn cached_price(entry: Option<(Instant, Option<f64>)>, now: Instant, ttl: Duration) -> Option<Option<f64>> {
+    match entry {
+        Some((fetched_at, Some(price))) if now.duration_since(fetched_at) < ttl => Some(Some(price)),
+        Some((fetched_at, None)) if now.duration_since(fetched_at) < FAILED_FETCH_RETRY => Some(None),
+        _ => None,
+    }
+}
+
+/// Current price of one ZEC in `currency` (e.g. `usd`)
+///
+/// Prices are cached for `PRICE_CACHE_SECS`. Returns `None` if the source is unavailable,
+/// so callers can leave fiat values out instead of failing the request.
+pub async fn zec_price(currency: &str) -> Option<f64> {
+    let currency = currency.to_lowercase();
+    let now = Instant::now();
+
+    let cached = PRICE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&currency)
+        .copied();
+    if let Some(price) = cached_price(cached, now, price_cache_ttl()) {
+        return price;
+    }
+
+    let source = PriceSource::from_env();
+    let result = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => source.fetch(&client, &currency).await,
+        Err(e) => Err(anyhow!("Failed to build HTTP client: {}", e)),
+    };
+    let price = match result {
+        Ok(price) if price.is_finite() && price > 0.0 => Some(price),
+        Ok(price) => {
+            tracing::warn!("Ignoring invalid ZEC/{} price {} from {:?}", currency, price, source);
+            None
+        }
+        Err(e) => {
+            tracing::warn!("Failed to fetch ZEC/{} price from {:?}: {:#}", currency, source, e);
+            None
+        }
+    };
+
+    PRICE_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(currency, (Instant::now(), price));
+    price
+}
+
+/// Value of `zatoshis` at `price` per ZEC, rounded to cents
+pub fn format_fiat(zatoshis: i64, price: f64) -> String {
+    format!("{:.2}", zatoshis as f64 / 100_000_000.0 * price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_fiat_rounds_to_cents() {
+        assert_eq!(format_fiat(100_000_000, 42.5), "42.50");
+        assert_eq!(format_fiat(12_345_678, 30.0), "3.70");
+        assert_eq!(format_fiat(0, 30.0), "0.00");
+        assert_eq!(format_fiat(-50_000_000, 30.0), "-15.00");
+    }
+
+    #[test]
+    fn test_cached_price_is_reused_until_ttl() {
+        let ttl = Duration::from_secs(60);
+        let fetched_at = Instant::now();
+
+        assert_eq!(cached_price(None, fetched_at, ttl), None);
+        assert_eq!(
+            cached_price(Some((fetched_at, Some(30.0))), fetched_at + Duration::from_secs(59), ttl),
+            Some(Some(30.0))
+        );
+        assert_eq!(cached_price(Some((fetched_at, Some(30.0))), fetched_at + ttl, ttl), None);
+    }
+
+    #[test]
+    fn test_failed_fetch_backs_off() {
+        let ttl = Duration::from_secs(60);
+        let failed_at = Instant::now();
+
+        // A recent failure is answered without asking the source again
+        assert_eq!(
+            cached_price(Some((failed_at, None)), failed_at + Duration::from_secs(1), ttl),
+            Some(None)
+        );
+        // After the back-off (shorter than the price TTL) the source is retried
+        assert_eq!(cached_price(Some((failed_at, None)), failed_at + FAILED_FETCH_RETRY, ttl), None);
+    }
+}
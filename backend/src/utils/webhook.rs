@@ -0,0 +1,199 @@
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// Header carrying the hex HMAC-SHA256 of the request body, keyed with the webhook secret
+pub const SIGNATURE_HEADER: &str = "X-Shield-Signature";
+
+/// Delivery attempts before a notification is dropped
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry; doubled after each failed attempt
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Body POSTed to a user's webhook when a received note is first seen
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceivedFundsPayload {
+    pub user_id: Uuid,
+    pub txid: String,
+    pub amount_zec: String,
+    pub confirmations: u64,
+}
+
+/// Hex HMAC-SHA256 of `body` under `secret`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Check that `url` is an https URL whose host resolves only to public addresses
+///
+/// Webhooks are fetched from the server, so a URL pointing at loopback, a private network
+/// or the cloud metadata endpoint would let users probe internal services.
+pub async fn check_url(url: &str) -> Result<()> {
+    let parsed = Url::parse(url).context("Invalid webhook URL")?;
+    if parsed.scheme() != "https" {
+        anyhow::bail!("Webhook URL must use https");
+    }
+    let host = parsed.host_str().context("Webhook URL has no host")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = parsed.port_or_known_default().unwrap_or(443);
+
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port))
+        .await
+        .with_context(|| format!("Failed to resolve webhook host {}", host))?
+        .collect();
+    if addrs.is_empty() {
+        anyhow::bail!("Webhook host {} did not resolve", host);
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        anyhow::bail!("Webhook host {} resolves to non-public address {}", host, addr.ip());
+    }
+    Ok(())
+}
+
+/// False for loopback, private, link-local, CGNAT and other non-routable addresses
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// POST `payload` to `url`, retrying with exponential backoff
+///
+/// Any non-2xx response counts as a failed delivery. The URL is checked again before
+/// sending, since its DNS may have changed since registration.
+pub async fn deliver(url: &str, secret: &str, payload: &ReceivedFundsPayload) -> Result<()> {
+    check_url(url).await?;
+
+    let body = serde_json::to_vec(payload).context("Failed to serialize webhook payload")?;
+    let signature = sign(secret, &body);
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to build HTTP client")?;
+
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let result = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Webhook delivery to {} failed (attempt {}/{}): {}, retrying in {:?}",
+                    url,
+                    attempt,
+                    MAX_ATTEMPTS,
+                    e,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Webhook delivery to {} failed after {} attempts", url, MAX_ATTEMPTS)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_matches_known_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_sign_depends_on_secret_and_body() {
+        let signature = sign("secret", b"{}");
+        assert_eq!(signature.len(), 64);
+        assert_ne!(signature, sign("other", b"{}"));
+        assert_ne!(signature, sign("secret", b"{ }"));
+    }
+
+    #[test]
+    fn test_is_public_ip_rejects_internal_ranges() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} should be rejected", ip);
+        }
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_url_rejects_http_and_internal_hosts() {
+        assert!(check_url("http://example.com/hook").await.is_err());
+        assert!(check_url("https://127.0.0.1/hook").await.is_err());
+        assert!(check_url("https://[::1]/hook").await.is_err());
+        assert!(check_url("https://169.254.169.254/latest/meta-data").await.is_err());
+        assert!(check_url("not a url").await.is_err());
+    }
+}
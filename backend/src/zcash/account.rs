@@ -7,16 +7,16 @@ use zcash_keys::keys::UnifiedSpendingKey;
 use zcash_protocol::consensus::{Network, Parameters};
 
 use super::database::Database;
-use super::lightwalletd::LightwalletdClient;
+use super::block_source::CompactBlockSource;
 
 /// Account manager for creating and managing Zcash accounts
-pub struct AccountManager {
-    db: Database,
+pub struct AccountManager<'a> {
+    db: &'a mut Database,
 }
 
-impl AccountManager {
-    /// Create a new account manager
-    pub fn new(db: Database) -> Self {
+impl<'a> AccountManager<'a> {
+    /// Create a new account manager borrowing an open wallet database
+    pub fn new(db: &'a mut Database) -> Self {
         Self { db }
     }
 
@@ -34,7 +34,7 @@ impl AccountManager {
         &mut self,
         account_name: &str,
         seed: &[u8],
-        lightwalletd: &LightwalletdClient,
+        lightwalletd: &impl CompactBlockSource,
         birthday_height: Option<u32>,
     ) -> Result<(AccountUuid, UnifiedSpendingKey)> {
         let network = self.db.network();
@@ -51,17 +51,17 @@ impl AccountManager {
             u32::from(network.activation_height(zcash_protocol::consensus::NetworkUpgrade::Sapling).unwrap())
         });
 
-        println!("  Creating account with birthday height: {}", effective_birthday);
+        tracing::info!("Creating account with birthday height: {}", effective_birthday);
 
         // Fetch tree state from the block BEFORE the birthday height
         // This is critical - we need tree state at (height - 1), not (height)
         let tree_state_height = effective_birthday.saturating_sub(1);
-        println!("  Fetching tree state at height: {} (birthday - 1)", tree_state_height);
+        tracing::debug!("Fetching tree state at height: {} (birthday - 1)", tree_state_height);
 
         let tree_state = lightwalletd.get_tree_state(tree_state_height as u64).await
             .context(format!("Failed to fetch tree state at height {}", tree_state_height))?;
 
-        println!("  ✓ Fetched tree state from lightwalletd");
+        tracing::debug!("Fetched tree state from lightwalletd");
 
         // Create birthday from tree state
         let birthday = AccountBirthday::from_treestate(tree_state, None)
@@ -80,7 +80,7 @@ impl AccountManager {
             let _ = conn.execute("DELETE FROM sapling_tree_checkpoint_marks_removed", []);
             let _ = conn.execute("DELETE FROM orchard_tree_checkpoints", []);
             let _ = conn.execute("DELETE FROM orchard_tree_checkpoint_marks_removed", []);
-            println!("  ✓ Cleared existing checkpoints before account creation");
+            tracing::debug!("Cleared existing checkpoints before account creation");
         }
 
         // Get mutable database handle
@@ -94,7 +94,7 @@ impl AccountManager {
             None, // key_source (optional metadata)
         )?;
 
-        println!("  ✓ Account created with proper tree state initialization");
+        tracing::info!("Account created with proper tree state initialization");
 
         Ok((account_id, usk))
     }
@@ -107,7 +107,7 @@ impl AccountManager {
         &mut self,
         account_name: &str,
         seed: &[u8],
-        lightwalletd: &LightwalletdClient,
+        lightwalletd: &impl CompactBlockSource,
         account_index: u32,
         birthday_height: Option<u32>,
     ) -> Result<(Account, UnifiedSpendingKey)> {
@@ -118,15 +118,15 @@ impl AccountManager {
             u32::from(network.activation_height(zcash_protocol::consensus::NetworkUpgrade::Sapling).unwrap())
         });
 
-        println!("  Importing account with birthday height: {}", effective_birthday);
+        tracing::info!("Importing account with birthday height: {}", effective_birthday);
 
         let tree_state_height = effective_birthday.saturating_sub(1);
-        println!("  Fetching tree state at height: {} (birthday - 1)", tree_state_height);
+        tracing::debug!("Fetching tree state at height: {} (birthday - 1)", tree_state_height);
 
         let tree_state = lightwalletd.get_tree_state(tree_state_height as u64).await
             .context(format!("Failed to fetch tree state at height {}", tree_state_height))?;
 
-        println!("  ✓ Fetched tree state from lightwalletd");
+        tracing::debug!("Fetched tree state from lightwalletd");
 
         let birthday = AccountBirthday::from_treestate(tree_state, None)
             .map_err(|_| anyhow::anyhow!("Failed to create birthday from tree state"))?;
@@ -144,7 +144,7 @@ impl AccountManager {
             None,
         )?;
 
-        println!("  ✓ Account imported with proper tree state initialization");
+        tracing::info!("Account imported with proper tree state initialization");
 
         Ok((account_id, usk))
     }
@@ -160,11 +160,16 @@ impl AccountManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::wallet::Wallet;
+    use super::super::block_source::mock::MockLightwalletd;
+    use super::super::wallet::Wallet;
     use tempfile::TempDir;
+    use zcash_client_backend::data_api::Account as _;
 
-    #[test]
-    fn test_create_account() {
+    /// Birthday of the test accounts (testnet, after Sapling activation)
+    const BIRTHDAY: u32 = 300_000;
+
+    #[tokio::test]
+    async fn test_create_account() {
         let temp_dir = TempDir::new().unwrap();
         let db_path = temp_dir.path().join("test_wallet.db");
 
@@ -173,38 +178,62 @@ mod tests {
         let seed = mnemonic.to_seed("");
 
         // Initialize database
-        let db = Database::new(&db_path, Network::TestNetwork).unwrap();
-        let mut account_mgr = AccountManager::new(db);
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+        let mut account_mgr = AccountManager::new(&mut db);
+        let chain = MockLightwalletd::starting_at(u64::from(BIRTHDAY) - 1);
 
         // Create account
-        let result = account_mgr.create_account("Test Account", &seed, None);
-        assert!(result.is_ok());
-
-        let (account_id, _usk) = result.unwrap();
-        println!("Created account: {:?}", account_id);
-        println!("USK derived successfully");
+        let (account_id, usk) = account_mgr
+            .create_account("Test Account", &seed, &chain, Some(BIRTHDAY))
+            .await
+            .unwrap();
+        // The first account created from a seed is ZIP-32 account 0
+        let expected_usk =
+            UnifiedSpendingKey::from_seed(&Network::TestNetwork, &seed, zip32::AccountId::ZERO).unwrap();
+        assert_eq!(
+            usk.to_unified_full_viewing_key().encode(&Network::TestNetwork),
+            expected_usk.to_unified_full_viewing_key().encode(&Network::TestNetwork)
+        );
 
         // Verify account is in database
-        let accounts = account_mgr.list_account_ids().unwrap();
-        assert_eq!(accounts.len(), 1);
+        assert_eq!(account_mgr.list_account_ids().unwrap(), vec![account_id]);
+    }
+
+    #[tokio::test]
+    async fn test_create_account_needs_tree_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test_wallet.db");
+
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+        let mut account_mgr = AccountManager::new(&mut db);
+        // The chain starts after the block the birthday's tree state comes from
+        let chain = MockLightwalletd::starting_at(u64::from(BIRTHDAY) + 1);
+
+        assert!(account_mgr
+            .create_account("Test Account", &[7u8; 32], &chain, Some(BIRTHDAY))
+            .await
+            .is_err());
+        assert!(account_mgr.list_account_ids().unwrap().is_empty());
     }
 
-    #[test]
-    fn test_import_account_hd() {
+    #[tokio::test]
+    async fn test_import_account_hd() {
         let temp_dir = TempDir::new().unwrap();
         let db_path = temp_dir.path().join("test_import.db");
 
         let (_wallet, mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
         let seed = mnemonic.to_seed("");
 
-        let db = Database::new(&db_path, Network::TestNetwork).unwrap();
-        let mut account_mgr = AccountManager::new(db);
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+        let mut account_mgr = AccountManager::new(&mut db);
+        let chain = MockLightwalletd::starting_at(u64::from(BIRTHDAY) - 1);
 
         // Import account with specific index
-        let result = account_mgr.import_account_hd("Imported Account", &seed, 0, None);
-        assert!(result.is_ok());
+        let (account, _usk) = account_mgr
+            .import_account_hd("Imported Account", &seed, &chain, 1, Some(BIRTHDAY))
+            .await
+            .unwrap();
 
-        let accounts = account_mgr.list_account_ids().unwrap();
-        assert_eq!(accounts.len(), 1);
+        assert_eq!(account_mgr.list_account_ids().unwrap(), vec![account.id()]);
     }
 }
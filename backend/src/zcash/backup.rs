@@ -0,0 +1,180 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// Current backup format version
+const BACKUP_VERSION: u32 = 1;
+
+/// Associated data bound into every ciphertext so blobs can't be reused in another context
+const BACKUP_AAD: &[u8] = b"shield-wallet-backup-v1";
+
+/// Shortest passphrase accepted when creating a backup
+pub const MIN_PASSPHRASE_LEN: usize = 12;
+
+/// Argon2id cost parameters used for new backups (OWASP recommended minimums)
+const ARGON2_M_COST_KIB: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+/// Upper bounds on costs read from a blob, so a crafted backup can't exhaust the server
+const MAX_M_COST_KIB: u32 = 256 * 1024;
+const MAX_T_COST: u32 = 10;
+const MAX_P_COST: u32 = 4;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Wallet secrets stored inside a backup
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupContents {
+    pub mnemonic: String,
+    pub birthday_height: u32,
+    /// `mainnet` or `testnet`, as stored in `wallets.network`
+    pub network: String,
+}
+
+/// Passphrase-encrypted wallet backup, safe to hand to the user
+///
+/// The key is derived from the passphrase with Argon2id and the contents sealed with
+/// AES-256-GCM. Binary fields are hex encoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedBackup {
+    pub version: u32,
+    pub kdf: String,
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// Encrypt wallet contents with a user-supplied passphrase
+///
+/// Argon2 is deliberately slow; call this off the async workers.
+pub fn encrypt_backup(contents: &BackupContents, passphrase: &str) -> Result<EncryptedBackup> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        bail!("Backup passphrase must be at least {} characters", MIN_PASSPHRASE_LEN);
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt, ARGON2_M_COST_KIB, ARGON2_T_COST, ARGON2_P_COST)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| anyhow!("Invalid backup key length"))?;
+
+    let plaintext = serde_json::to_vec(contents)?;
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: &plaintext, aad: BACKUP_AAD })
+        .map_err(|_| anyhow!("Failed to encrypt backup"))?;
+
+    Ok(EncryptedBackup {
+        version: BACKUP_VERSION,
+        kdf: "argon2id".to_string(),
+        m_cost: ARGON2_M_COST_KIB,
+        t_cost: ARGON2_T_COST,
+        p_cost: ARGON2_P_COST,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+/// Decrypt a backup produced by `encrypt_backup`
+///
+/// A wrong passphrase and a tampered blob both fail authentication and are reported the
+/// same way.
+pub fn decrypt_backup(backup: &EncryptedBackup, passphrase: &str) -> Result<BackupContents> {
+    if backup.version != BACKUP_VERSION {
+        bail!("Unsupported backup version {}", backup.version);
+    }
+    if backup.kdf != "argon2id" {
+        bail!("Unsupported backup key derivation '{}'", backup.kdf);
+    }
+    if backup.m_cost > MAX_M_COST_KIB || backup.t_cost > MAX_T_COST || backup.p_cost > MAX_P_COST {
+        bail!("Backup key derivation parameters are out of range");
+    }
+
+    let salt = hex::decode(&backup.salt).map_err(|_| anyhow!("Backup salt is not valid hex"))?;
+    let nonce = hex::decode(&backup.nonce).map_err(|_| anyhow!("Backup nonce is not valid hex"))?;
+    let ciphertext =
+        hex::decode(&backup.ciphertext).map_err(|_| anyhow!("Backup ciphertext is not valid hex"))?;
+    if nonce.len() != NONCE_LEN {
+        bail!("Backup nonce must be {} bytes", NONCE_LEN);
+    }
+
+    let key = derive_key(passphrase, &salt, backup.m_cost, backup.t_cost, backup.p_cost)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| anyhow!("Invalid backup key length"))?;
+
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: BACKUP_AAD })
+        .map_err(|_| anyhow!("Wrong passphrase or corrupted backup"))?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// Derive a 256-bit key from the passphrase with Argon2id
+fn derive_key(passphrase: &str, salt: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<[u8; 32]> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+        .map_err(|e| anyhow!("Invalid backup key derivation parameters: {}", e))?;
+
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Failed to derive backup key: {}", e))?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents() -> BackupContents {
+        BackupContents {
+            mnemonic: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string(),
+            birthday_height: 2_500_000,
+            network: "testnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let backup = encrypt_backup(&contents(), "correct horse battery staple").unwrap();
+        assert!(!backup.ciphertext.contains("abandon"));
+
+        let restored = decrypt_backup(&backup, "correct horse battery staple").unwrap();
+        assert_eq!(restored, contents());
+    }
+
+    #[test]
+    fn test_backup_wrong_passphrase() {
+        let backup = encrypt_backup(&contents(), "correct horse battery staple").unwrap();
+        assert!(decrypt_backup(&backup, "incorrect horse battery staple").is_err());
+    }
+
+    #[test]
+    fn test_backup_tampered_ciphertext() {
+        let mut backup = encrypt_backup(&contents(), "correct horse battery staple").unwrap();
+        let mut bytes = hex::decode(&backup.ciphertext).unwrap();
+        bytes[0] ^= 1;
+        backup.ciphertext = hex::encode(bytes);
+
+        assert!(decrypt_backup(&backup, "correct horse battery staple").is_err());
+    }
+
+    #[test]
+    fn test_backup_rejects_short_passphrase_and_excessive_costs() {
+        assert!(encrypt_backup(&contents(), "too short").is_err());
+
+        let mut backup = encrypt_backup(&contents(), "correct horse battery staple").unwrap();
+        backup.m_cost = MAX_M_COST_KIB + 1;
+        assert!(decrypt_backup(&backup, "correct horse battery staple").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use anyhow::{Context, Result};
+use std::future::Future;
+use std::pin::Pin;
+use tokio_stream::Stream;
+use zcash_client_backend::proto::compact_formats::CompactBlock;
+use zcash_client_backend::proto::service::TreeState;
+
+use super::lightwalletd::LightwalletdClient;
+
+/// Compact blocks in height order, as they arrive from the block source
+pub type BlockStream = Pin<Box<dyn Stream<Item = Result<CompactBlock>> + Send>>;
+
+/// The lightwalletd calls needed to create accounts and scan the chain
+///
+/// `LightwalletdClient` is the production implementation; tests use `MockLightwalletd`
+/// so scanning can run against a deterministic chain without a network.
+pub trait CompactBlockSource {
+    /// Height of the current chain tip
+    fn get_latest_block_height(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Compact blocks from `start_height` to `end_height` inclusive, in height order
+    fn get_compact_blocks(
+        &self,
+        start_height: u64,
+        end_height: u64,
+    ) -> impl Future<Output = Result<Vec<CompactBlock>>> + Send;
+
+    /// Stream of compact blocks from `start_height` to `end_height` inclusive
+    ///
+    /// Unlike `get_compact_blocks` nothing is buffered, so a long range can be consumed
+    /// a batch at a time.
+    fn stream_compact_blocks(
+        &self,
+        start_height: u64,
+        end_height: u64,
+    ) -> impl Future<Output = Result<BlockStream>> + Send;
+
+    /// Sapling and Orchard note commitment tree state as of the end of block `height`
+    fn get_tree_state(&self, height: u64) -> impl Future<Output = Result<TreeState>> + Send;
+}
+
+impl CompactBlockSource for LightwalletdClient {
+    async fn get_latest_block_height(&self) -> Result<u64> {
+        LightwalletdClient::get_latest_block_height(self).await
+    }
+
+    async fn get_compact_blocks(&self, start_height: u64, end_height: u64) -> Result<Vec<CompactBlock>> {
+        use tokio_stream::StreamExt;
+
+        // Stream compact blocks from lightwalletd
+        let mut stream = self.get_block_range(start_height, end_height).await
+            .context("Failed to start block stream")?;
+
+        let mut blocks = Vec::new();
+
+        // Collect all blocks from the stream
+        while let Some(block_result) = stream.next().await {
+            match block_result {
+                Ok(block) => {
+                    if blocks.len() % 1000 == 0 && !blocks.is_empty() {
+                        tracing::debug!("Downloaded {} blocks...", blocks.len());
+                    }
+                    blocks.push(block);
+                }
+                Err(e) => {
+                    anyhow::bail!("Failed to receive block: {}", e);
+                }
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    async fn stream_compact_blocks(&self, start_height: u64, end_height: u64) -> Result<BlockStream> {
+        use tokio_stream::StreamExt;
+
+        let stream = self.get_block_range(start_height, end_height).await
+            .context("Failed to start block stream")?;
+
+        Ok(Box::pin(stream.map(|block_result| {
+            block_result.map_err(|e| anyhow::anyhow!("Failed to receive block: {}", e))
+        })))
+    }
+
+    async fn get_tree_state(&self, height: u64) -> Result<TreeState> {
+        LightwalletdClient::get_tree_state(self, height).await
+    }
+}
+
+/// In-memory lightwalletd serving a deterministic chain, for tests
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use zcash_client_backend::proto::compact_formats::{ChainMetadata, CompactTx};
+
+    /// Deterministic hash of the block at `height` on chain `fork`
+    pub fn block_hash(height: u64, fork: u8) -> [u8; 32] {
+        let mut hash = [0u8; 32];
+        hash[..8].copy_from_slice(&height.to_le_bytes());
+        hash[8] = fork;
+        hash[31] = 0x5a;
+        hash
+    }
+
+    /// Chain of compact blocks that tests extend, fork, and hand to the scanner
+    ///
+    /// Clones share the same chain, so a test can keep one to reorg the chain while
+    /// the scanner owns another.
+    #[derive(Clone)]
+    pub struct MockLightwalletd {
+        blocks: Arc<Mutex<BTreeMap<u64, CompactBlock>>>,
+        streams_opened: Arc<AtomicUsize>,
+    }
+
+    impl MockLightwalletd {
+        /// Start a chain whose first block is at `height`
+        pub fn starting_at(height: u64) -> Self {
+            let mock = Self {
+                blocks: Arc::new(Mutex::new(BTreeMap::new())),
+                streams_opened: Arc::new(AtomicUsize::new(0)),
+            };
+            mock.insert_block(height, vec![0u8; 32], 0, Vec::new());
+            mock
+        }
+
+        /// Number of `stream_compact_blocks` calls so far
+        pub fn streams_opened(&self) -> usize {
+            self.streams_opened.load(Ordering::SeqCst)
+        }
+
+        /// Height of the highest block
+        pub fn tip(&self) -> u64 {
+            *self.blocks.lock().unwrap().keys().next_back().expect("chain is never empty")
+        }
+
+        /// Append empty blocks until the chain reaches `height`
+        pub fn extend_to(&self, height: u64) {
+            while self.tip() < height {
+                self.push_block(Vec::new());
+            }
+        }
+
+        /// Append a block containing `vtx` on top of the current tip
+        pub fn push_block(&self, vtx: Vec<CompactTx>) -> u64 {
+            let (height, prev_hash, fork) = {
+                let blocks = self.blocks.lock().unwrap();
+                let (height, tip) = blocks.iter().next_back().expect("chain is never empty");
+                (height + 1, tip.hash.clone(), tip.hash[8])
+            };
+            self.insert_block(height, prev_hash, fork, vtx);
+            height
+        }
+
+        /// Replace every block from `height` upwards with empty blocks on chain `fork`
+        pub fn reorg_from(&self, height: u64, fork: u8) {
+            let tip = self.tip();
+            let prev_hash = {
+                let mut blocks = self.blocks.lock().unwrap();
+                blocks.split_off(&height);
+                blocks.get(&(height - 1)).expect("reorg below chain start").hash.clone()
+            };
+            self.insert_block(height, prev_hash, fork, Vec::new());
+            self.extend_to(tip);
+        }
+
+        fn insert_block(&self, height: u64, prev_hash: Vec<u8>, fork: u8, vtx: Vec<CompactTx>) {
+            let mut blocks = self.blocks.lock().unwrap();
+            let (sapling_before, orchard_before) = height
+                .checked_sub(1)
+                .and_then(|prev_height| blocks.get(&prev_height))
+                .and_then(|prev| prev.chain_metadata.as_ref())
+                .map(|meta| (meta.sapling_commitment_tree_size, meta.orchard_commitment_tree_size))
+                .unwrap_or((0, 0));
+
+            let chain_metadata = ChainMetadata {
+                sapling_commitment_tree_size: sapling_before
+                    + vtx.iter().map(|tx| tx.outputs.len() as u32).sum::<u32>(),
+                orchard_commitment_tree_size: orchard_before
+                    + vtx.iter().map(|tx| tx.actions.len() as u32).sum::<u32>(),
+            };
+
+            blocks.insert(
+                height,
+                CompactBlock {
+                    height,
+                    hash: block_hash(height, fork).to_vec(),
+                    prev_hash,
+                    vtx,
+                    chain_metadata: Some(chain_metadata),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    impl CompactBlockSource for MockLightwalletd {
+        async fn get_latest_block_height(&self) -> Result<u64> {
+            Ok(self.tip())
+        }
+
+        async fn get_compact_blocks(&self, start_height: u64, end_height: u64) -> Result<Vec<CompactBlock>> {
+            let blocks = self.blocks.lock().unwrap();
+            Ok(blocks.range(start_height..=end_height).map(|(_, block)| block.clone()).collect())
+        }
+
+        /// Serves the blocks as they were when the stream was opened
+        async fn stream_compact_blocks(&self, start_height: u64, end_height: u64) -> Result<BlockStream> {
+            self.streams_opened.fetch_add(1, Ordering::SeqCst);
+            let blocks = self.get_compact_blocks(start_height, end_height).await?;
+            Ok(Box::pin(tokio_stream::iter(blocks.into_iter().map(Ok))))
+        }
+
+        /// Trees are only reported as empty, so ask for states below the first output
+        async fn get_tree_state(&self, height: u64) -> Result<TreeState> {
+            let blocks = self.blocks.lock().unwrap();
+            let block = blocks
+                .get(&height)
+                .ok_or_else(|| anyhow::anyhow!("No block at height {}", height))?;
+
+            // lightwalletd reports block hashes byte-reversed, as zcashd displays them
+            let mut display_hash = block.hash.clone();
+            display_hash.reverse();
+
+            Ok(TreeState {
+                network: "test".to_string(),
+                height,
+                hash: hex::encode(display_hash),
+                time: 0,
+                sapling_tree: String::new(),
+                orchard_tree: String::new(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockLightwalletd;
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_chain_links_blocks() {
+        let chain = MockLightwalletd::starting_at(100);
+        chain.extend_to(103);
+
+        let blocks = chain.get_compact_blocks(100, 103).await.unwrap();
+        assert_eq!(blocks.len(), 4);
+        for pair in blocks.windows(2) {
+            assert_eq!(pair[1].prev_hash, pair[0].hash);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reorg_replaces_blocks_above_fork_point() {
+        let chain = MockLightwalletd::starting_at(100);
+        chain.extend_to(105);
+        let before = chain.get_compact_blocks(100, 105).await.unwrap();
+
+        chain.reorg_from(103, 1);
+        let after = chain.get_compact_blocks(100, 105).await.unwrap();
+
+        assert_eq!(chain.tip(), 105);
+        assert_eq!(after[2].hash, before[2].hash);
+        assert_ne!(after[3].hash, before[3].hash);
+        assert_eq!(after[3].prev_hash, before[2].hash);
+    }
+}
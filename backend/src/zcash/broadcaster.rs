@@ -23,12 +23,12 @@ impl TransactionBroadcaster {
     /// # Returns
     /// The transaction ID as a hex string
     pub async fn broadcast(&mut self, raw_transaction: Vec<u8>) -> Result<String> {
-        println!("Broadcasting transaction...");
-        println!("  Size: {} bytes", raw_transaction.len());
+        tracing::info!("Broadcasting transaction...");
+        tracing::debug!("Size: {} bytes", raw_transaction.len());
 
         // Ensure we're connected to lightwalletd
         if !self.lightwalletd.is_connected() {
-            println!("  Connecting to lightwalletd...");
+            tracing::debug!("Connecting to lightwalletd...");
             self.lightwalletd.connect().await?;
         }
 
@@ -36,10 +36,10 @@ impl TransactionBroadcaster {
         let response = self.lightwalletd.send_transaction(raw_transaction).await?;
 
         // Debug: Print full response
-        println!("  Response from lightwalletd:");
-        println!("    error_code: {}", response.error_code);
-        println!("    error_message length: {} bytes", response.error_message.len());
-        println!("    error_message (first 200 chars): {}",
+        tracing::debug!("Response from lightwalletd:");
+        tracing::debug!("error_code: {}", response.error_code);
+        tracing::debug!("error_message length: {} bytes", response.error_message.len());
+        tracing::debug!("error_message (first 200 chars): {}",
                  if response.error_message.len() > 200 {
                      &response.error_message[..200]
                  } else {
@@ -68,8 +68,8 @@ impl TransactionBroadcaster {
         // The response should contain the txid
         let txid = response.error_message; // lightwalletd returns txid in error_message when successful
 
-        println!("✓ Transaction broadcast successfully");
-        println!("  TxID: {}", txid);
+        tracing::info!("Transaction broadcast successfully");
+        tracing::debug!("TxID: {}", txid);
 
         Ok(txid)
     }
@@ -82,7 +82,7 @@ impl TransactionBroadcaster {
         txid: &str,
         confirmations: u32,
     ) -> Result<u64> {
-        println!("Waiting for {} confirmation(s) of {}...", confirmations, txid);
+        tracing::info!("Waiting for {} confirmation(s) of {}...", confirmations, txid);
 
         // TODO: Poll lightwalletd for transaction status
         // This requires:
@@ -90,7 +90,7 @@ impl TransactionBroadcaster {
         // 2. Check confirmation count
         // 3. Poll until desired confirmations reached
 
-        println!("✓ Transaction confirmed (placeholder)");
+        tracing::info!("Transaction confirmed (placeholder)");
 
         Ok(0) // Return block height
     }
@@ -102,7 +102,7 @@ mod tests {
 
     #[test]
     fn test_broadcaster_creation() {
-        let lightwalletd = LightwalletdClient::new("http://localhost:9067".to_string());
+        let lightwalletd = LightwalletdClient::new("http://localhost:9067".to_string(), vec![]);
         let _broadcaster = TransactionBroadcaster::new(lightwalletd);
 
         // Just test that we can create a broadcaster
@@ -1,117 +1,185 @@
 use anyhow::{Result, Context};
+use crate::utils::env::env_or_positive;
+use std::sync::RwLock;
+use std::time::Duration;
 use tonic::transport::Channel;
 use tonic::Streaming;
 use zcash_client_backend::proto::service::compact_tx_streamer_client::CompactTxStreamerClient;
-use zcash_client_backend::proto::service::{ChainSpec, BlockRange, BlockId, RawTransaction, SendResponse, TreeState};
+use zcash_client_backend::proto::service::{
+    ChainSpec, BlockRange, BlockId, GetAddressUtxosArg, GetAddressUtxosReply, RawTransaction,
+    SendResponse, TreeState,
+};
 use zcash_client_backend::proto::compact_formats::CompactBlock;
 
+/// The currently connected endpoint and its gRPC client
+struct Connection {
+    index: usize,
+    client: CompactTxStreamerClient<Channel>,
+}
+
 pub struct LightwalletdClient {
-    endpoint: String,
-    client: Option<CompactTxStreamerClient<Channel>>,
+    /// Primary endpoint first, followed by fallbacks in the order they are tried
+    endpoints: Vec<String>,
+    connection: RwLock<Option<Connection>>,
 }
 
 impl LightwalletdClient {
-    pub fn new(endpoint: String) -> Self {
+    /// Blank endpoints are dropped, so an unset primary leaves only the fallbacks.
+    pub fn new(primary: String, fallbacks: Vec<String>) -> Self {
+        let endpoints = std::iter::once(primary)
+            .chain(fallbacks)
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+
         Self {
-            endpoint,
-            client: None,
+            endpoints,
+            connection: RwLock::new(None),
         }
     }
 
+    /// Create a client from a comma-separated endpoint list
+    ///
+    /// The first entry is the primary server; the rest are fallbacks,
+    /// e.g. `https://na.zec.rocks:443,https://eu.zec.rocks:443`.
+    pub fn from_endpoint_list(list: &str) -> Self {
+        let mut endpoints = list.split(',').map(str::to_string);
+
+        let primary = endpoints.next().unwrap_or_default();
+        Self::new(primary, endpoints.collect())
+    }
+
+    /// Connect to the first reachable endpoint, trying fallbacks in order
     pub async fn connect(&mut self) -> Result<()> {
-        // Check if we need TLS
-        let use_tls = self.endpoint.starts_with("https://");
-
-        let channel = if use_tls {
-            // Parse the domain from the endpoint for TLS config
-            let domain = self.endpoint
-                .trim_start_matches("https://")
-                .split(':')
-                .next()
-                .ok_or_else(|| anyhow::anyhow!("Invalid endpoint format"))?;
-
-            // Configure TLS with native system roots (enabled via Cargo.toml feature)
-            let tls = tonic::transport::ClientTlsConfig::new()
-                .domain_name(domain)
-                .with_native_roots();
-
-            Channel::from_shared(self.endpoint.clone())?
-                .tls_config(tls)?
-                .connect_timeout(std::time::Duration::from_secs(30))
-                .timeout(std::time::Duration::from_secs(600))  // 10 minutes for large downloads
-                .connect()
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to connect to {}: {}", self.endpoint, e))?
-        } else {
-            // No TLS for local development
-            Channel::from_shared(self.endpoint.clone())?
-                .connect_timeout(std::time::Duration::from_secs(30))
-                .timeout(std::time::Duration::from_secs(600))  // 10 minutes for large downloads
-                .connect()
-                .await
-                .map_err(|e| anyhow::anyhow!("Failed to connect to {}: {}", self.endpoint, e))?
-        };
+        self.connect_from(0).await
+    }
 
-        let client = CompactTxStreamerClient::new(channel);
-        self.client = Some(client);
-        Ok(())
+    /// The endpoint the client is currently connected to, if any
+    pub fn connected_endpoint(&self) -> Option<String> {
+        self.connection
+            .read()
+            .ok()?
+            .as_ref()
+            .map(|conn| self.endpoints[conn.index].clone())
     }
 
-    pub async fn get_latest_block_height(&self) -> Result<u64> {
-        if self.client.is_none() {
-            anyhow::bail!("Not connected. Call connect() first.");
+    /// Try each endpoint in turn starting at `start`, wrapping around the list
+    async fn connect_from(&self, start: usize) -> Result<()> {
+        let mut errors = Vec::new();
+
+        for offset in 0..self.endpoints.len() {
+            let index = (start + offset) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+
+            match open_channel(endpoint).await {
+                Ok(channel) => {
+                    if index > 0 {
+                        tracing::warn!("Connected to fallback lightwalletd endpoint {}", endpoint);
+                    }
+                    *self.connection.write().unwrap() = Some(Connection {
+                        index,
+                        client: CompactTxStreamerClient::new(channel)
+                            .max_decoding_message_size(max_message_bytes()),
+                    });
+                    return Ok(());
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to connect to lightwalletd endpoint {}: {}", endpoint, e);
+                    errors.push(e.to_string());
+                }
+            }
+        }
+
+        *self.connection.write().unwrap() = None;
+
+        if errors.is_empty() {
+            anyhow::bail!("No lightwalletd endpoints configured");
         }
+        anyhow::bail!("All lightwalletd endpoints failed: {}", errors.join("; "))
+    }
 
-        let mut client = self.client.clone().unwrap();
-        let request = tonic::Request::new(ChainSpec {});
+    /// Clone the connected client along with the index of its endpoint
+    fn client(&self) -> Result<(usize, CompactTxStreamerClient<Channel>)> {
+        self.connection
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|conn| (conn.index, conn.client.clone()))
+            .ok_or_else(|| anyhow::anyhow!("Not connected. Call connect() first."))
+    }
 
-        let response = client.get_latest_block(request).await?;
-        let block_id = response.into_inner();
+    /// Reconnect to the endpoint after `failed_index` following a transport error
+    async fn failover(&self, failed_index: usize, status: &tonic::Status) -> Result<()> {
+        tracing::warn!(
+            "Transport error from lightwalletd endpoint {}: {}. Trying next endpoint...",
+            self.endpoints[failed_index],
+            status.message()
+        );
+        self.connect_from(failed_index + 1).await
+    }
 
-        Ok(block_id.height)
+    pub async fn get_latest_block_height(&self) -> Result<u64> {
+        let mut attempts = 0;
+
+        loop {
+            let (index, mut client) = self.client()?;
+            let request = tonic::Request::new(ChainSpec {});
+
+            match client.get_latest_block(request).await {
+                Ok(response) => return Ok(response.into_inner().height),
+                Err(status) if is_transport_error(&status) && attempts + 1 < self.endpoints.len() => {
+                    attempts += 1;
+                    self.failover(index, &status).await?;
+                }
+                Err(status) => return Err(status.into()),
+            }
+        }
     }
 
     pub fn is_connected(&self) -> bool {
-        self.client.is_some()
+        self.connection.read().unwrap().is_some()
     }
 
     /// Stream a range of compact blocks from the server
     ///
     /// Returns a stream of CompactBlock messages that can be iterated over
     pub async fn get_block_range(&self, start_height: u64, end_height: u64) -> Result<Streaming<CompactBlock>> {
-        if self.client.is_none() {
-            anyhow::bail!("Not connected. Call connect() first.");
+        let mut attempts = 0;
+
+        loop {
+            let (index, mut client) = self.client()?;
+
+            let block_range = BlockRange {
+                start: Some(BlockId {
+                    height: start_height,
+                    hash: vec![],
+                }),
+                end: Some(BlockId {
+                    height: end_height,
+                    hash: vec![],
+                }),
+            };
+
+            let request = tonic::Request::new(block_range);
+            match client.get_block_range(request).await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) if is_transport_error(&status) && attempts + 1 < self.endpoints.len() => {
+                    attempts += 1;
+                    self.failover(index, &status).await?;
+                }
+                Err(status) => {
+                    return Err(anyhow::Error::from(status))
+                        .context(format!("Failed to get block range {}-{}", start_height, end_height));
+                }
+            }
         }
-
-        let mut client = self.client.clone().unwrap();
-
-        let block_range = BlockRange {
-            start: Some(BlockId {
-                height: start_height,
-                hash: vec![],
-            }),
-            end: Some(BlockId {
-                height: end_height,
-                hash: vec![],
-            }),
-        };
-
-        let request = tonic::Request::new(block_range);
-        let response = client.get_block_range(request).await
-            .context(format!("Failed to get block range {}-{}", start_height, end_height))?;
-
-        Ok(response.into_inner())
     }
 
     /// Send a transaction to the Zcash network
     ///
     /// Broadcasts the raw transaction bytes to lightwalletd, which relays it to the network
     pub async fn send_transaction(&self, raw_tx: Vec<u8>) -> Result<SendResponse> {
-        if self.client.is_none() {
-            anyhow::bail!("Not connected. Call connect() first.");
-        }
-
-        let mut client = self.client.clone().unwrap();
+        let (_, mut client) = self.client()?;
 
         let raw_transaction = RawTransaction {
             data: raw_tx.clone(),
@@ -130,11 +198,7 @@ impl LightwalletdClient {
     /// Returns the Sapling and Orchard note commitment tree state at the given height.
     /// This is essential for initializing wallet scanning from a specific birthday height.
     pub async fn get_tree_state(&self, height: u64) -> Result<TreeState> {
-        if self.client.is_none() {
-            anyhow::bail!("Not connected. Call connect() first.");
-        }
-
-        let mut client = self.client.clone().unwrap();
+        let (_, mut client) = self.client()?;
 
         let block_id = BlockId {
             height,
@@ -147,6 +211,100 @@ impl LightwalletdClient {
 
         Ok(response.into_inner())
     }
+
+    /// Get unspent transparent outputs for a set of transparent addresses
+    ///
+    /// Only UTXOs mined at or above `start_height` are returned.
+    pub async fn get_address_utxos(
+        &self,
+        addresses: Vec<String>,
+        start_height: u64,
+    ) -> Result<Vec<GetAddressUtxosReply>> {
+        let (_, mut client) = self.client()?;
+
+        let request = tonic::Request::new(GetAddressUtxosArg {
+            addresses,
+            start_height,
+            max_entries: 0, // unlimited
+        });
+        let response = client.get_address_utxos(request).await
+            .context("Failed to get address UTXOs")?;
+
+        Ok(response.into_inner().address_utxos)
+    }
+}
+
+/// Default seconds between HTTP/2 keepalive pings
+const DEFAULT_KEEPALIVE_INTERVAL_SECS: u64 = 30;
+
+/// Default seconds to wait for a keepalive ack before dropping the connection
+const DEFAULT_KEEPALIVE_TIMEOUT_SECS: u64 = 20;
+
+/// Default largest gRPC response accepted (tonic's own default is 4 MiB)
+const DEFAULT_MAX_MESSAGE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Largest gRPC response accepted, from `LIGHTWALLETD_MAX_MESSAGE_BYTES`
+///
+/// Blocks full of shielded outputs can exceed tonic's 4 MiB default.
+fn max_message_bytes() -> usize {
+    env_or_positive("LIGHTWALLETD_MAX_MESSAGE_BYTES", DEFAULT_MAX_MESSAGE_BYTES) as usize
+}
+
+/// Open a gRPC channel to a single lightwalletd endpoint
+///
+/// HTTP/2 keepalive pings (`LIGHTWALLETD_KEEPALIVE_INTERVAL_SECS`, with
+/// `LIGHTWALLETD_KEEPALIVE_TIMEOUT_SECS` to answer) keep the connection alive through the
+/// idle stretches of a long scan, and detect a dead one instead of hanging.
+async fn open_channel(endpoint: &str) -> Result<Channel> {
+    let keepalive_interval = Duration::from_secs(env_or_positive(
+        "LIGHTWALLETD_KEEPALIVE_INTERVAL_SECS",
+        DEFAULT_KEEPALIVE_INTERVAL_SECS,
+    ));
+    let keepalive_timeout = Duration::from_secs(env_or_positive(
+        "LIGHTWALLETD_KEEPALIVE_TIMEOUT_SECS",
+        DEFAULT_KEEPALIVE_TIMEOUT_SECS,
+    ));
+
+    let builder = Channel::from_shared(endpoint.to_string())?
+        .connect_timeout(Duration::from_secs(30))
+        .timeout(Duration::from_secs(600))  // 10 minutes for large downloads
+        .http2_keep_alive_interval(keepalive_interval)
+        .keep_alive_timeout(keepalive_timeout)
+        .keep_alive_while_idle(true);
+
+    // Check if we need TLS
+    let use_tls = endpoint.starts_with("https://");
+
+    let builder = if use_tls {
+        // Parse the domain from the endpoint for TLS config
+        let domain = endpoint
+            .trim_start_matches("https://")
+            .split(':')
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Invalid endpoint format"))?;
+
+        // Configure TLS with native system roots (enabled via Cargo.toml feature)
+        let tls = tonic::transport::ClientTlsConfig::new()
+            .domain_name(domain)
+            .with_native_roots();
+
+        builder.tls_config(tls)?
+    } else {
+        // No TLS for local development
+        builder
+    };
+
+    let channel = builder
+        .connect()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to connect to {}: {}", endpoint, e))?;
+
+    Ok(channel)
+}
+
+/// Whether a gRPC error means the server is unreachable rather than rejecting the request
+fn is_transport_error(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::Unavailable
 }
 
 #[cfg(test)]
@@ -155,13 +313,54 @@ mod tests {
 
     #[test]
     fn test_client_creation() {
-        let client = LightwalletdClient::new("http://localhost:9067".to_string());
+        let client = LightwalletdClient::new("http://localhost:9067".to_string(), vec![]);
         assert!(!client.is_connected());
+        assert_eq!(client.connected_endpoint(), None);
+    }
+
+    #[test]
+    fn test_from_endpoint_list() {
+        let client = LightwalletdClient::from_endpoint_list(
+            " https://na.zec.rocks:443, https://eu.zec.rocks:443 ,,",
+        );
+        assert_eq!(
+            client.endpoints,
+            vec!["https://na.zec.rocks:443", "https://eu.zec.rocks:443"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_no_endpoints() {
+        let mut client = LightwalletdClient::from_endpoint_list(" , ");
+        assert!(client.endpoints.is_empty());
+        let err = client.connect().await.unwrap_err();
+        assert_eq!(err.to_string(), "No lightwalletd endpoints configured");
+        assert!(!client.is_connected());
+    }
+
+    #[test]
+    fn test_new_drops_blank_primary() {
+        let client = LightwalletdClient::new(String::new(), vec!["https://eu.zec.rocks:443".to_string()]);
+        assert_eq!(client.endpoints, vec!["https://eu.zec.rocks:443"]);
+    }
+
+    // Needs network access to na.zec.rocks
+    #[tokio::test]
+    #[ignore]
+    async fn test_connect_falls_back_to_next_endpoint() {
+        let mut client = LightwalletdClient::new(
+            "http://127.0.0.1:1".to_string(),
+            vec!["https://na.zec.rocks:443".to_string()],
+        );
+
+        let result = client.connect().await;
+        assert!(result.is_ok(), "Failed to fall back to na.zec.rocks:443");
+        assert_eq!(client.connected_endpoint().as_deref(), Some("https://na.zec.rocks:443"));
     }
 
     #[tokio::test]
     async fn test_connect_to_zec_rocks_mainnet() {
-        let mut client = LightwalletdClient::new("https://na.zec.rocks:443".to_string());
+        let mut client = LightwalletdClient::new("https://na.zec.rocks:443".to_string(), vec![]);
 
         let result = client.connect().await;
 
@@ -177,7 +376,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_connect_to_eu_zec_rocks() {
-        let mut client = LightwalletdClient::new("https://eu.zec.rocks:443".to_string());
+        let mut client = LightwalletdClient::new("https://eu.zec.rocks:443".to_string(), vec![]);
 
         let result = client.connect().await;
 
@@ -193,7 +392,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_connect_to_localhost() {
-        let mut client = LightwalletdClient::new("http://localhost:9067".to_string());
+        let mut client = LightwalletdClient::new("http://localhost:9067".to_string(), vec![]);
 
         let result = client.connect().await;
 
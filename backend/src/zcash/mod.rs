@@ -1,11 +1,14 @@
 // Zcash wallet integration modules
 pub mod account;
+pub mod backup;
+pub mod block_source;
 pub mod broadcaster;
 pub mod config;
 pub mod database;
 pub mod lightwalletd;
 pub mod note_selection;
 pub mod params;
+pub mod pool;
 pub mod prover;
 pub mod scanner;
 pub mod transaction;
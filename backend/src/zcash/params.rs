@@ -1,5 +1,14 @@
 use anyhow::Result;
-use std::path::PathBuf;
+use once_cell::sync::OnceCell;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Expected sizes of the Sapling parameter files (zcash_proofs checks the hashes)
+const SAPLING_SPEND_BYTES: u64 = 47_958_396;
+const SAPLING_OUTPUT_BYTES: u64 = 3_592_860;
+
+/// Directory of verified parameters, set once `ensure_params` succeeds
+static PARAMS_DIR: OnceCell<PathBuf> = OnceCell::new();
 
 /// Downloads Zcash Sapling proving parameters if not already present
 ///
@@ -12,37 +21,107 @@ use std::path::PathBuf;
 /// - sapling-spend.params (~46MB)
 /// - sapling-output.params (~3.4MB)
 ///
-/// This only needs to be done once and files are cached permanently
+/// Both files are checked against the expected Sapling sizes and BLAKE2b hashes,
+/// whether freshly downloaded or already on disk. A file with the wrong size is
+/// deleted and downloaded again. Verification runs once per process; later calls
+/// return the cached directory.
 pub fn ensure_params() -> Result<PathBuf> {
-    println!("Checking Zcash proving parameters...");
+    PARAMS_DIR.get_or_try_init(fetch_and_verify_params).cloned()
+}
+
+fn fetch_and_verify_params() -> Result<PathBuf> {
+    tracing::info!("Checking Zcash proving parameters...");
+
+    let params_dir = zcash_proofs::default_params_folder()
+        .ok_or_else(|| anyhow::anyhow!("Could not determine Zcash params directory"))?;
+
+    let expected = [
+        (zcash_proofs::SAPLING_SPEND_NAME, SAPLING_SPEND_BYTES),
+        (zcash_proofs::SAPLING_OUTPUT_NAME, SAPLING_OUTPUT_BYTES),
+    ];
+
+    // zcash_proofs panics on a wrong-sized existing file, so clear those out first
+    let mut missing = 0;
+    for (name, bytes) in expected {
+        let path = params_dir.join(name);
+        match std::fs::metadata(&path) {
+            Ok(metadata) if metadata.len() != bytes => {
+                tracing::warn!(
+                    "{} is {} bytes, expected {}; deleting and downloading again",
+                    path.display(),
+                    metadata.len(),
+                    bytes
+                );
+                std::fs::remove_file(&path)?;
+                missing += 1;
+            }
+            Ok(_) => {}
+            Err(_) => missing += 1,
+        }
+    }
+
+    if missing > 0 {
+        tracing::info!(
+            "Downloading {} Sapling parameter file(s) (~50 MB) to {}...",
+            missing,
+            params_dir.display()
+        );
+    } else {
+        tracing::info!("Verifying parameter hashes...");
+    }
 
-    // Use zcash_proofs to download/verify parameters
-    // It handles all the logic including checking if files already exist
-    let paths = zcash_proofs::download_sapling_parameters(None)?;
+    // Downloads missing files and checks the hash of every file
+    let started = Instant::now();
+    let paths = zcash_proofs::download_sapling_parameters(None)
+        .map_err(|e| anyhow::anyhow!("Failed to download or verify Sapling parameters: {}", e))?;
+
+    verify_size(&paths.spend, SAPLING_SPEND_BYTES)?;
+    verify_size(&paths.output, SAPLING_OUTPUT_BYTES)?;
 
     // Extract the parent directory from the returned paths
     let params_dir = paths.spend.parent()
         .ok_or_else(|| anyhow::anyhow!("Invalid params path"))?
         .to_path_buf();
 
-    println!("✓ Proving parameters ready!");
-    println!("  Location: {}", params_dir.display());
-    println!("  - Spend: {} ({:.1} MB)",
-        paths.spend.display(),
-        std::fs::metadata(&paths.spend)?.len() as f64 / 1_000_000.0
-    );
-    println!("  - Output: {} ({:.1} MB)",
-        paths.output.display(),
-        std::fs::metadata(&paths.output)?.len() as f64 / 1_000_000.0
+    tracing::info!(
+        "Proving parameters ready in {:.1}s: {}",
+        started.elapsed().as_secs_f64(),
+        params_dir.display()
     );
 
     Ok(params_dir)
 }
 
+/// Fail if a parameter file doesn't have the expected size
+fn verify_size(path: &Path, expected_bytes: u64) -> Result<()> {
+    let actual = std::fs::metadata(path)?.len();
+    if actual != expected_bytes {
+        anyhow::bail!(
+            "{} is {} bytes, expected {}",
+            path.display(),
+            actual,
+            expected_bytes
+        );
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_verify_size() {
+        let path = std::env::temp_dir().join(format!("shield-verify-size-{}.params", std::process::id()));
+        std::fs::write(&path, [0u8; 16]).unwrap();
+
+        assert!(verify_size(&path, 16).is_ok());
+        assert!(verify_size(&path, 17).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(verify_size(&path, 16).is_err());
+    }
+
     #[test]
     fn test_ensure_params() {
         let result = ensure_params();
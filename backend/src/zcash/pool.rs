@@ -0,0 +1,251 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use zcash_protocol::consensus::Network;
+
+use super::database::Database;
+use crate::utils::env::env_or_positive;
+
+/// Number of wallet databases kept open when WALLET_DB_POOL_SIZE is not set
+const DEFAULT_POOL_CAPACITY: usize = 64;
+
+/// Shared handle to a pooled wallet database
+///
+/// Lock it for the duration of any wallet operation; the mutex also serializes
+/// concurrent requests for the same user.
+pub type PooledDatabase = Arc<Mutex<Database>>;
+
+/// Global pool of open per-user wallet databases
+pub static WALLET_DB_POOL: Lazy<WalletDbPool> = Lazy::new(|| {
+    WalletDbPool::new(env_or_positive("WALLET_DB_POOL_SIZE", DEFAULT_POOL_CAPACITY))
+});
+
+struct PoolEntry {
+    db: PooledDatabase,
+    last_used: Instant,
+}
+
+/// Cache of open wallet databases keyed by user ID
+///
+/// Opening a wallet database runs the schema migrations, so doing it on every
+/// request is wasteful for hot wallets. The pool keeps up to `capacity` handles
+/// open and evicts the least recently used one that isn't currently borrowed.
+pub struct WalletDbPool {
+    entries: Mutex<HashMap<Uuid, PoolEntry>>,
+    // Per-user locks held while a database is being opened, so two requests for the
+    // same user don't both open it and requests for other users aren't held up
+    opening: Mutex<HashMap<Uuid, Arc<Mutex<()>>>>,
+    capacity: usize,
+}
+
+impl WalletDbPool {
+    /// Create an empty pool holding at most `capacity` idle handles
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            opening: Mutex::new(HashMap::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Get the wallet database for a user, opening (or creating) it on first use
+    ///
+    /// Opening runs migrations, so it happens on a blocking thread without holding the
+    /// pool lock.
+    pub async fn get(
+        &self,
+        user_id: Uuid,
+        db_path: &Path,
+        network: Network,
+    ) -> Result<PooledDatabase> {
+        if let Some(db) = self.cached(user_id).await {
+            return Ok(db);
+        }
+
+        let open_lock = {
+            let mut opening = self.opening.lock().await;
+            opening
+                .entry(user_id)
+                .or_insert_with(|| Arc::new(Mutex::new(())))
+                .clone()
+        };
+        let _opening = open_lock.lock().await;
+
+        // Another request may have opened it while this one waited
+        if let Some(db) = self.cached(user_id).await {
+            return Ok(db);
+        }
+
+        let path = db_path.to_path_buf();
+        let db = tokio::task::spawn_blocking(move || open_or_create(&path, network))
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to open wallet database: {}", e))
+            .and_then(|opened| opened)
+            .map(|db| Arc::new(Mutex::new(db)));
+
+        if let Ok(db) = &db {
+            let mut entries = self.entries.lock().await;
+            if entries.len() >= self.capacity {
+                evict_lru(&mut entries);
+            }
+            entries.insert(
+                user_id,
+                PoolEntry {
+                    db: db.clone(),
+                    last_used: Instant::now(),
+                },
+            );
+        }
+
+        // Keep the lock in the map while other requests are still waiting on it
+        let mut opening = self.opening.lock().await;
+        if Arc::strong_count(&open_lock) == 2 {
+            opening.remove(&user_id);
+        }
+
+        db
+    }
+
+    /// The open handle for a user, if the pool has one
+    async fn cached(&self, user_id: Uuid) -> Option<PooledDatabase> {
+        let mut entries = self.entries.lock().await;
+        let entry = entries.get_mut(&user_id)?;
+        entry.last_used = Instant::now();
+        Some(entry.db.clone())
+    }
+
+    /// Drop the cached handle for a user
+    ///
+    /// Must be called before the database file is deleted or replaced so the
+    /// next `get` opens the new file instead of the stale handle.
+    pub async fn evict(&self, user_id: Uuid) {
+        self.entries.lock().await.remove(&user_id);
+    }
+
+    /// Close handles that have been idle for at least `max_idle`
+    ///
+    /// Handles that are currently borrowed by a request are kept regardless of age.
+    /// Returns the number of handles closed.
+    pub async fn close_idle(&self, max_idle: Duration) -> usize {
+        let mut entries = self.entries.lock().await;
+        let before = entries.len();
+
+        entries.retain(|_, entry| {
+            entry.last_used.elapsed() < max_idle || Arc::strong_count(&entry.db) > 1
+        });
+
+        before - entries.len()
+    }
+
+    /// Number of handles currently open
+    pub async fn open_count(&self) -> usize {
+        self.entries.lock().await.len()
+    }
+}
+
+/// Remove the least recently used handle that nobody is borrowing
+///
+/// If every handle is in use the pool temporarily grows past its capacity.
+fn evict_lru(entries: &mut HashMap<Uuid, PoolEntry>) {
+    let lru = entries
+        .iter()
+        .filter(|(_, entry)| Arc::strong_count(&entry.db) == 1)
+        .min_by_key(|(_, entry)| entry.last_used)
+        .map(|(user_id, _)| *user_id);
+
+    if let Some(user_id) = lru {
+        entries.remove(&user_id);
+        tracing::debug!("Evicted wallet database for user {} from pool", user_id);
+    }
+}
+
+/// Open an existing wallet database, or initialize a new one
+fn open_or_create(db_path: &Path, network: Network) -> Result<Database> {
+    if db_path.exists() {
+        match Database::open_existing(db_path, network) {
+            Ok(db) => return Ok(db),
+            Err(e) => {
+                tracing::warn!("Failed to open existing database, will reinitialize: {}", e);
+            }
+        }
+    }
+
+    Database::new(db_path, network)
+}
+
+/// Spawn a background task that periodically closes idle pooled databases
+pub fn spawn_reaper(interval: Duration, max_idle: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let closed = WALLET_DB_POOL.close_idle(max_idle).await;
+            if closed > 0 {
+                tracing::info!("Closed {} idle wallet database(s)", closed);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_db_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("shield-pool-{}.db", Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_pool_capacity_is_at_least_one() {
+        let pool = WalletDbPool::new(0);
+        assert_eq!(pool.capacity, 1);
+    }
+
+    #[tokio::test]
+    async fn test_close_idle_on_empty_pool() {
+        let pool = WalletDbPool::new(4);
+        assert_eq!(pool.close_idle(Duration::from_secs(0)).await, 0);
+        assert_eq!(pool.open_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_gets_share_one_handle() {
+        let db_path = temp_db_path();
+        let pool = WalletDbPool::new(4);
+        let user_id = Uuid::new_v4();
+
+        let (a, b) = tokio::join!(
+            pool.get(user_id, &db_path, Network::TestNetwork),
+            pool.get(user_id, &db_path, Network::TestNetwork),
+        );
+        assert!(Arc::ptr_eq(&a.unwrap(), &b.unwrap()));
+        assert_eq!(pool.open_count().await, 1);
+        assert!(pool.opening.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_borrowed_handle_is_not_evicted() {
+        let pool = WalletDbPool::new(1);
+        let (first, second, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let borrowed = pool
+            .get(first, &temp_db_path(), Network::TestNetwork)
+            .await
+            .unwrap();
+        // Over capacity, but the only candidate is in use, so the pool grows
+        drop(pool.get(second, &temp_db_path(), Network::TestNetwork).await.unwrap());
+        assert_eq!(pool.open_count().await, 2);
+
+        // The idle handle goes, the borrowed one stays
+        drop(pool.get(third, &temp_db_path(), Network::TestNetwork).await.unwrap());
+        let entries = pool.entries.lock().await;
+        assert!(entries.contains_key(&first));
+        assert!(!entries.contains_key(&second));
+        assert!(Arc::ptr_eq(&entries[&first].db, &borrowed));
+    }
+}
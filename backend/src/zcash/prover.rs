@@ -1,4 +1,5 @@
 use anyhow::Result;
+use once_cell::sync::OnceCell;
 use zcash_proofs::prover::LocalTxProver;
 use std::path::PathBuf;
 
@@ -12,8 +13,8 @@ pub struct TransactionProver {
 impl TransactionProver {
     /// Create a new transaction prover with the given proving parameters
     pub fn new(params_dir: PathBuf) -> Result<Self> {
-        println!("Initializing transaction prover...");
-        println!("  Params directory: {}", params_dir.display());
+        tracing::info!("Initializing transaction prover...");
+        tracing::debug!("Params directory: {}", params_dir.display());
 
         // Load the proving parameters
         let spend_path = params_dir.join("sapling-spend.params");
@@ -21,7 +22,7 @@ impl TransactionProver {
 
         let prover = LocalTxProver::new(&spend_path, &output_path);
 
-        println!("✓ Prover initialized");
+        tracing::info!("Prover initialized");
 
         Ok(Self { prover })
     }
@@ -34,16 +35,26 @@ impl TransactionProver {
     }
 }
 
-/// Get a LocalTxProver for transaction building
+/// Process-wide prover, loaded from the parameter files on first use
+static PROVER: OnceCell<LocalTxProver> = OnceCell::new();
+
+/// Get the shared LocalTxProver for transaction building
 ///
-/// This is a simple helper function that creates a prover using the
-/// standard proving parameters location.
-pub fn get_prover() -> Result<LocalTxProver> {
-    let params_dir = ensure_params()?;
-    let spend_path = params_dir.join("sapling-spend.params");
-    let output_path = params_dir.join("sapling-output.params");
-
-    Ok(LocalTxProver::new(&spend_path, &output_path))
+/// The first call checks the proving parameters and loads them from the standard
+/// location (~50MB, slow); later calls return the same prover. A failed load is
+/// not cached, so the next call tries again.
+pub fn get_prover() -> Result<&'static LocalTxProver> {
+    PROVER.get_or_try_init(|| {
+        let params_dir = ensure_params()?;
+        let spend_path = params_dir.join("sapling-spend.params");
+        let output_path = params_dir.join("sapling-output.params");
+
+        tracing::info!("Loading Sapling proving parameters...");
+        let prover = LocalTxProver::new(&spend_path, &output_path);
+        tracing::info!("Prover initialized");
+
+        Ok(prover)
+    })
 }
 
 #[cfg(all(test, feature = "disabled_tests"))]
@@ -4,7 +4,7 @@ use std::path::PathBuf;
 use zcash_client_backend::{
     data_api::{
         chain::{scan_cached_blocks, BlockSource, ChainState},
-        WalletRead,
+        WalletRead, WalletWrite,
     },
     proto::compact_formats::CompactBlock,
 };
@@ -12,10 +12,32 @@ use zcash_client_sqlite::WalletDb;
 use zcash_client_sqlite::util::SystemClock;
 use zcash_protocol::consensus::{BlockHeight, Network};
 use rand::rngs::OsRng;
+use serde::Serialize;
 use std::collections::HashMap;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 use zcash_primitives::block::BlockHash;
 
+use super::block_source::{BlockStream, CompactBlockSource};
 use super::lightwalletd::LightwalletdClient;
+use crate::utils::env::env_or_positive;
+
+/// How far below a detected reorg (or checkpoint conflict) to rewind before rescanning
+///
+/// Reorgs deeper than this are still handled: the rescan finds the next block that doesn't
+/// connect and rewinds again.
+const REORG_SAFETY_MARGIN: u64 = 10;
+
+/// Whether the hash we stored for a block disagrees with the one lightwalletd reports
+///
+/// A missing stored hash isn't treated as a reorg: there's nothing to compare against.
+fn hashes_diverge(stored: Option<BlockHash>, remote: &[u8]) -> bool {
+    match stored {
+        Some(hash) => hash.0[..] != *remote,
+        None => false,
+    }
+}
 
 /// In-memory block cache for storing compact blocks during scanning
 struct InMemoryBlockCache {
@@ -29,10 +51,41 @@ impl InMemoryBlockCache {
         }
     }
 
-    fn write_block(&mut self, height: BlockHeight, block: &CompactBlock) -> Result<()> {
-        self.blocks.insert(height, block.clone());
-        Ok(())
+    fn write_block(&mut self, block: CompactBlock) {
+        self.blocks.insert(BlockHeight::from_u32(block.height as u32), block);
     }
+
+    fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Cache one batch of blocks, run `scan` over it, then drop the batch
+    ///
+    /// The cache is emptied whether or not the scan succeeds, so memory use is
+    /// bounded by a single batch rather than growing across the whole scan.
+    fn scan_batch<T>(
+        &mut self,
+        blocks: Vec<CompactBlock>,
+        scan: impl FnOnce(&Self) -> Result<T>,
+    ) -> Result<T> {
+        for block in blocks {
+            self.write_block(block);
+        }
+        let result = scan(self);
+        self.blocks.clear();
+        result
+    }
+}
+
+/// Default number of blocks downloaded and scanned per batch
+const DEFAULT_SCAN_BATCH_SIZE: u64 = 10_000;
+
+/// Blocks per batch, from `SCAN_BATCH_SIZE` (each batch is held in memory while it's scanned)
+///
+/// Blocks are streamed from a single request for the whole range, so this bounds memory,
+/// not the number of requests.
+fn scan_batch_size() -> u64 {
+    env_or_positive("SCAN_BATCH_SIZE", DEFAULT_SCAN_BATCH_SIZE)
 }
 
 // Implement BlockSource trait for InMemoryBlockCache
@@ -75,20 +128,59 @@ impl BlockSource for InMemoryBlockCache {
     }
 }
 
+/// How far a running scan has got, published after every batch
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ScanProgress {
+    pub start_height: u64,
+    /// Highest block scanned so far (`start_height - 1` before the first batch)
+    pub scanned_height: u64,
+    pub chain_tip: u64,
+    pub blocks_scanned: usize,
+    pub notes_discovered: usize,
+}
+
+/// Optional hooks for callers that run a scan in the background
+///
+/// The cancellation token is checked between batches; progress is saved per batch, so a
+/// cancelled scan resumes where it stopped the next time the wallet is scanned.
+#[derive(Clone, Default)]
+pub struct ScanControl {
+    pub cancel: Option<CancellationToken>,
+    pub progress: Option<watch::Sender<ScanProgress>>,
+}
+
+impl ScanControl {
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|token| token.is_cancelled())
+    }
+
+    fn report(&self, progress: ScanProgress) {
+        if let Some(sender) = &self.progress {
+            sender.send_replace(progress);
+        }
+    }
+}
+
 /// Blockchain scanner for discovering wallet transactions
-pub struct BlockchainScanner {
-    wallet_db: WalletDb<Connection, Network, SystemClock, OsRng>,
+///
+/// Blocks come from any `CompactBlockSource`: lightwalletd in production, a mock
+/// chain in tests.
+pub struct BlockchainScanner<'a, S = LightwalletdClient> {
+    wallet_db: &'a mut WalletDb<Connection, Network, SystemClock, OsRng>,
     block_cache: InMemoryBlockCache,
-    lightwalletd: LightwalletdClient,
+    lightwalletd: S,
     network: Network,
     db_path: Option<PathBuf>,
+    control: ScanControl,
+    batch_size: u64,
+    configured_birthday: Option<u64>,
 }
 
-impl BlockchainScanner {
+impl<'a, S: CompactBlockSource> BlockchainScanner<'a, S> {
     /// Create a new blockchain scanner
     pub fn new(
-        wallet_db: WalletDb<Connection, Network, SystemClock, OsRng>,
-        lightwalletd: LightwalletdClient,
+        wallet_db: &'a mut WalletDb<Connection, Network, SystemClock, OsRng>,
+        lightwalletd: S,
         network: Network,
     ) -> Self {
         Self {
@@ -97,13 +189,16 @@ impl BlockchainScanner {
             lightwalletd,
             network,
             db_path: None,
+            control: ScanControl::default(),
+            batch_size: scan_batch_size(),
+            configured_birthday: configured_birthday(),
         }
     }
 
     /// Create a new blockchain scanner with database path for checkpoint management
     pub fn new_with_path(
-        wallet_db: WalletDb<Connection, Network, SystemClock, OsRng>,
-        lightwalletd: LightwalletdClient,
+        wallet_db: &'a mut WalletDb<Connection, Network, SystemClock, OsRng>,
+        lightwalletd: S,
         network: Network,
         db_path: PathBuf,
     ) -> Self {
@@ -113,9 +208,30 @@ impl BlockchainScanner {
             lightwalletd,
             network,
             db_path: Some(db_path),
+            control: ScanControl::default(),
+            batch_size: scan_batch_size(),
+            configured_birthday: configured_birthday(),
         }
     }
 
+    /// Report progress to, and stop early on cancellation from, `control`
+    pub fn with_control(mut self, control: ScanControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Use `birthday` instead of `WALLET_BIRTHDAY_HEIGHT` (`None` ignores the variable)
+    pub fn with_birthday(mut self, birthday: Option<u64>) -> Self {
+        self.configured_birthday = birthday;
+        self
+    }
+
+    /// Scan `batch_size` blocks at a time instead of the `SCAN_BATCH_SIZE` default
+    pub fn with_batch_size(mut self, batch_size: u64) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
     /// Scan the blockchain from the wallet's birthday height
     ///
     /// This downloads compact blocks from lightwalletd and scans them for
@@ -123,8 +239,15 @@ impl BlockchainScanner {
     ///
     /// Uses batched scanning - processes blocks in chunks and saves progress
     /// incrementally so interruptions don't lose all work.
-    pub async fn scan_from_birthday(&mut self) -> Result<ScanSummary> {
-        println!("Starting blockchain scan...");
+    ///
+    /// `user_id` is only used to tag the tracing span so logs can be correlated.
+    #[tracing::instrument(
+        name = "scan",
+        skip(self),
+        fields(%user_id, start_height = tracing::field::Empty, chain_tip = tracing::field::Empty)
+    )]
+    pub async fn scan_from_birthday(&mut self, user_id: Uuid) -> Result<ScanSummary> {
+        tracing::info!("Starting blockchain scan...");
 
         // Get the wallet's birthday (earliest block we need to scan)
         let birthday_height = self.get_wallet_birthday()
@@ -134,25 +257,36 @@ impl BlockchainScanner {
         let chain_tip = self.lightwalletd.get_latest_block_height().await
             .context("Failed to get chain tip")?;
 
-        println!("  Wallet birthday: {}", birthday_height);
-        println!("  Chain tip: {}", chain_tip);
+        tracing::info!("Wallet birthday: {}", birthday_height);
+        tracing::info!("Chain tip: {}", chain_tip);
+        tracing::Span::current().record("chain_tip", chain_tip);
 
         if chain_tip < birthday_height {
             anyhow::bail!("Chain tip ({}) is before wallet birthday ({})", chain_tip, birthday_height);
         }
 
         // Check what height has already been scanned
-        let last_scanned = self.get_last_scanned_height()?;
+        let mut last_scanned = self.get_last_scanned_height()?;
+
+        // Make sure the block we stopped at is still on the best chain
+        if let Some(last_height) = last_scanned {
+            let tip_blocks = self.download_blocks(last_height, last_height).await?;
+            if let Some(block) = tip_blocks.first() {
+                if hashes_diverge(self.stored_block_hash(last_height)?, &block.hash) {
+                    last_scanned = Some(self.rewind_for_reorg(last_height)?);
+                }
+            }
+        }
 
         let start = if let Some(last_height) = last_scanned {
             // Resume from where we left off - scan from next block
             let next_height = last_height + 1;
-            println!("  Last scanned height: {}", last_height);
-            println!("  Resuming from: {}", next_height);
+            tracing::debug!("Last scanned height: {}", last_height);
+            tracing::debug!("Resuming from: {}", next_height);
 
             // If we're already caught up, no need to scan
             if next_height > chain_tip {
-                println!("✓ Already up to date!");
+                tracing::info!("Already up to date!");
                 return Ok(ScanSummary {
                     start_height: chain_tip,
                     end_height: chain_tip,
@@ -164,45 +298,85 @@ impl BlockchainScanner {
             next_height
         } else {
             // First scan - start from birthday
-            println!("  First scan - starting from birthday");
+            tracing::info!("First scan - starting from birthday");
             birthday_height
         };
 
+        tracing::Span::current().record("start_height", start);
+
         let total_blocks = chain_tip - start + 1;
-        println!("  Blocks to scan: {}", total_blocks);
+        tracing::info!("Blocks to scan: {}", total_blocks);
 
-        // Process blocks in batches to save progress incrementally
-        const BATCH_SIZE: u64 = 50_000;
+        // Stream the range and scan it in batches, saving progress after each one
+        let batch_size_limit = self.batch_size;
+        let mut blocks_stream: Option<BlockStream> = None;
         let mut current_height = start;
         let mut total_blocks_scanned = 0;
         let mut total_notes_discovered = 0;
 
+        let progress = |scanned_height: u64, blocks_scanned: usize, notes_discovered: usize| ScanProgress {
+            start_height: start,
+            scanned_height,
+            chain_tip,
+            blocks_scanned,
+            notes_discovered,
+        };
+        self.control.report(progress(start - 1, 0, 0));
+
         while current_height <= chain_tip {
-            let batch_end = std::cmp::min(current_height + BATCH_SIZE - 1, chain_tip);
+            if self.control.is_cancelled() {
+                tracing::info!("Scan cancelled at height {}", current_height - 1);
+                anyhow::bail!("Scan cancelled at height {}", current_height - 1);
+            }
+
+            let batch_end = std::cmp::min(current_height + batch_size_limit - 1, chain_tip);
             let batch_size = batch_end - current_height + 1;
 
-            println!("\n📦 Batch: blocks {} to {} ({} blocks)",
+            tracing::info!("Batch: blocks {} to {} ({} blocks)",
                      current_height, batch_end, batch_size);
-            println!("   Progress: {}/{} blocks ({:.1}%)",
-                     current_height - start,
+            tracing::debug!("Progress: {}/{} blocks ({:.1}%)",
+                     current_height.saturating_sub(start),
                      total_blocks,
-                     ((current_height - start) as f64 / total_blocks as f64) * 100.0);
+                     (current_height.saturating_sub(start) as f64 / total_blocks as f64) * 100.0);
+
+            // Take this batch off the stream, opening it for the rest of the range if needed
+            tracing::debug!("Downloading...");
+            if blocks_stream.is_none() {
+                blocks_stream = Some(self.lightwalletd.stream_compact_blocks(current_height, chain_tip).await?);
+            }
+            let stream = blocks_stream.as_mut().expect("block stream was just opened");
+            let blocks = next_batch(stream, batch_size).await?;
+
+            match blocks.first() {
+                Some(first_block) if first_block.height == current_height => {}
+                _ => anyhow::bail!(
+                    "Block stream did not continue at height {} (chain tip {})",
+                    current_height,
+                    chain_tip
+                ),
+            }
 
-            // Download this batch
-            println!("   Downloading...");
-            let blocks = self.download_blocks(current_height, batch_end).await?;
+            // The chain may have reorged under us since the previous batch
+            let prev_height = current_height - 1;
+            if hashes_diverge(self.stored_block_hash(prev_height)?, &blocks[0].prev_hash) {
+                current_height = self.rewind_for_reorg(prev_height)? + 1;
+                blocks_stream = None;
+                continue;
+            }
 
             // Scan this batch
-            println!("   Scanning...");
-            let scan_result = self.scan_blocks(&blocks)?;
+            tracing::debug!("Scanning...");
+            let scan_result = self.scan_blocks(blocks)?;
 
             total_blocks_scanned += scan_result.blocks_scanned;
             total_notes_discovered += scan_result.notes_discovered;
 
-            println!("   ✓ Batch complete: {} blocks scanned, {} notes found",
+            tracing::info!("Batch complete: {} blocks scanned, {} notes found",
                      scan_result.blocks_scanned,
                      scan_result.notes_discovered);
 
+            self.control.report(progress(batch_end, total_blocks_scanned, total_notes_discovered));
+
             // Move to next batch
             current_height = batch_end + 1;
         }
@@ -214,9 +388,9 @@ impl BlockchainScanner {
             notes_discovered: total_notes_discovered,
         };
 
-        println!("\n✓ Scan complete!");
-        println!("  Total blocks scanned: {}", summary.blocks_scanned);
-        println!("  Total notes discovered: {}", summary.notes_discovered);
+        tracing::info!("Scan complete!");
+        tracing::info!("Total blocks scanned: {}", summary.blocks_scanned);
+        tracing::info!("Total notes discovered: {}", summary.notes_discovered);
 
         Ok(summary)
     }
@@ -239,32 +413,88 @@ impl BlockchainScanner {
             },
             Err(e) => {
                 // If the query fails, log and assume first scan
-                println!("  Note: Could not query chain height ({:?}), assuming first scan", e);
+                tracing::warn!("Note: Could not query chain height ({:?}), assuming first scan", e);
                 Ok(None)
             }
         }
     }
 
+    /// Hash of a block the wallet has already scanned, if it has one stored
+    fn stored_block_hash(&self, height: u64) -> Result<Option<BlockHash>> {
+        self.wallet_db
+            .get_block_hash(BlockHeight::from_u32(height as u32))
+            .map_err(|e| anyhow::anyhow!("Failed to read stored block hash: {:?}", e))
+    }
+
+    /// Truncate the wallet to a safety margin below a reorged block
+    ///
+    /// Returns the height the wallet was actually truncated to, which the
+    /// caller resumes scanning after.
+    fn rewind_for_reorg(&mut self, reorg_height: u64) -> Result<u64> {
+        let target = reorg_height.saturating_sub(REORG_SAFETY_MARGIN);
+        tracing::warn!("Chain reorg detected at height {}, rewinding to {}", reorg_height, target);
+
+        let truncated = self.wallet_db
+            .truncate_to_height(BlockHeight::from_u32(target as u32))
+            .map_err(|e| anyhow::anyhow!("Failed to rewind wallet after reorg: {:?}", e))?;
+
+        tracing::info!("Wallet truncated to height {}", truncated);
+        Ok(u64::from(truncated))
+    }
+
+    /// Rewind the wallet below its last scanned block to clear a checkpoint conflict
+    ///
+    /// Truncating drops the note commitment tree checkpoints above the target along
+    /// with the blocks, so the next scan only replays the tail instead of starting
+    /// over from the birthday. Returns the height the wallet was truncated to.
+    pub fn rewind_for_checkpoint_conflict(&mut self) -> Result<u64> {
+        let last_scanned = self.get_last_scanned_height()?
+            .context("Wallet has no scanned blocks to rewind")?;
+        let target = last_scanned.saturating_sub(REORG_SAFETY_MARGIN);
+        tracing::warn!("Checkpoint conflict at height {}, rewinding to {}", last_scanned, target);
+
+        let truncated = self.wallet_db
+            .truncate_to_height(BlockHeight::from_u32(target as u32))
+            .map_err(|e| anyhow::anyhow!("Failed to rewind wallet after checkpoint conflict: {:?}", e))?;
+
+        tracing::info!("Wallet truncated to height {}", truncated);
+        Ok(u64::from(truncated))
+    }
+
     /// Get the wallet's birthday height (earliest block to scan)
     ///
     /// Returns the wallet birthday height for scanning.
     ///
     /// For production wallets using from_sapling_activation(), this will return the
     /// Sapling activation height. No safety margin is needed since there's no checkpoint.
+    ///
+    /// Never earlier than the earliest account birthday in the wallet database: there is
+    /// no tree state below it, and accounts recreated with a later `resume_birthday` must
+    /// not rescan what was already synced.
     fn get_wallet_birthday(&self) -> Result<u64> {
-        const REORG_SAFETY_MARGIN: u64 = 0;
-
-        // First, check if user specified a custom birthday in environment
-        if let Ok(birthday_str) = std::env::var("WALLET_BIRTHDAY_HEIGHT") {
-            if !birthday_str.trim().is_empty() {
-                if let Ok(birthday) = birthday_str.trim().parse::<u64>() {
-                    let scan_from = birthday + REORG_SAFETY_MARGIN;
-                    println!("  Wallet birthday from env: {}", birthday);
-                    println!("  Starting scan from: {} (birthday + {} block safety margin)",
-                             scan_from, REORG_SAFETY_MARGIN);
-                    return Ok(scan_from);
-                }
+        let configured = self.get_configured_birthday()?;
+
+        let account_birthday = self.wallet_db.get_wallet_birthday()
+            .map_err(|e| anyhow::anyhow!("Failed to get account birthday: {:?}", e))?
+            .map(u64::from);
+
+        match account_birthday {
+            Some(account_birthday) if account_birthday > configured => {
+                tracing::debug!("Starting scan from account birthday: {}", account_birthday);
+                Ok(account_birthday)
             }
+            _ => Ok(configured),
+        }
+    }
+
+    /// The configured birthday (see `with_birthday`), or Sapling activation
+    fn get_configured_birthday(&self) -> Result<u64> {
+        // First, check if a custom birthday was configured. It is used as is: starting
+        // any later could skip the wallet's first notes, and reorgs are handled by rewinding
+        // REORG_SAFETY_MARGIN blocks when one is detected.
+        if let Some(birthday) = self.configured_birthday {
+            tracing::debug!("Configured wallet birthday: {}", birthday);
+            return Ok(birthday);
         }
 
         // Get the minimum birthday height across all accounts
@@ -277,7 +507,7 @@ impl BlockchainScanner {
                 Network::TestNetwork => 280_000, // Testnet sapling activation
                 Network::MainNetwork => 419_200, // Mainnet sapling activation
             };
-            println!("  Using default birthday (Sapling activation): {}", default_birthday);
+            tracing::debug!("Using default birthday (Sapling activation): {}", default_birthday);
             return Ok(default_birthday);
         }
 
@@ -289,44 +519,26 @@ impl BlockchainScanner {
             Network::TestNetwork => 280_000,
             Network::MainNetwork => 419_200,
         };
-        println!("  Using default birthday (Sapling activation): {}", default_birthday);
+        tracing::debug!("Using default birthday (Sapling activation): {}", default_birthday);
         Ok(default_birthday)
     }
 
-    /// Download compact blocks from lightwalletd
+    /// Download compact blocks from the block source
     async fn download_blocks(&mut self, start: u64, end: u64) -> Result<Vec<CompactBlock>> {
-        println!("  Downloading blocks {} to {}...", start, end);
+        tracing::debug!("Downloading blocks {} to {}...", start, end);
 
-        // Stream compact blocks from lightwalletd
-        let mut stream = self.lightwalletd.get_block_range(start, end).await
-            .context("Failed to start block stream")?;
+        let blocks = self.lightwalletd.get_compact_blocks(start, end).await?;
 
-        let mut blocks = Vec::new();
-
-        // Collect all blocks from the stream
-        use tokio_stream::StreamExt;
-        while let Some(block_result) = stream.next().await {
-            match block_result {
-                Ok(block) => {
-                    if blocks.len() % 1000 == 0 && !blocks.is_empty() {
-                        println!("    Downloaded {} blocks...", blocks.len());
-                    }
-                    blocks.push(block);
-                }
-                Err(e) => {
-                    anyhow::bail!("Failed to receive block: {}", e);
-                }
-            }
-        }
-
-        println!("  ✓ Downloaded {} blocks", blocks.len());
+        tracing::debug!("Downloaded {} blocks", blocks.len());
 
         Ok(blocks)
     }
 
     /// Scan cached blocks for wallet transactions
-    fn scan_blocks(&mut self, blocks: &[CompactBlock]) -> Result<ScanResult> {
-        println!("  Scanning {} blocks...", blocks.len());
+    ///
+    /// Takes ownership of the batch so it's moved into the cache rather than copied.
+    fn scan_blocks(&mut self, blocks: Vec<CompactBlock>) -> Result<ScanResult> {
+        tracing::debug!("Scanning {} blocks...", blocks.len());
 
         if blocks.is_empty() {
             return Ok(ScanResult {
@@ -335,16 +547,7 @@ impl BlockchainScanner {
             });
         }
 
-        // Insert blocks into the block cache
-        let mut blocks_written = 0;
-        for block in blocks {
-            let height = BlockHeight::from_u32(block.height as u32);
-            self.block_cache.write_block(height, block)
-                .context("Failed to write block to cache")?;
-            blocks_written += 1;
-        }
-
-        println!("  ✓ Cached {} blocks", blocks_written);
+        let block_count = blocks.len();
 
         // Get the starting height from first block
         let first_block = &blocks[0];
@@ -366,11 +569,11 @@ impl BlockchainScanner {
                     "DELETE FROM orchard_tree_checkpoints WHERE checkpoint_id = ?",
                     [clear_height],
                 );
-                println!("  ✓ Cleared checkpoint at height {}", clear_height);
+                tracing::debug!("Cleared checkpoint at height {}", clear_height);
             }
         }
 
-        println!("  Trial-decrypting notes...");
+        tracing::debug!("Trial-decrypting notes...");
 
         // Parse block hash from the first block's prev_hash
         let block_hash = if first_block.prev_hash.len() == 32 {
@@ -387,36 +590,82 @@ impl BlockchainScanner {
         // from account creation - they're stored in shard tables, not checkpoints
         let chain_state = ChainState::empty(start_height - 1, block_hash);
 
-        println!("  Scanning from height {}...", start_height);
+        tracing::debug!("Scanning from height {}...", start_height);
 
         // Scan the cached blocks
         // This will trial-decrypt notes and store discovered transactions
-        let summary = scan_cached_blocks(
-            &self.network,
-            &self.block_cache,
-            &mut self.wallet_db,
-            start_height,
-            &chain_state,
-            blocks.len(),
-        ).map_err(|e| anyhow::anyhow!("Failed to scan blocks: {:?}", e))?;
+        let network = self.network;
+        let wallet_db = &mut *self.wallet_db;
+        let summary = self.block_cache.scan_batch(blocks, |cache| {
+            tracing::debug!("Cached {} blocks", cache.len());
+            scan_cached_blocks(
+                &network,
+                cache,
+                wallet_db,
+                start_height,
+                &chain_state,
+                block_count,
+            ).map_err(|e| anyhow::anyhow!("Failed to scan blocks: {:?}", e))
+        })?;
 
         // Count received notes from both Sapling and Orchard pools
         let sapling_notes = summary.received_sapling_note_count();
         let orchard_notes = summary.received_orchard_note_count();
         let total_notes = sapling_notes + orchard_notes;
 
-        println!("  ✓ Scan complete");
-        println!("    Sapling notes: {}", sapling_notes);
-        println!("    Orchard notes: {}", orchard_notes);
-        println!("    Total notes discovered: {}", total_notes);
+        tracing::debug!("Scan complete");
+        tracing::debug!("Sapling notes: {}", sapling_notes);
+        tracing::debug!("Orchard notes: {}", orchard_notes);
+        tracing::debug!("Total notes discovered: {}", total_notes);
 
         Ok(ScanResult {
-            blocks_scanned: blocks.len(),
+            blocks_scanned: block_count,
             notes_discovered: total_notes,
         })
     }
 }
 
+/// Birthday from `WALLET_BIRTHDAY_HEIGHT`, if set
+fn configured_birthday() -> Option<u64> {
+    std::env::var("WALLET_BIRTHDAY_HEIGHT")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+}
+
+/// Take up to `max` blocks off `stream`, fewer only if it ends
+async fn next_batch(stream: &mut BlockStream, max: u64) -> Result<Vec<CompactBlock>> {
+    use tokio_stream::StreamExt;
+
+    let mut blocks = Vec::new();
+    while (blocks.len() as u64) < max {
+        match stream.next().await {
+            Some(block) => blocks.push(block?),
+            None => break,
+        }
+    }
+    Ok(blocks)
+}
+
+/// Birthday for an account recreated in a fresh wallet database
+///
+/// When the local SQLite file is lost but Postgres still records `last_synced_height`,
+/// scanning can resume there instead of at `birthday`. It must not skip an unspent note,
+/// though, since spending needs the note in the wallet database, so it starts no later than
+/// the block of the earliest unspent note Postgres knows about. Notes spent before that
+/// point don't affect the balance; the new database never sees them, so only their existing
+/// Postgres rows keep that history. A reset that clears those rows also clears
+/// `last_synced_height`, so the rescan starts at `birthday` again.
+pub fn resume_birthday(birthday: u32, last_synced_height: Option<u32>, earliest_unspent_height: Option<u32>) -> u32 {
+    let Some(last_synced) = last_synced_height else {
+        return birthday;
+    };
+    let resume = match earliest_unspent_height {
+        Some(unspent) => last_synced.min(unspent),
+        None => last_synced,
+    };
+    resume.max(birthday)
+}
+
 /// Summary of a blockchain scan operation
 #[derive(Debug, Clone)]
 pub struct ScanSummary {
@@ -433,6 +682,403 @@ struct ScanResult {
     notes_discovered: usize,
 }
 
+#[cfg(test)]
+mod reorg_tests {
+    use super::*;
+
+    #[test]
+    fn test_diverging_hash_is_reorg() {
+        let stored = BlockHash([1u8; 32]);
+        let mut remote = [1u8; 32];
+        assert!(!hashes_diverge(Some(stored), &remote));
+
+        remote[31] = 2;
+        assert!(hashes_diverge(Some(stored), &remote));
+    }
+
+    #[test]
+    fn test_missing_stored_hash_is_not_reorg() {
+        assert!(!hashes_diverge(None, &[7u8; 32]));
+    }
+}
+
+#[cfg(test)]
+mod resume_tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_birthday_without_checkpoint_uses_birthday() {
+        assert_eq!(resume_birthday(1_000, None, None), 1_000);
+        assert_eq!(resume_birthday(1_000, None, Some(1_500)), 1_000);
+    }
+
+    #[test]
+    fn test_resume_birthday_stops_at_earliest_unspent_note() {
+        assert_eq!(resume_birthday(1_000, Some(2_000), None), 2_000);
+        assert_eq!(resume_birthday(1_000, Some(2_000), Some(1_500)), 1_500);
+        assert_eq!(resume_birthday(1_000, Some(2_000), Some(2_500)), 2_000);
+        assert_eq!(resume_birthday(1_000, Some(900), None), 1_000);
+    }
+}
+
+#[cfg(test)]
+mod block_cache_tests {
+    use super::*;
+
+    fn block_at(height: u64) -> CompactBlock {
+        CompactBlock {
+            height,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_cache_emptied_after_each_batch() {
+        let mut cache = InMemoryBlockCache::new();
+
+        let seen = cache
+            .scan_batch(vec![block_at(100), block_at(101)], |cache| Ok(cache.len()))
+            .unwrap();
+        assert_eq!(seen, 2);
+        assert_eq!(cache.len(), 0);
+
+        let seen = cache
+            .scan_batch(vec![block_at(102)], |cache| Ok(cache.len()))
+            .unwrap();
+        assert_eq!(seen, 1);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_cache_emptied_when_scan_fails() {
+        let mut cache = InMemoryBlockCache::new();
+
+        let result: Result<()> =
+            cache.scan_batch(vec![block_at(100)], |_| anyhow::bail!("scan failed"));
+        assert!(result.is_err());
+        assert_eq!(cache.len(), 0);
+    }
+}
+
+#[cfg(test)]
+mod mock_chain_tests {
+    use super::*;
+    use super::super::account::AccountManager;
+    use super::super::block_source::mock::{self, MockLightwalletd};
+    use super::super::database::Database;
+    use zcash_client_backend::data_api::testing::{AddressType, TestFvk};
+    use zcash_client_backend::proto::compact_formats::CompactTx;
+    use zcash_keys::keys::UnifiedSpendingKey;
+    use zcash_protocol::value::Zatoshis;
+
+    /// Birthday of the test wallets (testnet, after Sapling activation)
+    const BIRTHDAY: u64 = 300_000;
+
+    fn temp_db_path() -> PathBuf {
+        std::env::temp_dir().join(format!("shield-scanner-{}.db", Uuid::new_v4()))
+    }
+
+    /// Scanner over the mock chain, born at `BIRTHDAY` whatever the environment says
+    fn mock_scanner<'a>(
+        wallet_db: &'a mut WalletDb<Connection, Network, SystemClock, OsRng>,
+        chain: &MockLightwalletd,
+        db_path: &PathBuf,
+    ) -> BlockchainScanner<'a, MockLightwalletd> {
+        BlockchainScanner::new_with_path(wallet_db, chain.clone(), Network::TestNetwork, db_path.clone())
+            .with_birthday(Some(BIRTHDAY))
+    }
+
+    /// A mock chain starting at the block before `BIRTHDAY`, plus a fresh wallet born on it
+    async fn wallet_on_mock_chain(db_path: &PathBuf) -> (MockLightwalletd, Database, UnifiedSpendingKey) {
+        let chain = MockLightwalletd::starting_at(BIRTHDAY - 1);
+        chain.extend_to(BIRTHDAY + 5);
+
+        let mut db = Database::new(db_path, Network::TestNetwork).unwrap();
+        let (_, usk) = AccountManager::new(&mut db)
+            .create_account("Test", &[7u8; 32], &chain, Some(BIRTHDAY as u32))
+            .await
+            .unwrap();
+
+        (chain, db, usk)
+    }
+
+    /// A transaction with one Sapling output paying the account's default address
+    fn payment_to(usk: &UnifiedSpendingKey, height: u64, value: u64, sapling_tree_size: u32) -> CompactTx {
+        let mut tx = CompactTx {
+            hash: mock::block_hash(height, 0xff).to_vec(),
+            ..Default::default()
+        };
+        usk.sapling().to_diversifiable_full_viewing_key().add_output(
+            &mut tx,
+            &Network::TestNetwork,
+            BlockHeight::from_u32(height as u32),
+            None,
+            AddressType::DefaultExternal,
+            Zatoshis::const_from_u64(value),
+            sapling_tree_size,
+            &mut OsRng,
+        );
+        tx
+    }
+
+    #[tokio::test]
+    async fn test_scan_discovers_note_on_mock_chain() {
+        let db_path = temp_db_path();
+        let (chain, mut db, usk) = wallet_on_mock_chain(&db_path).await;
+
+        let note_height = chain.tip() + 1;
+        chain.push_block(vec![payment_to(&usk, note_height, 50_000, 0)]);
+        chain.extend_to(note_height + 5);
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path);
+
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert_eq!(summary.start_height, BIRTHDAY);
+        assert_eq!(summary.end_height, chain.tip());
+        assert_eq!(summary.blocks_scanned as u64, chain.tip() - BIRTHDAY + 1);
+        assert_eq!(summary.notes_discovered, 1);
+
+        // A second scan resumes at the tip and finds nothing new
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert_eq!(summary.blocks_scanned, 0);
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_scan_streams_range_in_batches() {
+        let db_path = temp_db_path();
+        let (chain, mut db, usk) = wallet_on_mock_chain(&db_path).await;
+
+        let note_height = chain.tip() + 3;
+        chain.extend_to(note_height - 1);
+        chain.push_block(vec![payment_to(&usk, note_height, 50_000, 0)]);
+        chain.extend_to(BIRTHDAY + 21);
+        let (progress_tx, progress_rx) = watch::channel(ScanProgress::default());
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path)
+            .with_batch_size(4)
+            .with_control(ScanControl {
+                cancel: None,
+                progress: Some(progress_tx),
+            });
+
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert_eq!(summary.blocks_scanned as u64, chain.tip() - BIRTHDAY + 1);
+        assert_eq!(summary.notes_discovered, 1);
+        assert_eq!(progress_rx.borrow().scanned_height, chain.tip());
+        // Six batches, one request
+        assert_eq!(chain.streams_opened(), 1);
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_scan_stops_before_first_batch() {
+        let db_path = temp_db_path();
+        let (chain, mut db, _usk) = wallet_on_mock_chain(&db_path).await;
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let (progress_tx, progress_rx) = watch::channel(ScanProgress::default());
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path)
+            .with_control(ScanControl {
+                cancel: Some(cancel),
+                progress: Some(progress_tx),
+            });
+
+        let err = scanner.scan_from_birthday(Uuid::nil()).await.unwrap_err();
+        assert!(err.to_string().contains("cancelled"), "{}", err);
+
+        let progress = *progress_rx.borrow();
+        assert_eq!(progress.start_height, BIRTHDAY);
+        assert_eq!(progress.scanned_height, BIRTHDAY - 1);
+        assert_eq!(progress.blocks_scanned, 0);
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_scan_reports_progress_to_tip() {
+        let db_path = temp_db_path();
+        let (chain, mut db, _usk) = wallet_on_mock_chain(&db_path).await;
+        let (progress_tx, progress_rx) = watch::channel(ScanProgress::default());
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path)
+            .with_control(ScanControl {
+                cancel: None,
+                progress: Some(progress_tx),
+            });
+
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+
+        let progress = *progress_rx.borrow();
+        assert_eq!(progress.scanned_height, chain.tip());
+        assert_eq!(progress.chain_tip, chain.tip());
+        assert_eq!(progress.blocks_scanned, summary.blocks_scanned);
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_scan_rewinds_after_mock_reorg() {
+        let db_path = temp_db_path();
+        let (chain, mut db, _usk) = wallet_on_mock_chain(&db_path).await;
+        chain.extend_to(BIRTHDAY + 40);
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path);
+        scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+
+        // Replace the last few blocks the wallet scanned, then grow the new branch
+        let old_tip = chain.tip();
+        chain.reorg_from(old_tip - 2, 1);
+        chain.extend_to(old_tip + 3);
+
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert!(summary.start_height <= old_tip - 2);
+        assert_eq!(summary.end_height, chain.tip());
+        assert_eq!(
+            scanner.stored_block_hash(old_tip).unwrap(),
+            Some(BlockHash(mock::block_hash(old_tip, 1)))
+        );
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_scan_drops_notes_orphaned_by_deep_reorg() {
+        let db_path = temp_db_path();
+        let (chain, mut db, usk) = wallet_on_mock_chain(&db_path).await;
+        chain.extend_to(BIRTHDAY + 30);
+        let fork_height = chain.tip() + 1;
+        let note_height = fork_height + 5;
+        chain.extend_to(note_height - 1);
+        chain.push_block(vec![payment_to(&usk, note_height, 50_000, 0)]);
+        chain.extend_to(fork_height + 2 * REORG_SAFETY_MARGIN);
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path);
+        assert_eq!(scanner.scan_from_birthday(Uuid::nil()).await.unwrap().notes_discovered, 1);
+
+        // A reorg deeper than the margin replaces the block with the note
+        let old_tip = chain.tip();
+        chain.reorg_from(fork_height, 1);
+        chain.extend_to(old_tip + 2);
+
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert_eq!(summary.end_height, chain.tip());
+        assert_eq!(summary.notes_discovered, 0);
+        assert_eq!(
+            scanner.stored_block_hash(fork_height).unwrap(),
+            Some(BlockHash(mock::block_hash(fork_height, 1)))
+        );
+        assert_eq!(
+            scanner.stored_block_hash(note_height).unwrap(),
+            Some(BlockHash(mock::block_hash(note_height, 1)))
+        );
+        drop(scanner);
+
+        let conn = Connection::open(&db_path).unwrap();
+        // The note's transaction is no longer mined anywhere
+        let mined_notes: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sapling_received_notes n
+                 JOIN transactions t ON n.transaction_id = t.id_tx
+                 WHERE t.mined_height IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(mined_notes, 0);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_database_resumes_from_postgres_checkpoint() {
+        let db_path = temp_db_path();
+        let (chain, mut db, usk) = wallet_on_mock_chain(&db_path).await;
+
+        let note_height = chain.tip() + 10;
+        chain.extend_to(note_height - 1);
+        chain.push_block(vec![payment_to(&usk, note_height, 50_000, 0)]);
+        chain.extend_to(BIRTHDAY + 40);
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path);
+        let first = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert_eq!(first.notes_discovered, 1);
+        drop(scanner);
+        drop(db);
+
+        // The SQLite file is lost, but Postgres still has the sync height and the unspent note
+        let _ = std::fs::remove_file(&db_path);
+        let last_synced = first.end_height as u32;
+        chain.extend_to(BIRTHDAY + 50);
+
+        let start = resume_birthday(BIRTHDAY as u32, Some(last_synced), Some(note_height as u32));
+        assert_eq!(start as u64, note_height);
+
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+        AccountManager::new(&mut db)
+            .create_account("Test", &[7u8; 32], &chain, Some(start))
+            .await
+            .unwrap();
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path);
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+
+        // Only blocks from the unspent note on are scanned, and the note is found again
+        assert_eq!(summary.start_height, note_height);
+        assert_eq!(summary.end_height, chain.tip());
+        assert_eq!(summary.blocks_scanned as u64, chain.tip() - note_height + 1);
+        assert_eq!(summary.notes_discovered, 1);
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_conflict_rewind_keeps_scanned_history() {
+        let db_path = temp_db_path();
+        let (chain, mut db, usk) = wallet_on_mock_chain(&db_path).await;
+
+        let note_height = chain.tip() + 1;
+        chain.push_block(vec![payment_to(&usk, note_height, 50_000, 0)]);
+        chain.extend_to(BIRTHDAY + 40);
+
+        let wallet_db = db.get_wallet_db_mut().unwrap();
+        let mut scanner = mock_scanner(wallet_db, &chain, &db_path);
+        scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+
+        let rewound = scanner.rewind_for_checkpoint_conflict().unwrap();
+        assert!(rewound <= chain.tip() - REORG_SAFETY_MARGIN);
+        assert!(rewound > note_height);
+
+        // Only the tail is rescanned, and the note below the rewind point is kept
+        let summary = scanner.scan_from_birthday(Uuid::nil()).await.unwrap();
+        assert_eq!(summary.start_height, rewound + 1);
+        assert_eq!(summary.end_height, chain.tip());
+        assert_eq!(summary.notes_discovered, 0);
+        assert!(scanner.stored_block_hash(note_height).unwrap().is_some());
+
+        drop(scanner);
+        let _ = std::fs::remove_file(&db_path);
+    }
+}
+
 #[cfg(all(test, feature = "disabled_tests"))]
 mod tests {
     use super::*;
@@ -447,7 +1093,7 @@ mod tests {
         let database = Database::new(db_path.clone(), Network::TestNetwork).unwrap();
         let wallet_db = database.get_wallet_db_mut().unwrap();
 
-        let lightwalletd = LightwalletdClient::new("https://testnet.lightwalletd.com:9067".to_string());
+        let lightwalletd = LightwalletdClient::new("https://testnet.lightwalletd.com:9067".to_string(), vec![]);
 
         let scanner = BlockchainScanner::new(
             wallet_db,
@@ -467,7 +1113,7 @@ mod tests {
         // Testnet
         let database = Database::new(db_path.clone(), Network::TestNetwork).unwrap();
         let wallet_db = database.get_wallet_db_mut().unwrap();
-        let lightwalletd = LightwalletdClient::new("http://localhost:9067".to_string());
+        let lightwalletd = LightwalletdClient::new("http://localhost:9067".to_string(), vec![]);
         let scanner = BlockchainScanner::new(wallet_db, lightwalletd, Network::TestNetwork);
 
         let birthday = scanner.get_wallet_birthday().unwrap();
@@ -478,7 +1124,7 @@ mod tests {
         let db_path2 = temp_dir2.path().join("test_wallet.db");
         let database2 = Database::new(db_path2.clone(), Network::MainNetwork);
         let wallet_db2 = database2.init().unwrap();
-        let lightwalletd2 = LightwalletdClient::new("http://localhost:9067".to_string());
+        let lightwalletd2 = LightwalletdClient::new("http://localhost:9067".to_string(), vec![]);
         let scanner2 = BlockchainScanner::new(wallet_db2, lightwalletd2, Network::MainNetwork);
 
         let birthday2 = scanner2.get_wallet_birthday().unwrap();
@@ -1,40 +1,215 @@
 use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use rand::rngs::OsRng;
+use rusqlite::Connection;
 use std::convert::Infallible;
+use std::num::NonZeroU32;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tokio::sync::Semaphore;
 
 // Transaction building
 use zcash_client_backend::data_api::wallet::{
     create_proposed_transactions,
+    propose_send_max_transfer,
+    propose_shielding,
     propose_standard_transfer_to_address,
-    input_selection::GreedyInputSelectorError,
+    propose_transfer,
+    input_selection::{GreedyInputSelector, GreedyInputSelectorError},
     ConfirmationsPolicy,
     SpendingKeys,
 };
-use zcash_client_backend::data_api::{Account, WalletRead};
-use zcash_client_backend::fees::StandardFeeRule;
-use zcash_client_backend::wallet::OvkPolicy;
+use zcash_client_backend::data_api::{
+    error::Error as WalletError, Account, MaxSpendMode, WalletRead, WalletWrite,
+};
+use zcash_client_backend::fees::{
+    standard::SingleOutputChangeStrategy, ChangeError, DustOutputPolicy, StandardFeeRule,
+};
+use zcash_client_backend::proposal::Proposal;
+use zcash_client_backend::wallet::{OvkPolicy, WalletTransparentOutput};
 use zcash_primitives::transaction::fees::zip317::FeeError;
 use zcash_protocol::ShieldedProtocol;
 
 // Types
-use zcash_address::ZcashAddress;
-use zcash_client_sqlite::ReceivedNoteId;
+use zcash_address::{ConversionError, ZcashAddress};
+use zcash_client_backend::proto::service::GetAddressUtxosReply;
+use zcash_client_sqlite::{util::SystemClock, AccountUuid, ReceivedNoteId, WalletDb};
+use zcash_keys::address::{Address, UnifiedAddress};
+use zcash_keys::encoding::AddressCodec;
 use zcash_keys::keys::UnifiedSpendingKey;
 use zcash_primitives::memo::MemoBytes;
-use zcash_protocol::consensus::{Network, NetworkType};
+use zcash_primitives::transaction::TxId;
+use zcash_protocol::consensus::{BlockHeight, Network, NetworkType};
 use zcash_protocol::value::Zatoshis;
+use zcash_script::script;
+use zcash_transparent::address::{Script, TransparentAddress};
+use zcash_transparent::bundle::{OutPoint, TxOut};
+use zip321::{Payment, TransactionRequest};
 
 use super::database::Database;
+use crate::utils::env::env_or_positive;
+
+type WalletDatabase = WalletDb<Connection, Network, SystemClock, OsRng>;
+
+/// Minimum transparent balance worth shielding (covers the ZIP-317 fee for a small shielding tx)
+const SHIELDING_THRESHOLD_ZATOSHIS: u64 = 10_000;
+
+/// Transactions proved at once unless overridden by `PROVING_CONCURRENCY`
+const DEFAULT_PROVING_CONCURRENCY: usize = 2;
+
+// Caps concurrent proof generation so simultaneous sends can't take every core
+static PROVING_PERMITS: Lazy<Semaphore> = Lazy::new(|| Semaphore::new(proving_concurrency()));
+
+fn proving_concurrency() -> usize {
+    env_or_positive("PROVING_CONCURRENCY", DEFAULT_PROVING_CONCURRENCY)
+}
+
+/// Run CPU-heavy synchronous work without stalling the async runtime
+///
+/// The builder borrows the pooled wallet database, so the work can't be moved onto
+/// `spawn_blocking`; `block_in_place` hands this worker's other tasks to a fresh
+/// worker while it runs instead. Current-thread runtimes (tests) run it inline.
+fn run_blocking<T>(work: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(work)
+        }
+        _ => work(),
+    }
+}
+
+/// Maximum text memo length in bytes (512-byte memo field minus the 0xF4 marker)
+pub const MAX_MEMO_BYTES: usize = 511;
+
+/// One recipient of a multi-output transaction
+pub struct PaymentOutput<'a> {
+    pub to_address: &'a str,
+    pub amount_zat: u64,
+    pub memo: Option<&'a str>,
+}
+
+/// A single payment decoded from a ZIP-321 `zcash:` URI
+#[derive(Debug, PartialEq)]
+pub struct UriPayment {
+    pub to_address: String,
+    pub amount_zat: u64,
+    pub memo: Option<String>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+}
+
+/// The wallet can't fund a proposal
+///
+/// Returned (wrapped in `anyhow::Error`) when input selection fails for lack of funds,
+/// so callers can `downcast_ref` it instead of matching on error strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsufficientFunds {
+    /// Value of the inputs input selection could use
+    pub available_zat: u64,
+    /// Value needed to fund the outputs, including the fee
+    pub required_zat: u64,
+}
+
+impl std::fmt::Display for InsufficientFunds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Insufficient funds: {} zatoshis required (including fee), {} available",
+            self.required_zat, self.available_zat
+        )
+    }
+}
+
+impl std::error::Error for InsufficientFunds {}
+
+/// The recipient address belongs to a different network than the wallet
+///
+/// Returned (wrapped in `anyhow::Error`) so callers can report it as a client error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongNetwork {
+    pub address_network: NetworkType,
+    pub wallet_network: NetworkType,
+}
+
+impl std::fmt::Display for WrongNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Recipient address is {} but wallet is {}",
+            network_type_name(self.address_network),
+            network_type_name(self.wallet_network)
+        )
+    }
+}
+
+impl std::error::Error for WrongNetwork {}
+
+fn network_type_name(network_type: NetworkType) -> &'static str {
+    match network_type {
+        NetworkType::Main => "mainnet",
+        NetworkType::Test => "testnet",
+        NetworkType::Regtest => "regtest",
+    }
+}
 
 /// Transaction builder for creating shielded transactions
-pub struct TransactionBuilder {
-    db: Database,
+pub struct TransactionBuilder<'a> {
+    db: &'a mut Database,
     network: Network,
+    confirmations_policy: ConfirmationsPolicy,
+    fee_rule: StandardFeeRule,
+    change_memo: Option<MemoBytes>,
+    preferred_pool: Option<ShieldedProtocol>,
+    last_txid: Option<TxId>,
+    last_expiry_height: Option<u32>,
 }
 
-impl TransactionBuilder {
-    /// Create a new transaction builder
-    pub fn new(db: Database, network: Network) -> Self {
-        Self { db, network }
+impl<'a> TransactionBuilder<'a> {
+    /// Create a new transaction builder borrowing an open wallet database
+    pub fn new(db: &'a mut Database, network: Network) -> Self {
+        let confirmations_policy = confirmations_policy(None).unwrap_or(ConfirmationsPolicy::MIN);
+        Self {
+            db,
+            network,
+            confirmations_policy,
+            fee_rule: StandardFeeRule::Zip317,
+            change_memo: None,
+            preferred_pool: None,
+            last_txid: None,
+            last_expiry_height: None,
+        }
+    }
+
+    /// Require `min_confirmations` on spent notes instead of `MIN_CONFIRMATIONS`
+    ///
+    /// `None` keeps the default; `Some(0)` is rejected.
+    pub fn with_min_confirmations(mut self, min_confirmations: Option<u32>) -> Result<Self> {
+        self.confirmations_policy = confirmations_policy(min_confirmations)?;
+        Ok(self)
+    }
+
+    /// Use the named fee rule instead of ZIP-317
+    ///
+    /// `None` keeps the default; unknown names are rejected.
+    pub fn with_fee_rule(mut self, fee_rule: Option<&str>) -> Result<Self> {
+        self.fee_rule = parse_fee_rule(fee_rule)?;
+        Ok(self)
+    }
+
+    /// Attach `change_memo` to change outputs
+    ///
+    /// `None` keeps the default of no change memo; text over 511 bytes is rejected.
+    pub fn with_change_memo(mut self, change_memo: Option<&str>) -> Result<Self> {
+        self.change_memo = self.format_memo(change_memo)?;
+        Ok(self)
+    }
+
+    /// Pay single recipients only through the named shielded pool ("orchard" or "sapling")
+    ///
+    /// Change goes to the same pool. `None` keeps the default: the recipient's address
+    /// picks the pool and change falls back to Orchard. Unknown names are rejected.
+    pub fn with_preferred_pool(mut self, pool: Option<&str>) -> Result<Self> {
+        self.preferred_pool = parse_pool(pool)?;
+        Ok(self)
     }
 
     /// Build, sign, and return raw transaction bytes
@@ -50,114 +225,89 @@ impl TransactionBuilder {
     /// * `memo` - Optional memo text (max 511 bytes)
     ///
     /// # Returns
-    /// Raw transaction bytes ready for broadcast
+    /// Raw bytes of every transaction in the proposal, in broadcast order
     pub async fn build_and_sign_transaction(
         &mut self,
         usk: &UnifiedSpendingKey,
         to_address: &str,
         amount_zat: u64,
         memo: Option<&str>,
-    ) -> Result<(Vec<u8>, u64)> {  // Returns (raw_tx, fee_zatoshis)
-        println!("Building transaction...");
-        println!("  To: {}", to_address);
-        println!("  Amount: {} ZAT ({:.8} ZEC)", amount_zat, amount_zat as f64 / 100_000_000.0);
-
-        // Step 1: Parse and validate address
-        let recipient = ZcashAddress::try_from_encoded(to_address)
-            .context("Invalid recipient address")?;
-
-        // Convert Network to NetworkType
-        let network_type = match self.network {
-            Network::MainNetwork => NetworkType::Main,
-            Network::TestNetwork => NetworkType::Test,
-        };
+    ) -> Result<(Vec<Vec<u8>>, u64)> {  // Returns (raw_txs, fee_zatoshis)
+        tracing::info!("Building transaction...");
+        tracing::debug!("To: {}", to_address);
+        tracing::debug!("Amount: {} ZAT ({:.8} ZEC)", amount_zat, amount_zat as f64 / 100_000_000.0);
+        tracing::debug!("Memo: {}", memo.unwrap_or("[none]"));
 
-        let recipient_addr = recipient.convert_if_network(network_type)
-            .map_err(|_| anyhow::anyhow!("Address is for wrong network"))?;
+        tracing::info!("1. Creating transaction proposal...");
+        let proposal = self.propose_transfer_to(usk, to_address, amount_zat, memo)?;
 
-        // Step 2: Convert amount
-        let amount = Zatoshis::from_u64(amount_zat)
-            .map_err(|_| anyhow::anyhow!("Invalid amount"))?;
+        tracing::debug!("Proposal created");
+        tracing::debug!("Steps: {}", proposal.steps().len());
 
-        // Step 3: Format memo (if provided)
-        let memo_bytes = self.format_memo(memo)?;
-        println!("  Memo: {}", memo.unwrap_or("[none]"));
+        let total_fee = Self::estimate_fee_for_proposal(&proposal);
+        tracing::info!("Total fee: {} zatoshis ({} ZEC)", total_fee, total_fee as f64 / 100_000_000.0);
 
-        // Step 4: Get account ID from USK
-        let wallet_db = self.db.get_wallet_db_mut()?;
-        let ufvk = usk.to_unified_full_viewing_key();
-        let account = wallet_db.get_account_for_ufvk(&ufvk)?
-            .ok_or_else(|| anyhow::anyhow!("Account not found for this spending key"))?;
-        let account_id = Account::id(&account); // Use trait method explicitly
+        tracing::info!("2. Building transaction and generating zk-SNARK proofs...");
+        let raw_txs = self.create_transactions(usk, &proposal).await?;
 
-        println!("  From account: {:?}", account_id);
+        tracing::debug!("Transaction serialized ({} transaction(s))", raw_txs.len());
 
-        // Step 5: Create proposal
-        println!("\n1. Creating transaction proposal...");
-
-        let proposal = match propose_standard_transfer_to_address::<_, _, Infallible>(
-            wallet_db,
-            &self.network,
-            StandardFeeRule::Zip317,
-            account_id,
-            ConfirmationsPolicy::MIN, // minimum confirmations (1 for both trusted/untrusted)
-            &recipient_addr,
-            amount,
-            memo_bytes,
-            None, // change_memo
-            ShieldedProtocol::Orchard, // fallback_change_pool
-        ) {
-            Ok(p) => p,
-            Err(e) => anyhow::bail!("Failed to create transaction proposal: {:?}", e),
-        };
-
-        println!("  ✓ Proposal created");
-        println!("  Steps: {}", proposal.steps().len());
+        Ok((raw_txs, total_fee))
+    }
 
-        // Extract total fee from all steps
-        let total_fee: u64 = proposal.steps().iter()
-            .map(|step| u64::from(step.balance().fee_required()))
-            .sum();
+    /// Build and sign a single transaction paying several recipients
+    ///
+    /// All outputs go into one proposal, so the transaction pays one ZIP-317 fee
+    /// instead of one per recipient. Every output is validated before any inputs
+    /// are selected; if one is invalid nothing is built.
+    ///
+    /// # Returns
+    /// (raw_txs, fee_zatoshis)
+    pub async fn build_and_sign_batch(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        outputs: &[PaymentOutput<'_>],
+    ) -> Result<(Vec<Vec<u8>>, u64)> {
+        tracing::info!("Building batch transaction with {} outputs...", outputs.len());
 
-        println!("  Total fee: {} zatoshis ({} ZEC)", total_fee, total_fee as f64 / 100_000_000.0);
+        let proposal = self.propose_batch(usk, outputs)?;
+        let total_fee = Self::estimate_fee_for_proposal(&proposal);
 
-        // Step 6: Build transaction with proofs
-        println!("\n2. Building transaction and generating zk-SNARK proofs...");
+        tracing::info!("Total fee: {} zatoshis ({} ZEC)", total_fee, total_fee as f64 / 100_000_000.0);
 
-        use super::prover::get_prover;
-        let prover = get_prover()?;
+        let raw_txs = self.create_transactions(usk, &proposal).await?;
 
-        // Wrap USK in SpendingKeys for the new API
-        let spending_keys = SpendingKeys::new(usk.clone());
+        tracing::debug!("Batch transaction serialized ({} transaction(s))", raw_txs.len());
 
-        // Note: The type inference for create_proposed_transactions is complex
-        // We explicitly specify error type parameters for GreedyInputSelector and ZIP-317 fees
-        let txids = create_proposed_transactions::<_, _, GreedyInputSelectorError, _, FeeError, ReceivedNoteId>(
-            wallet_db,
-            &self.network,
-            &prover, // spend_prover
-            &prover, // output_prover (same object!)
-            &spending_keys,
-            OvkPolicy::Sender,
-            &proposal,
-        ).map_err(|e| anyhow::anyhow!("Transaction creation failed: {:#?}", e))?;
+        Ok((raw_txs, total_fee))
+    }
 
-        println!("  ✓ Transaction built and signed");
-        println!("  TxID count: {}", txids.len());
+    /// Build and sign a transaction sending every spendable shielded note to one recipient
+    ///
+    /// The fee is deducted from the sent amount, so no change output is created.
+    ///
+    /// # Returns
+    /// (raw_txs, amount_sent_zatoshis, fee_zatoshis)
+    pub async fn build_and_sign_send_max(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        to_address: &str,
+        memo: Option<&str>,
+    ) -> Result<(Vec<Vec<u8>>, u64, u64)> {
+        tracing::info!("Building send-max transaction...");
+        tracing::debug!("To: {}", to_address);
 
-        // Step 7: Get raw bytes
-        println!("\n3. Retrieving transaction bytes...");
+        let proposal = self.propose_send_max_to(usk, to_address, memo)?;
+        let amount_sent = paid_value(&proposal);
+        let total_fee = Self::estimate_fee_for_proposal(&proposal);
 
-        let txid = txids.first();
-        let transaction = wallet_db.get_transaction(*txid)?
-            .ok_or_else(|| anyhow::anyhow!("Transaction not found in database"))?;
+        tracing::info!("Sending {} zatoshis (fee: {} zatoshis)", amount_sent, total_fee);
 
-        let mut raw_tx = Vec::new();
-        transaction.write(&mut raw_tx)?;
+        let raw_txs = self.create_transactions(usk, &proposal).await?;
 
-        println!("  ✓ Transaction serialized ({} bytes)", raw_tx.len());
+        tracing::debug!("Send-max transaction serialized ({} transaction(s))", raw_txs.len());
 
-        Ok((raw_tx, total_fee))
+        Ok((raw_txs, amount_sent, total_fee))
     }
 
     /// Estimate transaction fee without building the full transaction
@@ -171,124 +321,1010 @@ impl TransactionBuilder {
         amount_zat: u64,
         memo: Option<&str>,
     ) -> Result<u64> {
-        // Step 1: Parse and validate address
-        let recipient = ZcashAddress::try_from_encoded(to_address)
-            .context("Invalid recipient address")?;
+        let proposal = self.propose_transfer_to(usk, to_address, amount_zat, memo)?;
+        Ok(Self::estimate_fee_for_proposal(&proposal))
+    }
 
-        let network_type = match self.network {
-            Network::MainNetwork => NetworkType::Main,
-            Network::TestNetwork => NetworkType::Test,
-        };
+    /// Dry-run a single-recipient transfer, returning the fee and input/output counts
+    pub async fn estimate_transfer(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        to_address: &str,
+        amount_zat: u64,
+        memo: Option<&str>,
+    ) -> Result<FeeEstimate> {
+        let proposal = self.propose_transfer_to(usk, to_address, amount_zat, memo)?;
+        Ok(FeeEstimate::from_proposal(&proposal, amount_zat))
+    }
 
-        let recipient_addr = recipient.convert_if_network(network_type)
-            .map_err(|_| anyhow::anyhow!("Address is for wrong network"))?;
+    /// Dry-run a batch send, returning the fee and input/output counts
+    pub async fn estimate_batch(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        outputs: &[PaymentOutput<'_>],
+    ) -> Result<FeeEstimate> {
+        let proposal = self.propose_batch(usk, outputs)?;
+        let amount_zat = outputs.iter().map(|output| output.amount_zat).sum();
+        Ok(FeeEstimate::from_proposal(&proposal, amount_zat))
+    }
 
-        // Step 2: Convert amount
-        let amount = Zatoshis::from_u64(amount_zat)
-            .map_err(|_| anyhow::anyhow!("Invalid amount"))?;
+    /// Dry-run shielding of all stored transparent UTXOs
+    ///
+    /// Only reads the wallet database: UTXOs are whatever the last balance sync stored.
+    pub async fn estimate_shielding(&mut self, usk: &UnifiedSpendingKey) -> Result<FeeEstimate> {
+        let proposal = self.propose_shielding_all(usk)?;
+        Ok(FeeEstimate::from_proposal(&proposal, shielded_value(&proposal)))
+    }
 
-        // Step 3: Format memo
-        let memo_bytes = self.format_memo(memo)?;
+    /// Total ZIP-317 fee across every step of a proposal
+    ///
+    /// Shared by the single, batch, and shielding paths; call it after the proposal is
+    /// built but before proofs are generated.
+    pub fn estimate_fee_for_proposal<FeeRuleT, NoteRef>(proposal: &Proposal<FeeRuleT, NoteRef>) -> u64 {
+        proposal.steps().iter()
+            .map(|step| u64::from(step.balance().fee_required()))
+            .sum()
+    }
 
-        // Step 4: Get account ID
+    /// Shielded value spendable under this builder's confirmations policy
+    ///
+    /// Used to report the real available balance when a proposal fails for lack of funds.
+    pub fn spendable_zat(&self) -> Result<u64> {
+        let wallet_db = self.db.get_wallet_db()?;
+        let summary = wallet_db
+            .get_wallet_summary(self.confirmations_policy)
+            .map_err(|e| anyhow::anyhow!("Failed to get wallet summary: {:?}", e))?;
+
+        Ok(summary
+            .map(|summary| {
+                summary
+                    .account_balances()
+                    .values()
+                    .map(|balance| {
+                        u64::from(balance.sapling_balance().spendable_value())
+                            + u64::from(balance.orchard_balance().spendable_value())
+                    })
+                    .sum()
+            })
+            .unwrap_or(0))
+    }
+
+    /// ID of the final transaction most recently built, as uppercase hex of the raw txid bytes
+    ///
+    /// This matches the `hex(txid)` form the wallet sync writes to PostgreSQL, so a
+    /// row recorded at broadcast time is updated in place once the tx is scanned.
+    pub fn last_txid_hex(&self) -> Option<String> {
+        self.last_txid.map(|txid| hex::encode_upper(txid.as_ref()))
+    }
+
+    /// ID of the final transaction most recently built, as block explorers display it
+    pub fn last_txid_display(&self) -> Option<String> {
+        self.last_txid.map(|txid| txid.to_string())
+    }
+
+    /// Latest expiry height among the transactions most recently built
+    ///
+    /// The wallet treats their inputs as spent until then, even if they're never broadcast.
+    pub fn last_expiry_height(&self) -> Option<u32> {
+        self.last_expiry_height
+    }
+
+    /// List the encoded transparent receivers belonging to the account of this spending key
+    pub fn transparent_receivers(&self, usk: &UnifiedSpendingKey) -> Result<Vec<String>> {
+        let wallet_db = self.db.get_wallet_db()?;
+        let ufvk = usk.to_unified_full_viewing_key();
+        let account = wallet_db.get_account_for_ufvk(&ufvk)?
+            .ok_or_else(|| anyhow::anyhow!("Account not found for this spending key"))?;
+
+        let receivers = wallet_db.get_transparent_receivers(Account::id(&account), true, true)?;
+
+        Ok(receivers
+            .into_keys()
+            .map(|addr| addr.encode(&self.network))
+            .collect())
+    }
+
+    /// Record transparent UTXOs fetched from lightwalletd in the wallet database
+    ///
+    /// Compact block scanning only discovers shielded notes, so transparent funds must be
+    /// stored explicitly before they can be selected as shielding inputs.
+    /// Returns the number of UTXOs stored.
+    pub fn store_transparent_utxos(&mut self, utxos: &[GetAddressUtxosReply]) -> Result<usize> {
         let wallet_db = self.db.get_wallet_db_mut()?;
+        let mut stored = 0;
+
+        for utxo in utxos {
+            let txid: [u8; 32] = utxo.txid[..]
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Invalid UTXO txid length: {}", utxo.txid.len()))?;
+            let index = u32::try_from(utxo.index)
+                .map_err(|_| anyhow::anyhow!("Invalid UTXO index: {}", utxo.index))?;
+            let value = Zatoshis::from_nonnegative_i64(utxo.value_zat)
+                .map_err(|_| anyhow::anyhow!("Invalid UTXO value: {}", utxo.value_zat))?;
+            let height = BlockHeight::try_from(utxo.height)
+                .map_err(|_| anyhow::anyhow!("Invalid UTXO height: {}", utxo.height))?;
+
+            let output = WalletTransparentOutput::from_parts(
+                OutPoint::new(txid, index),
+                TxOut::new(value, Script(script::Code(utxo.script.clone()))),
+                Some(height),
+            );
+
+            match output {
+                Some(output) => {
+                    wallet_db.put_received_transparent_utxo(&output)?;
+                    stored += 1;
+                }
+                None => tracing::warn!("Skipping UTXO with unrecognized script for {}", utxo.address),
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// Build and sign a transaction moving all transparent funds into the Orchard pool
+    ///
+    /// Transparent UTXOs must have been stored with `store_transparent_utxos` first.
+    ///
+    /// # Returns
+    /// (raw_txs, shielded_zatoshis, fee_zatoshis)
+    pub async fn build_shielding_transaction(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+    ) -> Result<(Vec<Vec<u8>>, u64, u64)> {
+        tracing::info!("Building shielding transaction...");
+
+        let proposal = self.propose_shielding_all(usk)?;
+
+        // Shielded value ends up as change to our own account
+        let shielded = shielded_value(&proposal);
+        let total_fee = Self::estimate_fee_for_proposal(&proposal);
+
+        tracing::info!("Shielding {} zatoshis (fee: {} zatoshis)", shielded, total_fee);
+
+        let raw_txs = self.create_transactions(usk, &proposal).await?;
+
+        tracing::debug!("Shielding transaction serialized ({} transaction(s))", raw_txs.len());
+
+        Ok((raw_txs, shielded, total_fee))
+    }
+
+    /// Look up the wallet account ID for a spending key
+    fn account_id(&self, usk: &UnifiedSpendingKey) -> Result<AccountUuid> {
+        let wallet_db = self.db.get_wallet_db()?;
         let ufvk = usk.to_unified_full_viewing_key();
         let account = wallet_db.get_account_for_ufvk(&ufvk)?
             .ok_or_else(|| anyhow::anyhow!("Account not found for this spending key"))?;
-        let account_id = Account::id(&account);
+        Ok(Account::id(&account))
+    }
+
+    /// Propose a payment to a single recipient
+    fn propose_transfer_to(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        to_address: &str,
+        amount_zat: u64,
+        memo: Option<&str>,
+    ) -> Result<Proposal<StandardFeeRule, ReceivedNoteId>> {
+        // Step 1: Parse and validate address
+        let (_, recipient_addr) = parse_recipient(to_address, self.network)?;
+        check_memo_allowed(&recipient_addr, memo)?;
+        if matches!(recipient_addr, Address::Tex(_)) {
+            // Input selection turns this into the ZIP-320 pair: shielded funds go to an
+            // ephemeral transparent address, which then pays the TEX address
+            tracing::info!("Recipient is a TEX address, proposing a two-step transfer");
+        }
+        let recipient_addr = match self.preferred_pool {
+            Some(pool) => restrict_to_pool(recipient_addr, pool)?,
+            None => recipient_addr,
+        };
+
+        // Step 2: Convert amount
+        let amount = Zatoshis::from_u64(amount_zat)
+            .map_err(|_| anyhow::anyhow!("Invalid amount"))?;
+
+        // Step 3: Format memo (if provided)
+        let memo_bytes = self.format_memo(memo)?;
+
+        // Step 4: Get account ID from USK
+        let account_id = self.account_id(usk)?;
 
         // Step 5: Create proposal (this calculates the fee)
-        let proposal = match propose_standard_transfer_to_address::<_, _, Infallible>(
+        let wallet_db = self.db.get_wallet_db_mut()?;
+        propose_standard_transfer_to_address::<_, _, Infallible>(
             wallet_db,
             &self.network,
-            StandardFeeRule::Zip317,
+            self.fee_rule,
             account_id,
-            ConfirmationsPolicy::MIN,
+            self.confirmations_policy,
             &recipient_addr,
             amount,
             memo_bytes,
+            self.change_memo.clone(),
+            self.preferred_pool.unwrap_or(ShieldedProtocol::Orchard), // fallback_change_pool
+        )
+        .map_err(proposal_error)
+    }
+
+    /// Propose sweeping all currently spendable Sapling and Orchard notes to one recipient
+    fn propose_send_max_to(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        to_address: &str,
+        memo: Option<&str>,
+    ) -> Result<Proposal<StandardFeeRule, ReceivedNoteId>> {
+        let (recipient, address) = parse_recipient(to_address, self.network)?;
+        check_memo_allowed(&address, memo)?;
+
+        let memo_bytes = self.format_memo(memo)?;
+        let account_id = self.account_id(usk)?;
+
+        let wallet_db = self.db.get_wallet_db_mut()?;
+        propose_send_max_transfer::<_, _, _, Infallible>(
+            wallet_db,
+            &self.network,
+            account_id,
+            &[ShieldedProtocol::Sapling, ShieldedProtocol::Orchard],
+            &self.fee_rule,
+            recipient,
+            memo_bytes,
+            MaxSpendMode::MaxSpendable,
+            self.confirmations_policy,
+        )
+        .map_err(proposal_error)
+    }
+
+    /// Propose a single transaction paying every output
+    fn propose_batch(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        outputs: &[PaymentOutput<'_>],
+    ) -> Result<Proposal<StandardFeeRule, ReceivedNoteId>> {
+        let request = payment_request(self.network, outputs)?;
+        let account_id = self.account_id(usk)?;
+
+        let input_selector = GreedyInputSelector::<WalletDatabase>::new();
+        let change_strategy = SingleOutputChangeStrategy::<WalletDatabase>::new(
+            self.fee_rule,
+            self.change_memo.clone(),
+            ShieldedProtocol::Orchard,
+            DustOutputPolicy::default(),
+        );
+
+        let wallet_db = self.db.get_wallet_db_mut()?;
+        propose_transfer::<_, _, _, _, Infallible>(
+            wallet_db,
+            &self.network,
+            account_id,
+            &input_selector,
+            &change_strategy,
+            request,
+            self.confirmations_policy,
+        )
+        .map_err(proposal_error)
+    }
+
+    /// Propose shielding every stored transparent UTXO of the account into Orchard
+    fn propose_shielding_all(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+    ) -> Result<Proposal<StandardFeeRule, Infallible>> {
+        let account_id = self.account_id(usk)?;
+
+        let wallet_db = self.db.get_wallet_db_mut()?;
+        let from_addrs: Vec<TransparentAddress> = wallet_db
+            .get_transparent_receivers(account_id, true, true)?
+            .into_keys()
+            .collect();
+
+        let input_selector = GreedyInputSelector::<WalletDatabase>::new();
+        let change_strategy = SingleOutputChangeStrategy::<WalletDatabase>::new(
+            self.fee_rule,
             None,
             ShieldedProtocol::Orchard,
+            DustOutputPolicy::default(),
+        );
+
+        match propose_shielding::<_, _, _, _, Infallible>(
+            wallet_db,
+            &self.network,
+            &input_selector,
+            &change_strategy,
+            Zatoshis::const_from_u64(SHIELDING_THRESHOLD_ZATOSHIS),
+            &from_addrs,
+            account_id,
+            ConfirmationsPolicy::MIN,
         ) {
-            Ok(p) => p,
-            Err(e) => anyhow::bail!("Failed to create transaction proposal: {:?}", e),
-        };
+            Ok(p) => Ok(p),
+            Err(e) => anyhow::bail!("Failed to create shielding proposal: {:?}", e),
+        }
+    }
 
-        // Extract fee from proposal
-        let total_fee: u64 = proposal.steps().iter()
-            .map(|step| u64::from(step.balance().fee_required()))
-            .sum();
+    /// Generate proofs, sign, and serialize every transaction of a proposal
+    ///
+    /// Multi-step proposals (e.g. a shielding step before a transfer) yield several
+    /// transactions; they are returned in the order they must be broadcast. Proving
+    /// waits for one of `PROVING_CONCURRENCY` permits and runs off the async workers.
+    async fn create_transactions<NoteRef: std::fmt::Debug>(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        proposal: &Proposal<StandardFeeRule, NoteRef>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let _permit = PROVING_PERMITS
+            .acquire()
+            .await
+            .context("Proving semaphore closed")?;
+
+        run_blocking(|| self.prove_and_sign(usk, proposal))
+    }
+
+    /// Synchronous body of `create_transactions`: proofs, signatures and serialization
+    fn prove_and_sign<NoteRef: std::fmt::Debug>(
+        &mut self,
+        usk: &UnifiedSpendingKey,
+        proposal: &Proposal<StandardFeeRule, NoteRef>,
+    ) -> Result<Vec<Vec<u8>>> {
+        use super::prover::get_prover;
+        let prover = get_prover()?;
+
+        // Wrap USK in SpendingKeys for the new API
+        let spending_keys = SpendingKeys::new(usk.clone());
+
+        let wallet_db = self.db.get_wallet_db_mut()?;
+
+        // Note: The type inference for create_proposed_transactions is complex
+        // We explicitly specify error type parameters for GreedyInputSelector and ZIP-317 fees
+        let txids = create_proposed_transactions::<_, _, GreedyInputSelectorError, _, FeeError, NoteRef>(
+            wallet_db,
+            &self.network,
+            prover, // spend_prover
+            prover, // output_prover (same object!)
+            &spending_keys,
+            OvkPolicy::Sender,
+            proposal,
+        ).map_err(|e| anyhow::anyhow!("Transaction creation failed: {:#?}", e))?;
 
-        Ok(total_fee)
+        let mut raw_txs = Vec::with_capacity(txids.len());
+        let mut expiry_height = None;
+        for txid in txids.iter() {
+            let transaction = wallet_db.get_transaction(*txid)?
+                .ok_or_else(|| anyhow::anyhow!("Transaction not found in database"))?;
+            expiry_height = expiry_height.max(Some(u32::from(transaction.expiry_height())));
+
+            let mut raw_tx = Vec::new();
+            transaction.write(&mut raw_tx)?;
+            raw_txs.push(raw_tx);
+        }
+
+        // The last step is the one that pays the recipients
+        self.last_txid = Some(*txids.last());
+        self.last_expiry_height = expiry_height;
+
+        Ok(raw_txs)
     }
 
     /// Format memo text into MemoBytes
     fn format_memo(&self, memo: Option<&str>) -> Result<Option<MemoBytes>> {
-        if let Some(text) = memo {
-            if text.len() > 511 {
-                anyhow::bail!("Memo too long (max 511 bytes, got {})", text.len());
+        memo.map(encode_text_memo).transpose()
+    }
+}
+
+/// Convert a proposal error, surfacing lack of funds as `InsufficientFunds`
+fn proposal_error<DbErrT, TreeErrT, SelectorErrT, FeeErrT, ChangeErrT, NoteRef>(
+    e: WalletError<DbErrT, TreeErrT, SelectorErrT, FeeErrT, ChangeErrT, NoteRef>,
+) -> anyhow::Error
+where
+    WalletError<DbErrT, TreeErrT, SelectorErrT, FeeErrT, ChangeErrT, NoteRef>: std::fmt::Debug,
+{
+    match e {
+        WalletError::InsufficientFunds { available, required }
+        | WalletError::Change(ChangeError::InsufficientFunds { available, required }) => {
+            InsufficientFunds {
+                available_zat: available.into(),
+                required_zat: required.into(),
             }
+            .into()
+        }
+        e => anyhow::anyhow!("Failed to create transaction proposal: {:?}", e),
+    }
+}
 
-            let mut memo_array = [0u8; 512];
-            memo_array[0] = 0xF4; // Text memo marker
-            let len = text.as_bytes().len().min(511);
-            memo_array[1..1+len].copy_from_slice(&text.as_bytes()[..len]);
+/// Fee and shape of a proposed transaction, computed before proof generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub fee_zat: u64,
+    /// Value paid to recipients (or shielded), excluding the fee
+    pub amount_zat: u64,
+    pub num_inputs: usize,
+    pub num_outputs: usize,
+}
 
-            Ok(Some(MemoBytes::from_bytes(&memo_array)?))
-        } else {
-            Ok(None)
+impl FeeEstimate {
+    fn from_proposal<FeeRuleT, NoteRef>(proposal: &Proposal<FeeRuleT, NoteRef>, amount_zat: u64) -> Self {
+        let num_inputs = proposal.steps().iter()
+            .map(|step| {
+                step.transparent_inputs().len()
+                    + step.shielded_inputs().map_or(0, |inputs| inputs.notes().len())
+            })
+            .sum();
+        let num_outputs = proposal.steps().iter()
+            .map(|step| step.transaction_request().payments().len() + step.balance().proposed_change().len())
+            .sum();
+
+        Self {
+            fee_zat: TransactionBuilder::estimate_fee_for_proposal(proposal),
+            amount_zat,
+            num_inputs,
+            num_outputs,
         }
     }
+
+    /// Amount plus fee
+    pub fn total_zat(&self) -> u64 {
+        self.amount_zat + self.fee_zat
+    }
+}
+
+/// Value a proposal pays to its recipients, excluding change and fees
+fn paid_value<FeeRuleT, NoteRef>(proposal: &Proposal<FeeRuleT, NoteRef>) -> u64 {
+    proposal.steps().iter()
+        .flat_map(|step| step.transaction_request().payments().values())
+        .map(|payment| u64::from(payment.amount()))
+        .sum()
+}
+
+/// Value a shielding proposal moves into the shielded pool (returned as change)
+fn shielded_value<FeeRuleT, NoteRef>(proposal: &Proposal<FeeRuleT, NoteRef>) -> u64 {
+    proposal.steps().iter()
+        .flat_map(|step| step.balance().proposed_change())
+        .map(|change| u64::from(change.value()))
+        .sum()
+}
+
+/// Default confirmations a note needs before it counts as spendable
+const DEFAULT_MIN_CONFIRMATIONS: u32 = 1;
+
+/// Confirmations required when a request doesn't specify any, from `MIN_CONFIRMATIONS`
+///
+/// Shared by balance display and transaction building so the spendable balance shown is
+/// exactly what a send can use.
+pub fn default_min_confirmations() -> u32 {
+    env_or_positive("MIN_CONFIRMATIONS", DEFAULT_MIN_CONFIRMATIONS)
+}
+
+/// Build the confirmations policy for spending, defaulting to `default_min_confirmations`
+pub fn confirmations_policy(min_confirmations: Option<u32>) -> Result<ConfirmationsPolicy> {
+    match min_confirmations.unwrap_or_else(default_min_confirmations) {
+        1 => Ok(ConfirmationsPolicy::MIN),
+        n => {
+            let n = NonZeroU32::new(n)
+                .ok_or_else(|| anyhow::anyhow!("min_confirmations must be at least 1"))?;
+            Ok(ConfirmationsPolicy::new_symmetrical(n, false))
+        }
+    }
+}
+
+/// Fee rule names accepted by `parse_fee_rule`
+pub const FEE_RULE_NAMES: [&str; 1] = ["zip317"];
+
+/// Map a fee rule name to a `StandardFeeRule`, defaulting to ZIP-317
+pub fn parse_fee_rule(name: Option<&str>) -> Result<StandardFeeRule> {
+    match name.map(|name| name.trim().to_lowercase()).as_deref() {
+        None | Some("zip317") => Ok(StandardFeeRule::Zip317),
+        Some(other) => anyhow::bail!(
+            "Unknown fee_rule '{}'; supported: {}",
+            other,
+            FEE_RULE_NAMES.join(", ")
+        ),
+    }
+}
+
+/// Pool names accepted by `parse_pool`
+pub const POOL_NAMES: [&str; 2] = ["orchard", "sapling"];
+
+/// Map a shielded pool name to a `ShieldedProtocol`; `None` means no preference
+pub fn parse_pool(name: Option<&str>) -> Result<Option<ShieldedProtocol>> {
+    match name.map(|name| name.trim().to_lowercase()).as_deref() {
+        None => Ok(None),
+        Some("orchard") => Ok(Some(ShieldedProtocol::Orchard)),
+        Some("sapling") => Ok(Some(ShieldedProtocol::Sapling)),
+        Some(other) => anyhow::bail!(
+            "Unknown prefer_pool '{}'; supported: {}",
+            other,
+            POOL_NAMES.join(", ")
+        ),
+    }
+}
+
+/// Check that `to_address` belongs to `network` and can receive into the named pool, if any
+///
+/// Lets callers reject a bad recipient, a memo the recipient can't receive, or an
+/// unusable `prefer_pool` before scanning or building anything.
+pub fn validate_recipient(
+    network: Network,
+    to_address: &str,
+    memo: Option<&str>,
+    pool: Option<&str>,
+) -> Result<()> {
+    let (_, address) = parse_recipient(to_address, network)?;
+    check_memo_allowed(&address, memo)?;
+    match parse_pool(pool)? {
+        Some(pool) => restrict_to_pool(address, pool).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+/// Reject a memo for a recipient that can only receive transparently
+///
+/// Transparent and ZIP-320 TEX addresses have no memo field; the wallet library would
+/// otherwise fail with an opaque `MemoForbidden`.
+fn check_memo_allowed(address: &Address, memo: Option<&str>) -> Result<()> {
+    if memo.is_none() {
+        return Ok(());
+    }
+    match address {
+        Address::Tex(_) => anyhow::bail!("TEX addresses can't receive memos"),
+        Address::Transparent(_) => anyhow::bail!("Transparent addresses can't receive memos"),
+        _ => Ok(()),
+    }
+}
+
+/// Parse a recipient address, checking it belongs to the wallet's network
+///
+/// A wrong-network address fails with `WrongNetwork`, naming both networks.
+fn parse_recipient(to_address: &str, network: Network) -> Result<(ZcashAddress, Address)> {
+    let recipient = ZcashAddress::try_from_encoded(to_address)
+        .context("Invalid recipient address")?;
+
+    let network_type = match network {
+        Network::MainNetwork => NetworkType::Main,
+        Network::TestNetwork => NetworkType::Test,
+    };
+
+    let address = recipient
+        .clone()
+        .convert_if_network::<Address>(network_type)
+        .map_err(|e| match e {
+            ConversionError::IncorrectNetwork { expected, actual } => anyhow::Error::new(WrongNetwork {
+                address_network: actual,
+                wallet_network: expected,
+            }),
+            other => anyhow::anyhow!("Unsupported recipient address: {}", other),
+        })?;
+
+    Ok((recipient, address))
+}
+
+/// Narrow a recipient address to its receiver in `pool`
+///
+/// A unified address is reduced to the one receiver so the payment can only land in that
+/// pool. Fails if the address has no receiver in `pool`.
+fn restrict_to_pool(address: Address, pool: ShieldedProtocol) -> Result<Address> {
+    let restricted = match (&address, pool) {
+        (Address::Unified(ua), ShieldedProtocol::Orchard) => ua
+            .orchard()
+            .and_then(|orchard| UnifiedAddress::from_receivers(Some(*orchard), None, None))
+            .map(Address::Unified),
+        (Address::Unified(ua), ShieldedProtocol::Sapling) => ua.sapling().copied().map(Address::Sapling),
+        (Address::Sapling(_), ShieldedProtocol::Sapling) => Some(address.clone()),
+        _ => None,
+    };
+
+    restricted.ok_or_else(|| anyhow::anyhow!("Recipient address has no {:?} receiver", pool))
+}
+
+/// Validate a set of outputs and turn them into a ZIP-321 transaction request
+///
+/// Errors name the zero-based index of the first invalid output. Memos are checked against
+/// the recipient up front so a memo to a transparent or TEX address gets the same message as
+/// a single send, rather than `Payment::new`'s generic rejection.
+pub fn payment_request(network: Network, outputs: &[PaymentOutput<'_>]) -> Result<TransactionRequest> {
+    if outputs.is_empty() {
+        anyhow::bail!("At least one output is required");
+    }
+
+    let mut payments = Vec::with_capacity(outputs.len());
+    for (index, output) in outputs.iter().enumerate() {
+        let (recipient, address) = parse_recipient(output.to_address, network)
+            .map_err(|e| anyhow::anyhow!("Output {}: {}", index, e))?;
+        check_memo_allowed(&address, output.memo)
+            .map_err(|e| anyhow::anyhow!("Output {}: {}", index, e))?;
+
+        let amount = Zatoshis::from_u64(output.amount_zat)
+            .map_err(|_| anyhow::anyhow!("Output {}: invalid amount", index))?;
+
+        let memo = output.memo
+            .map(encode_text_memo)
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Output {}: {}", index, e))?;
+
+        let payment = Payment::new(recipient, amount, memo, None, None, vec![])
+            .ok_or_else(|| anyhow::anyhow!("Output {}: recipient cannot receive memos", index))?;
+        payments.push(payment);
+    }
+
+    TransactionRequest::new(payments)
+        .map_err(|e| anyhow::anyhow!("Invalid payment request: {:?}", e))
+}
+
+/// Decode a single-payment ZIP-321 URI, checking the recipient is valid on `network`
+///
+/// Multi-payment URIs are rejected; their outputs belong in a batch send.
+pub fn parse_payment_uri(network: Network, uri: &str) -> Result<UriPayment> {
+    let request = TransactionRequest::from_uri(uri.trim())
+        .map_err(|e| anyhow::anyhow!("Invalid payment URI: {:?}", e))?;
+
+    let payment = match request.payments().values().collect::<Vec<_>>()[..] {
+        [payment] => payment,
+        [] => anyhow::bail!("Payment URI has no payments"),
+        _ => anyhow::bail!("Payment URI requests {} payments; only one is supported", request.payments().len()),
+    };
+
+    let to_address = payment.recipient_address().encode();
+    parse_recipient(&to_address, network)?;
+
+    Ok(UriPayment {
+        to_address,
+        amount_zat: payment.amount().into_u64(),
+        memo: payment.memo().and_then(|memo| decode_memo(memo.as_array())),
+        label: payment.label().cloned(),
+        message: payment.message().cloned(),
+    })
+}
+
+/// Encode a text memo as a 0xF4 marker followed by the UTF-8 bytes
+///
+/// The limit is checked against the byte length of the whole string, so multi-byte
+/// characters are never split; oversized memos are rejected rather than truncated.
+pub fn encode_text_memo(text: &str) -> Result<MemoBytes> {
+    let bytes = text.as_bytes();
+    if bytes.len() > MAX_MEMO_BYTES {
+        anyhow::bail!("Memo too long: memo is {}/{} bytes", bytes.len(), MAX_MEMO_BYTES);
+    }
+
+    let mut memo_array = [0u8; 512];
+    memo_array[0] = 0xF4; // Text memo marker
+    memo_array[1..1 + bytes.len()].copy_from_slice(bytes);
+
+    Ok(MemoBytes::from_bytes(&memo_array)?)
+}
+
+/// Decode stored memo bytes for display
+///
+/// Strips the 0xF4 text marker and trailing zero padding and returns the text if what's
+/// left is valid UTF-8, otherwise the hex of the unpadded bytes. Empty memos (all zeros,
+/// or the 0xF6 "no memo" marker) decode to `None`.
+pub fn decode_memo(bytes: &[u8]) -> Option<String> {
+    let end = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let unpadded = &bytes[..end];
+
+    match unpadded {
+        [] | [0xF6] => None,
+        [0xF4, text @ ..] | text => match std::str::from_utf8(text) {
+            Ok(text) if !text.is_empty() => Some(text.to_string()),
+            Ok(_) => None,
+            Err(_) => Some(hex::encode(unpadded)),
+        },
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::account::AccountManager;
-    use super::wallet::Wallet;
+    use super::super::account::AccountManager;
+    use super::super::block_source::mock::MockLightwalletd;
+    use super::super::scanner::BlockchainScanner;
+    use super::super::wallet::Wallet;
     use tempfile::TempDir;
+    use uuid::Uuid;
 
     #[tokio::test]
     async fn test_builder_creation() {
         let temp_dir = TempDir::new().unwrap();
         let db_path = temp_dir.path().join("test.db");
 
-        let db = Database::new(&db_path, Network::TestNetwork).unwrap();
-        let builder = TransactionBuilder::new(db, Network::TestNetwork);
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+        let builder = TransactionBuilder::new(&mut db, Network::TestNetwork);
 
         assert_eq!(builder.network, Network::TestNetwork);
     }
 
+    #[tokio::test]
+    async fn test_with_change_memo() {
+        let db_path = std::env::temp_dir().join(format!("change_memo_{}.db", uuid::Uuid::new_v4()));
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+
+        let builder = TransactionBuilder::new(&mut db, Network::TestNetwork);
+        assert!(builder.change_memo.is_none());
+
+        let builder = builder.with_change_memo(Some("change")).unwrap();
+        assert_eq!(builder.change_memo, Some(encode_text_memo("change").unwrap()));
+
+        let builder = builder.with_change_memo(None).unwrap();
+        assert!(builder.change_memo.is_none());
+
+        assert!(builder.with_change_memo(Some(&"x".repeat(MAX_MEMO_BYTES + 1))).is_err());
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn test_restrict_to_pool() {
+        let (wallet, _mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
+        let network = Network::TestNetwork;
+        let unified = Address::decode(&network, &wallet.get_address().unwrap()).unwrap();
+
+        match restrict_to_pool(unified.clone(), ShieldedProtocol::Orchard).unwrap() {
+            Address::Unified(ua) => assert!(ua.has_orchard() && !ua.has_sapling()),
+            other => panic!("expected a unified address, got {:?}", other),
+        }
+        assert!(matches!(
+            restrict_to_pool(unified, ShieldedProtocol::Sapling).unwrap(),
+            Address::Sapling(_)
+        ));
+
+        let transparent = Address::decode(&network, &wallet.get_transparent_address().unwrap()).unwrap();
+        assert!(restrict_to_pool(transparent, ShieldedProtocol::Orchard).is_err());
+    }
+
+    #[test]
+    fn test_parse_recipient_wrong_network() {
+        let (wallet, _mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
+        let testnet_address = wallet.get_address().unwrap();
+
+        assert!(parse_recipient(&testnet_address, Network::TestNetwork).is_ok());
+
+        let err = parse_recipient(&testnet_address, Network::MainNetwork).unwrap_err();
+        let wrong = err.downcast_ref::<WrongNetwork>().expect("expected WrongNetwork");
+        assert_eq!(wrong.address_network, NetworkType::Test);
+        assert_eq!(wrong.wallet_network, NetworkType::Main);
+        assert_eq!(err.to_string(), "Recipient address is testnet but wallet is mainnet");
+    }
+
+    #[test]
+    fn test_parse_payment_uri_round_trip() {
+        let (wallet, _mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
+        let address = wallet.get_address().unwrap();
+
+        let uri = payment_request(
+            Network::TestNetwork,
+            &[PaymentOutput { to_address: &address, amount_zat: 123_456, memo: Some("invoice 42") }],
+        )
+        .unwrap()
+        .to_uri();
+
+        let payment = parse_payment_uri(Network::TestNetwork, &uri).unwrap();
+        assert_eq!(payment.to_address, address);
+        assert_eq!(payment.amount_zat, 123_456);
+        assert_eq!(payment.memo.as_deref(), Some("invoice 42"));
+
+        let err = parse_payment_uri(Network::MainNetwork, &uri).unwrap_err();
+        assert!(err.downcast_ref::<WrongNetwork>().is_some());
+
+        assert!(parse_payment_uri(Network::TestNetwork, "zcash:not-an-address").is_err());
+    }
+
+    #[test]
+    fn test_validate_tex_recipient() {
+        let tex = ZcashAddress::from_tex(NetworkType::Test, [7u8; 20]).encode();
+
+        let (_, address) = parse_recipient(&tex, Network::TestNetwork).unwrap();
+        assert!(matches!(address, Address::Tex(_)));
+
+        assert!(validate_recipient(Network::TestNetwork, &tex, None, None).is_ok());
+        let err = validate_recipient(Network::TestNetwork, &tex, Some("hi"), None).unwrap_err();
+        assert_eq!(err.to_string(), "TEX addresses can't receive memos");
+        assert!(validate_recipient(Network::TestNetwork, &tex, None, Some("orchard")).is_err());
+        assert!(validate_recipient(Network::MainNetwork, &tex, None, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_pool() {
+        assert_eq!(parse_pool(None).unwrap(), None);
+        assert_eq!(parse_pool(Some(" Orchard ")).unwrap(), Some(ShieldedProtocol::Orchard));
+        assert_eq!(parse_pool(Some("sapling")).unwrap(), Some(ShieldedProtocol::Sapling));
+        assert!(parse_pool(Some("sprout")).is_err());
+    }
+
+    #[test]
+    fn test_confirmations_policy() {
+        let default = confirmations_policy(None).unwrap();
+        assert_eq!(default.trusted().get(), 1);
+        assert_eq!(default.untrusted().get(), 1);
+
+        let deep = confirmations_policy(Some(10)).unwrap();
+        assert_eq!(deep.trusted().get(), 10);
+        assert_eq!(deep.untrusted().get(), 10);
+        assert!(!deep.allow_zero_conf_shielding());
+
+        assert!(confirmations_policy(Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_parse_fee_rule() {
+        assert_eq!(parse_fee_rule(None).unwrap(), StandardFeeRule::Zip317);
+        assert_eq!(parse_fee_rule(Some("ZIP317")).unwrap(), StandardFeeRule::Zip317);
+        assert!(parse_fee_rule(Some("fixed")).is_err());
+    }
+
+    #[test]
+    fn test_proposal_error_detects_insufficient_funds() {
+        type TestError = WalletError<(), (), (), (), (), ()>;
+
+        let err = proposal_error(TestError::InsufficientFunds {
+            available: Zatoshis::const_from_u64(5_000),
+            required: Zatoshis::const_from_u64(20_000),
+        });
+        assert_eq!(
+            err.downcast_ref::<InsufficientFunds>(),
+            Some(&InsufficientFunds { available_zat: 5_000, required_zat: 20_000 })
+        );
+
+        let err = proposal_error(TestError::Change(ChangeError::InsufficientFunds {
+            available: Zatoshis::const_from_u64(1),
+            required: Zatoshis::const_from_u64(2),
+        }));
+        assert!(err.downcast_ref::<InsufficientFunds>().is_some());
+
+        let err = proposal_error(TestError::ScanRequired);
+        assert!(err.downcast_ref::<InsufficientFunds>().is_none());
+    }
+
+    #[test]
+    fn test_payment_request_rejects_empty_batch() {
+        assert!(payment_request(Network::TestNetwork, &[]).is_err());
+    }
+
+    #[test]
+    fn test_payment_request_reports_invalid_output_index() {
+        let outputs = [PaymentOutput {
+            to_address: "not-an-address",
+            amount_zat: 10_000,
+            memo: None,
+        }];
+
+        let err = payment_request(Network::TestNetwork, &outputs).unwrap_err();
+        assert!(err.to_string().starts_with("Output 0:"), "{}", err);
+    }
+
+    #[test]
+    fn test_payment_request_rejects_memo_for_transparent_output() {
+        let (wallet, _mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
+        let shielded = wallet.get_address().unwrap();
+        let transparent = wallet.get_transparent_receiver().unwrap();
+        let outputs = [
+            PaymentOutput { to_address: &shielded, amount_zat: 10_000, memo: Some("rent") },
+            PaymentOutput { to_address: &transparent, amount_zat: 10_000, memo: Some("rent") },
+        ];
+
+        let err = payment_request(Network::TestNetwork, &outputs).unwrap_err();
+        assert_eq!(err.to_string(), "Output 1: Transparent addresses can't receive memos");
+
+        // The same output without a memo is fine
+        let outputs = [PaymentOutput { to_address: &transparent, amount_zat: 10_000, memo: None }];
+        assert!(payment_request(Network::TestNetwork, &outputs).is_ok());
+    }
+
+    #[test]
+    fn test_payment_request_reports_oversized_memo_index() {
+        let (wallet, _mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
+        let address = wallet.get_address().unwrap();
+        let fits = "x".repeat(MAX_MEMO_BYTES);
+        let too_long = "x".repeat(MAX_MEMO_BYTES + 1);
+        let outputs = [
+            PaymentOutput { to_address: &address, amount_zat: 10_000, memo: Some(&fits) },
+            PaymentOutput { to_address: &address, amount_zat: 10_000, memo: None },
+            PaymentOutput { to_address: &address, amount_zat: 10_000, memo: Some(&too_long) },
+        ];
+
+        let err = payment_request(Network::TestNetwork, &outputs).unwrap_err();
+        assert!(err.to_string().starts_with("Output 2: Memo too long"), "{}", err);
+    }
+
+    #[test]
+    fn test_encode_text_memo_ascii() {
+        let memo = encode_text_memo("Thanks!").unwrap();
+        let bytes = memo.as_array();
+        assert_eq!(bytes[0], 0xF4);
+        assert_eq!(&bytes[1..8], b"Thanks!");
+        assert!(bytes[8..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_encode_text_memo_emoji_at_boundary() {
+        // 127 four-byte emoji = 508 bytes, one more would be 512
+        let fits = "🛡".repeat(127);
+        let memo = encode_text_memo(&fits).unwrap();
+        assert_eq!(&memo.as_array()[1..509], fits.as_bytes());
+
+        let err = encode_text_memo(&"🛡".repeat(128)).unwrap_err();
+        assert!(err.to_string().contains("512/511 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn test_encode_text_memo_cjk_at_boundary() {
+        // 170 three-byte characters = 510 bytes, plus one ASCII byte = 511
+        let fits = format!("{}a", "盾".repeat(170));
+        assert_eq!(fits.len(), MAX_MEMO_BYTES);
+        let memo = encode_text_memo(&fits).unwrap();
+        assert_eq!(&memo.as_array()[1..], fits.as_bytes());
+
+        // Only 171 characters, but 513 bytes
+        let err = encode_text_memo(&"盾".repeat(171)).unwrap_err();
+        assert!(err.to_string().contains("513/511 bytes"), "{}", err);
+    }
+
+    #[test]
+    fn test_decode_memo_round_trips_text() {
+        let memo = encode_text_memo("Thanks for lunch 🍜").unwrap();
+        assert_eq!(decode_memo(memo.as_array()).as_deref(), Some("Thanks for lunch 🍜"));
+    }
+
+    #[test]
+    fn test_decode_memo_without_marker() {
+        // Other wallets write ZIP-302 text memos with no marker byte
+        let mut bytes = [0u8; 512];
+        bytes[..5].copy_from_slice(b"hello");
+        assert_eq!(decode_memo(&bytes).as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn test_decode_memo_empty() {
+        assert_eq!(decode_memo(&[0u8; 512]), None);
+
+        let mut no_memo = [0u8; 512];
+        no_memo[0] = 0xF6;
+        assert_eq!(decode_memo(&no_memo), None);
+
+        assert_eq!(decode_memo(&[]), None);
+    }
+
+    #[test]
+    fn test_decode_memo_binary_falls_back_to_hex() {
+        let mut bytes = [0u8; 512];
+        bytes[..4].copy_from_slice(&[0xFF, 0x00, 0xC3, 0x28]);
+        assert_eq!(decode_memo(&bytes).as_deref(), Some("ff00c328"));
+    }
+
     #[tokio::test]
     async fn test_transaction_with_empty_wallet() {
-        // This should fail with "insufficient funds"
+        const BIRTHDAY: u64 = 300_000;
         let temp_dir = TempDir::new().unwrap();
         let db_path = temp_dir.path().join("test.db");
 
-        // Create account
-        let (_wallet, mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
+        let (wallet, mnemonic) = Wallet::generate_new(Network::TestNetwork).unwrap();
         let seed = mnemonic.to_seed("");
 
-        let db = Database::new(&db_path, Network::TestNetwork).unwrap();
-        let mut account_mgr = AccountManager::new(db);
-        let (_account_id, usk) = account_mgr.create_account("Test", &seed, None).unwrap();
-
-        // Try to build transaction
-        let db2 = Database::new(&db_path, Network::TestNetwork).unwrap();
-        let mut builder = TransactionBuilder::new(db2, Network::TestNetwork);
-
-        let result = builder.build_and_sign_transaction(
-            &usk,
-            "utest1qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqpq6d8kf", // dummy testnet address
-            10_000,
-            Some("Test"),
-        ).await;
+        // An account born on a mock chain, scanned to its tip without receiving anything
+        let chain = MockLightwalletd::starting_at(BIRTHDAY - 1);
+        chain.extend_to(BIRTHDAY + 5);
+        let mut db = Database::new(&db_path, Network::TestNetwork).unwrap();
+        let (_account_id, usk) = AccountManager::new(&mut db)
+            .create_account("Test", &seed, &chain, Some(BIRTHDAY as u32))
+            .await
+            .unwrap();
+        BlockchainScanner::new_with_path(
+            db.get_wallet_db_mut().unwrap(),
+            chain.clone(),
+            Network::TestNetwork,
+            db_path.clone(),
+        )
+        .with_birthday(Some(BIRTHDAY))
+        .scan_from_birthday(Uuid::nil())
+        .await
+        .unwrap();
 
-        // Should fail - no funds
-        assert!(result.is_err());
+        let mut builder = TransactionBuilder::new(&mut db, Network::TestNetwork);
+        let err = builder
+            .build_and_sign_transaction(&usk, &wallet.get_address().unwrap(), 10_000, Some("Test"))
+            .await
+            .unwrap_err();
 
-        // Verify it's an insufficient funds error (not a compilation/API error)
-        let err_msg = format!("{:?}", result.unwrap_err());
-        println!("Expected error: {}", err_msg);
+        assert_eq!(
+            err.downcast_ref::<InsufficientFunds>().map(|e| e.available_zat),
+            Some(0),
+            "expected InsufficientFunds, got {:?}",
+            err
+        );
     }
 }
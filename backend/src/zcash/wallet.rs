@@ -1,9 +1,20 @@
 use anyhow::Result;
 use bip39::Mnemonic;
 use rand::Rng;
+use zcash_keys::encoding::AddressCodec;
 use zcash_keys::keys::{UnifiedSpendingKey, UnifiedAddressRequest, ReceiverRequirement};
 use zcash_protocol::consensus::{Network, TestNetwork, MainNetwork};
-use zip32::AccountId;
+use zip32::{AccountId, DiversifierIndex};
+
+/// Every address form a wallet hands out for deposits
+pub struct WalletAddresses {
+    /// Unified address with shielded and transparent receivers
+    pub unified: String,
+    /// Unified address with shielded receivers only
+    pub shielded: String,
+    /// Bare transparent (t-) address, for senders that can't pay unified addresses
+    pub transparent: String,
+}
 
 /// Represents a Zcash wallet with keys
 pub struct Wallet {
@@ -37,17 +48,25 @@ impl Wallet {
     /// 2. Derive spending key using ZIP 32 (Zcash's key derivation)
     /// 3. Create wallet with the key
     pub fn from_mnemonic(mnemonic: &Mnemonic, network: Network) -> Result<Self> {
+        Self::from_mnemonic_account(mnemonic, network, 0)
+    }
+
+    /// Restore HD account `account_index` of the wallet from its mnemonic
+    ///
+    /// Account 0 is the wallet's primary account; further accounts share the seed but have
+    /// unrelated keys and addresses.
+    pub fn from_mnemonic_account(mnemonic: &Mnemonic, network: Network, account_index: u32) -> Result<Self> {
         // Step 1: Convert mnemonic to seed
         // BIP39 standard: mnemonic → 512-bit seed using PBKDF2
         // The empty string "" is the passphrase (optional, we don't use one)
         let seed = mnemonic.to_seed("");
 
         // Step 2: Derive Unified Spending Key from seed
-        // ZIP 32 path: m/32'/133'/0'
+        // ZIP 32 path: m/32'/133'/account_index'
         // - 32' = purpose (ZIP 32)
         // - 133' = coin_type (Zcash)
-        // - 0' = account 0 (first account)
-        let account_id = AccountId::try_from(0)
+        // - account_index' = account (0 is the first account)
+        let account_id = AccountId::try_from(account_index)
             .map_err(|e| anyhow::anyhow!("Invalid account ID: {:?}", e))?;
 
         let spending_key = match network {
@@ -134,6 +153,78 @@ impl Wallet {
 
         Ok(address_str)
     }
+
+    /// Get the transparent receiver of `get_transparent_address` as a bare t-address
+    pub fn get_transparent_receiver(&self) -> Result<String> {
+        let ufvk = self.spending_key.to_unified_full_viewing_key();
+
+        use ReceiverRequirement::*;
+        let request = UnifiedAddressRequest::unsafe_custom(Allow, Require, Require);
+
+        let (ua, _diversifier_index) = ufvk.default_address(request)
+            .map_err(|e| anyhow::anyhow!("Failed to generate transparent address: {:?}", e))?;
+
+        let taddr = ua.transparent()
+            .ok_or_else(|| anyhow::anyhow!("Unified address has no transparent receiver"))?;
+
+        Ok(taddr.encode(&self.network))
+    }
+
+    /// Get the Sapling receiver of `get_address` as a legacy `zs1...` address
+    ///
+    /// For counterparties that reject unified addresses. It shares the unified address's
+    /// diversifier, so payments to it are found by the same viewing key and land in the same
+    /// account.
+    pub fn get_sapling_address(&self) -> Result<String> {
+        let ufvk = self.spending_key.to_unified_full_viewing_key();
+
+        use ReceiverRequirement::*;
+        let request = UnifiedAddressRequest::unsafe_custom(Allow, Require, Omit);
+
+        let (ua, _diversifier_index) = ufvk.default_address(request)
+            .map_err(|e| anyhow::anyhow!("Failed to generate address: {:?}", e))?;
+
+        let sapling = ua.sapling()
+            .ok_or_else(|| anyhow::anyhow!("Unified address has no Sapling receiver"))?;
+
+        Ok(sapling.encode(&self.network))
+    }
+
+    /// Get the shielded unified address at the first valid diversifier index at or after `start`
+    ///
+    /// Every diversified address decrypts with the same viewing key, so funds sent to any
+    /// of them show up in the same account when scanning.
+    ///
+    /// # Returns
+    /// (address, diversifier_index) - the index actually used, which may be past `start`
+    pub fn get_diversified_address(&self, start: u64) -> Result<(String, u64)> {
+        let ufvk = self.spending_key.to_unified_full_viewing_key();
+
+        use ReceiverRequirement::*;
+        let request = UnifiedAddressRequest::unsafe_custom(Allow, Require, Omit);
+
+        let (ua, index) = ufvk.find_address(DiversifierIndex::from(start), request)
+            .map_err(|e| anyhow::anyhow!("Failed to generate diversified address: {:?}", e))?;
+
+        let index = u64::try_from(index)
+            .map_err(|_| anyhow::anyhow!("Diversifier index out of range"))?;
+
+        let address_str = match self.network {
+            Network::TestNetwork => ua.encode(&TestNetwork),
+            Network::MainNetwork => ua.encode(&MainNetwork),
+        };
+
+        Ok((address_str, index))
+    }
+
+    /// Derive all deposit addresses at once
+    pub fn get_addresses(&self) -> Result<WalletAddresses> {
+        Ok(WalletAddresses {
+            unified: self.get_transparent_address()?,
+            shielded: self.get_address()?,
+            transparent: self.get_transparent_receiver()?,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -181,4 +272,95 @@ mod tests {
         assert!(address.starts_with("utest1"));
         assert!(address.len() > 100);
     }
+
+    #[test]
+    fn test_get_diversified_address() {
+        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let mnemonic = Mnemonic::parse_in(Language::English, test_mnemonic)
+            .expect("Failed to parse mnemonic");
+
+        let wallet = Wallet::from_mnemonic(&mnemonic, Network::TestNetwork)
+            .expect("Failed to create wallet");
+
+        // Index 0 search yields the default address
+        let (default_address, default_index) = wallet.get_diversified_address(0).unwrap();
+        assert_eq!(default_address, wallet.get_address().unwrap());
+
+        let (next_address, next_index) = wallet.get_diversified_address(default_index + 1).unwrap();
+        assert!(next_index > default_index);
+        assert_ne!(next_address, default_address);
+        assert!(next_address.starts_with("utest1"));
+    }
+
+    #[test]
+    fn test_get_addresses() {
+        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let mnemonic = Mnemonic::parse_in(Language::English, test_mnemonic)
+            .expect("Failed to parse mnemonic");
+
+        let wallet = Wallet::from_mnemonic(&mnemonic, Network::TestNetwork)
+            .expect("Failed to create wallet");
+
+        let addresses = wallet.get_addresses().expect("Failed to get addresses");
+
+        assert!(addresses.unified.starts_with("utest1"));
+        assert!(addresses.shielded.starts_with("utest1"));
+        assert_ne!(addresses.unified, addresses.shielded);
+        assert!(addresses.transparent.starts_with("tm"));
+    }
+
+    #[test]
+    fn test_get_sapling_address() {
+        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let mnemonic = Mnemonic::parse_in(Language::English, test_mnemonic)
+            .expect("Failed to parse mnemonic");
+
+        let wallet = Wallet::from_mnemonic(&mnemonic, Network::TestNetwork)
+            .expect("Failed to create wallet");
+
+        let address = wallet.get_sapling_address().expect("Failed to get Sapling address");
+        assert!(address.starts_with("ztestsapling1"));
+
+        let payment_address = match zcash_keys::address::Address::decode(&Network::TestNetwork, &address) {
+            Some(zcash_keys::address::Address::Sapling(payment_address)) => payment_address,
+            _ => panic!("Not a Sapling address: {}", address),
+        };
+
+        // Same receiver as the unified address, and recognised by the account's viewing key
+        let ua = match zcash_keys::address::Address::decode(&Network::TestNetwork, &wallet.get_address().unwrap()) {
+            Some(zcash_keys::address::Address::Unified(ua)) => ua,
+            _ => panic!("Not a unified address"),
+        };
+        assert_eq!(ua.sapling(), Some(&payment_address));
+
+        let ufvk = wallet.spending_key().to_unified_full_viewing_key();
+        let dfvk = ufvk.sapling().expect("UFVK has no Sapling key");
+        assert!(dfvk.decrypt_diversifier(&payment_address).is_some());
+
+        let mainnet = Wallet::from_mnemonic(&mnemonic, Network::MainNetwork)
+            .expect("Failed to create wallet");
+        assert!(mainnet.get_sapling_address().unwrap().starts_with("zs1"));
+    }
+
+    #[test]
+    fn test_accounts_have_distinct_addresses() {
+        let test_mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art";
+
+        let mnemonic = Mnemonic::parse_in(Language::English, test_mnemonic)
+            .expect("Failed to parse mnemonic");
+
+        let primary = Wallet::from_mnemonic(&mnemonic, Network::TestNetwork)
+            .expect("Failed to create wallet");
+        let account_0 = Wallet::from_mnemonic_account(&mnemonic, Network::TestNetwork, 0)
+            .expect("Failed to create account 0");
+        let account_1 = Wallet::from_mnemonic_account(&mnemonic, Network::TestNetwork, 1)
+            .expect("Failed to create account 1");
+
+        assert_eq!(account_0.get_address().unwrap(), primary.get_address().unwrap());
+        assert_ne!(account_1.get_address().unwrap(), primary.get_address().unwrap());
+        assert!(account_1.get_address().unwrap().starts_with("utest1"));
+    }
 }
@@ -77,7 +77,7 @@ export function SendTransactionModal({
       const feeResponse = await walletAPI.estimateFee({
         user_id: userId,
         to_address: toAddress,
-        amount_zec: amountNum,
+        amount_zec: amount.trim(),
         memo: memo || undefined,
       });
 
@@ -107,8 +107,6 @@ export function SendTransactionModal({
   };
 
   const handleConfirmSend = async () => {
-    const amountNum = parseFloat(amount);
-
     setIsLoading(true);
     setError(null);
 
@@ -116,7 +114,7 @@ export function SendTransactionModal({
       const response = await walletAPI.sendTransaction({
         user_id: userId,
         to_address: toAddress,
-        amount_zec: amountNum,
+        amount_zec: amount.trim(),
         memo: memo || undefined,
       });
 
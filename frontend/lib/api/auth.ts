@@ -69,6 +69,16 @@ class AuthAPI {
       },
     });
   }
+
+  async updateMe(fullName: string, accessToken: string): Promise<User> {
+    return this.fetch<User>('/users/me', {
+      method: 'PATCH',
+      headers: {
+        Authorization: `Bearer ${accessToken}`,
+      },
+      body: JSON.stringify({ full_name: fullName }),
+    });
+  }
 }
 
 export const authAPI = new AuthAPI();
@@ -9,6 +9,8 @@ export interface SolanaBalanceResponse {
 export interface BridgeQuoteRequest {
   amount_lamports: number;
   recipient_zcash_address: string;
+  /** Hours until the quote expires (clamped to 1-24) */
+  deadline_hours?: number;
 }
 
 export interface BridgeQuoteResponse {
@@ -18,11 +20,22 @@ export interface BridgeQuoteResponse {
   amount_out_formatted: string;
   deposit_address: string;
   time_estimate: number;
+  /** RFC 3339 expiry of the quote */
+  deadline: string;
+}
+
+export interface BridgeEstimateResponse {
+  amount_out_formatted: string;
+  /** Expected swap time in seconds */
+  time_estimate: number;
 }
 
 export interface ExecuteBridgeRequest {
   amount_lamports: number;
   recipient_zcash_address: string;
+  /** Refuse the swap (409) if the fresh quote pays fewer zatoshis than this */
+  min_amount_out?: string;
+  deadline_hours?: number;
 }
 
 export interface ExecuteBridgeResponse {
@@ -30,6 +43,7 @@ export interface ExecuteBridgeResponse {
   solana_signature: string;
   deposit_address: string;
   expected_zec: string;
+  deadline: string;
 }
 
 export interface BridgeStatusRequest {
@@ -51,6 +65,35 @@ export interface BridgeStatusResponse {
   };
 }
 
+export interface BridgeHistoryRequest {
+  page?: number;
+  page_size?: number;
+}
+
+export interface BridgeRecord {
+  id: string;
+  status: string;
+  error_message: string | null;
+  amount_sol: string;
+  expected_zec: string | null;
+  actual_zec: string | null;
+  deposit_address: string;
+  recipient_address: string;
+  solana_signature: string | null;
+  zec_tx_hash: string | null;
+  created_at: string | null;
+  updated_at: string | null;
+  completed_at: string | null;
+}
+
+export interface BridgeHistoryResponse {
+  bridges: BridgeRecord[];
+  total_count: number;
+  page: number;
+  page_size: number;
+  has_more: boolean;
+}
+
 class SolanaAPI {
   private async fetch<T>(
     url: string,
@@ -86,6 +129,18 @@ class SolanaAPI {
     });
   }
 
+  /** Cheap cached preview for live input; doesn't reserve a deposit address */
+  async estimateBridge(amountLamports: number, accessToken: string): Promise<BridgeEstimateResponse> {
+    return this.fetch<BridgeEstimateResponse>(
+      '/solana/bridge/estimate',
+      accessToken,
+      {
+        method: 'POST',
+        body: JSON.stringify({ amount_lamports: amountLamports }),
+      }
+    );
+  }
+
   async getBridgeQuote(
     request: BridgeQuoteRequest,
     accessToken: string
@@ -127,6 +182,20 @@ class SolanaAPI {
       }
     );
   }
+
+  async listBridges(
+    request: BridgeHistoryRequest,
+    accessToken: string
+  ): Promise<BridgeHistoryResponse> {
+    return this.fetch<BridgeHistoryResponse>(
+      '/solana/bridge/history',
+      accessToken,
+      {
+        method: 'POST',
+        body: JSON.stringify(request),
+      }
+    );
+  }
 }
 
 export const solanaAPI = new SolanaAPI();
@@ -20,34 +20,110 @@ export interface GetBalanceRequest {
 
 export interface BalanceResponse {
   balance_zec: string;
+  /** Unspent transparent funds, not counted in balance_zec */
+  transparent_zec?: string;
+  /** Present when transparent funds need shielding before private spending */
+  transparent_hint?: string;
   synced: boolean;
   last_synced_height: number | null;
   blocks_scanned?: number | null;
   notes_found?: number | null;
   chain_tip?: number | null;
+  /** Confirmed balance in fiat_currency; only when requested with fiat and the price is available */
+  balance_fiat?: string;
+  fiat_currency?: string;
+}
+
+export interface SyncStatusResponse {
+  last_synced_height: number | null;
+  /** Null when lightwalletd couldn't be reached */
+  chain_tip: number | null;
+  blocks_behind: number | null;
+  is_synced: boolean;
+  last_synced_at: string | null;
 }
 
 export interface SendTransactionRequest {
   user_id: string;
   to_address: string;
-  amount_zec: number;
+  /** Omit when send_max is set */
+  amount_zec?: string;
+  /** Send the whole spendable balance, fee deducted from the amount */
+  send_max?: boolean;
   memo?: string;
+  /** Memo for the change output; ignored with send_max */
+  change_memo?: string;
+  /** Fee rule name; defaults to "zip317" */
+  fee_rule?: string;
+  /** Route the payment and change through one pool; not allowed with send_max */
+  prefer_pool?: 'orchard' | 'sapling';
+  /** HD account to spend from; defaults to the primary account (0) */
+  account_index?: number;
+}
+
+export interface Account {
+  account_index: number;
+  name: string;
+  address: string;
+  birthday_height: number;
+}
+
+/** Passphrase-encrypted wallet backup; opaque to the client */
+export interface EncryptedBackup {
+  version: number;
+  kdf: string;
+  m_cost: number;
+  t_cost: number;
+  p_cost: number;
+  salt: string;
+  nonce: string;
+  ciphertext: string;
+}
+
+export interface ImportBackupResponse {
+  address: string;
+  birthday_height: number;
+  network: string;
+  /** False when the backup matched the current wallet */
+  restored: boolean;
 }
 
 export interface SendTransactionResponse {
   txid: string;
+  txids: string[];
   from_address: string;
   to_address: string;
   amount_zec: number;
   fee_zec: number;
   explorer_url: string;
+  explorer_urls: string[];
   message: string;
 }
 
+export interface PrepareSendResponse {
+  prepare_id: string;
+  /** txid the transaction will have once confirmed */
+  txid: string | null;
+  from_address: string;
+  to_address: string;
+  amount_zec: number;
+  fee_zec: number;
+  total_zec: number;
+  expires_in_secs: number;
+  expiry_height: number | null;
+}
+
+export interface CancelSendResponse {
+  cancelled: boolean;
+  /** Inputs stay reserved until the signed transaction expires at this height */
+  funds_reserved_until_height: number | null;
+}
+
 export interface GetTransactionsRequest {
   user_id: string;
   page?: number;
   page_size?: number;
+  before?: string;
 }
 
 export interface Transaction {
@@ -55,9 +131,42 @@ export interface Transaction {
   timestamp: string | null;
   block_height: number | null;
   amount_zec: string;
+  /** In the response's fiat_currency; only when requested with fiat and the price is available */
+  amount_fiat?: string;
   direction: 'sent' | 'received';
   memo: string | null;
   fee_zec: string | null;
+  status: 'pending' | 'mined';
+  label: string | null;
+}
+
+export interface TransactionOutput {
+  direction: 'sent' | 'received';
+  pool: 'sapling' | 'orchard' | null;
+  note_index: number | null;
+  address: string | null;
+  amount_zec: string;
+  amount_fiat?: string;
+  memo: string | null;
+  is_change: boolean;
+  spent: boolean;
+}
+
+export interface TransactionDetail {
+  txid: string;
+  timestamp: string | null;
+  block_height: number | null;
+  fee_zec: string | null;
+  status: 'pending' | 'mined';
+  confirmations: number;
+  label: string | null;
+  outputs: TransactionOutput[];
+  fiat_currency?: string;
+}
+
+export interface SetTransactionLabelResponse {
+  txid: string;
+  label: string | null;
 }
 
 export interface TransactionsResponse {
@@ -66,12 +175,23 @@ export interface TransactionsResponse {
   page: number;
   page_size: number;
   has_more: boolean;
+  next_cursor: string | null;
+  fiat_currency?: string;
+}
+
+/** A zcash: payment URI decoded into fields for sendTransaction */
+export interface ParsedPaymentUri {
+  to_address: string;
+  amount_zec: string;
+  memo: string | null;
+  label: string | null;
+  message: string | null;
 }
 
 export interface EstimateFeeRequest {
   user_id: string;
   to_address: string;
-  amount_zec: number;
+  amount_zec: string;
   memo?: string;
 }
 
@@ -81,13 +201,18 @@ export interface EstimateFeeResponse {
 }
 
 const API_URL = process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8000/api';
+const TOKEN_KEY = 'shield_access_token';
 
 class WalletAPI {
   private async fetch<T>(url: string, options?: RequestInit): Promise<T> {
+    // Wallet routes identify the user from the access token, not the request body
+    const accessToken = typeof window !== 'undefined' ? localStorage.getItem(TOKEN_KEY) : null;
+
     const response = await fetch(`${API_URL}${url}`, {
       ...options,
       headers: {
         'Content-Type': 'application/json',
+        ...(accessToken ? { Authorization: `Bearer ${accessToken}` } : {}),
         ...options?.headers,
       },
       cache: 'no-store', // Disable caching for all API calls
@@ -111,13 +236,102 @@ class WalletAPI {
     });
   }
 
-  async getBalance(userId: string): Promise<BalanceResponse> {
-    return this.fetch<BalanceResponse>('/wallet/balance', {
+  /** Legacy Sapling (zs1...) address, for services that reject unified addresses */
+  async getSaplingAddress(accessToken: string): Promise<AddressResponse> {
+    return this.fetch<AddressResponse>('/wallet/address/sapling', {
+      method: 'POST',
+      headers: {
+        Authorization: `Bearer ${accessToken}`,
+      },
+    });
+  }
+
+  /** QR code image of the receive address, or of a ZIP-321 payment URI when amount is given */
+  async getAddressQr(
+    options: { amount?: string; memo?: string; format?: 'png' | 'svg' } = {},
+  ): Promise<Blob> {
+    const accessToken = typeof window !== 'undefined' ? localStorage.getItem(TOKEN_KEY) : null;
+    const params = new URLSearchParams();
+    if (options.amount) params.set('amount', options.amount);
+    if (options.memo) params.set('memo', options.memo);
+    if (options.format) params.set('format', options.format);
+    const query = params.toString();
+
+    const response = await fetch(`${API_URL}/wallet/address/qr${query ? `?${query}` : ''}`, {
+      headers: accessToken ? { Authorization: `Bearer ${accessToken}` } : {},
+      cache: 'no-store',
+    });
+
+    if (!response.ok) {
+      const error = await response.json().catch(() => ({ error: 'An error occurred' }));
+      throw new Error(error.error || `HTTP ${response.status}`);
+    }
+
+    return response.blob();
+  }
+
+  /** Balance of one HD account, or of all accounts combined when accountIndex is omitted */
+  async getBalance(userId: string, force = false, accountIndex?: number, fiat?: string): Promise<BalanceResponse> {
+    return this.fetch<BalanceResponse>(`/wallet/balance${fiat ? `?fiat=${fiat}` : ''}`, {
+      method: 'POST',
+      body: JSON.stringify({ user_id: userId, force, account_index: accountIndex }),
+    });
+  }
+
+  /** Sync progress without triggering a scan; cheap enough to poll */
+  async getSyncStatus(userId: string): Promise<SyncStatusResponse> {
+    return this.fetch<SyncStatusResponse>('/wallet/sync-status', {
       method: 'POST',
       body: JSON.stringify({ user_id: userId }),
     });
   }
 
+  async listAccounts(accessToken: string): Promise<Account[]> {
+    return this.fetch<Account[]>('/wallet/accounts', {
+      method: 'GET',
+      headers: {
+        Authorization: `Bearer ${accessToken}`,
+      },
+    });
+  }
+
+  async createAccount(name: string, accessToken: string): Promise<Account> {
+    return this.fetch<Account>('/wallet/accounts', {
+      method: 'POST',
+      headers: {
+        Authorization: `Bearer ${accessToken}`,
+      },
+      body: JSON.stringify({ name }),
+    });
+  }
+
+  /** Email users must pass their password; the passphrase encrypts the backup */
+  async exportBackup(passphrase: string, accessToken: string, password?: string): Promise<EncryptedBackup> {
+    return this.fetch<EncryptedBackup>('/wallet/backup', {
+      method: 'POST',
+      headers: {
+        Authorization: `Bearer ${accessToken}`,
+      },
+      body: JSON.stringify({ password, passphrase }),
+    });
+  }
+
+  /** Set replace to overwrite a wallet with a different seed */
+  async importBackup(
+    backup: EncryptedBackup,
+    passphrase: string,
+    accessToken: string,
+    options: { password?: string; replace?: boolean } = {},
+  ): Promise<ImportBackupResponse> {
+    return this.fetch<ImportBackupResponse>('/wallet/backup/import', {
+      method: 'POST',
+      headers: {
+        Authorization: `Bearer ${accessToken}`,
+      },
+      body: JSON.stringify({ backup, passphrase, password: options.password, replace: options.replace }),
+    });
+  }
+
   async sendTransaction(request: SendTransactionRequest): Promise<SendTransactionResponse> {
     return this.fetch<SendTransactionResponse>('/wallet/send', {
       method: 'POST',
@@ -125,23 +339,69 @@ class WalletAPI {
     });
   }
 
-  async getTransactions(userId: string, page?: number, pageSize?: number): Promise<TransactionsResponse> {
-    return this.fetch<TransactionsResponse>('/wallet/transactions', {
+  async prepareSend(request: SendTransactionRequest): Promise<PrepareSendResponse> {
+    return this.fetch<PrepareSendResponse>('/wallet/send/prepare', {
+      method: 'POST',
+      body: JSON.stringify(request),
+    });
+  }
+
+  async confirmSend(prepareId: string): Promise<SendTransactionResponse> {
+    return this.fetch<SendTransactionResponse>('/wallet/send/confirm', {
+      method: 'POST',
+      body: JSON.stringify({ prepare_id: prepareId }),
+    });
+  }
+
+  async cancelSend(prepareId: string): Promise<CancelSendResponse> {
+    return this.fetch<CancelSendResponse>('/wallet/send/cancel', {
+      method: 'POST',
+      body: JSON.stringify({ prepare_id: prepareId }),
+    });
+  }
+
+  async getTransactions(userId: string, page?: number, pageSize?: number, before?: string, fiat?: string): Promise<TransactionsResponse> {
+    return this.fetch<TransactionsResponse>(`/wallet/transactions${fiat ? `?fiat=${fiat}` : ''}`, {
       method: 'POST',
       body: JSON.stringify({
         user_id: userId,
         page,
         page_size: pageSize,
+        before,
       }),
     });
   }
 
+  async getTransaction(userId: string, txid: string, fiat?: string): Promise<TransactionDetail> {
+    return this.fetch<TransactionDetail>(`/wallet/transactions/detail${fiat ? `?fiat=${fiat}` : ''}`, {
+      method: 'POST',
+      body: JSON.stringify({
+        user_id: userId,
+        txid,
+      }),
+    });
+  }
+
+  async setTransactionLabel(txid: string, label: string | null): Promise<SetTransactionLabelResponse> {
+    return this.fetch<SetTransactionLabelResponse>('/wallet/transactions/label', {
+      method: 'POST',
+      body: JSON.stringify({ txid, label }),
+    });
+  }
+
   async estimateFee(request: EstimateFeeRequest): Promise<EstimateFeeResponse> {
     return this.fetch<EstimateFeeResponse>('/wallet/estimate-fee', {
       method: 'POST',
       body: JSON.stringify(request),
     });
   }
+
+  async parsePaymentUri(uri: string): Promise<ParsedPaymentUri> {
+    return this.fetch<ParsedPaymentUri>('/wallet/parse-uri', {
+      method: 'POST',
+      body: JSON.stringify({ uri }),
+    });
+  }
 }
 
 export const walletAPI = new WalletAPI();
@@ -0,0 +1,45 @@
+// Proxy for the merchant's wallet routes, authenticated on the server
+import { API_URL, invalidateMerchantAccessToken, merchantAccessToken } from '@/lib/server/merchantSession';
+
+// Only read-only routes are exposed to the POS; sending stays out of reach of the browser
+const ALLOWED_ACTIONS = new Set(['balance', 'transactions']);
+
+async function forward(action: string, body: string, accessToken: string): Promise<Response> {
+  return fetch(`${API_URL}/wallet/${action}`, {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+      Authorization: `Bearer ${accessToken}`,
+    },
+    body,
+    cache: 'no-store',
+  });
+}
+
+export async function POST(request: Request, { params }: { params: Promise<{ action: string }> }) {
+  const { action } = await params;
+  if (!ALLOWED_ACTIONS.has(action)) {
+    return Response.json({ error: 'Not found' }, { status: 404 });
+  }
+
+  const body = await request.text();
+  try {
+    let accessToken = await merchantAccessToken();
+    let response = await forward(action, body, accessToken);
+
+    // The token may have been revoked before it expired; renew once and retry
+    if (response.status === 401) {
+      invalidateMerchantAccessToken(accessToken);
+      accessToken = await merchantAccessToken();
+      response = await forward(action, body, accessToken);
+    }
+
+    return new Response(response.body, {
+      status: response.status,
+      headers: { 'Content-Type': response.headers.get('Content-Type') ?? 'application/json' },
+    });
+  } catch (error) {
+    console.error('Merchant wallet request failed:', error);
+    return Response.json({ error: 'Merchant wallet is unavailable' }, { status: 502 });
+  }
+}
@@ -1,5 +1,7 @@
 // POS Wallet API client for communicating with Shield backend
-const API_URL = process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8000/api';
+// Requests go through the POS's own /api/wallet routes, which add the merchant's access
+// token on the server (see lib/server/merchantSession.ts)
+const API_URL = '/api';
 
 export interface BalanceResponse {
   balance_zec: string;
@@ -0,0 +1,100 @@
+// Server-side session for the merchant account
+//
+// The POS reads the merchant's wallet through the backend, which needs an access token.
+// Tokens expire after a few minutes and must never reach the browser, so this module signs
+// in with MERCHANT_EMAIL / MERCHANT_PASSWORD on the server, keeps the (rotating) refresh
+// token in memory and hands out a fresh access token when the current one is close to expiry.
+// Only import it from route handlers.
+
+const API_URL = process.env.API_URL || process.env.NEXT_PUBLIC_API_URL || 'http://localhost:8000/api';
+// Refresh this long before the access token actually expires
+const EXPIRY_MARGIN_MS = 60_000;
+
+interface AuthResponse {
+  access_token: string;
+  refresh_token: string;
+}
+
+interface Session {
+  accessToken: string;
+  refreshToken: string;
+  expiresAt: number;
+}
+
+let session: Session | null = null;
+// Concurrent requests share one sign-in/refresh instead of racing to rotate the token
+let pending: Promise<Session> | null = null;
+
+function tokenExpiry(accessToken: string): number {
+  try {
+    const payload = JSON.parse(Buffer.from(accessToken.split('.')[1], 'base64url').toString());
+    return payload.exp * 1000;
+  } catch {
+    return 0;
+  }
+}
+
+async function authenticate(url: string, body: unknown): Promise<Session> {
+  const response = await fetch(`${API_URL}${url}`, {
+    method: 'POST',
+    headers: { 'Content-Type': 'application/json' },
+    body: JSON.stringify(body),
+    cache: 'no-store',
+  });
+
+  if (!response.ok) {
+    throw new Error(`Merchant sign-in failed: HTTP ${response.status}`);
+  }
+
+  const auth: AuthResponse = await response.json();
+  return {
+    accessToken: auth.access_token,
+    refreshToken: auth.refresh_token,
+    expiresAt: tokenExpiry(auth.access_token),
+  };
+}
+
+async function renew(current: Session | null): Promise<Session> {
+  if (current) {
+    try {
+      return await authenticate('/auth/refresh', current.refreshToken);
+    } catch {
+      // Refresh token expired or was revoked; sign in again below
+    }
+  }
+
+  const email = process.env.MERCHANT_EMAIL;
+  const password = process.env.MERCHANT_PASSWORD;
+  if (!email || !password) {
+    throw new Error('MERCHANT_EMAIL and MERCHANT_PASSWORD must be set');
+  }
+  return authenticate('/auth/login', { email, password });
+}
+
+// Access token for the merchant account, renewing it if needed
+export async function merchantAccessToken(): Promise<string> {
+  if (session && session.expiresAt - EXPIRY_MARGIN_MS > Date.now()) {
+    return session.accessToken;
+  }
+
+  if (!pending) {
+    pending = renew(session)
+      .then((renewed) => {
+        session = renewed;
+        return renewed;
+      })
+      .finally(() => {
+        pending = null;
+      });
+  }
+  return (await pending).accessToken;
+}
+
+// Drop the cached access token after the backend rejected it
+export function invalidateMerchantAccessToken(accessToken: string) {
+  if (session?.accessToken === accessToken) {
+    session = { ...session, expiresAt: 0 };
+  }
+}
+
+export { API_URL };